@@ -0,0 +1,4 @@
+// Пересобираем проект при изменении миграций (они встраиваются через sqlx::migrate!)
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
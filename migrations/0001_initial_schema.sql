@@ -0,0 +1,33 @@
+-- Каноническая схема базы данных.
+-- Миграция идемпотентна: базы, созданные вручную по старому sql/init.sql
+-- или по README, приводятся к этой же схеме.
+
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    login VARCHAR(255) UNIQUE NOT NULL,
+    hashed_password VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deletion_requested_at TIMESTAMPTZ
+);
+
+ALTER TABLE users ALTER COLUMN login TYPE VARCHAR(255);
+ALTER TABLE users ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deletion_requested_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS players (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    x DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    y DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    z DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    is_online BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+ALTER TABLE players ALTER COLUMN x TYPE DOUBLE PRECISION, ALTER COLUMN x SET DEFAULT 0.0;
+ALTER TABLE players ALTER COLUMN y TYPE DOUBLE PRECISION, ALTER COLUMN y SET DEFAULT 0.0;
+ALTER TABLE players ALTER COLUMN z TYPE DOUBLE PRECISION, ALTER COLUMN z SET DEFAULT 0.0;
+ALTER TABLE players ADD COLUMN IF NOT EXISTS is_online BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Старый sql/init.sql создавал внешний ключ без каскадного удаления
+ALTER TABLE players DROP CONSTRAINT IF EXISTS players_user_id_fkey;
+ALTER TABLE players ADD CONSTRAINT players_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
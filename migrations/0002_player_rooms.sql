@@ -0,0 +1,2 @@
+-- Комната (мир или инстанс), в которой игрок находился при выходе из игры
+ALTER TABLE players ADD COLUMN IF NOT EXISTS room VARCHAR(64) NOT NULL DEFAULT 'lobby';
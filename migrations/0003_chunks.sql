@@ -0,0 +1,9 @@
+-- Чанки тайлового мира: CHUNK_SIZE x CHUNK_SIZE тайлов (u16 little-endian) на комнату и координаты
+CREATE TABLE IF NOT EXISTS chunks (
+    room VARCHAR(64) NOT NULL,
+    cx INTEGER NOT NULL,
+    cy INTEGER NOT NULL,
+    tiles BYTEA NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (room, cx, cy)
+);
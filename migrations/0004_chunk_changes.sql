@@ -0,0 +1,13 @@
+-- Чанки хранятся как изменения поверх сгенерированного мира: пары (индекс тайла, тайл),
+-- оба u16 little-endian. До появления генерации сохранялись полные снимки пустого мира,
+-- поэтому их непустые тайлы и есть изменения; на месте пустых теперь будет сгенерированный рельеф
+UPDATE chunks SET tiles = COALESCE((
+    SELECT string_agg(
+        set_byte(set_byte(set_byte(set_byte('\x00000000'::bytea,
+            0, i % 256), 1, i / 256), 2, get_byte(tiles, 2 * i)), 3, get_byte(tiles, 2 * i + 1)),
+        ''::bytea ORDER BY i)
+    FROM generate_series(0, 1023) AS i
+    WHERE get_byte(tiles, 2 * i) <> 0 OR get_byte(tiles, 2 * i + 1) <> 0
+), ''::bytea);
+
+ALTER TABLE chunks RENAME COLUMN tiles TO changes;
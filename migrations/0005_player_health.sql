@@ -0,0 +1,3 @@
+-- Здоровье игрока; NULL — игрок еще не получал урона, действует game.max_health
+ALTER TABLE players ADD COLUMN IF NOT EXISTS health INTEGER;
+ALTER TABLE players ADD COLUMN IF NOT EXISTS max_health INTEGER;
@@ -0,0 +1,8 @@
+-- Инвентари игроков: по строке на непустую ячейку
+CREATE TABLE IF NOT EXISTS inventory_slots (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    slot INTEGER NOT NULL CHECK (slot >= 0),
+    item VARCHAR(64) NOT NULL,
+    count INTEGER NOT NULL CHECK (count > 0),
+    PRIMARY KEY (user_id, slot)
+);
@@ -0,0 +1,8 @@
+-- Составы групп (game.persist_parties): по строке на участника, position 0 — лидер
+CREATE TABLE IF NOT EXISTS party_members (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    party_id BIGINT NOT NULL,
+    position INTEGER NOT NULL CHECK (position >= 0)
+);
+
+CREATE INDEX IF NOT EXISTS party_members_party_id ON party_members (party_id);
@@ -0,0 +1,28 @@
+-- Кланы, их участники с рангами и приглашения
+CREATE TABLE IF NOT EXISTS clans (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(32) NOT NULL,
+    tag VARCHAR(5) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Имена кланов уникальны без учета регистра
+CREATE UNIQUE INDEX IF NOT EXISTS clans_name_lower ON clans (LOWER(name));
+
+-- Игрок состоит не больше чем в одном клане
+CREATE TABLE IF NOT EXISTS clan_members (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    clan_id INTEGER NOT NULL REFERENCES clans(id) ON DELETE CASCADE,
+    rank VARCHAR(16) NOT NULL CHECK (rank IN ('leader', 'officer', 'member', 'recruit')),
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS clan_members_clan_id ON clan_members (clan_id);
+
+CREATE TABLE IF NOT EXISTS clan_invites (
+    clan_id INTEGER NOT NULL REFERENCES clans(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invited_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (clan_id, user_id)
+);
@@ -0,0 +1,14 @@
+-- Дружба между игроками: строка — запрос от user_id к friend_id,
+-- принятый запрос (accepted_at задан) — дружба
+CREATE TABLE IF NOT EXISTS friends (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    friend_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    accepted_at TIMESTAMPTZ,
+    PRIMARY KEY (user_id, friend_id),
+    CHECK (user_id <> friend_id)
+);
+
+-- Между двумя игроками не больше одной строки, в каком бы направлении ни был запрос
+CREATE UNIQUE INDEX IF NOT EXISTS friends_pair ON friends (LEAST(user_id, friend_id), GREATEST(user_id, friend_id));
+CREATE INDEX IF NOT EXISTS friends_friend_id ON friends (friend_id);
@@ -0,0 +1,3 @@
+-- Комнату игрока всегда записывает сервер (мир по умолчанию берется из конфигурации),
+-- поэтому имя комнаты в схеме не задается
+ALTER TABLE players ALTER COLUMN room DROP DEFAULT;
@@ -0,0 +1,2 @@
+-- Время гибели игрока; возрождение по game.respawn_delay_secs отсчитывается от него и после переподключения
+ALTER TABLE players ADD COLUMN IF NOT EXISTS died_at TIMESTAMPTZ;
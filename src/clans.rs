@@ -0,0 +1,376 @@
+// src/clans.rs
+// Кланы: постоянные объединения игроков с рангами. Управлять кланом можно и через REST
+// (/api/clans, /api/clan), и из игры (сообщения Clan*): обе точки входа вызывают функции ниже.
+// Все данные хранятся в базе; игроки онлайн узнают об изменениях через AppState::players_tx,
+// а тег клана входит в позиции игроков и рассылается комнате при изменении (ClanTagChanged)
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tracing::{error, info};
+
+use crate::routes::game::{ErrorCode, GameMessage};
+use crate::state::AppState;
+use crate::storage::StorageError;
+
+pub type ClanId = i32;
+
+// Ранги от старшего к младшему
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClanRank {
+    Leader,
+    Officer,
+    Member,
+    Recruit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClanPermission {
+    // Приглашать игроков
+    Invite,
+    // Исключать участников младше себя по рангу
+    Kick,
+    // Назначать ранги (назначение лидером передает лидерство)
+    SetRank,
+    Disband,
+}
+
+impl ClanRank {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClanRank::Leader => "leader",
+            ClanRank::Officer => "officer",
+            ClanRank::Member => "member",
+            ClanRank::Recruit => "recruit",
+        }
+    }
+
+    pub fn can(self, permission: ClanPermission) -> bool {
+        match permission {
+            ClanPermission::Invite | ClanPermission::Kick => self <= ClanRank::Officer,
+            ClanPermission::SetRank | ClanPermission::Disband => self == ClanRank::Leader,
+        }
+    }
+}
+
+impl FromStr for ClanRank {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "leader" => Ok(ClanRank::Leader),
+            "officer" => Ok(ClanRank::Officer),
+            "member" => Ok(ClanRank::Member),
+            "recruit" => Ok(ClanRank::Recruit),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClanMember {
+    pub user_id: i32,
+    pub login: String,
+    pub rank: ClanRank,
+    pub joined_at: DateTime<Utc>,
+}
+
+// Клан и его участники (в порядке вступления)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clan {
+    pub id: ClanId,
+    pub name: String,
+    pub tag: String,
+    pub created_at: DateTime<Utc>,
+    pub members: Vec<ClanMember>,
+}
+
+impl Clan {
+    pub fn member(&self, user_id: i32) -> Option<&ClanMember> {
+        self.members.iter().find(|member| member.user_id == user_id)
+    }
+}
+
+// Приглашение в клан; invited_by — None, если пригласивший удалил аккаунт
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClanInvite {
+    pub clan: ClanId,
+    pub name: String,
+    pub tag: String,
+    pub invited_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClanLeaveReason {
+    Left,
+    Kicked,
+}
+
+#[derive(Debug)]
+pub enum ClanError {
+    // Неверное имя, тег или ранг
+    Invalid(String),
+    NotFound(String),
+    // Ранг игрока не позволяет это действие
+    Forbidden(String),
+    // Имя или тег заняты, игрок уже в клане
+    Conflict(String),
+    Storage(StorageError),
+}
+
+impl fmt::Display for ClanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClanError::Invalid(message)
+            | ClanError::NotFound(message)
+            | ClanError::Forbidden(message)
+            | ClanError::Conflict(message) => f.write_str(message),
+            ClanError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<StorageError> for ClanError {
+    fn from(e: StorageError) -> Self {
+        ClanError::Storage(e)
+    }
+}
+
+impl ClanError {
+    // Ошибка для игрового клиента; подробности ошибок хранилища остаются в логе
+    pub fn into_game_error(self) -> (ErrorCode, String) {
+        match self {
+            ClanError::Storage(e) => {
+                error!(error = %e, "Clan storage error");
+                (ErrorCode::InvalidClanAction, "clan action failed, try again later".to_string())
+            },
+            other => (ErrorCode::InvalidClanAction, other.to_string()),
+        }
+    }
+}
+
+type ClanResult<T> = Result<T, ClanError>;
+
+// Имя: 3–32 символа, буквы, цифры, пробел, '-' и '_'
+pub fn validate_name(name: &str) -> ClanResult<&str> {
+    let name = name.trim();
+    let length = name.chars().count();
+    if !(3..=32).contains(&length) || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_')) {
+        return Err(ClanError::Invalid("clan name must be 3 to 32 letters, digits, spaces, '-' or '_'".to_string()));
+    }
+    Ok(name)
+}
+
+// Тег: 2–5 заглавных латинских букв или цифр
+pub fn validate_tag(tag: &str) -> ClanResult<&str> {
+    if !(2..=5).contains(&tag.len()) || !tag.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        return Err(ClanError::Invalid("clan tag must be 2 to 5 uppercase letters or digits".to_string()));
+    }
+    Ok(tag)
+}
+
+fn send(app_state: &AppState, msg: GameMessage) {
+    // Ошибка означает лишь отсутствие подписчиков
+    let _ = app_state.players_tx.send(msg);
+}
+
+// Меняет тег клана игрока онлайн и сообщает об этом его комнате
+async fn set_tag(app_state: &AppState, user_id: i32, tag: Option<String>) {
+    for room in app_state.rooms.all() {
+        let mut positions = room.active_player_positions.lock().await;
+        if let Some(position) = positions.get_mut(&user_id) {
+            position.clan_tag = tag.clone();
+            drop(positions);
+            let _ = room.game_state_tx.send(GameMessage::ClanTagChanged { user_id, tag });
+            return;
+        }
+    }
+}
+
+async fn load(app_state: &AppState, clan: ClanId) -> ClanResult<Clan> {
+    app_state.storage.clans.load_clan(clan)
+        .await?
+        .ok_or_else(|| ClanError::NotFound(format!("clan {} does not exist", clan)))
+}
+
+// Клан игрока и ранг в нем
+async fn membership(app_state: &AppState, user_id: i32) -> ClanResult<(Clan, ClanRank)> {
+    let not_member = || ClanError::NotFound("you are not in a clan".to_string());
+    let id = app_state.storage.clans.clan_of(user_id).await?.ok_or_else(not_member)?;
+    let clan = load(app_state, id).await?;
+    let rank = clan.member(user_id).ok_or_else(not_member)?.rank;
+    Ok((clan, rank))
+}
+
+fn require(rank: ClanRank, permission: ClanPermission) -> ClanResult<()> {
+    if !rank.can(permission) {
+        return Err(ClanError::Forbidden(format!("rank {} cannot do this", rank.as_str())));
+    }
+    Ok(())
+}
+
+// Права проверяются по прочитанному составу клана, а хранилище выполняет запись, только если
+// ранги проверенных участников с тех пор не изменились (applied = false — изменились)
+fn applied(applied: bool) -> ClanResult<()> {
+    if !applied {
+        return Err(ClanError::Conflict("the clan has changed, try again".to_string()));
+    }
+    Ok(())
+}
+
+// Клан и тег игрока (при входе в игру)
+pub async fn tag_of(app_state: &AppState, user_id: i32) -> ClanResult<Option<(ClanId, String)>> {
+    let Some(id) = app_state.storage.clans.clan_of(user_id).await? else {
+        return Ok(None);
+    };
+    Ok(app_state.storage.clans.load_clan(id).await?.map(|clan| (clan.id, clan.tag)))
+}
+
+pub async fn get(app_state: &AppState, clan: ClanId) -> ClanResult<Clan> {
+    load(app_state, clan).await
+}
+
+pub async fn my_clan(app_state: &AppState, user_id: i32) -> ClanResult<Clan> {
+    Ok(membership(app_state, user_id).await?.0)
+}
+
+pub async fn create(app_state: &AppState, user_id: i32, name: &str, tag: &str) -> ClanResult<Clan> {
+    let name = validate_name(name)?;
+    let tag = validate_tag(tag)?;
+    if app_state.storage.clans.clan_of(user_id).await?.is_some() {
+        return Err(ClanError::Conflict("you are already in a clan".to_string()));
+    }
+    let id = match app_state.storage.clans.create_clan(name, tag, user_id).await {
+        Ok(id) => id,
+        Err(StorageError::Conflict(_)) => return Err(ClanError::Conflict("clan name or tag is already taken".to_string())),
+        Err(e) => return Err(e.into()),
+    };
+    let clan = load(app_state, id).await?;
+    info!(clan = id, %tag, leader = user_id, "Clan created");
+    send(app_state, GameMessage::ClanUpdated(clan.clone()));
+    set_tag(app_state, user_id, Some(clan.tag.clone())).await;
+    Ok(clan)
+}
+
+pub async fn invite(app_state: &AppState, user_id: i32, target: i32) -> ClanResult<()> {
+    let (clan, rank) = membership(app_state, user_id).await?;
+    require(rank, ClanPermission::Invite)?;
+    if app_state.storage.users.find_by_id(target).await?.is_none() {
+        return Err(ClanError::NotFound(format!("player {} does not exist", target)));
+    }
+    match app_state.storage.clans.invite(clan.id, target, user_id, &[(user_id, rank)]).await {
+        Ok(invited) => applied(invited)?,
+        Err(StorageError::Conflict(_)) => return Err(ClanError::Conflict(format!("player {} is already in a clan", target))),
+        Err(e) => return Err(e.into()),
+    }
+    send(app_state, GameMessage::ClanInvited { clan: clan.id, name: clan.name, tag: clan.tag, from: user_id, to: target });
+    Ok(())
+}
+
+pub async fn invites(app_state: &AppState, user_id: i32) -> ClanResult<Vec<ClanInvite>> {
+    Ok(app_state.storage.clans.invites_for(user_id).await?)
+}
+
+pub async fn accept(app_state: &AppState, user_id: i32, clan: ClanId) -> ClanResult<Clan> {
+    match app_state.storage.clans.accept_invite(clan, user_id).await {
+        Ok(true) => {},
+        Ok(false) => return Err(ClanError::NotFound(format!("no invite from clan {}", clan))),
+        Err(StorageError::Conflict(_)) => return Err(ClanError::Conflict("you are already in a clan".to_string())),
+        Err(e) => return Err(e.into()),
+    }
+    let clan = load(app_state, clan).await?;
+    info!(clan = clan.id, user_id, "Player joined clan");
+    send(app_state, GameMessage::ClanUpdated(clan.clone()));
+    set_tag(app_state, user_id, Some(clan.tag.clone())).await;
+    Ok(clan)
+}
+
+pub async fn decline(app_state: &AppState, user_id: i32, clan: ClanId) -> ClanResult<()> {
+    if !app_state.storage.clans.decline_invite(clan, user_id).await? {
+        return Err(ClanError::NotFound(format!("no invite from clan {}", clan)));
+    }
+    Ok(())
+}
+
+// Убирает участника и рассылает новый состав клана
+async fn remove(app_state: &AppState, clan: ClanId, user_id: i32, reason: ClanLeaveReason, expected: &[(i32, ClanRank)]) -> ClanResult<()> {
+    applied(app_state.storage.clans.remove_member(clan, user_id, expected).await?)?;
+    send(app_state, GameMessage::ClanLeft { clan, user_id, reason });
+    set_tag(app_state, user_id, None).await;
+    if let Some(clan) = app_state.storage.clans.load_clan(clan).await? {
+        send(app_state, GameMessage::ClanUpdated(clan));
+    }
+    Ok(())
+}
+
+// Лидер может уйти, только если он последний участник: тогда клан распускается
+pub async fn leave(app_state: &AppState, user_id: i32) -> ClanResult<()> {
+    let (clan, rank) = membership(app_state, user_id).await?;
+    if rank == ClanRank::Leader {
+        if clan.members.len() > 1 {
+            return Err(ClanError::Forbidden("transfer leadership or disband the clan first".to_string()));
+        }
+        return disband(app_state, user_id).await;
+    }
+    remove(app_state, clan.id, user_id, ClanLeaveReason::Left, &[(user_id, rank)]).await?;
+    info!(clan = clan.id, user_id, "Player left clan");
+    Ok(())
+}
+
+pub async fn kick(app_state: &AppState, user_id: i32, target: i32) -> ClanResult<()> {
+    let (clan, rank) = membership(app_state, user_id).await?;
+    require(rank, ClanPermission::Kick)?;
+    let target_rank = clan.member(target)
+        .ok_or_else(|| ClanError::NotFound(format!("player {} is not in your clan", target)))?
+        .rank;
+    if target_rank <= rank {
+        return Err(ClanError::Forbidden(format!("cannot kick a {}", target_rank.as_str())));
+    }
+    remove(app_state, clan.id, target, ClanLeaveReason::Kicked, &[(user_id, rank), (target, target_rank)]).await?;
+    info!(clan = clan.id, user_id, target, "Player kicked from clan");
+    Ok(())
+}
+
+// Назначение другого участника лидером делает прежнего лидера офицером
+pub async fn set_rank(app_state: &AppState, user_id: i32, target: i32, new_rank: ClanRank) -> ClanResult<()> {
+    let (clan, rank) = membership(app_state, user_id).await?;
+    require(rank, ClanPermission::SetRank)?;
+    if target == user_id {
+        return Err(ClanError::Invalid("cannot change your own rank".to_string()));
+    }
+    let target_rank = clan.member(target)
+        .ok_or_else(|| ClanError::NotFound(format!("player {} is not in your clan", target)))?
+        .rank;
+    let ranks = match new_rank {
+        ClanRank::Leader => vec![(target, ClanRank::Leader), (user_id, ClanRank::Officer)],
+        _ => vec![(target, new_rank)],
+    };
+    applied(app_state.storage.clans.set_ranks(clan.id, &ranks, &[(user_id, rank), (target, target_rank)]).await?)?;
+    info!(clan = clan.id, target, rank = new_rank.as_str(), "Clan rank changed");
+    send(app_state, GameMessage::ClanUpdated(load(app_state, clan.id).await?));
+    Ok(())
+}
+
+pub async fn disband(app_state: &AppState, user_id: i32) -> ClanResult<()> {
+    let (clan, rank) = membership(app_state, user_id).await?;
+    require(rank, ClanPermission::Disband)?;
+    applied(app_state.storage.clans.delete_clan(clan.id, &[(user_id, rank)]).await?)?;
+    info!(clan = clan.id, "Clan disbanded");
+    send(app_state, GameMessage::ClanDisbanded { clan: clan.id });
+    for member in &clan.members {
+        set_tag(app_state, member.user_id, None).await;
+    }
+    Ok(())
+}
+
+// Сообщение в чат клана (text уже проверен как сообщение чата)
+pub async fn chat(app_state: &AppState, user_id: i32, text: String) -> ClanResult<()> {
+    let clan = app_state.storage.clans.clan_of(user_id)
+        .await?
+        .ok_or_else(|| ClanError::NotFound("you are not in a clan".to_string()))?;
+    send(app_state, GameMessage::ClanChatMessage { clan, user_id, text });
+    Ok(())
+}
@@ -0,0 +1,207 @@
+// src/collision.rs
+// Авторитетное перемещение: игрок — круг радиуса PLAYER_RADIUS, который не может войти
+// в твердые тайлы и полигоны статической геометрии. Перемещение проходит мелкими шагами
+// отдельно по осям, поэтому упершийся в стену игрок скользит вдоль нее, а не застревает
+use serde::{Deserialize, Serialize};
+
+use crate::tiles::{self, TileWorld};
+
+pub const PLAYER_RADIUS: f64 = 0.3;
+// Шаг меньше радиуса: круг не может проскочить сквозь тайл между двумя проверками
+const MAX_SUBSTEP: f64 = 0.15;
+// Перемещение длиннее этого за один ввод отклоняется целиком (телепорт или подделанный ввод)
+pub const MAX_MOVE_DISTANCE: f64 = 256.0;
+// Итерации бинарного поиска точки касания при столкновении
+const CONTACT_ITERATIONS: u32 = 8;
+
+// Простой (без самопересечений) многоугольник, например из карты.
+// В JSON — массив вершин [[x, y], ...]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(f64, f64)>", into = "Vec<(f64, f64)>")]
+pub struct Polygon {
+    points: Vec<(f64, f64)>,
+    // Ограничивающий прямоугольник: min_x, min_y, max_x, max_y
+    bounds: (f64, f64, f64, f64),
+}
+
+impl Polygon {
+    // None, если вершин меньше трех
+    pub fn new(points: Vec<(f64, f64)>) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let bounds = points.iter().fold(
+            (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            |(min_x, min_y, max_x, max_y), &(x, y)| (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+        );
+        Some(Polygon { points, bounds })
+    }
+
+    pub fn rectangle(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Polygon::new(vec![(x, y), (x + width, y), (x + width, y + height), (x, y + height)])
+            .expect("rectangle has four points")
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    // min_x, min_y, max_x, max_y
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        self.bounds
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        let (min_x, min_y, max_x, max_y) = self.bounds;
+        ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        self.points.iter().copied().zip(self.points.iter().copied().cycle().skip(1))
+    }
+
+    // Трассировка луча: точка внутри, если луч вправо пересекает нечетное число ребер
+    pub fn contains(&self, (px, py): (f64, f64)) -> bool {
+        self.edges()
+            .filter(|&((x1, y1), (x2, y2))| {
+                (y1 > py) != (y2 > py) && px < x1 + (py - y1) * (x2 - x1) / (y2 - y1)
+            })
+            .count()
+            % 2
+            == 1
+    }
+
+    pub fn intersects_circle(&self, (x, y): (f64, f64), radius: f64) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.bounds;
+        if x + radius <= min_x || x - radius >= max_x || y + radius <= min_y || y - radius >= max_y {
+            return false;
+        }
+        self.contains((x, y)) || self.edges().any(|(a, b)| segment_distance((x, y), a, b) < radius)
+    }
+}
+
+impl TryFrom<Vec<(f64, f64)>> for Polygon {
+    type Error = &'static str;
+
+    fn try_from(points: Vec<(f64, f64)>) -> Result<Self, Self::Error> {
+        Polygon::new(points).ok_or("polygon needs at least three points")
+    }
+}
+
+impl From<Polygon> for Vec<(f64, f64)> {
+    fn from(polygon: Polygon) -> Self {
+        polygon.points
+    }
+}
+
+fn segment_distance((px, py): (f64, f64), (ax, ay): (f64, f64), (bx, by): (f64, f64)) -> f64 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 { 0.0 } else { (((px - ax) * dx + (py - ay) * dy) / length_sq).clamp(0.0, 1.0) };
+    (px - (ax + t * dx)).hypot(py - (ay + t * dy))
+}
+
+// Статическая геометрия, с которой сталкивается игрок
+pub trait CollisionWorld {
+    fn is_solid(&self, x: i32, y: i32) -> bool;
+
+    fn polygons(&self) -> &[Polygon] {
+        &[]
+    }
+}
+
+// Тайлы плюс коллизии карты комнаты, если она есть
+impl CollisionWorld for TileWorld {
+    fn is_solid(&self, x: i32, y: i32) -> bool {
+        tiles::is_solid(self.tile_or_generated(x, y))
+    }
+
+    fn polygons(&self) -> &[Polygon] {
+        self.generator().map().map_or(&[], |map| map.metadata.colliders.as_slice())
+    }
+}
+
+// Препятствия, которые уже пересекает круг в начале перемещения (например, тайл поставили
+// на игрока): их не учитываем, чтобы игрок мог из них выйти
+struct Ignored {
+    tiles: Vec<(i32, i32)>,
+    polygons: Vec<usize>,
+}
+
+fn tiles_under(x: f64, y: f64, radius: f64) -> impl Iterator<Item = (i32, i32)> {
+    let (min_x, max_x) = ((x - radius).floor() as i32, (x + radius).floor() as i32);
+    let (min_y, max_y) = ((y - radius).floor() as i32, (y + radius).floor() as i32);
+    (min_y..=max_y).flat_map(move |ty| (min_x..=max_x).map(move |tx| (tx, ty)))
+}
+
+// Касание допускается, пересечение — нет
+pub fn circle_hits_tile((x, y): (f64, f64), radius: f64, (tx, ty): (i32, i32)) -> bool {
+    let nearest_x = x.clamp(tx as f64, tx as f64 + 1.0);
+    let nearest_y = y.clamp(ty as f64, ty as f64 + 1.0);
+    (x - nearest_x).powi(2) + (y - nearest_y).powi(2) < radius * radius
+}
+
+fn overlapping(world: &impl CollisionWorld, center: (f64, f64), radius: f64) -> Ignored {
+    Ignored {
+        tiles: tiles_under(center.0, center.1, radius)
+            .filter(|&(tx, ty)| world.is_solid(tx, ty) && circle_hits_tile(center, radius, (tx, ty)))
+            .collect(),
+        polygons: world.polygons()
+            .iter()
+            .enumerate()
+            .filter(|(_, polygon)| polygon.intersects_circle(center, radius))
+            .map(|(index, _)| index)
+            .collect(),
+    }
+}
+
+fn blocked(world: &impl CollisionWorld, ignored: &Ignored, center: (f64, f64), radius: f64) -> bool {
+    let hits_tile = tiles_under(center.0, center.1, radius).any(|(tx, ty)| {
+        world.is_solid(tx, ty) && circle_hits_tile(center, radius, (tx, ty)) && !ignored.tiles.contains(&(tx, ty))
+    });
+    hits_tile || world.polygons().iter().enumerate().any(|(index, polygon)| {
+        polygon.intersects_circle(center, radius) && !ignored.polygons.contains(&index)
+    })
+}
+
+// Свободная позиция, до которой игрок может дойти из from в направлении to
+pub fn resolve_move(world: &impl CollisionWorld, from: (f64, f64), to: (f64, f64), radius: f64) -> (f64, f64) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let distance = dx.hypot(dy);
+    if !distance.is_finite() || distance > MAX_MOVE_DISTANCE {
+        return from;
+    }
+    let steps = (distance / MAX_SUBSTEP).ceil().max(1.0) as u32;
+    let (step_x, step_y) = (dx / steps as f64, dy / steps as f64);
+    let ignored = overlapping(world, from, radius);
+
+    let mut position = from;
+    let mut collided = false;
+    for _ in 0..steps {
+        // По осям отдельно: заблокированная ось останавливается у препятствия, другая продолжает движение
+        for axis_step in [(step_x, 0.0), (0.0, step_y)] {
+            if axis_step == (0.0, 0.0) {
+                continue;
+            }
+            let target = (position.0 + axis_step.0, position.1 + axis_step.1);
+            if !blocked(world, &ignored, target, radius) {
+                position = target;
+                continue;
+            }
+            // Подходим к препятствию вплотную
+            collided = true;
+            let (mut free, mut hit) = (0.0, 1.0);
+            for _ in 0..CONTACT_ITERATIONS {
+                let mid = (free + hit) / 2.0;
+                if blocked(world, &ignored, (position.0 + axis_step.0 * mid, position.1 + axis_step.1 * mid), radius) {
+                    hit = mid;
+                } else {
+                    free = mid;
+                }
+            }
+            position = (position.0 + axis_step.0 * free, position.1 + axis_step.1 * free);
+        }
+    }
+    // Без столкновений возвращаем ровно запрошенную точку, без накопленной ошибки шагов
+    if collided { position } else { to }
+}
@@ -0,0 +1,148 @@
+// src/combat.rs
+// Бой между игроками: здоровье, атаки с проверкой дальности и перезарядки, гибель и
+// возрождение по таймеру. Все решения принимает сервер, клиент присылает только Attack
+use std::time::Instant;
+use tracing::{debug, error, info, trace};
+
+use crate::models::player::{Player, Vitals};
+use crate::rooms::Room;
+use crate::routes::game::{ErrorCode, GameMessage};
+use crate::state::AppState;
+use crate::telemetry;
+
+// Боевое состояние игрока в комнате
+#[derive(Debug, Clone)]
+pub struct Combatant {
+    pub vitals: Vitals,
+    // Когда погибший игрок возродится; None — игрок жив
+    pub respawn_at: Option<Instant>,
+    last_attack: Option<Instant>,
+}
+
+impl Combatant {
+    pub fn new(vitals: Vitals) -> Self {
+        Combatant { vitals, respawn_at: None, last_attack: None }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.vitals.is_dead()
+    }
+}
+
+// Атаки запрещены, если атакующий или цель в зоне карты со свойством pvp = false
+fn pvp_disabled(room: &Room, point: (f64, f64)) -> bool {
+    room.map.as_deref().is_some_and(|map| {
+        map.zones_at(point).any(|zone| zone.properties.get("pvp").and_then(|value| value.as_bool()) == Some(false))
+    })
+}
+
+// Атака attacker по target: наносит game.attack_damage урона и рассылает Damaged
+// (и Died, если цель погибла). Ошибка — код и текст для клиента
+pub async fn attack(app_state: &AppState, room: &Room, attacker: i32, target: i32) -> Result<(), (ErrorCode, String)> {
+    let config = &app_state.config.game;
+    if attacker == target {
+        return Err((ErrorCode::InvalidTarget, "cannot attack yourself".to_string()));
+    }
+    let (from, to) = {
+        let positions = room.active_player_positions.lock().await;
+        match (positions.get(&attacker), positions.get(&target)) {
+            (Some(from), Some(to)) => ((from.x, from.y), (to.x, to.y)),
+            _ => return Err((ErrorCode::InvalidTarget, format!("player {} is not in this room", target))),
+        }
+    };
+    let distance = (to.0 - from.0).hypot(to.1 - from.1);
+    if distance > config.attack_range {
+        return Err((ErrorCode::OutOfReach, format!("target is {:.1} tiles away, attack range is {}", distance, config.attack_range)));
+    }
+    if pvp_disabled(room, from) || pvp_disabled(room, to) {
+        return Err((ErrorCode::InvalidTarget, "attacks are disabled in this zone".to_string()));
+    }
+
+    let now = Instant::now();
+    let (vitals, amount) = {
+        let mut combatants = room.combatants.lock().await;
+        let Some(attacker_state) = combatants.get(&attacker) else {
+            return Err((ErrorCode::InvalidTarget, "attacker is not in this room".to_string()));
+        };
+        if attacker_state.is_dead() {
+            return Err((ErrorCode::PlayerDead, "dead players cannot attack".to_string()));
+        }
+        if attacker_state.last_attack.is_some_and(|last| now.duration_since(last) < app_state.config.attack_cooldown()) {
+            return Err((ErrorCode::OnCooldown, "attack is on cooldown".to_string()));
+        }
+        let target_state = match combatants.get_mut(&target) {
+            Some(target_state) if !target_state.is_dead() => target_state,
+            Some(_) => return Err((ErrorCode::InvalidTarget, format!("player {} is already dead", target))),
+            None => return Err((ErrorCode::InvalidTarget, format!("player {} is not in this room", target))),
+        };
+        let amount = config.attack_damage.min(target_state.vitals.health);
+        target_state.vitals.health -= amount;
+        if target_state.is_dead() {
+            target_state.respawn_at = Some(now + app_state.config.respawn_delay());
+        }
+        let vitals = target_state.vitals;
+        if let Some(attacker_state) = combatants.get_mut(&attacker) {
+            attacker_state.last_attack = Some(now);
+        }
+        (vitals, amount)
+    };
+
+    trace!(target, amount, health = vitals.health, "Attack hit");
+    let _ = room.game_state_tx.send(GameMessage::Damaged { user_id: target, attacker, amount, health: vitals.health });
+    if vitals.is_dead() {
+        info!(target, "Player killed");
+        let _ = room.game_state_tx.send(GameMessage::Died { user_id: target, killer: attacker });
+    }
+    save_vitals(app_state, target, &vitals).await;
+    Ok(())
+}
+
+// Возрождает игроков комнаты, чей таймер истек: полное здоровье, ближайшая к месту
+// гибели точка возрождения и сообщение Respawned. Вызывается игровым циклом
+pub async fn respawn_due(app_state: &AppState, room: &Room) {
+    let now = Instant::now();
+    let due: Vec<(i32, Vitals)> = {
+        let mut combatants = room.combatants.lock().await;
+        combatants
+            .iter_mut()
+            .filter(|(_, combatant)| combatant.respawn_at.is_some_and(|at| at <= now))
+            .map(|(&user_id, combatant)| {
+                *combatant = Combatant::new(Vitals::full(combatant.vitals.max_health));
+                (user_id, combatant.vitals)
+            })
+            .collect()
+    };
+
+    for (user_id, vitals) in due {
+        let position = {
+            let mut positions = room.active_player_positions.lock().await;
+            let Some(position) = positions.get_mut(&user_id) else {
+                continue;
+            };
+            (position.x, position.y) = room.respawn_point((position.x, position.y));
+            (position.vx, position.vy, position.vz) = (0.0, 0.0, 0.0);
+            position.clone()
+        };
+        room.moved_players.lock().await.insert(user_id);
+        debug!(user_id, room = %room.name, x = position.x, y = position.y, "Player respawned");
+        let _ = room.game_state_tx.send(GameMessage::Respawned { user_id, x: position.x, y: position.y, health: vitals.health });
+
+        let player = Player { user_id, x: position.x, y: position.y, z: position.z, room: room.name.clone() };
+        let query_started = Instant::now();
+        let result = app_state.storage.players.save_position(&player).await;
+        telemetry::db_query("save_position", query_started.elapsed());
+        if let Err(e) = result {
+            error!(error = %e, user_id, "Error saving respawn position");
+        }
+        save_vitals(app_state, user_id, &vitals).await;
+    }
+}
+
+pub async fn save_vitals(app_state: &AppState, user_id: i32, vitals: &Vitals) {
+    let query_started = Instant::now();
+    let result = app_state.storage.players.save_vitals(user_id, vitals).await;
+    telemetry::db_query("save_vitals", query_started.elapsed());
+    if let Err(e) = result {
+        error!(error = %e, user_id, "Error saving player health");
+    }
+}
@@ -1,17 +1,515 @@
+// src/config.rs
+// Конфигурация собирается слоями: значения по умолчанию -> TOML-файл -> переменные окружения.
+// Путь к файлу задается флагом --config <путь> или переменной CONFIG_PATH;
+// если ни то ни другое не задано, читается ./config.toml (при наличии).
 use dotenv::dotenv;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
-    pub database_url: String,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub game: GameConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    // Адрес, на котором слушает сервер. 0.0.0.0 нужен, чтобы сервер был доступен извне контейнера
+    pub bind_address: String,
+    // Сертификат и ключ в формате PEM; если заданы, сервер принимает только https:// и wss://
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    // Сообщение дня, отдается в /api/server-info
+    pub motd: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    // Применять миграции автоматически при запуске
+    pub auto_migrate: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
     pub jwt_secret: String,
+    // Время жизни JWT-токена
+    pub token_lifetime_hours: i64,
+    // Срок, в течение которого удаление аккаунта можно отменить
+    pub deletion_grace_days: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    // Емкость канала широковещания игровых сообщений
+    pub broadcast_capacity: usize,
+    // Частота игрового цикла (тиков в секунду)
+    pub tick_rate: u32,
+    // Постоянные миры; существуют всегда, даже без игроков
+    pub worlds: Vec<String>,
+    // Мир для новых игроков и для тех, чей инстанс уже удален
+    pub default_world: String,
+    // Предел числа комнат вместе с инстансами
+    pub max_rooms: usize,
+    // Радиус в чанках вокруг игрока, которые ему отправляются (квадрат 2 * N + 1)
+    pub view_distance: i32,
+    // Как далеко от себя (в тайлах) игрок может ставить и ломать тайлы
+    pub reach_distance: f64,
+    // Сид процедурной генерации; вместе с именем комнаты однозначно задает ее рельеф
+    pub world_seed: u64,
+    // "noise" — рельеф и биомы из шума, "flat" — пустой мир
+    pub generator: GeneratorKind,
+    // Карты Tiled по именам комнат (постоянных миров или инстансов)
+    pub maps: HashMap<String, PathBuf>,
+    // Здоровье нового игрока и после возрождения
+    pub max_health: i32,
+    // Урон одной атаки
+    pub attack_damage: i32,
+    // Дальность атаки в тайлах
+    pub attack_range: f64,
+    // Минимальный интервал между атаками одного игрока
+    pub attack_cooldown_ms: u64,
+    // Сколько погибший игрок ждет возрождения
+    pub respawn_delay_secs: u64,
+    // Точки возрождения по комнатам; выбирается ближайшая к месту гибели.
+    // Дополняются объектами класса respawn на карте комнаты
+    pub respawn_points: HashMap<String, Vec<(f64, f64)>>,
+    // Файл определений предметов (TOML); без него предметов в игре нет
+    pub items_path: Option<PathBuf>,
+    // Число ячеек инвентаря игрока
+    pub inventory_slots: usize,
+    // Файл определений NPC и спаунеров (TOML); без него NPC в игре нет
+    pub npcs_path: Option<PathBuf>,
+    // Каталог скриптов игровых правил (*.rhai); изменения подхватываются без перезапуска
+    pub scripts_dir: Option<PathBuf>,
+    // Наибольшее число участников группы
+    pub max_party_size: usize,
+    // Сколько отключившийся участник остается в группе
+    pub party_reconnect_secs: u64,
+    // Сохранять составы групп в базе, чтобы они переживали перезапуск сервера
+    pub persist_parties: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratorKind {
+    Noise,
+    Flat,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // Фильтр в синтаксисе tracing (например, "info" или "anarchy_core=debug,tower_http=info").
+    // Переменная RUST_LOG, если задана, имеет приоритет
+    pub level: String,
+    // "text" для человека или "json" для сборщиков логов
+    pub format: LogFormat,
+    // В горячем пути логируется только каждое N-е обновление позиции (1 — все)
+    pub position_sample_rate: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "0.0.0.0:3000".to_string(),
+            tls_cert_path: None,
+            tls_key_path: None,
+            motd: "Welcome to Anarchy Core!".to_string(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            auto_migrate: true,
+        }
+    }
 }
 
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: String::new(),
+            token_lifetime_hours: 24,
+            deletion_grace_days: 7,
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            broadcast_capacity: 128,
+            tick_rate: 20,
+            worlds: vec!["lobby".to_string()],
+            default_world: "lobby".to_string(),
+            max_rooms: 1000,
+            view_distance: 2,
+            reach_distance: 6.0,
+            world_seed: 20240601,
+            generator: GeneratorKind::Noise,
+            maps: HashMap::new(),
+            max_health: 100,
+            attack_damage: 10,
+            attack_range: 2.0,
+            attack_cooldown_ms: 500,
+            respawn_delay_secs: 5,
+            respawn_points: HashMap::new(),
+            items_path: None,
+            inventory_slots: 20,
+            npcs_path: None,
+            scripts_dir: None,
+            max_party_size: 5,
+            party_reconnect_secs: 60,
+            persist_parties: false,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+            position_sample_rate: 100,
+        }
+    }
+}
+
+impl std::str::FromStr for GeneratorKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "noise" => Ok(GeneratorKind::Noise),
+            "flat" => Ok(GeneratorKind::Flat),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    // Переменная окружения задана, но ее значение не разбирается
+    Env { var: &'static str, value: String },
+    Missing(&'static str),
+    Invalid { key: &'static str, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "cannot read config file {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {}: {}", path.display(), e),
+            ConfigError::Env { var, value } => write!(f, "environment variable {} has invalid value {:?}", var, value),
+            ConfigError::Missing(key) => write!(f, "required setting {} is not set", key),
+            ConfigError::Invalid { key, reason } => write!(f, "invalid setting {}: {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl Config {
-    pub fn from_env() -> Result<Self, env::VarError> {
+    // Полная загрузка для запуска сервера: .env, файл, окружение и проверка
+    pub fn load() -> Result<Self, ConfigError> {
         dotenv().ok();
-        Ok(Config {
-            database_url: env::var("DATABASE_URL")?,
-            jwt_secret: env::var("JWT_SECRET")?,
-        })
+        let mut config = match config_path_from_args().or_else(|| env::var("CONFIG_PATH").ok().map(PathBuf::from)) {
+            // Явно указанный файл обязан существовать
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?,
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    // Переменные окружения перекрывают значения из файла
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        override_string("BIND_ADDRESS", &mut self.server.bind_address);
+        if let Ok(path) = env::var("TLS_CERT_PATH") {
+            self.server.tls_cert_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("TLS_KEY_PATH") {
+            self.server.tls_key_path = Some(PathBuf::from(path));
+        }
+        override_string("MOTD", &mut self.server.motd);
+        override_string("DATABASE_URL", &mut self.database.url);
+        override_parsed("DB_MAX_CONNECTIONS", &mut self.database.max_connections)?;
+        override_parsed("DB_MIN_CONNECTIONS", &mut self.database.min_connections)?;
+        override_parsed("DB_ACQUIRE_TIMEOUT_SECS", &mut self.database.acquire_timeout_secs)?;
+        override_bool("AUTO_MIGRATE", &mut self.database.auto_migrate)?;
+        override_string("JWT_SECRET", &mut self.auth.jwt_secret);
+        override_parsed("TOKEN_LIFETIME_HOURS", &mut self.auth.token_lifetime_hours)?;
+        override_parsed("DELETION_GRACE_DAYS", &mut self.auth.deletion_grace_days)?;
+        override_parsed("BROADCAST_CAPACITY", &mut self.game.broadcast_capacity)?;
+        override_parsed("TICK_RATE", &mut self.game.tick_rate)?;
+        if let Ok(value) = env::var("WORLDS") {
+            self.game.worlds = value.split(',').map(|w| w.trim().to_string()).filter(|w| !w.is_empty()).collect();
+        }
+        override_string("DEFAULT_WORLD", &mut self.game.default_world);
+        override_parsed("MAX_ROOMS", &mut self.game.max_rooms)?;
+        override_parsed("VIEW_DISTANCE", &mut self.game.view_distance)?;
+        override_parsed("REACH_DISTANCE", &mut self.game.reach_distance)?;
+        override_parsed("WORLD_SEED", &mut self.game.world_seed)?;
+        override_parsed("WORLD_GENERATOR", &mut self.game.generator)?;
+        // MAPS=комната=путь,комната=путь
+        if let Ok(value) = env::var("MAPS") {
+            self.game.maps = value
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    let (room, path) = entry.split_once('=').ok_or_else(|| ConfigError::Env { var: "MAPS", value: value.clone() })?;
+                    Ok((room.trim().to_string(), PathBuf::from(path.trim())))
+                })
+                .collect::<Result<_, ConfigError>>()?;
+        }
+        override_parsed("MAX_HEALTH", &mut self.game.max_health)?;
+        override_parsed("ATTACK_DAMAGE", &mut self.game.attack_damage)?;
+        override_parsed("ATTACK_RANGE", &mut self.game.attack_range)?;
+        override_parsed("ATTACK_COOLDOWN_MS", &mut self.game.attack_cooldown_ms)?;
+        override_parsed("RESPAWN_DELAY_SECS", &mut self.game.respawn_delay_secs)?;
+        if let Ok(path) = env::var("ITEMS_PATH") {
+            self.game.items_path = Some(PathBuf::from(path));
+        }
+        override_parsed("INVENTORY_SLOTS", &mut self.game.inventory_slots)?;
+        if let Ok(path) = env::var("NPCS_PATH") {
+            self.game.npcs_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("SCRIPTS_DIR") {
+            self.game.scripts_dir = Some(PathBuf::from(path));
+        }
+        override_parsed("MAX_PARTY_SIZE", &mut self.game.max_party_size)?;
+        override_parsed("PARTY_RECONNECT_SECS", &mut self.game.party_reconnect_secs)?;
+        override_bool("PERSIST_PARTIES", &mut self.game.persist_parties)?;
+        override_string("LOG_LEVEL", &mut self.log.level);
+        override_parsed("LOG_FORMAT", &mut self.log.format)?;
+        override_parsed("LOG_POSITION_SAMPLE_RATE", &mut self.log.position_sample_rate)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.bind_address()?;
+        match (&self.server.tls_cert_path, &self.server.tls_key_path) {
+            (Some(_), None) => return Err(ConfigError::Missing("server.tls_key_path")),
+            (None, Some(_)) => return Err(ConfigError::Missing("server.tls_cert_path")),
+            (Some(cert), Some(key)) => {
+                for (key_name, path) in [("server.tls_cert_path", cert), ("server.tls_key_path", key)] {
+                    if !path.is_file() {
+                        return Err(invalid(key_name, format!("file {} does not exist", path.display())));
+                    }
+                }
+            },
+            (None, None) => {},
+        }
+
+        if self.database.url.is_empty() {
+            return Err(ConfigError::Missing("database.url (DATABASE_URL)"));
+        }
+        if self.database.max_connections == 0 {
+            return Err(invalid("database.max_connections", "must be at least 1".to_string()));
+        }
+        if self.database.min_connections > self.database.max_connections {
+            return Err(invalid("database.min_connections", "must not exceed database.max_connections".to_string()));
+        }
+
+        if self.auth.jwt_secret.is_empty() {
+            return Err(ConfigError::Missing("auth.jwt_secret (JWT_SECRET)"));
+        }
+        if self.auth.token_lifetime_hours <= 0 {
+            return Err(invalid("auth.token_lifetime_hours", "must be positive".to_string()));
+        }
+        // Верхняя граница — чтобы сроки удаления (now ± grace) не выходили за диапазон дат
+        if !(0..=3650).contains(&self.auth.deletion_grace_days) {
+            return Err(invalid("auth.deletion_grace_days", "must be between 0 and 3650".to_string()));
+        }
+
+        if self.game.broadcast_capacity == 0 {
+            return Err(invalid("game.broadcast_capacity", "must be at least 1".to_string()));
+        }
+        if !(1..=1000).contains(&self.game.tick_rate) {
+            return Err(invalid("game.tick_rate", "must be between 1 and 1000".to_string()));
+        }
+        if let Some(world) = self.game.worlds.iter().find(|w| !crate::rooms::is_valid_room_name(w)) {
+            return Err(invalid("game.worlds", format!("{:?} is not a valid room name", world)));
+        }
+        if !self.game.worlds.contains(&self.game.default_world) {
+            return Err(invalid("game.default_world", "must be one of game.worlds".to_string()));
+        }
+        if self.game.max_rooms < self.game.worlds.len() {
+            return Err(invalid("game.max_rooms", "must not be less than the number of game.worlds".to_string()));
+        }
+        if !(0..=8).contains(&self.game.view_distance) {
+            return Err(invalid("game.view_distance", "must be between 0 and 8".to_string()));
+        }
+        if !self.game.reach_distance.is_finite() || self.game.reach_distance <= 0.0 {
+            return Err(invalid("game.reach_distance", "must be positive".to_string()));
+        }
+        for (room, path) in &self.game.maps {
+            if !crate::rooms::is_valid_room_name(room) {
+                return Err(invalid("game.maps", format!("{:?} is not a valid room name", room)));
+            }
+            if !path.is_file() {
+                return Err(invalid("game.maps", format!("file {} does not exist", path.display())));
+            }
+        }
+        if self.game.max_health <= 0 {
+            return Err(invalid("game.max_health", "must be positive".to_string()));
+        }
+        if self.game.attack_damage < 0 {
+            return Err(invalid("game.attack_damage", "must not be negative".to_string()));
+        }
+        if !self.game.attack_range.is_finite() || self.game.attack_range <= 0.0 {
+            return Err(invalid("game.attack_range", "must be positive".to_string()));
+        }
+        for (room, points) in &self.game.respawn_points {
+            if !crate::rooms::is_valid_room_name(room) {
+                return Err(invalid("game.respawn_points", format!("{:?} is not a valid room name", room)));
+            }
+            if points.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+                return Err(invalid("game.respawn_points", format!("room {:?} has a non-finite point", room)));
+            }
+        }
+        if let Some(path) = self.game.items_path.as_ref().filter(|path| !path.is_file()) {
+            return Err(invalid("game.items_path", format!("file {} does not exist", path.display())));
+        }
+        if !(1..=100).contains(&self.game.inventory_slots) {
+            return Err(invalid("game.inventory_slots", "must be between 1 and 100".to_string()));
+        }
+        if let Some(path) = self.game.npcs_path.as_ref().filter(|path| !path.is_file()) {
+            return Err(invalid("game.npcs_path", format!("file {} does not exist", path.display())));
+        }
+        if let Some(path) = self.game.scripts_dir.as_ref().filter(|path| !path.is_dir()) {
+            return Err(invalid("game.scripts_dir", format!("directory {} does not exist", path.display())));
+        }
+        if !(2..=50).contains(&self.game.max_party_size) {
+            return Err(invalid("game.max_party_size", "must be between 2 and 50".to_string()));
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log.level) {
+            return Err(invalid("log.level", e.to_string()));
+        }
+        if self.log.position_sample_rate == 0 {
+            return Err(invalid("log.position_sample_rate", "must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn bind_address(&self) -> Result<SocketAddr, ConfigError> {
+        self.server.bind_address
+            .parse()
+            .map_err(|e| invalid("server.bind_address", format!("{:?}: {}", self.server.bind_address, e)))
+    }
+
+    pub fn tls_enabled(&self) -> bool {
+        self.server.tls_cert_path.is_some() && self.server.tls_key_path.is_some()
+    }
+
+    pub fn tick_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(1.0 / self.game.tick_rate as f64)
+    }
+
+    pub fn attack_cooldown(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.game.attack_cooldown_ms)
+    }
+
+    pub fn respawn_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.game.respawn_delay_secs)
+    }
+
+    pub fn party_reconnect_grace(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.game.party_reconnect_secs)
+    }
+}
+
+fn invalid(key: &'static str, reason: String) -> ConfigError {
+    ConfigError::Invalid { key, reason }
+}
+
+fn config_path_from_args() -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
     }
-}
\ No newline at end of file
+    None
+}
+
+fn override_string(var: &'static str, target: &mut String) {
+    if let Ok(value) = env::var(var) {
+        *target = value;
+    }
+}
+
+fn override_parsed<T: std::str::FromStr>(var: &'static str, target: &mut T) -> Result<(), ConfigError> {
+    if let Ok(value) = env::var(var) {
+        *target = value.trim().parse().map_err(|_| ConfigError::Env { var, value })?;
+    }
+    Ok(())
+}
+
+fn override_bool(var: &'static str, target: &mut bool) -> Result<(), ConfigError> {
+    if let Ok(value) = env::var(var) {
+        *target = match value.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" => true,
+            "false" | "0" | "no" => false,
+            _ => return Err(ConfigError::Env { var, value }),
+        };
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+// src/db.rs
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fmt;
+
+// Миграции из каталога migrations/ встраиваются в бинарник при сборке
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// Таблицы и столбцы (с типом из information_schema), которые создают встроенные миграции.
+// Меняется вместе с каталогом migrations/
+const EXPECTED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("users", "id", "integer"),
+    ("users", "login", "character varying"),
+    ("users", "hashed_password", "character varying"),
+    ("users", "created_at", "timestamp with time zone"),
+    ("users", "deletion_requested_at", "timestamp with time zone"),
+    ("players", "user_id", "integer"),
+    ("players", "x", "double precision"),
+    ("players", "y", "double precision"),
+    ("players", "z", "double precision"),
+    ("players", "is_online", "boolean"),
+    ("players", "room", "character varying"),
+    ("players", "health", "integer"),
+    ("players", "max_health", "integer"),
+    ("players", "died_at", "timestamp with time zone"),
+    ("chunks", "room", "character varying"),
+    ("chunks", "cx", "integer"),
+    ("chunks", "cy", "integer"),
+    ("chunks", "changes", "bytea"),
+    ("chunks", "updated_at", "timestamp with time zone"),
+    ("inventory_slots", "user_id", "integer"),
+    ("inventory_slots", "slot", "integer"),
+    ("inventory_slots", "item", "character varying"),
+    ("inventory_slots", "count", "integer"),
+    ("party_members", "user_id", "integer"),
+    ("party_members", "party_id", "bigint"),
+    ("party_members", "position", "integer"),
+    ("clans", "id", "integer"),
+    ("clans", "name", "character varying"),
+    ("clans", "tag", "character varying"),
+    ("clans", "created_at", "timestamp with time zone"),
+    ("clan_members", "user_id", "integer"),
+    ("clan_members", "clan_id", "integer"),
+    ("clan_members", "rank", "character varying"),
+    ("clan_members", "joined_at", "timestamp with time zone"),
+    ("clan_invites", "clan_id", "integer"),
+    ("clan_invites", "user_id", "integer"),
+    ("clan_invites", "invited_by", "integer"),
+    ("clan_invites", "created_at", "timestamp with time zone"),
+    ("friends", "user_id", "integer"),
+    ("friends", "friend_id", "integer"),
+    ("friends", "created_at", "timestamp with time zone"),
+    ("friends", "accepted_at", "timestamp with time zone"),
+];
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Database(sqlx::Error),
+    Migration(MigrateError),
+    // Таблица _sqlx_migrations отсутствует: база не инициализирована
+    NotInitialized,
+    // В базе есть миграции, неизвестные этому бинарнику (база новее сервера)
+    UnknownMigration(i64),
+    // Содержимое примененной миграции отличается от встроенной
+    ChecksumMismatch(i64),
+    // Миграция была прервана и оставила базу в неопределенном состоянии
+    Dirty(i64),
+    // Встроенные миграции, еще не примененные к базе
+    Pending(Vec<i64>),
+    // Таблица или столбец (table.column), которых нет в базе, хотя миграции применены
+    MissingColumn(String),
+    // Столбец, тип которого изменен вручную: (table.column, ожидаемый тип, тип в базе)
+    ColumnType(String, &'static str, String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Database(e) => write!(f, "database error: {}", e),
+            SchemaError::Migration(e) => write!(f, "migration failed: {}", e),
+            SchemaError::NotInitialized => write!(f, "database schema is not initialized; run with AUTO_MIGRATE=true or --migrate"),
+            SchemaError::UnknownMigration(v) => write!(f, "database has migration {} unknown to this server build", v),
+            SchemaError::ChecksumMismatch(v) => write!(f, "migration {} was modified after being applied", v),
+            SchemaError::Dirty(v) => write!(f, "migration {} is partially applied; fix the database manually", v),
+            SchemaError::Pending(v) => write!(f, "pending migrations {:?}; run with AUTO_MIGRATE=true or --migrate", v),
+            SchemaError::MissingColumn(c) => write!(f, "column {} is missing from the database", c),
+            SchemaError::ColumnType(c, expected, actual) => write!(f, "column {} has type {}, expected {}", c, actual, expected),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<sqlx::Error> for SchemaError {
+    fn from(e: sqlx::Error) -> Self {
+        SchemaError::Database(e)
+    }
+}
+
+// Применяет встроенные миграции (если разрешено) и проверяет, что схема базы
+// совпадает с той, под которую собран сервер
+pub async fn prepare_schema(pool: &PgPool, auto_migrate: bool) -> Result<(), SchemaError> {
+    if auto_migrate {
+        MIGRATOR.run(pool).await.map_err(SchemaError::Migration)?;
+    }
+    verify_schema(pool).await
+}
+
+// Сверяет журнал _sqlx_migrations со встроенными миграциями, а затем сами таблицы
+// с EXPECTED_COLUMNS: так обнаруживаются и изменения схемы в обход миграций
+pub async fn verify_schema(pool: &PgPool) -> Result<(), SchemaError> {
+    let initialized: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+    if !initialized {
+        return Err(SchemaError::NotInitialized);
+    }
+
+    let applied: Vec<(i64, Vec<u8>, bool)> = sqlx::query_as(
+        "SELECT version, checksum, success FROM _sqlx_migrations ORDER BY version"
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let embedded: HashMap<i64, &[u8]> = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| (m.version, m.checksum.as_ref()))
+        .collect();
+
+    for (version, checksum, success) in &applied {
+        if !success {
+            return Err(SchemaError::Dirty(*version));
+        }
+        match embedded.get(version) {
+            None => return Err(SchemaError::UnknownMigration(*version)),
+            Some(expected) if *expected != checksum.as_slice() => return Err(SchemaError::ChecksumMismatch(*version)),
+            Some(_) => {},
+        }
+    }
+
+    let mut pending: Vec<i64> = embedded
+        .keys()
+        .filter(|v| !applied.iter().any(|(applied_v, _, _)| applied_v == *v))
+        .copied()
+        .collect();
+    if !pending.is_empty() {
+        pending.sort_unstable();
+        return Err(SchemaError::Pending(pending));
+    }
+
+    verify_columns(pool).await
+}
+
+// Лишние таблицы и столбцы не мешают серверу и ошибкой не считаются
+async fn verify_columns(pool: &PgPool) -> Result<(), SchemaError> {
+    let columns: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT table_name::TEXT, column_name::TEXT, data_type::TEXT FROM information_schema.columns
+         WHERE table_schema = current_schema()"
+    )
+        .fetch_all(pool)
+        .await?;
+    let actual: HashMap<(&str, &str), &str> = columns
+        .iter()
+        .map(|(table, column, data_type)| ((table.as_str(), column.as_str()), data_type.as_str()))
+        .collect();
+
+    for (table, column, expected) in EXPECTED_COLUMNS {
+        match actual.get(&(*table, *column)) {
+            None => return Err(SchemaError::MissingColumn(format!("{}.{}", table, column))),
+            Some(data_type) if data_type != expected => {
+                return Err(SchemaError::ColumnType(format!("{}.{}", table, column), expected, data_type.to_string()));
+            },
+            Some(_) => {},
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,212 @@
+// src/entities.rs
+// Сущности мира, кроме игроков: лежащие предметы, NPC, снаряды. У каждой сущности есть
+// выданный сервером id, вид и набор компонентов. Клиенты с возможностью "entities"
+// получают их так же, как игроков: список при входе в комнату (InitialEntities),
+// появление (EntitySpawned), изменения раз в тик (EntityUpdated) и исчезновение (EntityDespawned)
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tracing::trace;
+
+use crate::items::ItemStack;
+use crate::models::player::Vitals;
+use crate::npc::Behavior;
+use crate::rooms::Room;
+use crate::routes::game::GameMessage;
+
+// Имя возможности протокола, согласуемой в Hello/Welcome
+pub const FEATURE: &str = "entities";
+
+pub type EntityId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Item,
+    Npc,
+    Projectile,
+}
+
+// Данные компонентов; у сущности заполнены только нужные ее виду
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Components {
+    // Лежащий предмет
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<ItemStack>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vitals: Option<Vitals>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Игрок, создавший сущность (например, выпустивший снаряд)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<i32>,
+    // Текущее поведение NPC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behavior: Option<Behavior>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub id: EntityId,
+    pub kind: EntityKind,
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub z: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    // Скорость в единицах в секунду; игровой цикл сам перемещает сущность
+    #[serde(default)]
+    pub vx: f64,
+    #[serde(default)]
+    pub vy: f64,
+    #[serde(default)]
+    pub vz: f64,
+    #[serde(flatten)]
+    pub components: Components,
+    // Когда сущность исчезнет сама (снаряды); клиенту не передается
+    #[serde(skip)]
+    pub expires_at: Option<Instant>,
+}
+
+impl Entity {
+    pub fn new(kind: EntityKind, (x, y): (f64, f64), components: Components) -> Self {
+        Entity {
+            id: 0,
+            kind,
+            x,
+            y,
+            z: 0.0,
+            rotation: 0.0,
+            vx: 0.0,
+            vy: 0.0,
+            vz: 0.0,
+            components,
+            expires_at: None,
+        }
+    }
+
+    pub fn position(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    fn is_moving(&self) -> bool {
+        self.vx != 0.0 || self.vy != 0.0 || self.vz != 0.0
+    }
+}
+
+// Сущности комнаты
+#[derive(Debug)]
+pub struct EntityStore {
+    entities: HashMap<EntityId, Entity>,
+    next_id: EntityId,
+    // Сущности, измененные с прошлого тика; их состояние рассылает игровой цикл
+    changed: HashSet<EntityId>,
+}
+
+impl Default for EntityStore {
+    fn default() -> Self {
+        EntityStore { entities: HashMap::new(), next_id: 1, changed: HashSet::new() }
+    }
+}
+
+impl EntityStore {
+    // Добавляет сущность, назначая ей id
+    pub fn insert(&mut self, mut entity: Entity) -> &Entity {
+        entity.id = self.next_id;
+        self.next_id += 1;
+        self.entities.entry(entity.id).or_insert(entity)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    // Изменяет сущность; новое состояние разошлет игровой цикл на ближайшем тике
+    pub fn update<R>(&mut self, id: EntityId, f: impl FnOnce(&mut Entity) -> R) -> Option<R> {
+        let entity = self.entities.get_mut(&id)?;
+        self.changed.insert(id);
+        Some(f(entity))
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        self.changed.remove(&id);
+        self.entities.remove(&id)
+    }
+
+    // Все сущности по возрастанию id
+    pub fn all(&self) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = self.entities.values().cloned().collect();
+        entities.sort_by_key(|entity| entity.id);
+        entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    // Шаг игрового цикла длительностью dt секунд: движущиеся сущности смещаются по скорости,
+    // истекшие удаляются. Возвращает измененные с прошлого шага сущности и id удаленных
+    pub fn step(&mut self, dt: f64, now: Instant) -> (Vec<Entity>, Vec<EntityId>) {
+        let mut expired: Vec<EntityId> = self.entities
+            .values()
+            .filter(|entity| entity.expires_at.is_some_and(|at| at <= now))
+            .map(|entity| entity.id)
+            .collect();
+        expired.sort_unstable();
+        for id in &expired {
+            self.remove(*id);
+        }
+
+        for entity in self.entities.values_mut().filter(|entity| entity.is_moving()) {
+            entity.x += entity.vx * dt;
+            entity.y += entity.vy * dt;
+            entity.z += entity.vz * dt;
+            self.changed.insert(entity.id);
+        }
+        let mut changed: Vec<Entity> = self.changed
+            .drain()
+            .filter_map(|id| self.entities.get(&id).cloned())
+            .collect();
+        changed.sort_by_key(|entity| entity.id);
+        (changed, expired)
+    }
+}
+
+// Создает сущность в комнате и сообщает о ней игрокам
+pub async fn spawn(room: &Room, entity: Entity) -> Entity {
+    let mut entities = room.entities.lock().await;
+    let entity = entities.insert(entity).clone();
+    trace!(id = entity.id, kind = ?entity.kind, room = %room.name, "Entity spawned");
+    // Рассылка под блокировкой: EntitySpawned не может прийти после EntityDespawned
+    let _ = room.game_state_tx.send(GameMessage::EntitySpawned(entity.clone()));
+    entity
+}
+
+// Удаляет сущность из комнаты и сообщает об этом игрокам
+pub async fn despawn(room: &Room, id: EntityId) -> Option<Entity> {
+    let mut entities = room.entities.lock().await;
+    let entity = entities.remove(id)?;
+    trace!(id, room = %room.name, "Entity despawned");
+    let _ = room.game_state_tx.send(GameMessage::EntityDespawned { id });
+    Some(entity)
+}
+
+// Шаг сущностей комнаты; вызывается игровым циклом каждый тик
+pub async fn tick(room: &Room, dt: f64) {
+    let mut entities = room.entities.lock().await;
+    if entities.is_empty() {
+        return;
+    }
+    let (changed, expired) = entities.step(dt, Instant::now());
+    for id in expired {
+        let _ = room.game_state_tx.send(GameMessage::EntityDespawned { id });
+    }
+    for entity in changed {
+        let _ = room.game_state_tx.send(GameMessage::EntityUpdated(entity));
+    }
+}
@@ -0,0 +1,166 @@
+// src/friends.rs
+// Друзья игрока. Запросы дружбы отправляются, принимаются и отклоняются через REST (/api/friends),
+// дружба хранится в базе, а статус онлайн берется из сессий игроков. Друзья в игре получают
+// FriendOnline и FriendOffline через AppState::players_tx, когда игрок входит в игру и выходит из нее,
+// а новые друзья онлайн — FriendOnline друг о друге при принятии запроса
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::{error, info};
+
+use crate::routes::game::GameMessage;
+use crate::state::AppState;
+use crate::storage::StorageError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Friend {
+    pub user_id: i32,
+    pub login: String,
+    pub online: bool,
+    // Когда запрос дружбы был принят
+    pub since: DateTime<Utc>,
+}
+
+// Запрос дружбы; user_id и login — другого игрока (отправителя входящего, получателя исходящего)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriendRequest {
+    pub user_id: i32,
+    pub login: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriendList {
+    pub friends: Vec<Friend>,
+    pub incoming: Vec<FriendRequest>,
+    pub outgoing: Vec<FriendRequest>,
+}
+
+#[derive(Debug)]
+pub enum FriendError {
+    // Запрос дружбы самому себе
+    Invalid(String),
+    NotFound(String),
+    // Игроки уже друзья или между ними есть запрос
+    Conflict(String),
+    Storage(StorageError),
+}
+
+impl fmt::Display for FriendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FriendError::Invalid(message) | FriendError::NotFound(message) | FriendError::Conflict(message) => f.write_str(message),
+            FriendError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<StorageError> for FriendError {
+    fn from(e: StorageError) -> Self {
+        FriendError::Storage(e)
+    }
+}
+
+type FriendResult<T> = Result<T, FriendError>;
+
+pub async fn list(app_state: &AppState, user_id: i32) -> FriendResult<FriendList> {
+    let friends = &app_state.storage.friends;
+    Ok(FriendList {
+        friends: friends.friends(user_id).await?,
+        incoming: friends.incoming_requests(user_id).await?,
+        outgoing: friends.outgoing_requests(user_id).await?,
+    })
+}
+
+pub async fn send_request(app_state: &AppState, user_id: i32, target: i32) -> FriendResult<()> {
+    if target == user_id {
+        return Err(FriendError::Invalid("cannot befriend yourself".to_string()));
+    }
+    if app_state.storage.users.find_by_id(target).await?.is_none() {
+        return Err(FriendError::NotFound(format!("player {} does not exist", target)));
+    }
+    match app_state.storage.friends.create_request(user_id, target).await {
+        Ok(()) => {},
+        Err(StorageError::Conflict(_)) => {
+            return Err(FriendError::Conflict(format!("already friends or a request with player {} is pending", target)));
+        },
+        Err(e) => return Err(e.into()),
+    }
+    info!(user_id, target, "Friend request sent");
+    Ok(())
+}
+
+pub async fn accept(app_state: &AppState, user_id: i32, from: i32) -> FriendResult<()> {
+    if !app_state.storage.friends.accept_request(from, user_id).await? {
+        return Err(FriendError::NotFound(format!("no friend request from player {}", from)));
+    }
+    info!(user_id, from, "Friend request accepted");
+    notify_new_friends(app_state, user_id, from).await;
+    Ok(())
+}
+
+pub async fn decline(app_state: &AppState, user_id: i32, from: i32) -> FriendResult<()> {
+    if !app_state.storage.friends.delete_request(from, user_id).await? {
+        return Err(FriendError::NotFound(format!("no friend request from player {}", from)));
+    }
+    Ok(())
+}
+
+pub async fn remove(app_state: &AppState, user_id: i32, friend: i32) -> FriendResult<()> {
+    if !app_state.storage.friends.remove_friend(user_id, friend).await? {
+        return Err(FriendError::NotFound(format!("player {} is not your friend", friend)));
+    }
+    info!(user_id, friend, "Friend removed");
+    Ok(())
+}
+
+// Сообщает друзьям онлайн о входе (online) или выходе игрока
+async fn notify_friends(app_state: &AppState, user_id: i32, online: bool) {
+    let friends = match app_state.storage.friends.friends(user_id).await {
+        Ok(friends) => friends,
+        Err(e) => {
+            error!(error = %e, "Error loading friends for presence notification");
+            return;
+        },
+    };
+    for friend in friends.into_iter().filter(|friend| friend.online) {
+        let msg = match online {
+            true => GameMessage::FriendOnline { user_id, to: friend.user_id },
+            false => GameMessage::FriendOffline { user_id, to: friend.user_id },
+        };
+        // Ошибка означает лишь отсутствие подписчиков
+        let _ = app_state.players_tx.send(msg);
+    }
+}
+
+// Новые друзья, которые оба в игре, узнают друг о друге сразу, а не при следующем входе
+async fn notify_new_friends(app_state: &AppState, user_id: i32, friend: i32) {
+    let friends = match app_state.storage.friends.friends(user_id).await {
+        Ok(friends) => friends,
+        Err(e) => {
+            error!(error = %e, "Error loading friends for presence notification");
+            return;
+        },
+    };
+    if !friends.iter().any(|f| f.user_id == friend && f.online) {
+        return;
+    }
+    match app_state.storage.friends.friends(friend).await {
+        Ok(friends) if friends.iter().any(|f| f.user_id == user_id && f.online) => {
+            let _ = app_state.players_tx.send(GameMessage::FriendOnline { user_id: friend, to: user_id });
+            let _ = app_state.players_tx.send(GameMessage::FriendOnline { user_id, to: friend });
+        },
+        Ok(_) => {},
+        Err(e) => error!(error = %e, "Error loading friends for presence notification"),
+    }
+}
+
+// Игрок вошел в игру (сессия уже открыта)
+pub async fn connected(app_state: &AppState, user_id: i32) {
+    notify_friends(app_state, user_id, true).await;
+}
+
+// Игрок вышел из игры (сессия уже закрыта)
+pub async fn disconnected(app_state: &AppState, user_id: i32) {
+    notify_friends(app_state, user_id, false).await;
+}
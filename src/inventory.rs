@@ -0,0 +1,172 @@
+// src/inventory.rs
+// Действия игрока с инвентарем: перекладывание, использование, выбрасывание и подбор предметов.
+// Инвентарь меняется и записывается в хранилище под блокировкой AppState::inventories, как и
+// при обмене, поэтому запись одного действия не затирает результат другого. Каждое действие
+// возвращает новое состояние инвентаря для его владельца. Инвентарь один на все соединения
+// игрока: он читается с первым из них и выгружается с последним
+use std::time::Instant;
+use tracing::{debug, error};
+
+use crate::combat;
+use crate::entities::{self, Components, Entity, EntityId, EntityKind};
+use crate::items::Inventory;
+use crate::rooms::Room;
+use crate::routes::game::{ErrorCode, GameMessage};
+use crate::state::AppState;
+use crate::telemetry;
+
+type ActionResult = Result<Inventory, (ErrorCode, String)>;
+
+fn invalid(message: String) -> (ErrorCode, String) {
+    (ErrorCode::InvalidItemAction, message)
+}
+
+// Инвентарь для нового соединения игрока: из хранилища читается, только если он еще не
+// загружен другим соединением
+pub async fn load(app_state: &AppState, user_id: i32) -> Inventory {
+    if let Some(inventory) = app_state.inventories.lock().await.get(&user_id) {
+        return inventory.clone();
+    }
+    let stacks = app_state.storage.inventories.load_inventory(user_id)
+        .await
+        .unwrap_or_else(|e| {
+            error!(error = %e, user_id, "Error fetching inventory from DB");
+            Vec::new()
+        });
+    let inventory = Inventory::new(app_state.config.game.inventory_slots, stacks);
+    // Параллельно открытое соединение могло успеть загрузить инвентарь раньше
+    app_state.inventories.lock().await.entry(user_id).or_insert(inventory).clone()
+}
+
+// Закрыто последнее соединение игрока; все изменения уже записаны. Если за это время
+// открылось новое соединение, инвентарь остается загруженным
+pub async fn unload(app_state: &AppState, user_id: i32) {
+    let mut inventories = app_state.inventories.lock().await;
+    if !app_state.is_connected(user_id) {
+        inventories.remove(&user_id);
+    }
+}
+
+// Вызывается под блокировкой AppState::inventories
+async fn save(app_state: &AppState, user_id: i32, inventory: &Inventory) {
+    let query_started = Instant::now();
+    let result = app_state.storage.inventories.save_inventory(user_id, &inventory.stacks()).await;
+    telemetry::db_query("save_inventory", query_started.elapsed());
+    if let Err(e) = result {
+        error!(error = %e, user_id, "Error saving inventory");
+    }
+}
+
+async fn position(room: &Room, user_id: i32) -> Result<(f64, f64), (ErrorCode, String)> {
+    room.active_player_positions.lock().await
+        .get(&user_id)
+        .map(|p| (p.x, p.y))
+        .ok_or_else(|| invalid("player is not in this room".to_string()))
+}
+
+pub async fn move_item(app_state: &AppState, user_id: i32, from: usize, to: usize) -> ActionResult {
+    let inventory = {
+        let mut inventories = app_state.inventories.lock().await;
+        let inventory = inventories.get_mut(&user_id).ok_or_else(|| invalid("inventory is not loaded".to_string()))?;
+        inventory.move_stack(from, to, |item| app_state.items.max_stack(item)).map_err(invalid)?;
+        save(app_state, user_id, inventory).await;
+        inventory.clone()
+    };
+    Ok(inventory)
+}
+
+// Использует один предмет из ячейки; сейчас единственный эффект — лечение
+pub async fn use_item(app_state: &AppState, room: &Room, user_id: i32, slot: usize) -> ActionResult {
+    let (inventory, amount, vitals) = {
+        let mut inventories = app_state.inventories.lock().await;
+        let inventory = inventories.get_mut(&user_id).ok_or_else(|| invalid("inventory is not loaded".to_string()))?;
+        let stack = inventory.get(slot).ok_or_else(|| invalid(format!("slot {} is empty", slot)))?;
+        let heal = app_state.items
+            .get(&stack.item)
+            .and_then(|definition| definition.heal)
+            .ok_or_else(|| invalid(format!("{} cannot be used", stack.item)))?;
+
+        let mut combatants = room.combatants.lock().await;
+        let combatant = combatants.get_mut(&user_id).ok_or_else(|| invalid("player is not in this room".to_string()))?;
+        if combatant.is_dead() {
+            return Err((ErrorCode::PlayerDead, "dead players cannot use items".to_string()));
+        }
+        let vitals = &mut combatant.vitals;
+        let amount = heal.min(vitals.max_health - vitals.health);
+        if amount == 0 {
+            return Err(invalid("already at full health".to_string()));
+        }
+        vitals.health += amount;
+        let vitals = *vitals;
+        drop(combatants);
+        inventory.take(slot, 1);
+        save(app_state, user_id, inventory).await;
+        (inventory.clone(), amount, vitals)
+    };
+    let _ = room.game_state_tx.send(GameMessage::Healed { user_id, amount, health: vitals.health });
+    combat::save_vitals(app_state, user_id, &vitals).await;
+    Ok(inventory)
+}
+
+// Выбрасывает count предметов (без count — всю стопку) под ноги игроку
+pub async fn drop_item(app_state: &AppState, room: &Room, user_id: i32, slot: usize, count: Option<u32>) -> ActionResult {
+    if count == Some(0) {
+        return Err(invalid("count must be positive".to_string()));
+    }
+    let (x, y) = position(room, user_id).await?;
+    let (inventory, stack) = {
+        let mut inventories = app_state.inventories.lock().await;
+        let inventory = inventories.get_mut(&user_id).ok_or_else(|| invalid("inventory is not loaded".to_string()))?;
+        let stack = inventory.take(slot, count.unwrap_or(u32::MAX)).ok_or_else(|| invalid(format!("slot {} is empty", slot)))?;
+        save(app_state, user_id, inventory).await;
+        (inventory.clone(), stack)
+    };
+    let dropped = entities::spawn(room, Entity::new(EntityKind::Item, (x, y), Components { item: Some(stack), ..Default::default() })).await;
+    debug!(id = dropped.id, "Item dropped");
+    Ok(inventory)
+}
+
+// Подбирает лежащий предмет в пределах game.reach_distance; что не поместилось, остается лежать
+pub async fn pick_up(app_state: &AppState, room: &Room, user_id: i32, id: EntityId) -> ActionResult {
+    let (x, y) = position(room, user_id).await?;
+    let (inventory, count) = {
+        let mut entities = room.entities.lock().await;
+        let Some((item, distance)) = entities.get(id).and_then(|entity| {
+            let stack = entity.components.item.clone()?;
+            Some((stack, (entity.x - x).hypot(entity.y - y)))
+        }) else {
+            return Err(invalid(format!("item {} is not in this room", id)));
+        };
+        let reach = app_state.config.game.reach_distance;
+        if distance > reach {
+            return Err((ErrorCode::OutOfReach, format!("item is {:.1} tiles away, reach is {}", distance, reach)));
+        }
+
+        let mut inventories = app_state.inventories.lock().await;
+        let inventory = inventories.get_mut(&user_id).ok_or_else(|| invalid("inventory is not loaded".to_string()))?;
+        let max_stack = app_state.items.max_stack(&item.item);
+        let count = item.count.min(inventory.capacity_for(&item.item, max_stack));
+        if count == 0 {
+            return Err(invalid("inventory is full".to_string()));
+        }
+        inventory.add(&item.item, count, max_stack);
+        // Рассылка под блокировкой сущностей, как в entities::despawn
+        let _ = room.game_state_tx.send(GameMessage::ItemPickedUp { id, user_id, count });
+        if count == item.count {
+            entities.remove(id);
+            let _ = room.game_state_tx.send(GameMessage::EntityDespawned { id });
+        } else {
+            entities.update(id, |entity| {
+                if let Some(stack) = entity.components.item.as_mut() {
+                    stack.count -= count;
+                }
+            });
+        }
+        // Комнату не держим, пока идет запись
+        drop(entities);
+        save(app_state, user_id, inventory).await;
+        (inventory.clone(), count)
+    };
+    debug!(id, count, "Item picked up");
+    Ok(inventory)
+}
@@ -0,0 +1,226 @@
+// src/items.rs
+// Предметы: определения из файла данных (game.items_path), инвентари игроков со стопками
+// и ограниченным числом ячеек и предметы, выброшенные в мир
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::config::GameConfig;
+
+// Имя возможности протокола, согласуемой в Hello/Welcome
+pub const FEATURE: &str = "inventory";
+// Ограничение длины id предмета (совпадает с размером колонки inventory_slots.item)
+pub const MAX_ITEM_ID_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    // Сколько предметов помещается в одну ячейку
+    #[serde(default = "single")]
+    pub max_stack: u32,
+    // Использование восстанавливает столько здоровья и расходует предмет
+    #[serde(default)]
+    pub heal: Option<i32>,
+}
+
+fn single() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemsFile {
+    #[serde(default, rename = "item")]
+    items: Vec<ItemDefinition>,
+}
+
+#[derive(Debug)]
+pub enum ItemError {
+    Read(PathBuf, std::io::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemError::Read(path, e) => write!(f, "cannot read item definitions {}: {}", path.display(), e),
+            ItemError::Invalid(path, reason) => write!(f, "invalid item definitions {}: {}", path.display(), reason),
+        }
+    }
+}
+
+impl std::error::Error for ItemError {}
+
+// Определения предметов по id
+#[derive(Debug, Default)]
+pub struct ItemRegistry {
+    items: HashMap<String, ItemDefinition>,
+}
+
+impl ItemRegistry {
+    // Файл TOML с таблицами [[item]]
+    pub fn load(path: &Path) -> Result<ItemRegistry, ItemError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ItemError::Read(path.to_path_buf(), e))?;
+        let file: ItemsFile = toml::from_str(&text).map_err(|e| ItemError::Invalid(path.to_path_buf(), e.to_string()))?;
+        ItemRegistry::from_definitions(file.items).map_err(|reason| ItemError::Invalid(path.to_path_buf(), reason))
+    }
+
+    pub fn from_definitions(definitions: Vec<ItemDefinition>) -> Result<ItemRegistry, String> {
+        let mut items = HashMap::new();
+        for definition in definitions {
+            if definition.id.is_empty() || definition.id.len() > MAX_ITEM_ID_LEN {
+                return Err(format!("item id {:?} must be 1 to {} characters long", definition.id, MAX_ITEM_ID_LEN));
+            }
+            if definition.max_stack == 0 {
+                return Err(format!("item {:?} must have max_stack of at least 1", definition.id));
+            }
+            if definition.heal.is_some_and(|heal| heal <= 0) {
+                return Err(format!("item {:?} must heal a positive amount", definition.id));
+            }
+            if let Some(duplicate) = items.insert(definition.id.clone(), definition) {
+                return Err(format!("item {:?} is defined twice", duplicate.id));
+            }
+        }
+        Ok(ItemRegistry { items })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Предметы, исчезнувшие из файла данных, не складываются в стопки
+    pub fn max_stack(&self, id: &str) -> u32 {
+        self.get(id).map_or(1, |definition| definition.max_stack)
+    }
+}
+
+// Определения из game.items_path; без файла предметов нет
+pub fn load_items(config: &GameConfig) -> Result<ItemRegistry, ItemError> {
+    let Some(path) = &config.items_path else {
+        return Ok(ItemRegistry::default());
+    };
+    let registry = ItemRegistry::load(path)?;
+    info!(path = %path.display(), items = registry.len(), "Loaded item definitions");
+    Ok(registry)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+// Ячейки инвентаря; None — пустая ячейка
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    // Сохраненные стопки раскладываются по своим ячейкам; не поместившиеся в size отбрасываются
+    pub fn new(size: usize, stacks: Vec<(u32, ItemStack)>) -> Self {
+        let mut slots = vec![None; size];
+        for (slot, stack) in stacks {
+            if let Some(cell) = slots.get_mut(slot as usize) {
+                *cell = Some(stack);
+            }
+        }
+        Inventory { slots }
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    // Непустые ячейки с номерами (для хранилища)
+    pub fn stacks(&self) -> Vec<(u32, ItemStack)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, stack)| Some((slot as u32, stack.clone()?)))
+            .collect()
+    }
+
+    // Сколько предметов item еще поместится
+    pub fn capacity_for(&self, item: &str, max_stack: u32) -> u32 {
+        self.slots
+            .iter()
+            .map(|slot| match slot {
+                None => max_stack,
+                Some(stack) if stack.item == item => max_stack.saturating_sub(stack.count),
+                Some(_) => 0,
+            })
+            .sum()
+    }
+
+    // Добавляет предметы: сначала в неполные стопки, затем в пустые ячейки.
+    // Возвращает число не поместившихся
+    pub fn add(&mut self, item: &str, mut count: u32, max_stack: u32) -> u32 {
+        for stack in self.slots.iter_mut().flatten().filter(|stack| stack.item == item) {
+            let added = count.min(max_stack.saturating_sub(stack.count));
+            stack.count += added;
+            count -= added;
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if count == 0 {
+                break;
+            }
+            let added = count.min(max_stack);
+            *slot = Some(ItemStack { item: item.to_string(), count: added });
+            count -= added;
+        }
+        count
+    }
+
+    // Забирает count предметов из ячейки (всю стопку, если count больше)
+    pub fn take(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let cell = self.slots.get_mut(slot)?;
+        let stack = cell.as_mut()?;
+        if count == 0 {
+            return None;
+        }
+        if count >= stack.count {
+            return cell.take();
+        }
+        stack.count -= count;
+        Some(ItemStack { item: stack.item.clone(), count })
+    }
+
+    // Перекладывает стопку: одинаковые предметы объединяются до max_stack, разные меняются местами
+    pub fn move_stack(&mut self, from: usize, to: usize, max_stack: impl Fn(&str) -> u32) -> Result<(), String> {
+        if from >= self.slots.len() || to >= self.slots.len() {
+            return Err(format!("inventory has {} slots", self.slots.len()));
+        }
+        if self.slots[from].is_none() {
+            return Err(format!("slot {} is empty", from));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if let (Some(source), Some(target)) = (&self.slots[from], &self.slots[to]) {
+            if source.item == target.item {
+                let moved = source.count.min(max_stack(&source.item).saturating_sub(target.count));
+                if let Some(target) = self.slots[to].as_mut() {
+                    target.count += moved;
+                }
+                self.take(from, moved);
+                return Ok(());
+            }
+        }
+        self.slots.swap(from, to);
+        Ok(())
+    }
+}
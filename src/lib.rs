@@ -0,0 +1,31 @@
+// src/lib.rs
+pub mod clans;
+pub mod collision;
+pub mod combat;
+pub mod config;
+pub mod db;
+pub mod entities;
+pub mod friends;
+pub mod inventory;
+pub mod items;
+pub mod logging;
+pub mod map;
+pub mod models;
+pub mod npc;
+pub mod party;
+pub mod pathfinding;
+pub mod rooms;
+pub mod routes;
+pub mod scripting;
+pub mod snapshot;
+pub mod state;
+pub mod storage;
+pub mod telemetry;
+pub mod tiles;
+pub mod trading;
+pub mod world;
+pub mod worldgen;
+pub mod xml;
+
+#[cfg(feature = "testing")]
+pub mod testing;
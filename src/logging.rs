@@ -0,0 +1,20 @@
+// src/logging.rs
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogConfig, LogFormat};
+
+// Устанавливает глобальный подписчик tracing. RUST_LOG перекрывает log.level
+pub fn init(config: &LogConfig) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(&config.level));
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(true);
+
+    match config.format {
+        LogFormat::Text => builder.init(),
+        // В JSON поля текущего span (user_id, conn_id) попадают в каждую запись
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(false).init(),
+    }
+}
@@ -1,55 +1,145 @@
 // src/main.rs
-mod config;
-mod routes;
-mod models;
-mod state;
-
-use axum::{Router, serve, Extension};
+use axum::serve;
 use sqlx::postgres::PgPoolOptions;
-use std::net::SocketAddr;
-use {config::Config, routes::create_router};
+use anarchy_core::{config::Config, db, items, logging, map, npc, party, routes::{self, create_app}, scripting, world};
 use tokio::net::TcpListener;
-use tokio::sync::broadcast;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+
+// Ошибки запуска выводятся понятным сообщением вместо паники.
+// eprintln, а не tracing: до загрузки конфигурации подписчик логов еще не установлен
+fn exit_with_error(context: &str, error: impl std::fmt::Display) -> ! {
+    eprintln!("{}: {}", context, error);
+    std::process::exit(1);
+}
 
-use crate::state::AppState;
-use crate::routes::game::GameMessage;
+// Время на корректное закрытие соединений после сигнала остановки
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+// Ждет Ctrl+C или SIGTERM и переводит сервер в режим остановки: /ready отвечает 503,
+// новые WebSocket-соединения отклоняются, открытые закрываются
+async fn shutdown_signal(app_state: Arc<AppState>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!(error = %e, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => { signal.recv().await; },
+            Err(e) => {
+                warn!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            },
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutdown signal received");
+    app_state.begin_shutdown();
+}
 
 #[tokio::main]
 async fn main() {
     // Загрузка конфигурации
-    let config = Config::from_env().expect("Failed to load config");
+    let config = Config::load().unwrap_or_else(|e| exit_with_error("Configuration error", e));
+    logging::init(&config.log);
+    let addr = config.bind_address().unwrap_or_else(|e| exit_with_error("Configuration error", e));
+    // Карты Tiled, предметы, NPC и скрипты читаются до подключения к базе: ошибка в данных останавливает запуск сразу
+    let maps = map::load_maps(&config.game).unwrap_or_else(|e| exit_with_error("Failed to load maps", e));
+    let items = items::load_items(&config.game).unwrap_or_else(|e| exit_with_error("Failed to load items", e));
+    let npcs = npc::load_npcs(&config.game).unwrap_or_else(|e| exit_with_error("Failed to load NPCs", e));
+    let scripts = scripting::load_scripts(&config.game).unwrap_or_else(|e| exit_with_error("Failed to load scripts", e));
 
     // Подключение к PostgreSQL
     let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&config.database_url)
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(Duration::from_secs(config.database.acquire_timeout_secs))
+        .connect(&config.database.url)
         .await
-        .expect("Failed to connect to DB");
+        .unwrap_or_else(|e| exit_with_error("Failed to connect to DB", e));
+
+    // Флаг --migrate: только применить миграции и выйти
+    let migrate_only = std::env::args().any(|arg| arg == "--migrate");
 
-    // Инициализация канала широковещания для сообщений о состоянии игры
-    let (game_state_tx, _) = broadcast::channel::<GameMessage>(128); // Увеличен размер канала до 128
+    // Миграции и проверка соответствия схемы: при расхождении сервер не запускается
+    if let Err(e) = db::prepare_schema(&pool, config.database.auto_migrate || migrate_only).await {
+        exit_with_error("Database schema check failed", e);
+    }
+    if migrate_only {
+        info!("Migrations applied successfully");
+        return;
+    }
 
-    // Инициализация HashMap для активных игроков
-    let active_player_positions = Arc::new(Mutex::new(HashMap::new()));
+    let storage = Storage::postgres(pool);
+
+    // После перезапуска ни один игрок не может быть онлайн
+    if let Err(e) = storage.sessions.reset_sessions().await {
+        exit_with_error("Failed to reset online status", e);
+    }
+
+    // Фоновая очистка аккаунтов, срок отмены удаления которых истек
+    tokio::spawn(routes::account::run_purge_task(storage.clone(), config.auth.deletion_grace_days));
 
     // Создаем экземпляр AppState
-    let app_state = Arc::new(AppState {
-        pool,
-        game_state_tx: Arc::new(game_state_tx),
-        active_player_positions,
-    });
+    let tls_paths = config.server.tls_cert_path.clone().zip(config.server.tls_key_path.clone());
+    let app_state = Arc::new(AppState::with_maps(storage, config, maps).with_items(items).with_npcs(npcs).with_scripts(scripts));
+
+    // Сохраненные группы ждут возвращения участников game.party_reconnect_secs
+    if app_state.config.game.persist_parties {
+        match party::restore(&app_state).await {
+            Ok(count) => info!(count, "Parties restored"),
+            Err(e) => exit_with_error("Failed to restore parties", e),
+        }
+    }
+
+    // Игровой цикл
+    world::spawn_world(app_state.clone());
 
     // Создание роутера и передача AppState как Extension
-    let app = Router::new()
-        .nest("/api", create_router())
-        .layer(Extension(app_state));
-
-    // Запуск сервера с помощью axum::serve
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    println!("Server running on {}", addr);
-    let listener = TcpListener::bind(addr).await.unwrap();
-    serve(listener, app.into_make_service()).await.unwrap();
-}
\ No newline at end of file
+    let app = create_app(app_state.clone());
+
+    // Запуск сервера: с TLS (https/wss), если заданы сертификат и ключ, иначе обычный HTTP
+    if let Some((cert_path, key_path)) = tls_paths {
+        let tls_config = axum_server::tls_rustls::RustlsConfig::from_pem_file(&cert_path, &key_path)
+            .await
+            .unwrap_or_else(|e| exit_with_error("Failed to load TLS certificate", e));
+        let handle = axum_server::Handle::new();
+        let shutdown_handle = handle.clone();
+        let signal_state = app_state.clone();
+        tokio::spawn(async move {
+            shutdown_signal(signal_state).await;
+            shutdown_handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+        });
+        info!(%addr, "Server running (TLS)");
+        if let Err(e) = axum_server::bind_rustls(addr, tls_config).handle(handle).serve(app.into_make_service()).await {
+            exit_with_error("Server error", e);
+        }
+    } else {
+        let listener = TcpListener::bind(addr)
+            .await
+            .unwrap_or_else(|e| exit_with_error(&format!("Failed to bind {}", addr), e));
+        info!(%addr, "Server running");
+        if let Err(e) = serve(listener, app.into_make_service())
+            .with_graceful_shutdown(shutdown_signal(app_state.clone()))
+            .await
+        {
+            exit_with_error("Server error", e);
+        }
+    }
+    // Несохраненные изменения тайлового мира записываются перед выходом
+    world::save_all_chunks(&app_state).await;
+    info!("Server stopped");
+}
@@ -0,0 +1,700 @@
+// src/map.rs
+// Карты редактора Tiled для статичных миров: JSON (.tmj, .json) и XML (.tmx).
+// Тайловые слои задают местность комнаты в пределах карты (вне ее работает обычный генератор),
+// объектные слои — точки появления, зоны, телепорты и коллизии. Поддерживаются ортогональные
+// конечные карты с данными слоев в CSV, XML или base64 без сжатия
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::collision::Polygon;
+use crate::config::GameConfig;
+use crate::entities::{Components, Entity, EntityKind};
+use crate::items::ItemStack;
+use crate::rooms::is_valid_room_name;
+use crate::tiles::{Tile, AIR};
+use crate::xml::{self, Element};
+
+// Имя возможности протокола, согласуемой в Hello/Welcome
+pub const FEATURE: &str = "maps";
+// Старшие биты gid — флаги отражения и поворота тайла
+const GID_FLAGS: u32 = 0xF000_0000;
+// Число сторон многоугольника, которым приближается эллипс
+const ELLIPSE_SEGMENTS: usize = 16;
+
+// Пользовательские свойства Tiled: строки, числа и флаги
+pub type Properties = BTreeMap<String, serde_json::Value>;
+
+#[derive(Debug)]
+pub enum MapError {
+    Read(PathBuf, std::io::Error),
+    // Файл не разбирается или описывает некорректную карту
+    Invalid(PathBuf, String),
+    // Возможность Tiled, которую сервер не поддерживает (бесконечные карты, сжатие слоев и т. п.)
+    Unsupported(PathBuf, String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Read(path, e) => write!(f, "cannot read map {}: {}", path.display(), e),
+            MapError::Invalid(path, reason) => write!(f, "invalid map {}: {}", path.display(), reason),
+            MapError::Unsupported(path, what) => write!(f, "map {} uses unsupported {}", path.display(), what),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+// Ошибка разбора без пути к файлу; путь добавляет load
+enum Problem {
+    Invalid(String),
+    Unsupported(String),
+}
+
+impl Problem {
+    fn at(self, path: &Path) -> MapError {
+        match self {
+            Problem::Invalid(reason) => MapError::Invalid(path.to_path_buf(), reason),
+            Problem::Unsupported(what) => MapError::Unsupported(path.to_path_buf(), what),
+        }
+    }
+}
+
+fn invalid(reason: String) -> Problem {
+    Problem::Invalid(reason)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+// Именованная область с произвольными свойствами (например, безопасная зона)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub area: Polygon,
+    pub properties: Properties,
+}
+
+// Игрок, вошедший в область, переносится в точку назначения: в явные координаты,
+// в точку появления spawn или в точку появления комнаты. room — другая комната, иначе эта
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Teleporter {
+    pub name: String,
+    pub area: Polygon,
+    pub room: Option<String>,
+    pub spawn: Option<String>,
+    pub position: Option<(f64, f64)>,
+}
+
+impl Teleporter {
+    // map и fallback — карта и точка появления комнаты назначения
+    pub fn destination(&self, map: Option<&TiledMap>, fallback: (f64, f64)) -> (f64, f64) {
+        self.position
+            .or_else(|| self.spawn.as_deref().and_then(|spawn| map?.spawn_point(Some(spawn))))
+            .unwrap_or(fallback)
+    }
+}
+
+// Описание карты для клиента (сообщение MapInfo). Координаты — в тайлах
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapMetadata {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    // Размер тайла в пикселях редактора
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub properties: Properties,
+    // Имена тайловых слоев снизу вверх
+    pub layers: Vec<String>,
+    pub spawns: Vec<SpawnPoint>,
+    pub zones: Vec<Zone>,
+    pub teleporters: Vec<Teleporter>,
+    pub colliders: Vec<Polygon>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Point((f64, f64)),
+    Area(Polygon),
+    Line(Vec<(f64, f64)>),
+}
+
+impl ObjectShape {
+    pub fn center(&self) -> (f64, f64) {
+        match self {
+            ObjectShape::Point(point) => *point,
+            ObjectShape::Area(polygon) => polygon.center(),
+            ObjectShape::Line(points) => points[0],
+        }
+    }
+}
+
+// Объект объектного слоя в координатах тайлов; class — класс (тип) объекта в Tiled
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub layer: String,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    // None — в клетке нет тайла этого слоя
+    tiles: Vec<Option<Tile>>,
+}
+
+#[derive(Debug)]
+pub struct TiledMap {
+    pub metadata: MapMetadata,
+    pub layers: Vec<TileLayer>,
+    // Все объекты, в том числе пользовательских классов, для игровой логики
+    pub objects: Vec<MapObject>,
+}
+
+impl TiledMap {
+    // Формат определяется по расширению; имя карты — имя файла без расширения
+    pub fn load(path: &Path) -> Result<TiledMap, MapError> {
+        let text = std::fs::read_to_string(path).map_err(|e| MapError::Read(path.to_path_buf(), e))?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let raw = match extension.as_str() {
+            "tmj" | "json" => serde_json::from_str(&text).map_err(|e| invalid(e.to_string())),
+            "tmx" => xml::parse(&text).map_err(invalid).and_then(|root| tmx_map(&root)),
+            _ => Err(Problem::Unsupported(format!("file extension {:?} (expected .tmj, .json or .tmx)", extension))),
+        };
+        raw.and_then(|raw| build(name, raw)).map_err(|problem| problem.at(path))
+    }
+
+    pub fn contains_tile(&self, x: i32, y: i32) -> bool {
+        (0..self.metadata.width as i32).contains(&x) && (0..self.metadata.height as i32).contains(&y)
+    }
+
+    // Верхний непустой тайл клетки; пустая клетка карты — голая земля. None — вне карты
+    pub fn tile(&self, x: i32, y: i32) -> Option<Tile> {
+        if !self.contains_tile(x, y) {
+            return None;
+        }
+        let index = (y as u32 * self.metadata.width + x as u32) as usize;
+        Some(self.layers.iter().rev().find_map(|layer| layer.tiles[index]).unwrap_or(AIR))
+    }
+
+    // Точка появления по имени; без имени — "default", иначе первая на карте
+    pub fn spawn_point(&self, name: Option<&str>) -> Option<(f64, f64)> {
+        let spawns = &self.metadata.spawns;
+        let spawn = match name {
+            Some(name) => spawns.iter().find(|spawn| spawn.name == name),
+            None => spawns.iter().find(|spawn| spawn.name == "default").or(spawns.first()),
+        };
+        spawn.map(|spawn| (spawn.x, spawn.y))
+    }
+
+    pub fn zones_at(&self, point: (f64, f64)) -> impl Iterator<Item = &Zone> + '_ {
+        self.metadata.zones.iter().filter(move |zone| zone.area.contains(point))
+    }
+
+    // Центры объектов класса respawn
+    pub fn respawn_points(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.objects.iter().filter(|object| object.class == "respawn").map(|object| object.shape.center())
+    }
+
+    // Предметы, лежащие на карте изначально: объекты класса item со свойствами item и count
+    // (по умолчанию 1). id назначает хранилище сущностей комнаты
+    pub fn placed_items(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.objects.iter().filter(|object| object.class == "item").filter_map(|object| {
+            let item = string_property(&object.properties, "item")?;
+            let count = object.properties.get("count").and_then(|count| count.as_u64()).unwrap_or(1);
+            let stack = ItemStack { item, count: count.clamp(1, u32::MAX as u64) as u32 };
+            Some(Entity::new(EntityKind::Item, object.shape.center(), Components { item: Some(stack), ..Default::default() }))
+        })
+    }
+
+    // Телепорт, в область которого игрок вошел при перемещении from -> to
+    pub fn entered_teleporter(&self, from: (f64, f64), to: (f64, f64)) -> Option<&Teleporter> {
+        self.metadata.teleporters.iter().find(|teleporter| teleporter.area.contains(to) && !teleporter.area.contains(from))
+    }
+}
+
+// Карты из game.maps по именам комнат. Телепорты в другие комнаты проверяются здесь:
+// точка появления назначения должна быть на карте той комнаты
+pub fn load_maps(config: &GameConfig) -> Result<HashMap<String, Arc<TiledMap>>, MapError> {
+    let mut maps = HashMap::new();
+    for (room, path) in &config.maps {
+        let map = TiledMap::load(path)?;
+        info!(room = %room, path = %path.display(), width = map.metadata.width, height = map.metadata.height, "Loaded map");
+        maps.insert(room.clone(), (path, Arc::new(map)));
+    }
+    for (path, map) in maps.values() {
+        for teleporter in &map.metadata.teleporters {
+            let (Some(room), Some(spawn)) = (&teleporter.room, &teleporter.spawn) else {
+                continue;
+            };
+            let target = maps.get(room).map(|(_, target)| target);
+            if target.and_then(|target| target.spawn_point(Some(spawn))).is_none() {
+                let reason = format!("teleport {:?} leads to unknown spawn {:?} in room {}", teleporter.name, spawn, room);
+                return Err(MapError::Invalid(path.to_path_buf(), reason));
+            }
+        }
+    }
+    Ok(maps.into_iter().map(|(room, (_, map))| (room, map)).collect())
+}
+
+// --- Формат JSON (.tmj); TMX разбирается в те же структуры ---
+
+#[derive(Deserialize)]
+struct RawMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default = "orthogonal")]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<RawLayer>,
+    #[serde(default)]
+    tilesets: Vec<RawTileset>,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+fn orthogonal() -> String {
+    "orthogonal".to_string()
+}
+
+#[derive(Deserialize)]
+struct RawTileset {
+    firstgid: u32,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawLayer {
+    // tilelayer, objectgroup, group или imagelayer
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    data: Option<RawData>,
+    encoding: Option<String>,
+    compression: Option<String>,
+    objects: Vec<RawObject>,
+    layers: Vec<RawLayer>,
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawData {
+    Gids(Vec<u32>),
+    Encoded(String),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawObject {
+    id: u32,
+    name: String,
+    // Класс объекта: "type" до Tiled 1.9, "class" начиная с нее
+    #[serde(rename = "type")]
+    kind: String,
+    class: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    point: bool,
+    ellipse: bool,
+    polygon: Option<Vec<RawPoint>>,
+    polyline: Option<Vec<RawPoint>>,
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Deserialize)]
+struct RawPoint {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+struct RawProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+// --- Формат TMX ---
+
+fn parse_attr<T: std::str::FromStr>(element: &Element, name: &str) -> Result<Option<T>, Problem> {
+    element.attr(name)
+        .map(|value| value.trim().parse().map_err(|_| invalid(format!("<{}> has invalid {}={:?}", element.name, name, value))))
+        .transpose()
+}
+
+fn required_attr<T: std::str::FromStr>(element: &Element, name: &str) -> Result<T, Problem> {
+    parse_attr(element, name)?.ok_or_else(|| invalid(format!("<{}> has no {} attribute", element.name, name)))
+}
+
+fn text_attr(element: &Element, name: &str) -> String {
+    element.attr(name).unwrap_or_default().to_string()
+}
+
+fn tmx_map(root: &Element) -> Result<RawMap, Problem> {
+    if root.name != "map" {
+        return Err(invalid(format!("root element is <{}>, expected <map>", root.name)));
+    }
+    Ok(RawMap {
+        width: required_attr(root, "width")?,
+        height: required_attr(root, "height")?,
+        tilewidth: required_attr(root, "tilewidth")?,
+        tileheight: required_attr(root, "tileheight")?,
+        orientation: root.attr("orientation").map_or_else(orthogonal, str::to_string),
+        infinite: parse_attr::<u8>(root, "infinite")? == Some(1),
+        layers: tmx_layers(root)?,
+        tilesets: root.children_named("tileset")
+            .map(|tileset| Ok(RawTileset { firstgid: required_attr(tileset, "firstgid")? }))
+            .collect::<Result<_, Problem>>()?,
+        properties: tmx_properties(root)?,
+    })
+}
+
+fn tmx_layers(parent: &Element) -> Result<Vec<RawLayer>, Problem> {
+    let mut layers = Vec::new();
+    for element in &parent.children {
+        let kind = match element.name.as_str() {
+            "layer" => "tilelayer",
+            "objectgroup" => "objectgroup",
+            "group" => "group",
+            "imagelayer" => "imagelayer",
+            _ => continue,
+        };
+        let mut layer = RawLayer {
+            kind: kind.to_string(),
+            name: text_attr(element, "name"),
+            properties: tmx_properties(element)?,
+            objects: element.children_named("object").map(tmx_object).collect::<Result<_, _>>()?,
+            layers: tmx_layers(element)?,
+            ..RawLayer::default()
+        };
+        if let Some(data) = element.child("data") {
+            layer.encoding = data.attr("encoding").map(str::to_string);
+            layer.compression = data.attr("compression").map(str::to_string);
+            // Без encoding тайлы перечислены элементами <tile gid="..."/>
+            layer.data = Some(match layer.encoding {
+                Some(_) => RawData::Encoded(data.text.clone()),
+                None => RawData::Gids(data.children_named("tile")
+                    .map(|tile| Ok(parse_attr(tile, "gid")?.unwrap_or(0)))
+                    .collect::<Result<_, Problem>>()?),
+            });
+        }
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+fn tmx_object(element: &Element) -> Result<RawObject, Problem> {
+    let points = |name: &str| -> Result<Option<Vec<RawPoint>>, Problem> {
+        let Some(shape) = element.child(name) else {
+            return Ok(None);
+        };
+        text_attr(shape, "points")
+            .split_whitespace()
+            .map(|pair| {
+                let (x, y) = pair.split_once(',').ok_or_else(|| invalid(format!("invalid {} point {:?}", name, pair)))?;
+                match (x.parse(), y.parse()) {
+                    (Ok(x), Ok(y)) => Ok(RawPoint { x, y }),
+                    _ => Err(invalid(format!("invalid {} point {:?}", name, pair))),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    };
+    Ok(RawObject {
+        id: parse_attr(element, "id")?.unwrap_or(0),
+        name: text_attr(element, "name"),
+        kind: text_attr(element, "type"),
+        class: text_attr(element, "class"),
+        x: parse_attr(element, "x")?.unwrap_or(0.0),
+        y: parse_attr(element, "y")?.unwrap_or(0.0),
+        width: parse_attr(element, "width")?.unwrap_or(0.0),
+        height: parse_attr(element, "height")?.unwrap_or(0.0),
+        point: element.child("point").is_some(),
+        ellipse: element.child("ellipse").is_some(),
+        polygon: points("polygon")?,
+        polyline: points("polyline")?,
+        properties: tmx_properties(element)?,
+    })
+}
+
+// Значения в TMX — строки; типизированные свойства приводятся к числам и флагам, как в JSON
+fn tmx_properties(element: &Element) -> Result<Vec<RawProperty>, Problem> {
+    let Some(properties) = element.child("properties") else {
+        return Ok(Vec::new());
+    };
+    properties.children_named("property")
+        .map(|property| {
+            let name = text_attr(property, "name");
+            // Многострочные строки хранятся текстом элемента
+            let text = property.attr("value").map_or_else(|| property.text.clone(), str::to_string);
+            let bad_value = || invalid(format!("property {:?} has invalid value {:?}", name, text));
+            let value = match property.attr("type").unwrap_or("string") {
+                "int" => serde_json::Value::from(text.trim().parse::<i64>().map_err(|_| bad_value())?),
+                "float" => serde_json::Value::from(text.trim().parse::<f64>().map_err(|_| bad_value())?),
+                "bool" => serde_json::Value::from(text.trim().parse::<bool>().map_err(|_| bad_value())?),
+                _ => serde_json::Value::from(text),
+            };
+            Ok(RawProperty { name, value })
+        })
+        .collect()
+}
+
+// --- Построение карты ---
+
+fn properties(raw: &[RawProperty]) -> Properties {
+    raw.iter().map(|property| (property.name.clone(), property.value.clone())).collect()
+}
+
+fn decode_gids(layer: &RawLayer) -> Result<Vec<u32>, Problem> {
+    if let Some(compression) = layer.compression.as_deref().filter(|c| !c.is_empty()) {
+        return Err(Problem::Unsupported(format!("{} compression of layer {:?}", compression, layer.name)));
+    }
+    match (&layer.data, layer.encoding.as_deref()) {
+        (None, _) => Err(invalid(format!("layer {:?} has no data", layer.name))),
+        (Some(RawData::Gids(gids)), _) => Ok(gids.clone()),
+        (Some(RawData::Encoded(text)), Some("csv")) => text
+            .split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| gid.parse().map_err(|_| invalid(format!("layer {:?} has invalid gid {:?}", layer.name, gid))))
+            .collect(),
+        (Some(RawData::Encoded(text)), Some("base64")) => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .map_err(|e| invalid(format!("layer {:?}: {}", layer.name, e)))?;
+            if !bytes.len().is_multiple_of(4) {
+                return Err(invalid(format!("layer {:?} data is not a whole number of gids", layer.name)));
+            }
+            Ok(bytes.chunks_exact(4).map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]])).collect())
+        },
+        (Some(RawData::Encoded(_)), encoding) => Err(Problem::Unsupported(format!("layer encoding {:?}", encoding.unwrap_or("")))),
+    }
+}
+
+struct Builder {
+    map: TiledMap,
+    // firstgid наборов тайлов по возрастанию
+    firstgids: Vec<u32>,
+    // Пикселей в тайле по x и y
+    scale: (f64, f64),
+}
+
+fn build(name: String, raw: RawMap) -> Result<TiledMap, Problem> {
+    if raw.orientation != "orthogonal" {
+        return Err(Problem::Unsupported(format!("{} orientation", raw.orientation)));
+    }
+    if raw.infinite {
+        return Err(Problem::Unsupported("infinite maps".to_string()));
+    }
+    if raw.width == 0 || raw.height == 0 || raw.tilewidth == 0 || raw.tileheight == 0 {
+        return Err(invalid("map and tile sizes must be positive".to_string()));
+    }
+    let mut firstgids: Vec<u32> = raw.tilesets.iter().map(|tileset| tileset.firstgid).collect();
+    firstgids.sort_unstable();
+    let mut builder = Builder {
+        map: TiledMap {
+            metadata: MapMetadata {
+                name,
+                width: raw.width,
+                height: raw.height,
+                tile_width: raw.tilewidth,
+                tile_height: raw.tileheight,
+                properties: properties(&raw.properties),
+                layers: Vec::new(),
+                spawns: Vec::new(),
+                zones: Vec::new(),
+                teleporters: Vec::new(),
+                colliders: Vec::new(),
+            },
+            layers: Vec::new(),
+            objects: Vec::new(),
+        },
+        firstgids,
+        scale: (raw.tilewidth as f64, raw.tileheight as f64),
+    };
+    builder.add_layers(&raw.layers, false)?;
+
+    let map = builder.map;
+    for teleporter in &map.metadata.teleporters {
+        if let (None, Some(spawn)) = (&teleporter.room, &teleporter.spawn) {
+            if map.spawn_point(Some(spawn)).is_none() {
+                return Err(invalid(format!("teleport {:?} leads to unknown spawn {:?}", teleporter.name, spawn)));
+            }
+        }
+    }
+    Ok(map)
+}
+
+fn bool_property(raw: &[RawProperty], name: &str) -> bool {
+    raw.iter().any(|property| property.name == name && property.value == serde_json::Value::Bool(true))
+}
+
+fn string_property(properties: &Properties, name: &str) -> Option<String> {
+    properties.get(name).and_then(|value| value.as_str()).map(str::to_string)
+}
+
+impl Builder {
+    // collision — слой или одна из его групп помечены свойством collision = true
+    fn add_layers(&mut self, layers: &[RawLayer], collision: bool) -> Result<(), Problem> {
+        for layer in layers {
+            let collision = collision || bool_property(&layer.properties, "collision");
+            match layer.kind.as_str() {
+                "tilelayer" => self.add_tile_layer(layer)?,
+                "objectgroup" => {
+                    for object in &layer.objects {
+                        self.add_object(&layer.name, object, collision)?;
+                    }
+                },
+                "group" => self.add_layers(&layer.layers, collision)?,
+                // Слои изображений нужны только для отрисовки
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    // Тайл — номер в своем наборе тайлов: порядок тайлов в наборе должен совпадать с id тайлов сервера
+    fn tile_of(&self, gid: u32) -> Result<Option<Tile>, Problem> {
+        let gid = gid & !GID_FLAGS;
+        if gid == 0 {
+            return Ok(None);
+        }
+        let firstgid = self.firstgids.iter().rev().find(|firstgid| **firstgid <= gid).copied().unwrap_or(1);
+        Tile::try_from(gid - firstgid)
+            .map(Some)
+            .map_err(|_| invalid(format!("tile {} does not fit into a tile id", gid - firstgid)))
+    }
+
+    fn add_tile_layer(&mut self, layer: &RawLayer) -> Result<(), Problem> {
+        let gids = decode_gids(layer)?;
+        let expected = (self.map.metadata.width * self.map.metadata.height) as usize;
+        if gids.len() != expected {
+            return Err(invalid(format!("layer {:?} has {} tiles, expected {}", layer.name, gids.len(), expected)));
+        }
+        let tiles = gids.into_iter().map(|gid| self.tile_of(gid)).collect::<Result<_, _>>()?;
+        self.map.metadata.layers.push(layer.name.clone());
+        self.map.layers.push(TileLayer { name: layer.name.clone(), tiles });
+        Ok(())
+    }
+
+    fn to_tiles(&self, x: f64, y: f64) -> (f64, f64) {
+        (x / self.scale.0, y / self.scale.1)
+    }
+
+    fn shape(&self, raw: &RawObject) -> Result<ObjectShape, Problem> {
+        let vertices = |points: &[RawPoint]| -> Vec<(f64, f64)> {
+            points.iter().map(|point| self.to_tiles(raw.x + point.x, raw.y + point.y)).collect()
+        };
+        if let Some(points) = &raw.polygon {
+            return Polygon::new(vertices(points))
+                .map(ObjectShape::Area)
+                .ok_or_else(|| invalid(format!("polygon object {} has fewer than three points", raw.id)));
+        }
+        if let Some(points) = &raw.polyline {
+            if points.is_empty() {
+                return Err(invalid(format!("polyline object {} has no points", raw.id)));
+            }
+            return Ok(ObjectShape::Line(vertices(points)));
+        }
+        let (x, y) = self.to_tiles(raw.x, raw.y);
+        // Объекты нулевого размера без флага point встречаются в картах старых версий Tiled
+        if raw.point || (raw.width == 0.0 && raw.height == 0.0) {
+            return Ok(ObjectShape::Point((x, y)));
+        }
+        let (width, height) = self.to_tiles(raw.width, raw.height);
+        if !raw.ellipse {
+            return Ok(ObjectShape::Area(Polygon::rectangle(x, y, width, height)));
+        }
+        let (rx, ry) = (width / 2.0, height / 2.0);
+        let points = (0..ELLIPSE_SEGMENTS)
+            .map(|i| {
+                let angle = std::f64::consts::TAU * i as f64 / ELLIPSE_SEGMENTS as f64;
+                (x + rx + rx * angle.cos(), y + ry + ry * angle.sin())
+            })
+            .collect();
+        Ok(ObjectShape::Area(Polygon::new(points).expect("ellipse has many points")))
+    }
+
+    // Классы spawn, zone, teleport и collider разбираются сервером; остальные (в том числе
+    // respawn и item) доступны в objects
+    fn add_object(&mut self, layer: &str, raw: &RawObject, collision: bool) -> Result<(), Problem> {
+        let object = MapObject {
+            id: raw.id,
+            name: raw.name.clone(),
+            class: if raw.class.is_empty() { raw.kind.clone() } else { raw.class.clone() },
+            layer: layer.to_string(),
+            shape: self.shape(raw)?,
+            properties: properties(&raw.properties),
+        };
+        let metadata = &mut self.map.metadata;
+        match (object.class.as_str(), &object.shape) {
+            ("spawn", shape) => {
+                let (x, y) = shape.center();
+                metadata.spawns.push(SpawnPoint { name: object.name.clone(), x, y });
+            },
+            ("zone", ObjectShape::Area(area)) => metadata.zones.push(Zone {
+                name: object.name.clone(),
+                area: area.clone(),
+                properties: object.properties.clone(),
+            }),
+            ("teleport", ObjectShape::Area(area)) => metadata.teleporters.push(teleporter(&object, area)?),
+            ("collider", ObjectShape::Area(area)) => metadata.colliders.push(area.clone()),
+            ("item", _) if string_property(&object.properties, "item").is_none() => {
+                return Err(invalid(format!("item object {} must have an item property", object.id)));
+            },
+            ("zone" | "teleport" | "collider", _) => {
+                return Err(invalid(format!("{} object {} must be a rectangle, ellipse or polygon", object.class, object.id)));
+            },
+            (_, ObjectShape::Area(area)) if collision => metadata.colliders.push(area.clone()),
+            _ => {},
+        }
+        self.map.objects.push(object);
+        Ok(())
+    }
+}
+
+// Назначение телепорта задается свойствами room, spawn и x, y (в тайлах)
+fn teleporter(object: &MapObject, area: &Polygon) -> Result<Teleporter, Problem> {
+    let room = string_property(&object.properties, "room");
+    if let Some(room) = room.as_deref().filter(|room| !is_valid_room_name(room)) {
+        return Err(invalid(format!("teleport {} leads to invalid room {:?}", object.id, room)));
+    }
+    let coordinate = |name: &str| object.properties.get(name).and_then(|value| value.as_f64());
+    let position = match (coordinate("x"), coordinate("y")) {
+        (Some(x), Some(y)) => Some((x, y)),
+        (None, None) => None,
+        _ => return Err(invalid(format!("teleport {} must have both x and y properties", object.id))),
+    };
+    let teleporter = Teleporter {
+        name: object.name.clone(),
+        area: area.clone(),
+        room,
+        spawn: string_property(&object.properties, "spawn"),
+        position,
+    };
+    if teleporter.room.is_none() && teleporter.spawn.is_none() && teleporter.position.is_none() {
+        return Err(invalid(format!("teleport {} has no destination (room, spawn or x and y)", object.id)));
+    }
+    Ok(teleporter)
+}
@@ -1,9 +1,29 @@
 use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Player {
     pub user_id: i32,
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-}
\ No newline at end of file
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    // Комната, в которой находится игрок
+    pub room: String,
+}
+
+// Здоровье игрока; хранится в players отдельно от позиции
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vitals {
+    pub health: i32,
+    pub max_health: i32,
+}
+
+impl Vitals {
+    pub fn full(max_health: i32) -> Self {
+        Vitals { health: max_health, max_health }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+}
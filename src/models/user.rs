@@ -1,10 +1,14 @@
 // src/models/user.rs
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-#[derive(Debug, FromRow, Serialize, Deserialize)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct User {
     pub id: i32,
     pub login: String,
-    pub hashed_password: String
-}
\ No newline at end of file
+    pub hashed_password: String,
+    pub created_at: DateTime<Utc>,
+    // Время запроса на удаление аккаунта (None, если удаление не запрошено)
+    pub deletion_requested_at: Option<DateTime<Utc>>,
+}
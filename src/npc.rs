@@ -0,0 +1,441 @@
+// src/npc.rs
+// NPC: определения и спаунеры из файла данных (game.npcs_path) и поведение, которое
+// игровой цикл обновляет каждый тик. NPC — сущность вида npc; спаунер поддерживает в своей
+// комнате заданное число NPC вокруг точки "дома". Состояния: стоит (idle), бродит у дома
+// (wander), преследует ближайшего игрока (chase), возвращается домой (return_home).
+// Поведение задает только скорость сущности, перемещает ее entities::tick
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::collision::{self, CollisionWorld};
+use crate::config::GameConfig;
+use crate::entities::{self, Components, Entity, EntityKind};
+use crate::models::player::Vitals;
+use crate::pathfinding;
+use crate::rooms::{is_valid_room_name, Room};
+use crate::state::AppState;
+
+// Ограничение длины id NPC
+pub const MAX_NPC_ID_LEN: usize = 64;
+// Радиус NPC при столкновениях, как у игрока
+pub const NPC_RADIUS: f64 = collision::PLAYER_RADIUS;
+// Сколько тайлов может просмотреть один поиск пути
+const MAX_PATH_NODES: usize = 4096;
+// NPC дошел до точки пути, если он ближе этого расстояния
+const ARRIVE_DISTANCE: f64 = 0.1;
+// Преследующий NPC останавливается на таком расстоянии от игрока
+const CHASE_DISTANCE: f64 = 1.0;
+// Сколько NPC стоит на месте между прогулками
+const IDLE_MIN: Duration = Duration::from_secs(1);
+const IDLE_MAX: Duration = Duration::from_secs(4);
+// Сколько попыток найти доступную точку для прогулки
+const WANDER_ATTEMPTS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NpcDefinition {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_max_health")]
+    pub max_health: i32,
+    // Скорость в тайлах в секунду
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    // Игрок ближе этого расстояния становится целью; 0 — NPC никого не преследует
+    #[serde(default = "default_aggro_range")]
+    pub aggro_range: f64,
+    // Дальше этого расстояния от дома NPC бросает цель и возвращается
+    #[serde(default = "default_leash_range")]
+    pub leash_range: f64,
+    // Радиус прогулок вокруг дома; 0 — NPC стоит на месте
+    #[serde(default = "default_wander_radius")]
+    pub wander_radius: f64,
+}
+
+fn default_max_health() -> i32 {
+    20
+}
+
+fn default_speed() -> f64 {
+    2.0
+}
+
+fn default_aggro_range() -> f64 {
+    6.0
+}
+
+fn default_leash_range() -> f64 {
+    12.0
+}
+
+fn default_wander_radius() -> f64 {
+    4.0
+}
+
+// Спаунер: в комнате room вокруг точки (x, y) всегда есть count NPC вида npc
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnerDefinition {
+    pub room: String,
+    pub npc: String,
+    pub x: f64,
+    pub y: f64,
+    #[serde(default = "single")]
+    pub count: usize,
+}
+
+fn single() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NpcsFile {
+    #[serde(default, rename = "npc")]
+    npcs: Vec<NpcDefinition>,
+    #[serde(default, rename = "spawner")]
+    spawners: Vec<SpawnerDefinition>,
+}
+
+#[derive(Debug)]
+pub enum NpcError {
+    Read(PathBuf, std::io::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for NpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NpcError::Read(path, e) => write!(f, "cannot read NPC definitions {}: {}", path.display(), e),
+            NpcError::Invalid(path, reason) => write!(f, "invalid NPC definitions {}: {}", path.display(), reason),
+        }
+    }
+}
+
+impl std::error::Error for NpcError {}
+
+// Определения NPC по id и спаунеры
+#[derive(Debug, Default)]
+pub struct NpcRegistry {
+    npcs: HashMap<String, Arc<NpcDefinition>>,
+    spawners: Vec<SpawnerDefinition>,
+}
+
+impl NpcRegistry {
+    // Файл TOML с таблицами [[npc]] и [[spawner]]
+    pub fn load(path: &Path) -> Result<NpcRegistry, NpcError> {
+        let text = std::fs::read_to_string(path).map_err(|e| NpcError::Read(path.to_path_buf(), e))?;
+        let file: NpcsFile = toml::from_str(&text).map_err(|e| NpcError::Invalid(path.to_path_buf(), e.to_string()))?;
+        NpcRegistry::from_definitions(file.npcs, file.spawners).map_err(|reason| NpcError::Invalid(path.to_path_buf(), reason))
+    }
+
+    pub fn from_definitions(definitions: Vec<NpcDefinition>, spawners: Vec<SpawnerDefinition>) -> Result<NpcRegistry, String> {
+        let mut npcs = HashMap::new();
+        for definition in definitions {
+            if definition.id.is_empty() || definition.id.len() > MAX_NPC_ID_LEN {
+                return Err(format!("NPC id {:?} must be 1 to {} characters long", definition.id, MAX_NPC_ID_LEN));
+            }
+            if definition.max_health <= 0 {
+                return Err(format!("NPC {:?} must have positive max_health", definition.id));
+            }
+            let distances = [definition.speed, definition.aggro_range, definition.leash_range, definition.wander_radius];
+            if distances.iter().any(|value| !value.is_finite() || *value < 0.0) || definition.speed == 0.0 {
+                return Err(format!("NPC {:?} must have positive speed and non-negative ranges", definition.id));
+            }
+            if let Some(duplicate) = npcs.insert(definition.id.clone(), Arc::new(definition)) {
+                return Err(format!("NPC {:?} is defined twice", duplicate.id));
+            }
+        }
+        for spawner in &spawners {
+            if !is_valid_room_name(&spawner.room) {
+                return Err(format!("spawner room {:?} is not a valid room name", spawner.room));
+            }
+            if !npcs.contains_key(&spawner.npc) {
+                return Err(format!("spawner in room {:?} refers to unknown NPC {:?}", spawner.room, spawner.npc));
+            }
+            if !spawner.x.is_finite() || !spawner.y.is_finite() {
+                return Err(format!("spawner in room {:?} has a non-finite position", spawner.room));
+            }
+            if !(1..=100).contains(&spawner.count) {
+                return Err(format!("spawner in room {:?} must have count between 1 and 100", spawner.room));
+            }
+        }
+        Ok(NpcRegistry { npcs, spawners })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Arc<NpcDefinition>> {
+        self.npcs.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.npcs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.npcs.is_empty()
+    }
+
+    // Спаунеры комнаты с их номерами
+    pub fn spawners<'a>(&'a self, room: &'a str) -> impl Iterator<Item = (usize, &'a SpawnerDefinition)> + 'a {
+        self.spawners.iter().enumerate().filter(move |(_, spawner)| spawner.room == room)
+    }
+}
+
+// Определения из game.npcs_path; без файла NPC нет
+pub fn load_npcs(config: &GameConfig) -> Result<NpcRegistry, NpcError> {
+    let Some(path) = &config.npcs_path else {
+        return Ok(NpcRegistry::default());
+    };
+    let registry = NpcRegistry::load(path)?;
+    info!(path = %path.display(), npcs = registry.len(), spawners = registry.spawners.len(), "Loaded NPC definitions");
+    Ok(registry)
+}
+
+// Состояние поведения; передается клиентам в компоненте behavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Behavior {
+    Idle,
+    Wander,
+    Chase,
+    ReturnHome,
+}
+
+// Состояние одного NPC комнаты
+#[derive(Debug)]
+pub struct Npc {
+    definition: Arc<NpcDefinition>,
+    // Номер спаунера в NpcRegistry
+    spawner: usize,
+    home: (f64, f64),
+    behavior: Behavior,
+    // Оставшиеся тайлы пути
+    path: VecDeque<(i32, i32)>,
+    // Тайл, к которому проложен путь
+    goal: Option<(i32, i32)>,
+    idle_until: Instant,
+    random: u64,
+}
+
+fn tile_of((x, y): (f64, f64)) -> (i32, i32) {
+    (x.floor() as i32, y.floor() as i32)
+}
+
+fn tile_center((x, y): (i32, i32)) -> (f64, f64) {
+    (x as f64 + 0.5, y as f64 + 0.5)
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+impl Npc {
+    pub fn new(definition: Arc<NpcDefinition>, spawner: usize, home: (f64, f64), seed: u64) -> Self {
+        Npc {
+            definition,
+            spawner,
+            home,
+            behavior: Behavior::Idle,
+            path: VecDeque::new(),
+            goal: None,
+            idle_until: Instant::now(),
+            random: seed,
+        }
+    }
+
+    pub fn behavior(&self) -> Behavior {
+        self.behavior
+    }
+
+    pub fn home(&self) -> (f64, f64) {
+        self.home
+    }
+
+    // Псевдослучайное число из [0, 1) (splitmix64)
+    fn next_random(&mut self) -> f64 {
+        self.random = self.random.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.random;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (z ^ (z >> 31)) as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    fn rest(&mut self, now: Instant) {
+        self.behavior = Behavior::Idle;
+        self.path.clear();
+        self.goal = None;
+        self.idle_until = now + IDLE_MIN + (IDLE_MAX - IDLE_MIN).mul_f64(self.next_random());
+    }
+
+    // Прокладывает путь к тайлу goal; false, если пути нет
+    fn route(&mut self, world: &impl CollisionWorld, position: (f64, f64), goal: (i32, i32)) -> bool {
+        self.goal = Some(goal);
+        match pathfinding::find_path(world, tile_of(position), goal, NPC_RADIUS, MAX_PATH_NODES) {
+            Some(path) => {
+                self.path = path.into();
+                true
+            },
+            None => {
+                self.path.clear();
+                false
+            },
+        }
+    }
+
+    fn return_home(&mut self, world: &impl CollisionWorld, position: (f64, f64), now: Instant) {
+        self.behavior = Behavior::ReturnHome;
+        if !self.route(world, position, tile_of(self.home)) {
+            // Дом недоступен (например, его застроили): ждем и пробуем снова
+            self.rest(now);
+        }
+    }
+
+    // Случайная доступная точка в радиусе прогулки
+    fn wander(&mut self, world: &impl CollisionWorld, position: (f64, f64), now: Instant) {
+        let radius = self.definition.wander_radius;
+        for _ in 0..WANDER_ATTEMPTS {
+            let angle = self.next_random() * std::f64::consts::TAU;
+            let length = self.next_random() * radius;
+            let goal = tile_of((self.home.0 + angle.cos() * length, self.home.1 + angle.sin() * length));
+            if goal != tile_of(position) && self.route(world, position, goal) {
+                self.behavior = Behavior::Wander;
+                return;
+            }
+        }
+        self.rest(now);
+    }
+
+    // Решение на тик: новое состояние и скорость, с которой NPC двинется за dt секунд.
+    // players — позиции живых игроков комнаты
+    pub fn think(
+        &mut self,
+        world: &impl CollisionWorld,
+        position: (f64, f64),
+        players: &[(f64, f64)],
+        now: Instant,
+        dt: f64,
+    ) -> (f64, f64) {
+        let definition = self.definition.clone();
+        let from_home = distance(position, self.home);
+        // Цель — ближайший игрок в радиусе агрессии, не уводящий NPC дальше привязи
+        let target = players
+            .iter()
+            .copied()
+            .filter(|player| distance(*player, position) < definition.aggro_range && distance(*player, self.home) <= definition.leash_range)
+            .min_by(|a, b| distance(*a, position).total_cmp(&distance(*b, position)));
+
+        match (self.behavior, target) {
+            (Behavior::ReturnHome, _) => {},
+            _ if from_home > definition.leash_range => self.return_home(world, position, now),
+            (_, Some(target)) => {
+                let goal = tile_of(target);
+                if self.behavior != Behavior::Chase || self.goal != Some(goal) {
+                    self.behavior = Behavior::Chase;
+                    self.route(world, position, goal);
+                }
+                if distance(position, target) <= CHASE_DISTANCE {
+                    return (0.0, 0.0);
+                }
+            },
+            (Behavior::Chase, None) => self.return_home(world, position, now),
+            (Behavior::Idle, None) if now >= self.idle_until && definition.wander_radius > 0.0 => self.wander(world, position, now),
+            _ => {},
+        }
+
+        let Some(&next) = self.path.front() else {
+            match self.behavior {
+                Behavior::Wander | Behavior::ReturnHome => self.rest(now),
+                // Путь к цели не найден: пробуем снова на следующем тике
+                Behavior::Chase => self.goal = None,
+                Behavior::Idle => {},
+            }
+            return (0.0, 0.0);
+        };
+        let waypoint = tile_center(next);
+        let remaining = distance(position, waypoint);
+        let step = (definition.speed * dt).min(remaining);
+        let requested = if remaining <= ARRIVE_DISTANCE {
+            waypoint
+        } else {
+            (position.0 + (waypoint.0 - position.0) / remaining * step, position.1 + (waypoint.1 - position.1) / remaining * step)
+        };
+        let resolved = collision::resolve_move(world, position, requested, NPC_RADIUS);
+        if distance(resolved, waypoint) <= ARRIVE_DISTANCE {
+            self.path.pop_front();
+        } else if distance(resolved, position) < f64::EPSILON {
+            // Путь перегородили: прокладываем заново
+            self.path.clear();
+            self.goal = None;
+        }
+        ((resolved.0 - position.0) / dt, (resolved.1 - position.1) / dt)
+    }
+}
+
+// Поведение NPC комнаты на один тик длительностью dt секунд: спаунеры восполняют
+// недостающих NPC, каждый NPC выбирает состояние и скорость. Вызывается игровым циклом
+// до entities::tick, который и перемещает NPC
+pub async fn tick(app_state: &AppState, room: &Room, dt: f64) {
+    let spawners: Vec<(usize, &SpawnerDefinition)> = app_state.npcs.spawners(&room.name).collect();
+    if spawners.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+    let positions: Vec<(i32, (f64, f64))> = room.active_player_positions.lock().await
+        .values()
+        .map(|p| (p.user_id, (p.x, p.y)))
+        .collect();
+    let players: Vec<(f64, f64)> = {
+        let combatants = room.combatants.lock().await;
+        positions
+            .into_iter()
+            .filter(|(user_id, _)| !combatants.get(user_id).is_some_and(|combatant| combatant.is_dead()))
+            .map(|(_, position)| position)
+            .collect()
+    };
+
+    let mut npcs = room.npcs.lock().await;
+    for (index, spawner) in spawners {
+        let Some(definition) = app_state.npcs.get(&spawner.npc) else {
+            continue;
+        };
+        let alive = npcs.values().filter(|npc| npc.spawner == index).count();
+        for _ in alive..spawner.count {
+            let home = (spawner.x, spawner.y);
+            let components = Components {
+                name: Some(definition.name.clone()),
+                vitals: Some(Vitals::full(definition.max_health)),
+                behavior: Some(Behavior::Idle),
+                ..Default::default()
+            };
+            let entity = entities::spawn(room, Entity::new(EntityKind::Npc, home, components)).await;
+            debug!(id = entity.id, npc = %definition.id, room = %room.name, "NPC spawned");
+            npcs.insert(entity.id, Npc::new(definition.clone(), index, home, entity.id));
+        }
+    }
+
+    let mut entities = room.entities.lock().await;
+    // NPC, чья сущность удалена, больше не думает; спаунер восполнит его на следующем тике
+    npcs.retain(|id, _| entities.get(*id).is_some());
+    let tiles = room.tiles.lock().await;
+    for (&id, npc) in npcs.iter_mut() {
+        let Some(entity) = entities.get(id) else {
+            continue;
+        };
+        let (vx, vy) = npc.think(&*tiles, entity.position(), &players, now, dt);
+        let behavior = Some(npc.behavior());
+        if (entity.vx, entity.vy) != (vx, vy) || entity.components.behavior != behavior {
+            entities.update(id, |entity| {
+                (entity.vx, entity.vy) = (vx, vy);
+                if vx != 0.0 || vy != 0.0 {
+                    entity.rotation = vy.atan2(vx).rem_euclid(std::f64::consts::TAU);
+                }
+                entity.components.behavior = behavior;
+            });
+        }
+    }
+}
@@ -0,0 +1,350 @@
+// src/party.rs
+// Группы игроков. Лидер приглашает игроков (PartyInvite), те соглашаются (PartyAccept);
+// участник может выйти (PartyLeave), лидер — исключить участника (PartyKick). Участники
+// могут быть в разных комнатах, поэтому сообщения групп идут через общий канал
+// AppState::players_tx, а позиции участников — через AppState::party_positions_tx, а не через
+// каналы комнат. Участник отключается, когда закрыто последнее из его соединений, и остается
+// в группе game.party_reconnect_secs; составы групп сохраняются в хранилище, если включено
+// game.persist_parties, и восстанавливаются при запуске
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::MutexGuard;
+use tracing::{debug, error, info};
+
+use crate::routes::game::{ErrorCode, GameMessage, PlayerPositionUpdate};
+use crate::state::AppState;
+use crate::storage::StorageResult;
+use crate::telemetry;
+
+pub type PartyId = u64;
+
+// Сколько действует приглашение в группу
+pub const PARTY_INVITE_TIMEOUT: Duration = Duration::from_secs(60);
+
+type PartyResult = Result<(), (ErrorCode, String)>;
+
+fn invalid(message: impl Into<String>) -> (ErrorCode, String) {
+    (ErrorCode::InvalidPartyAction, message.into())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartyMember {
+    pub user_id: i32,
+    pub online: bool,
+}
+
+// Состав группы, который получают все ее участники; участники — в порядке вступления
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Party {
+    pub id: PartyId,
+    pub leader: i32,
+    pub members: Vec<PartyMember>,
+}
+
+impl Party {
+    pub fn contains(&self, user_id: i32) -> bool {
+        self.members.iter().any(|member| member.user_id == user_id)
+    }
+
+    // Участники для хранилища: лидер первым
+    fn stored_members(&self) -> Vec<i32> {
+        let mut members = vec![self.leader];
+        members.extend(self.members.iter().map(|member| member.user_id).filter(|id| *id != self.leader));
+        members
+    }
+}
+
+// Почему игрок перестал быть участником группы
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartyLeaveReason {
+    Left,
+    Kicked,
+    // В группе не осталось других участников
+    Disbanded,
+    // Не вернулся в игру за game.party_reconnect_secs
+    Expired,
+}
+
+struct OpenParty {
+    party: Party,
+    // Когда отключились участники, которые сейчас не в игре
+    offline_since: HashMap<i32, Instant>,
+}
+
+#[derive(Default)]
+pub struct PartyRegistry {
+    next_id: PartyId,
+    // Игроки онлайн: приглашать можно только их
+    online: HashSet<i32>,
+    // Приглашения (от кого, кому) и когда они истекают
+    invites: HashMap<(i32, i32), Instant>,
+    parties: HashMap<PartyId, OpenParty>,
+    by_player: HashMap<i32, PartyId>,
+}
+
+impl PartyRegistry {
+    // Группа, в которой состоит игрок
+    pub fn party_of(&self, user_id: i32) -> Option<&Party> {
+        self.by_player.get(&user_id).and_then(|id| self.parties.get(id)).map(|open| &open.party)
+    }
+
+    fn open_mut(&mut self, user_id: i32) -> Result<&mut OpenParty, (ErrorCode, String)> {
+        self.by_player
+            .get(&user_id)
+            .and_then(|id| self.parties.get_mut(id))
+            .ok_or_else(|| invalid("you are not in a party"))
+    }
+
+    // Убирает участника; группа из одного игрока распускается, ушедшего лидера сменяет
+    // следующий по времени вступления участник онлайн. Возвращает новый состав
+    // (пустой — группа распущена)
+    fn remove_member(&mut self, app_state: &AppState, id: PartyId, user_id: i32, reason: PartyLeaveReason) -> Vec<i32> {
+        let Some(open) = self.parties.get_mut(&id) else {
+            return Vec::new();
+        };
+        open.party.members.retain(|member| member.user_id != user_id);
+        open.offline_since.remove(&user_id);
+        self.by_player.remove(&user_id);
+        send(app_state, GameMessage::PartyLeft { id, user_id, reason });
+        if open.party.members.len() < 2 {
+            let open = self.parties.remove(&id).expect("party is open");
+            for member in open.party.members {
+                self.by_player.remove(&member.user_id);
+                send(app_state, GameMessage::PartyLeft { id, user_id: member.user_id, reason: PartyLeaveReason::Disbanded });
+            }
+            debug!(id, "Party disbanded");
+            return Vec::new();
+        }
+        if open.party.leader == user_id {
+            let members = &open.party.members;
+            open.party.leader = members.iter().find(|member| member.online).unwrap_or(&members[0]).user_id;
+            debug!(id, leader = open.party.leader, "Party leader changed");
+        }
+        send(app_state, GameMessage::PartyUpdated(open.party.clone()));
+        open.party.stored_members()
+    }
+}
+
+fn send(app_state: &AppState, msg: GameMessage) {
+    // Ошибка означает лишь отсутствие подписчиков
+    let _ = app_state.players_tx.send(msg);
+}
+
+// Записывает составы групп (пустой — удаляет группу), если включено game.persist_parties.
+// Блокировка групп снимается до записи, но очередь записи занимается еще под ней.
+// Хранилище — копия для перезапуска сервера, поэтому ошибка записи только логируется
+async fn save(app_state: &AppState, parties: MutexGuard<'_, PartyRegistry>, changes: Vec<(PartyId, Vec<i32>)>) {
+    if !app_state.config.game.persist_parties || changes.is_empty() {
+        return;
+    }
+    let _queue = app_state.party_saves.lock().await;
+    drop(parties);
+    for (id, members) in changes {
+        let query_started = Instant::now();
+        let result = app_state.storage.parties.save_party(id, &members).await;
+        telemetry::db_query("save_party", query_started.elapsed());
+        if let Err(e) = result {
+            error!(error = %e, id, "Error saving party");
+        }
+    }
+}
+
+// Восстанавливает сохраненные группы при запуске; их участники считаются отключившимися
+// только что и должны вернуться за game.party_reconnect_secs
+pub async fn restore(app_state: &AppState) -> StorageResult<usize> {
+    let stored = app_state.storage.parties.load_parties().await?;
+    let mut parties = app_state.parties.lock().await;
+    let now = Instant::now();
+    for (id, members) in stored {
+        parties.next_id = parties.next_id.max(id);
+        if members.len() < 2 {
+            continue;
+        }
+        let party = Party {
+            id,
+            leader: members[0],
+            members: members.iter().map(|user_id| PartyMember { user_id: *user_id, online: false }).collect(),
+        };
+        for user_id in &members {
+            parties.by_player.insert(*user_id, id);
+        }
+        let offline_since = members.iter().map(|user_id| (*user_id, now)).collect();
+        parties.parties.insert(id, OpenParty { party, offline_since });
+    }
+    Ok(parties.parties.len())
+}
+
+// Открыто первое соединение игрока; участник группы снова отмечается онлайн
+pub async fn connect(app_state: &AppState, user_id: i32) {
+    let mut parties = app_state.parties.lock().await;
+    parties.online.insert(user_id);
+    if let Ok(open) = parties.open_mut(user_id) {
+        open.offline_since.remove(&user_id);
+        for member in open.party.members.iter_mut().filter(|member| member.user_id == user_id) {
+            member.online = true;
+        }
+        send(app_state, GameMessage::PartyUpdated(open.party.clone()));
+    }
+}
+
+// Закрыто последнее соединение игрока: он остается в группе до возвращения или истечения
+// game.party_reconnect_secs. Если за это время открылось новое соединение, игрок остается онлайн
+pub async fn disconnect(app_state: &AppState, user_id: i32) {
+    let mut parties = app_state.parties.lock().await;
+    if app_state.is_connected(user_id) {
+        return;
+    }
+    parties.online.remove(&user_id);
+    parties.invites.retain(|(from, to), _| *from != user_id && *to != user_id);
+    if let Ok(open) = parties.open_mut(user_id) {
+        open.offline_since.insert(user_id, Instant::now());
+        for member in open.party.members.iter_mut().filter(|member| member.user_id == user_id) {
+            member.online = false;
+        }
+        send(app_state, GameMessage::PartyUpdated(open.party.clone()));
+    }
+}
+
+// Исключает из групп участников, отключившихся раньше now - game.party_reconnect_secs
+pub async fn expire_offline(app_state: &AppState, now: Instant) {
+    let grace = app_state.config.party_reconnect_grace();
+    let mut parties = app_state.parties.lock().await;
+    let expired: Vec<(PartyId, i32)> = parties.parties
+        .values()
+        .flat_map(|open| open.offline_since.iter().map(|(user_id, since)| (open.party.id, *user_id, *since)))
+        .filter(|(_, _, since)| now.saturating_duration_since(*since) >= grace)
+        .map(|(id, user_id, _)| (id, user_id))
+        .collect();
+    let mut changes = Vec::new();
+    for (id, user_id) in expired {
+        debug!(id, user_id, "Party member did not reconnect in time");
+        let members = parties.remove_member(app_state, id, user_id, PartyLeaveReason::Expired);
+        changes.retain(|(changed, _)| *changed != id);
+        changes.push((id, members));
+    }
+    save(app_state, parties, changes).await;
+}
+
+pub async fn invite(app_state: &AppState, user_id: i32, target: i32) -> PartyResult {
+    if target == user_id {
+        return Err(invalid("cannot invite yourself"));
+    }
+    let mut parties = app_state.parties.lock().await;
+    if !parties.online.contains(&target) {
+        return Err(invalid(format!("player {} is not online", target)));
+    }
+    if parties.by_player.contains_key(&target) {
+        return Err(invalid(format!("player {} is already in a party", target)));
+    }
+    if let Some(party) = parties.party_of(user_id) {
+        if party.leader != user_id {
+            return Err(invalid("only the party leader can invite"));
+        }
+        if party.members.len() >= app_state.config.game.max_party_size {
+            return Err(invalid("the party is full"));
+        }
+    }
+    parties.invites.insert((user_id, target), Instant::now() + PARTY_INVITE_TIMEOUT);
+    send(app_state, GameMessage::PartyInvited { from: user_id, to: target });
+    Ok(())
+}
+
+// Принимает приглашение; если пригласивший еще не в группе, группа создается с ним во главе
+pub async fn accept(app_state: &AppState, user_id: i32, from: i32) -> PartyResult {
+    let mut parties = app_state.parties.lock().await;
+    let now = Instant::now();
+    parties.invites.retain(|_, expires_at| *expires_at > now);
+    if parties.invites.remove(&(from, user_id)).is_none() {
+        return Err(invalid(format!("no party invite from player {}", from)));
+    }
+    if parties.by_player.contains_key(&user_id) {
+        return Err(invalid("you are already in a party"));
+    }
+    let max_party_size = app_state.config.game.max_party_size;
+    let id = match parties.by_player.get(&from).copied() {
+        Some(id) => {
+            let open = parties.parties.get_mut(&id).expect("party is open");
+            if open.party.leader != from {
+                return Err(invalid(format!("player {} is no longer the party leader", from)));
+            }
+            if open.party.members.len() >= max_party_size {
+                return Err(invalid("the party is full"));
+            }
+            open.party.members.push(PartyMember { user_id, online: true });
+            id
+        },
+        None => {
+            parties.next_id += 1;
+            let id = parties.next_id;
+            let members = [from, user_id].map(|user_id| PartyMember { user_id, online: true }).to_vec();
+            let party = Party { id, leader: from, members };
+            parties.parties.insert(id, OpenParty { party, offline_since: HashMap::new() });
+            parties.by_player.insert(from, id);
+            info!(id, leader = from, "Party created");
+            id
+        },
+    };
+    parties.by_player.insert(user_id, id);
+    let party = &parties.parties[&id].party;
+    debug!(id, user_id, "Player joined party");
+    send(app_state, GameMessage::PartyUpdated(party.clone()));
+    let members = party.stored_members();
+    save(app_state, parties, vec![(id, members)]).await;
+    Ok(())
+}
+
+pub async fn leave(app_state: &AppState, user_id: i32) -> PartyResult {
+    let mut parties = app_state.parties.lock().await;
+    let id = parties.open_mut(user_id)?.party.id;
+    let members = parties.remove_member(app_state, id, user_id, PartyLeaveReason::Left);
+    save(app_state, parties, vec![(id, members)]).await;
+    Ok(())
+}
+
+pub async fn kick(app_state: &AppState, user_id: i32, target: i32) -> PartyResult {
+    let mut parties = app_state.parties.lock().await;
+    let party = &parties.open_mut(user_id)?.party;
+    if party.leader != user_id {
+        return Err(invalid("only the party leader can kick"));
+    }
+    if target == user_id {
+        return Err(invalid("use PartyLeave to leave the party"));
+    }
+    if !party.contains(target) {
+        return Err(invalid(format!("player {} is not in your party", target)));
+    }
+    let id = party.id;
+    let members = parties.remove_member(app_state, id, target, PartyLeaveReason::Kicked);
+    save(app_state, parties, vec![(id, members)]).await;
+    Ok(())
+}
+
+// Сообщение в чат группы (text уже проверен как сообщение чата)
+pub async fn chat(app_state: &AppState, user_id: i32, text: String) -> PartyResult {
+    let mut parties = app_state.parties.lock().await;
+    let party = parties.open_mut(user_id)?.party.id;
+    send(app_state, GameMessage::PartyChatMessage { party, user_id, text });
+    Ok(())
+}
+
+// Рассылает группам позиции их участников, переместившихся за тик в комнате room.
+// Участники получают их независимо от комнаты и расстояния
+pub async fn share_positions(app_state: &AppState, room: &str, positions: &[PlayerPositionUpdate]) {
+    let parties = app_state.parties.lock().await;
+    if parties.parties.is_empty() {
+        return;
+    }
+    for position in positions {
+        if let Some(&party) = parties.by_player.get(&position.user_id) {
+            let _ = app_state.party_positions_tx.send(GameMessage::PartyMemberPosition {
+                party,
+                user_id: position.user_id,
+                room: room.to_string(),
+                x: position.x,
+                y: position.y,
+            });
+        }
+    }
+}
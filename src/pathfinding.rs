@@ -0,0 +1,89 @@
+// src/pathfinding.rs
+// Поиск пути по тайловой сетке (A*) для NPC. Ходить можно по тайлам, которые не являются
+// препятствием и не задевают коллайдеры карты; по диагонали — только если свободны
+// оба соседних тайла, чтобы не срезать углы
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::collision::CollisionWorld;
+
+// Стоимости шагов в десятых долях тайла
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Центр тайла можно занять кругом radius
+pub fn is_walkable(world: &impl CollisionWorld, (x, y): (i32, i32), radius: f64) -> bool {
+    let center = (x as f64 + 0.5, y as f64 + 0.5);
+    !world.is_solid(x, y) && !world.polygons().iter().any(|polygon| polygon.intersects_circle(center, radius))
+}
+
+// Оценка оставшегося пути (октильное расстояние), не превышает настоящую стоимость
+fn heuristic((ax, ay): (i32, i32), (bx, by): (i32, i32)) -> u32 {
+    let (dx, dy) = (ax.abs_diff(bx), ay.abs_diff(by));
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+// Путь из тайла from в тайл to: тайлы после from до to включительно (пустой, если from == to).
+// None — пути нет или для поиска понадобилось бы просмотреть больше max_nodes тайлов
+pub fn find_path(
+    world: &impl CollisionWorld,
+    from: (i32, i32),
+    to: (i32, i32),
+    radius: f64,
+    max_nodes: usize,
+) -> Option<Vec<(i32, i32)>> {
+    if from == to {
+        return Some(Vec::new());
+    }
+    if !is_walkable(world, to, radius) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    let mut cost: HashMap<(i32, i32), u32> = HashMap::from([(from, 0)]);
+    // При равной оценке раньше раскрывается тайл ближе к цели
+    open.push(Reverse((heuristic(from, to), heuristic(from, to), from)));
+    let mut expanded = 0;
+
+    while let Some(Reverse((_, _, current))) = open.pop() {
+        if current == to {
+            let mut path = vec![current];
+            let mut node = current;
+            while let Some(&previous) = came_from.get(&node) {
+                if previous == from {
+                    break;
+                }
+                path.push(previous);
+                node = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        expanded += 1;
+        if expanded > max_nodes {
+            return None;
+        }
+
+        let current_cost = cost[&current];
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let next = (current.0 + dx, current.1 + dy);
+            let diagonal = dx != 0 && dy != 0;
+            if !is_walkable(world, next, radius) {
+                continue;
+            }
+            if diagonal && !(is_walkable(world, (current.0 + dx, current.1), radius) && is_walkable(world, (current.0, current.1 + dy), radius)) {
+                continue;
+            }
+            let next_cost = current_cost + if diagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+            if cost.get(&next).is_some_and(|&known| known <= next_cost) {
+                continue;
+            }
+            cost.insert(next, next_cost);
+            came_from.insert(next, current);
+            let remaining = heuristic(next, to);
+            open.push(Reverse((next_cost + remaining, remaining, next)));
+        }
+    }
+    None
+}
@@ -0,0 +1,269 @@
+// src/rooms.rs
+// Комнаты: у каждой свое состояние игроков и свои каналы рассылки.
+// Постоянные миры (game.worlds) существуют всегда, инстансы создаются по запросу
+// игрока и удаляются, когда из них уходит последний участник
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::combat::Combatant;
+use crate::config::{GameConfig, GeneratorKind};
+use crate::entities::{EntityId, EntityStore};
+use crate::map::TiledMap;
+use crate::npc::Npc;
+use crate::routes::game::{GameMessage, PlayerPositionUpdate};
+use crate::snapshot::WorldSnapshot;
+use crate::tiles::TileWorld;
+use crate::worldgen::WorldGenerator;
+
+// Ограничение длины имени комнаты (совпадает с размером колонки players.room)
+pub const MAX_ROOM_NAME_LEN: usize = 64;
+
+pub struct Room {
+    pub name: String,
+    // Постоянный мир из конфигурации; иначе инстанс
+    pub persistent: bool,
+    pub game_state_tx: broadcast::Sender<GameMessage>,
+    // Снимки мира каждого тика для клиентов с дельта-снимками
+    pub snapshot_tx: broadcast::Sender<Arc<WorldSnapshot>>,
+    // Текущие позиции игроков комнаты
+    pub active_player_positions: Mutex<HashMap<i32, PlayerPositionUpdate>>,
+    // Игроки, сменившие позицию с прошлого тика; их позиции рассылаются игровым циклом
+    pub moved_players: Mutex<HashSet<i32>>,
+    // Загруженные чанки тайлового мира комнаты
+    pub tiles: Mutex<TileWorld>,
+    // Точка появления (x, y): с карты комнаты или на сгенерированной местности
+    pub spawn: (f64, f64),
+    // Карта Tiled комнаты (зоны, телепорты, объекты для игровой логики)
+    pub map: Option<Arc<TiledMap>>,
+    // Здоровье и таймеры боя игроков комнаты; переходит вместе с игроком в другую комнату
+    pub combatants: Mutex<HashMap<i32, Combatant>>,
+    // Точки возрождения из game.respawn_points и с карты; пусто — возрождение в точке появления
+    pub respawn_points: Vec<(f64, f64)>,
+    // Сущности, кроме игроков: предметы с карты и выброшенные игроками, NPC, снаряды.
+    // Пропадают вместе с инстансом и при перезапуске
+    pub entities: Mutex<EntityStore>,
+    // Поведение NPC комнаты по id их сущностей. Блокируется раньше entities
+    pub npcs: Mutex<HashMap<EntityId, Npc>>,
+    // Число соединений каждого игрока в комнате: игрок уходит из нее вместе с последним из них
+    connections: std::sync::Mutex<HashMap<i32, usize>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RoomError {
+    InvalidName(String),
+    // Достигнут лимит game.max_rooms, новый инстанс создать нельзя
+    TooManyRooms,
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomError::InvalidName(name) => write!(f, "invalid room name {:?}", name),
+            RoomError::TooManyRooms => write!(f, "room limit reached"),
+        }
+    }
+}
+
+impl std::error::Error for RoomError {}
+
+struct Registered {
+    room: Arc<Room>,
+    // Участники комнаты; инстанс удаляется, когда счетчик падает до нуля
+    members: usize,
+}
+
+pub struct RoomRegistry {
+    // std::sync::Mutex: блокировка никогда не удерживается через .await
+    rooms: std::sync::Mutex<HashMap<String, Registered>>,
+    default_world: String,
+    max_rooms: usize,
+    channel_capacity: usize,
+    generator: GeneratorKind,
+    world_seed: u64,
+    maps: HashMap<String, Arc<TiledMap>>,
+    respawn_points: HashMap<String, Vec<(f64, f64)>>,
+}
+
+// Имя комнаты: латиница, цифры, '-' и '_'
+pub fn is_valid_room_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ROOM_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Room {
+    fn new(name: &str, persistent: bool, channel_capacity: usize, generator: WorldGenerator, mut respawn_points: Vec<(f64, f64)>) -> Self {
+        let mut entities = EntityStore::default();
+        if let Some(map) = generator.map() {
+            respawn_points.extend(map.respawn_points());
+            for item in map.placed_items() {
+                entities.insert(item);
+            }
+        }
+        Room {
+            spawn: generator.spawn_point(),
+            map: generator.map().cloned(),
+            tiles: Mutex::new(TileWorld::new(generator)),
+            name: name.to_string(),
+            persistent,
+            game_state_tx: broadcast::channel(channel_capacity).0,
+            snapshot_tx: broadcast::channel(channel_capacity).0,
+            active_player_positions: Mutex::new(HashMap::new()),
+            moved_players: Mutex::new(HashSet::new()),
+            combatants: Mutex::new(HashMap::new()),
+            respawn_points,
+            entities: Mutex::new(entities),
+            npcs: Mutex::new(HashMap::new()),
+            connections: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn connections(&self) -> std::sync::MutexGuard<'_, HashMap<i32, usize>> {
+        self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn connection_entered(&self, user_id: i32) {
+        *self.connections().entry(user_id).or_insert(0) += 1;
+    }
+
+    // Соединение игрока ушло из комнаты; true, если других его соединений в ней нет
+    pub fn connection_left(&self, user_id: i32) -> bool {
+        let mut connections = self.connections();
+        let Some(count) = connections.get_mut(&user_id) else {
+            return true;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return false;
+        }
+        connections.remove(&user_id);
+        true
+    }
+
+    pub fn has_connection(&self, user_id: i32) -> bool {
+        self.connections().contains_key(&user_id)
+    }
+
+    // Позиция только что появившегося в комнате игрока
+    pub fn spawn_position(&self, user_id: i32) -> PlayerPositionUpdate {
+        PlayerPositionUpdate { user_id, x: self.spawn.0, y: self.spawn.1, ..Default::default() }
+    }
+
+    // Ближайшая к месту гибели точка возрождения
+    pub fn respawn_point(&self, near: (f64, f64)) -> (f64, f64) {
+        let distance = |point: &(f64, f64)| (point.0 - near.0).hypot(point.1 - near.1);
+        self.respawn_points
+            .iter()
+            .copied()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(self.spawn)
+    }
+}
+
+impl RoomRegistry {
+    // maps — загруженные карты комнат (см. map::load_maps)
+    pub fn new(config: &GameConfig, maps: HashMap<String, Arc<TiledMap>>) -> Self {
+        let registry = RoomRegistry {
+            rooms: std::sync::Mutex::new(HashMap::new()),
+            default_world: config.default_world.clone(),
+            max_rooms: config.max_rooms,
+            channel_capacity: config.broadcast_capacity,
+            generator: config.generator,
+            world_seed: config.world_seed,
+            maps,
+            respawn_points: config.respawn_points.clone(),
+        };
+        let worlds = config.worlds
+            .iter()
+            .map(|name| {
+                let room = Arc::new(registry.new_room(name, true));
+                (name.clone(), Registered { room, members: 0 })
+            })
+            .collect();
+        *registry.rooms() = worlds;
+        registry
+    }
+
+    fn new_room(&self, name: &str, persistent: bool) -> Room {
+        let generator = WorldGenerator::new(self.generator, self.world_seed, name);
+        let generator = match self.maps.get(name) {
+            Some(map) => generator.with_map(map.clone()),
+            None => generator,
+        };
+        let respawn_points = self.respawn_points.get(name).cloned().unwrap_or_default();
+        Room::new(name, persistent, self.channel_capacity, generator, respawn_points)
+    }
+
+    fn rooms(&self) -> std::sync::MutexGuard<'_, HashMap<String, Registered>> {
+        self.rooms.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn default_world(&self) -> &str {
+        &self.default_world
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Room>> {
+        self.rooms().get(name).map(|registered| registered.room.clone())
+    }
+
+    // Все комнаты (для игрового цикла)
+    pub fn all(&self) -> Vec<Arc<Room>> {
+        self.rooms().values().map(|registered| registered.room.clone()).collect()
+    }
+
+    // Входит в комнату, создавая инстанс при необходимости. Каждому успешному join
+    // должен соответствовать ровно один leave
+    pub fn join(&self, name: &str) -> Result<Arc<Room>, RoomError> {
+        if !is_valid_room_name(name) {
+            return Err(RoomError::InvalidName(name.to_string()));
+        }
+        if let Some(registered) = self.rooms().get_mut(name) {
+            registered.members += 1;
+            return Ok(registered.room.clone());
+        }
+        // Поиск точки появления может занять время, поэтому инстанс создается без блокировки
+        let instance = self.new_room(name, false);
+        let mut rooms = self.rooms();
+        if !rooms.contains_key(name) && rooms.len() >= self.max_rooms {
+            return Err(RoomError::TooManyRooms);
+        }
+        // Инстанс мог успеть создать другой игрок
+        let registered = rooms.entry(name.to_string()).or_insert_with(|| Registered {
+            room: Arc::new(instance),
+            members: 0,
+        });
+        registered.members += 1;
+        Ok(registered.room.clone())
+    }
+
+    // Вход в существующую комнату, иначе в мир по умолчанию (например, если инстанс,
+    // в котором игрок вышел из игры, уже удален)
+    pub fn join_existing_or_default(&self, name: &str) -> Arc<Room> {
+        let mut rooms = self.rooms();
+        let name = if rooms.contains_key(name) { name } else { self.default_world.as_str() };
+        let registered = rooms.get_mut(name).expect("default world is always registered");
+        registered.members += 1;
+        registered.room.clone()
+    }
+
+    // Выход из комнаты; опустевший инстанс удаляется. true, если комната удалена
+    // (игровой цикл ее больше не видит, и несохраненные чанки должен записать вызывающий)
+    pub fn leave(&self, room: &Room) -> bool {
+        let mut rooms = self.rooms();
+        if let Some(registered) = rooms.get_mut(&room.name) {
+            registered.members = registered.members.saturating_sub(1);
+            if registered.members == 0 && !registered.room.persistent {
+                rooms.remove(&room.name);
+                return true;
+            }
+        }
+        false
+    }
+
+    // Игроков онлайн во всех комнатах
+    pub fn online_players(&self) -> usize {
+        self.rooms().values().map(|registered| registered.members).sum()
+    }
+}
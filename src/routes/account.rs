@@ -0,0 +1,268 @@
+// src/routes/account.rs
+use axum::{
+    extract::Json,
+    response::{IntoResponse, Response},
+    http::StatusCode,
+    Extension,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::{
+    clans::{ClanId, ClanRank},
+    friends::FriendRequest,
+    routes::auth::{Claims, ErrorResponse},
+    state::AppState,
+    storage::{Storage, StorageError, StorageResult},
+};
+
+// Как часто фоновая задача проверяет аккаунты, срок удаления которых истек
+const PURGE_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Serialize)]
+pub struct DeletionStatusResponse {
+    deletion_requested_at: Option<DateTime<Utc>>,
+    deletion_scheduled_for: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedUser {
+    id: i32,
+    login: String,
+    created_at: DateTime<Utc>,
+    deletion_requested_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedPlayer {
+    x: f64,
+    y: f64,
+    z: f64,
+    room: String,
+    // Здоровье, если игрок уже получал урон
+    health: Option<i32>,
+    max_health: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedItem {
+    slot: u32,
+    item: String,
+    count: u32,
+}
+
+#[derive(Serialize)]
+pub struct ExportedClan {
+    id: ClanId,
+    name: String,
+    tag: String,
+    rank: ClanRank,
+    joined_at: DateTime<Utc>,
+}
+
+// Группа, в которой игрок состоит по данным хранилища (game.persist_parties)
+#[derive(Serialize)]
+pub struct ExportedParty {
+    id: u64,
+    leader: i32,
+    members: Vec<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedFriend {
+    user_id: i32,
+    login: String,
+    since: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct AccountExport {
+    exported_at: DateTime<Utc>,
+    user: ExportedUser,
+    player: Option<ExportedPlayer>,
+    inventory: Vec<ExportedItem>,
+    clan: Option<ExportedClan>,
+    party: Option<ExportedParty>,
+    friends: Vec<ExportedFriend>,
+    incoming_friend_requests: Vec<FriendRequest>,
+    outgoing_friend_requests: Vec<FriendRequest>,
+}
+
+pub(crate) fn user_id_from_claims(claims: &Claims) -> Option<i32> {
+    let user_id = claims.sub.parse().ok();
+    if user_id.is_none() {
+        warn!(sub = %claims.sub, "Failed to parse user_id from claims.sub");
+    }
+    user_id
+}
+
+pub(crate) fn invalid_token() -> Response {
+    (StatusCode::UNAUTHORIZED, Json(ErrorResponse { message: "Invalid token".to_string() })).into_response()
+}
+
+fn account_not_found() -> Response {
+    (StatusCode::NOT_FOUND, Json(ErrorResponse { message: "Account not found".to_string() })).into_response()
+}
+
+fn internal_error(context: &str, e: StorageError) -> Response {
+    error!(error = %e, "{}", context);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { message: "Internal server error".to_string() })).into_response()
+}
+
+fn deletion_status(requested_at: Option<DateTime<Utc>>, grace_days: i64) -> DeletionStatusResponse {
+    DeletionStatusResponse {
+        deletion_requested_at: requested_at,
+        deletion_scheduled_for: requested_at.map(|t| t + Duration::days(grace_days)),
+    }
+}
+
+// Проверяет, запрошено ли удаление аккаунта (такие аккаунты не допускаются в игру)
+pub async fn is_deletion_pending(storage: &Storage, user_id: i32) -> StorageResult<bool> {
+    let user = storage.users.find_by_id(user_id).await?;
+    Ok(user.is_some_and(|u| u.deletion_requested_at.is_some()))
+}
+
+// Запрашивает удаление аккаунта. Сами данные удаляются только по истечении auth.deletion_grace_days
+pub async fn request_deletion(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<DeletionStatusResponse>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+
+    let requested_at = app_state.storage.users.request_deletion(user_id)
+        .await
+        .map_err(|e| internal_error("Account deletion request DB error", e))?
+        .ok_or_else(account_not_found)?;
+
+    info!(user_id, "Account deletion requested");
+    // Уже открытые игровые соединения тоже закрываются
+    app_state.kick(user_id);
+    Ok(Json(deletion_status(Some(requested_at), app_state.config.auth.deletion_grace_days)))
+}
+
+// Отменяет запрос на удаление, пока не истек срок ожидания
+pub async fn cancel_deletion(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<DeletionStatusResponse>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+
+    let cutoff = Utc::now() - Duration::days(app_state.config.auth.deletion_grace_days);
+    let cancelled = app_state.storage.users.cancel_deletion(user_id, cutoff)
+        .await
+        .map_err(|e| internal_error("Account deletion cancel DB error", e))?;
+
+    if !cancelled {
+        // Аккаунт есть, но срок отмены истек: данные удалит ближайшая очистка
+        let exists = app_state.storage.users.find_by_id(user_id)
+            .await
+            .map_err(|e| internal_error("Account deletion cancel DB error", e))?
+            .is_some();
+        if !exists {
+            return Err(account_not_found());
+        }
+        let message = "Deletion grace period has expired".to_string();
+        return Err((StatusCode::GONE, Json(ErrorResponse { message })).into_response());
+    }
+
+    info!(user_id, "Account deletion cancelled");
+    Ok(Json(deletion_status(None, app_state.config.auth.deletion_grace_days)))
+}
+
+// Выгрузка всех персональных данных пользователя в JSON
+pub async fn export_data(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<AccountExport>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+
+    let user = app_state.storage.users.find_by_id(user_id)
+        .await
+        .map_err(|e| internal_error("Account export user DB error", e))?
+        .ok_or_else(account_not_found)?;
+
+    let player = app_state.storage.players.load_player(user_id)
+        .await
+        .map_err(|e| internal_error("Account export player DB error", e))?;
+    let vitals = app_state.storage.players.load_vitals(user_id)
+        .await
+        .map_err(|e| internal_error("Account export player DB error", e))?;
+    let inventory = app_state.storage.inventories.load_inventory(user_id)
+        .await
+        .map_err(|e| internal_error("Account export inventory DB error", e))?;
+    let clan_id = app_state.storage.clans.clan_of(user_id)
+        .await
+        .map_err(|e| internal_error("Account export clan DB error", e))?;
+    let clan = match clan_id {
+        Some(clan_id) => app_state.storage.clans.load_clan(clan_id)
+            .await
+            .map_err(|e| internal_error("Account export clan DB error", e))?,
+        None => None,
+    };
+    let party = app_state.storage.parties.party_of(user_id)
+        .await
+        .map_err(|e| internal_error("Account export party DB error", e))?;
+    let friends = app_state.storage.friends.friends(user_id)
+        .await
+        .map_err(|e| internal_error("Account export friends DB error", e))?;
+    let incoming_friend_requests = app_state.storage.friends.incoming_requests(user_id)
+        .await
+        .map_err(|e| internal_error("Account export friends DB error", e))?;
+    let outgoing_friend_requests = app_state.storage.friends.outgoing_requests(user_id)
+        .await
+        .map_err(|e| internal_error("Account export friends DB error", e))?;
+
+    Ok(Json(AccountExport {
+        exported_at: Utc::now(),
+        user: ExportedUser {
+            id: user.id,
+            login: user.login,
+            created_at: user.created_at,
+            deletion_requested_at: user.deletion_requested_at,
+        },
+        player: player.map(|p| ExportedPlayer {
+            x: p.x,
+            y: p.y,
+            z: p.z,
+            room: p.room,
+            health: vitals.map(|v| v.health),
+            max_health: vitals.map(|v| v.max_health),
+        }),
+        inventory: inventory
+            .into_iter()
+            .map(|(slot, stack)| ExportedItem { slot, item: stack.item, count: stack.count })
+            .collect(),
+        clan: clan.and_then(|clan| {
+            let member = clan.member(user_id)?.clone();
+            Some(ExportedClan { id: clan.id, name: clan.name, tag: clan.tag, rank: member.rank, joined_at: member.joined_at })
+        }),
+        party: party.map(|(id, members)| ExportedParty { id, leader: members[0], members }),
+        friends: friends
+            .into_iter()
+            .map(|friend| ExportedFriend { user_id: friend.user_id, login: friend.login, since: friend.since })
+            .collect(),
+        incoming_friend_requests,
+        outgoing_friend_requests,
+    }))
+}
+
+// Окончательно удаляет аккаунты, у которых истек срок ожидания, вместе со связанными данными
+pub async fn purge_expired_accounts(storage: &Storage, grace_days: i64) -> StorageResult<u64> {
+    let cutoff = Utc::now() - Duration::days(grace_days);
+    storage.users.purge_deleted_before(cutoff).await
+}
+
+// Фоновая задача периодической очистки
+pub async fn run_purge_task(storage: Storage, grace_days: i64) {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(PURGE_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        match purge_expired_accounts(&storage, grace_days).await {
+            Ok(0) => {},
+            Ok(count) => info!(count, "Purged expired accounts"),
+            Err(e) => error!(error = %e, "Error purging expired accounts"),
+        }
+    }
+}
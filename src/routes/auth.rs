@@ -13,18 +13,19 @@ use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{encode, decode, DecodingKey, Validation, Header, EncodingKey};
 use chrono::Utc;
 use std::sync::Arc;
+use tracing::{debug, error, info};
 
-use crate::{models::user::User, config::Config, state::AppState};
+use crate::{state::AppState, storage::StorageError, telemetry};
 
 #[derive(Serialize, Deserialize)]
 pub struct RegisterRequest {
-    login: String,
-    password: String,
+    pub login: String,
+    pub password: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct LoginResponse {
-    token: String,
+    pub token: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)] // Добавлены Clone и Debug
@@ -34,8 +35,8 @@ pub struct Claims {
 }
 
 #[derive(Serialize)]
-struct ErrorResponse {
-    message: String,
+pub struct ErrorResponse {
+    pub message: String,
 }
 
 // JWT Authentication Middleware
@@ -44,8 +45,7 @@ pub async fn auth_middleware(
     mut req: Request<Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let config = Config::from_env().unwrap();
-    let jwt_secret = config.jwt_secret.as_bytes();
+    let jwt_secret = app_state.config.auth.jwt_secret.as_bytes();
 
     let token = req.headers()
         .get(axum::http::header::AUTHORIZATION)
@@ -70,7 +70,7 @@ pub async fn auth_middleware(
             Ok(next.run(req).await)
         },
         Err(e) => {
-            eprintln!("JWT validation failed: {:?}", e);
+            debug!(error = %e, "JWT validation failed");
             Err(StatusCode::UNAUTHORIZED)
         }
     }
@@ -81,19 +81,22 @@ pub async fn register(
     Json(payload): Json<RegisterRequest>,
 ) -> impl IntoResponse {
     let hashed_password = hash(&payload.password, DEFAULT_COST).unwrap();
-    match sqlx::query!(
-        "INSERT INTO users (login, hashed_password) VALUES ($1, $2)",
-        payload.login,
-        hashed_password
-    )
-        .execute(&app_state.pool)
-        .await
-    {
-        Ok(_) => "Registered successfully".into_response(),
-        Err(e) => {
-            let error_msg = format!("Registration failed: {}", e);
+    let result = app_state.storage.users.create_user(&payload.login, &hashed_password).await;
+    telemetry::registration_attempt(result.is_ok());
+    match result {
+        Ok(user_id) => {
+            info!(user_id, "User registered");
+            "Registered successfully".into_response()
+        },
+        Err(StorageError::Conflict(_)) => {
+            let error_msg = "Registration failed: login is already taken".to_string();
             (StatusCode::BAD_REQUEST, Json(ErrorResponse { message: error_msg })).into_response()
         },
+        Err(e) => {
+            error!(error = %e, "Registration storage error");
+            let error_msg = "Registration failed".to_string();
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { message: error_msg })).into_response()
+        },
     }
 }
 
@@ -101,32 +104,39 @@ pub async fn login(
     Extension(app_state): Extension<Arc<AppState>>,
     Json(payload): Json<RegisterRequest>,
 ) -> Result<Json<LoginResponse>, Response> {
-    let user = sqlx::query_as::<_, User>("SELECT id, login, hashed_password FROM users WHERE login = $1")
-        .bind(payload.login)
-        .fetch_one(&app_state.pool)
+    let invalid_credentials = || {
+        telemetry::login_attempt(false);
+        (StatusCode::UNAUTHORIZED, Json(ErrorResponse { message: "Invalid credentials".to_string() })).into_response()
+    };
+
+    let user = app_state.storage.users.find_by_login(&payload.login)
         .await
         .map_err(|e| {
-            eprintln!("Login DB fetch error: {:?}", e);
-            (StatusCode::UNAUTHORIZED, Json(ErrorResponse { message: "Invalid credentials".to_string() })).into_response()
-        })?;
+            error!(error = %e, "Login DB fetch error");
+            telemetry::login_attempt(false);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { message: "Internal server error".to_string() })).into_response()
+        })?
+        .ok_or_else(invalid_credentials)?;
 
     if verify(&payload.password, &user.hashed_password).unwrap_or(false) {
-        let config = Config::from_env().unwrap();
         let claims = Claims {
             sub: user.id.to_string(),
-            exp: (Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
+            exp: (Utc::now() + chrono::Duration::hours(app_state.config.auth.token_lifetime_hours)).timestamp() as usize,
         };
         let token = encode(
             &Header::default(),
             &claims,
-            &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+            &EncodingKey::from_secret(app_state.config.auth.jwt_secret.as_bytes()),
         )
             .map_err(|e| {
-                eprintln!("Login JWT encoding error: {:?}", e);
+                error!(error = %e, "Login JWT encoding error");
+                telemetry::login_attempt(false);
                 (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { message: "Failed to generate token".to_string() })).into_response()
             })?;
+        telemetry::login_attempt(true);
+        info!(user_id = user.id, "User logged in");
         Ok(Json(LoginResponse { token }))
     } else {
-        Err((StatusCode::UNAUTHORIZED, Json(ErrorResponse { message: "Invalid credentials".to_string() })).into_response())
+        Err(invalid_credentials())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,163 @@
+// src/routes/clans.rs
+// REST API кланов: те же действия, что и игровые сообщения Clan*, для клиентов вне игры
+use axum::{
+    extract::{Json, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::{
+    clans::{self, Clan, ClanError, ClanId, ClanInvite, ClanRank},
+    routes::{account::{invalid_token, user_id_from_claims}, auth::{Claims, ErrorResponse}},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct CreateClanRequest {
+    name: String,
+    tag: String,
+}
+
+#[derive(Deserialize)]
+pub struct InviteRequest {
+    user_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct SetRankRequest {
+    rank: ClanRank,
+}
+
+fn clan_error(e: ClanError) -> Response {
+    let status = match &e {
+        ClanError::Invalid(_) => StatusCode::BAD_REQUEST,
+        ClanError::NotFound(_) => StatusCode::NOT_FOUND,
+        ClanError::Forbidden(_) => StatusCode::FORBIDDEN,
+        ClanError::Conflict(_) => StatusCode::CONFLICT,
+        ClanError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let message = match e {
+        ClanError::Storage(e) => {
+            error!(error = %e, "Clan storage error");
+            "Internal server error".to_string()
+        },
+        other => other.to_string(),
+    };
+    (status, Json(ErrorResponse { message })).into_response()
+}
+
+// POST /clans — создать клан; создатель становится лидером
+pub async fn create(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Json(request): Json<CreateClanRequest>,
+) -> Result<(StatusCode, Json<Clan>), Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    let clan = clans::create(&app_state, user_id, &request.name, &request.tag).await.map_err(clan_error)?;
+    Ok((StatusCode::CREATED, Json(clan)))
+}
+
+// GET /clans/:id
+pub async fn get(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Path(clan): Path<ClanId>,
+) -> Result<Json<Clan>, Response> {
+    Ok(Json(clans::get(&app_state, clan).await.map_err(clan_error)?))
+}
+
+// GET /clan — клан текущего игрока
+pub async fn my_clan(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Clan>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    Ok(Json(clans::my_clan(&app_state, user_id).await.map_err(clan_error)?))
+}
+
+// DELETE /clan — распустить клан (только лидер)
+pub async fn disband(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    clans::disband(&app_state, user_id).await.map_err(clan_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// POST /clan/leave
+pub async fn leave(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    clans::leave(&app_state, user_id).await.map_err(clan_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// POST /clan/invites — пригласить игрока в свой клан
+pub async fn invite(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Json(request): Json<InviteRequest>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    clans::invite(&app_state, user_id, request.user_id).await.map_err(clan_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// GET /clan/invites — приглашения текущему игроку
+pub async fn invites(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<ClanInvite>>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    Ok(Json(clans::invites(&app_state, user_id).await.map_err(clan_error)?))
+}
+
+// POST /clan/invites/:clan/accept
+pub async fn accept(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(clan): Path<ClanId>,
+) -> Result<Json<Clan>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    Ok(Json(clans::accept(&app_state, user_id, clan).await.map_err(clan_error)?))
+}
+
+// POST /clan/invites/:clan/decline
+pub async fn decline(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(clan): Path<ClanId>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    clans::decline(&app_state, user_id, clan).await.map_err(clan_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// DELETE /clan/members/:user_id — исключить участника
+pub async fn kick(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(target): Path<i32>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    clans::kick(&app_state, user_id, target).await.map_err(clan_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// PUT /clan/members/:user_id/rank
+pub async fn set_rank(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(target): Path<i32>,
+    Json(request): Json<SetRankRequest>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    clans::set_rank(&app_state, user_id, target, request.rank).await.map_err(clan_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,92 @@
+// src/routes/friends.rs
+// REST API друзей: список со статусом онлайн и запросы дружбы
+use axum::{
+    extract::{Json, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::{
+    friends::{self, FriendError, FriendList},
+    routes::{account::{invalid_token, user_id_from_claims}, auth::{Claims, ErrorResponse}},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct FriendRequestBody {
+    user_id: i32,
+}
+
+fn friend_error(e: FriendError) -> Response {
+    let status = match &e {
+        FriendError::Invalid(_) => StatusCode::BAD_REQUEST,
+        FriendError::NotFound(_) => StatusCode::NOT_FOUND,
+        FriendError::Conflict(_) => StatusCode::CONFLICT,
+        FriendError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let message = match e {
+        FriendError::Storage(e) => {
+            error!(error = %e, "Friends storage error");
+            "Internal server error".to_string()
+        },
+        other => other.to_string(),
+    };
+    (status, Json(ErrorResponse { message })).into_response()
+}
+
+// GET /friends — друзья, входящие и исходящие запросы
+pub async fn list(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<FriendList>, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    Ok(Json(friends::list(&app_state, user_id).await.map_err(friend_error)?))
+}
+
+// POST /friends/requests — отправить запрос дружбы
+pub async fn send_request(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Json(request): Json<FriendRequestBody>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    friends::send_request(&app_state, user_id, request.user_id).await.map_err(friend_error)?;
+    Ok(StatusCode::CREATED)
+}
+
+// POST /friends/requests/:user_id/accept
+pub async fn accept(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(from): Path<i32>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    friends::accept(&app_state, user_id, from).await.map_err(friend_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// POST /friends/requests/:user_id/decline
+pub async fn decline(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(from): Path<i32>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    friends::decline(&app_state, user_id, from).await.map_err(friend_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// DELETE /friends/:user_id — удалить из друзей
+pub async fn remove(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Path(friend): Path<i32>,
+) -> Result<StatusCode, Response> {
+    let user_id = user_id_from_claims(&claims).ok_or_else(invalid_token)?;
+    friends::remove(&app_state, user_id, friend).await.map_err(friend_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
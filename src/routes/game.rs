@@ -1,21 +1,60 @@
 use axum::{
-    extract::{WebSocketUpgrade, ws::{Message, WebSocket}},
+    extract::{WebSocketUpgrade, ws::{close_code, CloseFrame, Message, WebSocket}},
     response::IntoResponse,
+    http::StatusCode,
     Extension
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use tracing::{debug, error, info, info_span, trace, warn, Instrument, Span};
 
+use crate::clans::{self, Clan, ClanId, ClanLeaveReason, ClanRank};
+use crate::collision::{self, PLAYER_RADIUS};
+use crate::combat::{self, Combatant};
+use crate::entities::{self, Entity, EntityId};
+use crate::friends;
+use crate::inventory;
+use crate::items::{self, Inventory};
+use crate::map::{self, MapMetadata, Teleporter};
+use crate::models::player::{Player, Vitals};
+use crate::party::{self, Party, PartyId, PartyLeaveReason};
+use crate::rooms::Room;
+use crate::scripting;
+use crate::snapshot::{self, SnapshotDelta, SnapshotEncoder, WorldSnapshot};
 use crate::state::AppState;
-use crate::routes::auth::Claims;
+use crate::telemetry;
+use crate::tiles::{self, ChunkPos, Tile, AIR};
+use crate::trading::{self, Trade, TradeId};
+use crate::world;
+use crate::routes::{account, auth::Claims};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PlayerPositionUpdate {
     pub user_id: i32,
     pub x: f64,
     pub y: f64,
     pub z: f64,
+    // От клиента: номер ввода, строго возрастающий в пределах соединения (начиная с 1).
+    // От сервера: номер последнего примененного ввода этого игрока (0 — вводов еще не было);
+    // по нему клиент сверяет предсказанную позицию с авторитетной
+    pub seq: u64,
+    // Поворот (радианы) и скорость не сохраняются в базе, клиент может их не передавать
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default)]
+    pub vx: f64,
+    #[serde(default)]
+    pub vy: f64,
+    #[serde(default)]
+    pub vz: f64,
+    // Тег клана игрока; назначает сервер, значение от клиента игнорируется
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clan_tag: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,65 +64,753 @@ pub enum GameMessage {
     PlayerDisconnected { user_id: i32 },
     InitialPlayers(Vec<PlayerPositionUpdate>),
     PlayerLogout { user_id: i32 },
+    // Рукопожатие: первое сообщение клиента и ответ сервера на него
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+    Welcome {
+        protocol_version: u32,
+        user_id: i32,
+        // Возможности, которые поддерживают и клиент, и сервер
+        features: Vec<String>,
+        // Комната, в которую попал игрок
+        room: String,
+        // Здоровье игрока на момент входа
+        vitals: Vitals,
+    },
+    // Ответ клиенту на некорректный ввод
+    Error { code: ErrorCode, message: String },
+    // Возможность "delta_snapshots": снимок мира относительно подтвержденной базы
+    // и подтверждение его получения клиентом
+    Snapshot(SnapshotDelta),
+    SnapshotAck { tick: u64 },
+    // Переход в другую комнату (инстанс создается, если его еще нет) и подтверждение перехода;
+    // после RoomChanged сервер присылает InitialPlayers новой комнаты
+    ChangeRoom { room: String },
+    RoomChanged { room: String },
+    // Тайловый мир (возможность "tiles"): клиент ставит и ломает тайлы, сервер присылает
+    // чанки вокруг игрока, сообщает о выгрузке дальних и рассылает изменения видимых
+    PlaceTile { x: i32, y: i32, tile: Tile },
+    BreakTile { x: i32, y: i32 },
+    ChunkData { cx: i32, cy: i32, tiles: Vec<Tile> },
+    ChunkUnload { cx: i32, cy: i32 },
+    TileChanged { x: i32, y: i32, tile: Tile },
+    // Возможность "maps": описание карты Tiled комнаты перед InitialPlayers
+    MapInfo(MapMetadata),
+    // Бой: клиент атакует игрока своей комнаты, сервер рассылает всей комнате урон,
+    // гибель и возрождение (после game.respawn_delay_secs в ближайшей точке возрождения)
+    Attack { target: i32 },
+    Damaged { user_id: i32, attacker: i32, amount: i32, health: i32 },
+    Died { user_id: i32, killer: i32 },
+    Respawned { user_id: i32, x: f64, y: f64, health: i32 },
+    // Возможность "inventory": инвентарь игрока (после Welcome и после каждого изменения)
+    // и действия с ячейками. Выброшенные предметы — сущности вида item
+    Inventory(Inventory),
+    MoveItem { from: usize, to: usize },
+    UseItem { slot: usize },
+    // Без count выбрасывается вся стопка
+    DropItem {
+        slot: usize,
+        #[serde(default)]
+        count: Option<u32>,
+    },
+    PickUpItem { id: EntityId },
+    // Подобрано count предметов; лежащий предмет исчезает (EntityDespawned), когда их не остается
+    ItemPickedUp { id: EntityId, user_id: i32, count: u32 },
+    Healed { user_id: i32, amount: i32, health: i32 },
+    // Возможность "entities": сущности комнаты, кроме игроков (InitialEntities — после
+    // InitialPlayers), их появление, изменения (раз в тик) и исчезновение
+    InitialEntities(Vec<Entity>),
+    EntitySpawned(Entity),
+    EntityUpdated(Entity),
+    EntityDespawned { id: EntityId },
+    // Чат комнаты: клиент отправляет текст, сервер рассылает его всей комнате
+    // (скрипты могут изменить или отменить сообщение)
+    Chat { text: String },
+    ChatMessage { user_id: i32, text: String },
+    // Сообщение от сервера (скриптов): одному игроку (to) или всей комнате
+    SystemMessage {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<i32>,
+        text: String,
+    },
+    // Обмен предметами (возможность "inventory"): предложение обмена игроку рядом, согласие,
+    // выкладывание предметов из ячеек (count 0 — убрать), фиксация, подтверждение и отмена.
+    // Обе стороны получают состояние обмена после каждого изменения и TradeClosed в конце;
+    // после успешного обмена — новый Inventory
+    TradeRequest { target: i32 },
+    TradeRequested { from: i32, to: i32 },
+    TradeAccept { from: i32 },
+    TradeOffer { slot: usize, count: u32 },
+    TradeLock,
+    TradeConfirm,
+    TradeCancel,
+    TradeUpdated(Trade),
+    TradeClosed {
+        id: TradeId,
+        parties: [i32; 2],
+        completed: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    // Группы: приглашение, согласие, выход, исключение (только лидер) и чат группы.
+    // Участники получают состав группы после каждого изменения и позиции друг друга
+    // (раз в тик, из любой комнаты); выбывший — PartyLeft
+    PartyInvite { target: i32 },
+    PartyInvited { from: i32, to: i32 },
+    PartyAccept { from: i32 },
+    PartyLeave,
+    PartyKick { target: i32 },
+    PartyChat { text: String },
+    PartyUpdated(Party),
+    PartyLeft { id: PartyId, user_id: i32, reason: PartyLeaveReason },
+    PartyChatMessage { party: PartyId, user_id: i32, text: String },
+    PartyMemberPosition { party: PartyId, user_id: i32, room: String, x: f64, y: f64 },
+    // Кланы: создание, приглашение (офицеры и лидер), вступление, выход, исключение младших
+    // по рангу, назначение рангов и роспуск (только лидер), чат клана. Участники онлайн получают
+    // состав клана после каждого изменения; выбывший — ClanLeft. Смена тега игрока рассылается
+    // его комнате (ClanTagChanged, tag = null — игрок больше не в клане)
+    ClanCreate { name: String, tag: String },
+    ClanInvite { target: i32 },
+    ClanInvited { clan: ClanId, name: String, tag: String, from: i32, to: i32 },
+    ClanAccept { clan: ClanId },
+    ClanLeave,
+    ClanKick { target: i32 },
+    ClanSetRank { target: i32, rank: ClanRank },
+    ClanDisband,
+    ClanChat { text: String },
+    ClanUpdated(Clan),
+    ClanLeft { clan: ClanId, user_id: i32, reason: ClanLeaveReason },
+    ClanDisbanded { clan: ClanId },
+    ClanChatMessage { clan: ClanId, user_id: i32, text: String },
+    ClanTagChanged { user_id: i32, tag: Option<String> },
+    // Друг user_id вошел в игру или вышел из нее; to — получатель уведомления
+    FriendOnline { user_id: i32, to: i32 },
+    FriendOffline { user_id: i32, to: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // Первым сообщением должен быть Hello
+    HandshakeRequired,
+    UnsupportedProtocol,
+    // Сообщение не разбирается как GameMessage
+    InvalidMessage,
+    // Сообщение разобрано, но клиенту его отправлять нельзя
+    UnexpectedMessage,
+    // В комнату нельзя перейти: неверное имя, лимит комнат или игрок уже в ней
+    RoomUnavailable,
+    // Тайл дальше game.reach_distance от игрока или вне видимых чанков; цель атаки дальше game.attack_range
+    OutOfReach,
+    // Ставить некуда (клетка занята) или ломать нечего
+    InvalidTileAction,
+    // Атаковать нельзя: себя, игрока не из этой комнаты, погибшего или в зоне без pvp
+    InvalidTarget,
+    // С прошлой атаки прошло меньше game.attack_cooldown_ms
+    OnCooldown,
+    // Погибший игрок до возрождения не может атаковать, лечиться и менять комнату
+    PlayerDead,
+    // Пустая или несуществующая ячейка, неизвестный предмет, нет места в инвентаре
+    // или предмет нельзя использовать
+    InvalidItemAction,
+    // Пустое или слишком длинное сообщение чата
+    InvalidChatMessage,
+    // Действие обмена сейчас невозможно: нет обмена или предложения, игрок занят,
+    // предложение не зафиксировано или ячейка пуста
+    InvalidTradeAction,
+    // Действие с группой сейчас невозможно: нет группы или приглашения, игрок не лидер,
+    // группа заполнена или игрок уже в группе
+    InvalidPartyAction,
+    // Действие с кланом невозможно: неверные имя или тег, они заняты, нет клана или приглашения,
+    // ранг игрока не позволяет действие
+    InvalidClanAction,
 }
 
+// Версия протокола обмена сообщениями; меняется при несовместимых изменениях GameMessage
+pub const PROTOCOL_VERSION: u32 = 12;
+// Необязательные возможности протокола, которые сервер умеет согласовывать в рукопожатии
+pub const SERVER_FEATURES: &[&str] = &[snapshot::FEATURE, tiles::FEATURE, map::FEATURE, items::FEATURE, entities::FEATURE];
+// Сколько ждать Hello после подключения
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Длина сообщения чата в символах
+pub const MAX_CHAT_LEN: usize = scripting::MAX_MESSAGE_LEN;
+
 pub type SharedGameState = Arc<broadcast::Sender<GameMessage>>;
 
+impl GameMessage {
+    // Имя варианта (совпадает с полем "type" в JSON), используется в метриках
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GameMessage::PlayerPosition(_) => "PlayerPosition",
+            GameMessage::PlayerDisconnected { .. } => "PlayerDisconnected",
+            GameMessage::InitialPlayers(_) => "InitialPlayers",
+            GameMessage::PlayerLogout { .. } => "PlayerLogout",
+            GameMessage::Hello { .. } => "Hello",
+            GameMessage::Welcome { .. } => "Welcome",
+            GameMessage::Error { .. } => "Error",
+            GameMessage::Snapshot(_) => "Snapshot",
+            GameMessage::SnapshotAck { .. } => "SnapshotAck",
+            GameMessage::ChangeRoom { .. } => "ChangeRoom",
+            GameMessage::RoomChanged { .. } => "RoomChanged",
+            GameMessage::PlaceTile { .. } => "PlaceTile",
+            GameMessage::BreakTile { .. } => "BreakTile",
+            GameMessage::ChunkData { .. } => "ChunkData",
+            GameMessage::ChunkUnload { .. } => "ChunkUnload",
+            GameMessage::TileChanged { .. } => "TileChanged",
+            GameMessage::MapInfo(_) => "MapInfo",
+            GameMessage::Attack { .. } => "Attack",
+            GameMessage::Damaged { .. } => "Damaged",
+            GameMessage::Died { .. } => "Died",
+            GameMessage::Respawned { .. } => "Respawned",
+            GameMessage::Inventory(_) => "Inventory",
+            GameMessage::MoveItem { .. } => "MoveItem",
+            GameMessage::UseItem { .. } => "UseItem",
+            GameMessage::DropItem { .. } => "DropItem",
+            GameMessage::PickUpItem { .. } => "PickUpItem",
+            GameMessage::ItemPickedUp { .. } => "ItemPickedUp",
+            GameMessage::Healed { .. } => "Healed",
+            GameMessage::InitialEntities(_) => "InitialEntities",
+            GameMessage::EntitySpawned(_) => "EntitySpawned",
+            GameMessage::EntityUpdated(_) => "EntityUpdated",
+            GameMessage::EntityDespawned { .. } => "EntityDespawned",
+            GameMessage::Chat { .. } => "Chat",
+            GameMessage::ChatMessage { .. } => "ChatMessage",
+            GameMessage::SystemMessage { .. } => "SystemMessage",
+            GameMessage::TradeRequest { .. } => "TradeRequest",
+            GameMessage::TradeRequested { .. } => "TradeRequested",
+            GameMessage::TradeAccept { .. } => "TradeAccept",
+            GameMessage::TradeOffer { .. } => "TradeOffer",
+            GameMessage::TradeLock => "TradeLock",
+            GameMessage::TradeConfirm => "TradeConfirm",
+            GameMessage::TradeCancel => "TradeCancel",
+            GameMessage::TradeUpdated(_) => "TradeUpdated",
+            GameMessage::TradeClosed { .. } => "TradeClosed",
+            GameMessage::PartyInvite { .. } => "PartyInvite",
+            GameMessage::PartyInvited { .. } => "PartyInvited",
+            GameMessage::PartyAccept { .. } => "PartyAccept",
+            GameMessage::PartyLeave => "PartyLeave",
+            GameMessage::PartyKick { .. } => "PartyKick",
+            GameMessage::PartyChat { .. } => "PartyChat",
+            GameMessage::PartyUpdated(_) => "PartyUpdated",
+            GameMessage::PartyLeft { .. } => "PartyLeft",
+            GameMessage::PartyChatMessage { .. } => "PartyChatMessage",
+            GameMessage::PartyMemberPosition { .. } => "PartyMemberPosition",
+            GameMessage::ClanCreate { .. } => "ClanCreate",
+            GameMessage::ClanInvite { .. } => "ClanInvite",
+            GameMessage::ClanInvited { .. } => "ClanInvited",
+            GameMessage::ClanAccept { .. } => "ClanAccept",
+            GameMessage::ClanLeave => "ClanLeave",
+            GameMessage::ClanKick { .. } => "ClanKick",
+            GameMessage::ClanSetRank { .. } => "ClanSetRank",
+            GameMessage::ClanDisband => "ClanDisband",
+            GameMessage::ClanChat { .. } => "ClanChat",
+            GameMessage::ClanUpdated(_) => "ClanUpdated",
+            GameMessage::ClanLeft { .. } => "ClanLeft",
+            GameMessage::ClanDisbanded { .. } => "ClanDisbanded",
+            GameMessage::ClanChatMessage { .. } => "ClanChatMessage",
+            GameMessage::ClanTagChanged { .. } => "ClanTagChanged",
+            GameMessage::FriendOnline { .. } => "FriendOnline",
+            GameMessage::FriendOffline { .. } => "FriendOffline",
+        }
+    }
+}
+
+impl From<Player> for PlayerPositionUpdate {
+    fn from(player: Player) -> Self {
+        PlayerPositionUpdate { user_id: player.user_id, x: player.x, y: player.y, z: player.z, ..Default::default() }
+    }
+}
+
+// Запись игрока для хранилища: позиция в комнате room
+fn player_record(update: &PlayerPositionUpdate, room: &str) -> Player {
+    Player { user_id: update.user_id, x: update.x, y: update.y, z: update.z, room: room.to_string() }
+}
+
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, app_state, claims))
+    let Ok(user_id) = claims.sub.parse::<i32>() else {
+        warn!(sub = %claims.sub, "Failed to parse user_id from claims.sub");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    // Во время остановки новые игроки не принимаются
+    if app_state.is_shutting_down() {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+
+    // Аккаунты, ожидающие удаления, не допускаются в игру. Подписка на отключения оформляется
+    // до проверки, чтобы не пропустить запрос на удаление, пришедший во время подключения
+    let kick_rx = app_state.subscribe_kicks();
+    match account::is_deletion_pending(&app_state.storage, user_id).await {
+        Ok(false) => {},
+        Ok(true) => return StatusCode::FORBIDDEN.into_response(),
+        Err(e) => {
+            error!(user_id, error = %e, "Error checking deletion status");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    // Все записи соединения попадают в span с user_id и conn_id
+    let conn_id = app_state.next_connection_id.fetch_add(1, Ordering::Relaxed);
+    let span = info_span!("ws", user_id, conn_id, room = tracing::field::Empty);
+    ws.on_upgrade(move |socket| handle_socket(socket, app_state, user_id, kick_rx).instrument(span)).into_response()
+}
+
+async fn send_message(socket: &mut WebSocket, msg: &GameMessage) -> bool {
+    let Ok(serialized_msg) = serde_json::to_string(msg) else {
+        return false;
+    };
+    if socket.send(Message::Text(serialized_msg)).await.is_err() {
+        return false;
+    }
+    telemetry::message_out(msg.kind());
+    true
+}
+
+async fn send_error(socket: &mut WebSocket, code: ErrorCode, message: String) -> bool {
+    send_message(socket, &GameMessage::Error { code, message }).await
+}
+
+// Отказ в рукопожатии: Error с подробностями и Close-кадр с понятной причиной
+async fn reject_handshake(socket: &mut WebSocket, code: ErrorCode, reason: String) {
+    warn!(?code, %reason, "Handshake rejected");
+    send_error(socket, code, reason.clone()).await;
+    let _ = socket.send(Message::Close(Some(CloseFrame {
+        code: close_code::PROTOCOL,
+        reason: reason.into(),
+    }))).await;
+}
+
+// Первое сообщение клиента должно быть Hello с поддерживаемой версией протокола.
+// Возвращает согласованные возможности (Welcome отправляется после входа в комнату);
+// None — соединение нужно закрыть
+async fn handshake(socket: &mut WebSocket) -> Option<Vec<String>> {
+    let first_text = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        while let Some(Ok(msg)) = socket.recv().await {
+            match msg {
+                Message::Text(text) => return Some(text),
+                Message::Close(_) => return None,
+                _ => continue,
+            }
+        }
+        None
+    }).await;
+
+    let text = match first_text {
+        Ok(Some(text)) => text,
+        // Клиент ушел, не дождавшись рукопожатия
+        Ok(None) => return None,
+        Err(_) => {
+            reject_handshake(socket, ErrorCode::HandshakeRequired, "Hello was not received in time".to_string()).await;
+            return None;
+        },
+    };
+
+    match serde_json::from_str::<GameMessage>(&text) {
+        Ok(GameMessage::Hello { protocol_version, features }) => {
+            telemetry::message_in("Hello");
+            if protocol_version != PROTOCOL_VERSION {
+                let reason = format!(
+                    "unsupported protocol version {} (server supports {})",
+                    protocol_version, PROTOCOL_VERSION
+                );
+                reject_handshake(socket, ErrorCode::UnsupportedProtocol, reason).await;
+                return None;
+            }
+            Some(features
+                .into_iter()
+                .filter(|feature| SERVER_FEATURES.contains(&feature.as_str()))
+                .collect())
+        },
+        Ok(other) => {
+            telemetry::message_in(other.kind());
+            let reason = format!("first message must be Hello, got {}", other.kind());
+            reject_handshake(socket, ErrorCode::HandshakeRequired, reason).await;
+            None
+        },
+        Err(_) => {
+            telemetry::message_in("invalid");
+            reject_handshake(socket, ErrorCode::HandshakeRequired, "first message must be a valid Hello".to_string()).await;
+            None
+        },
+    }
 }
 
-async fn handle_socket(mut socket: WebSocket, app_state: Arc<AppState>, claims: Claims) {
-    let mut game_state_rx = app_state.game_state_tx.subscribe();
+async fn recv_snapshot(
+    snapshot_rx: &mut Option<broadcast::Receiver<Arc<WorldSnapshot>>>,
+) -> Result<Arc<WorldSnapshot>, broadcast::error::RecvError> {
+    match snapshot_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
 
-    let current_user_id: i32 = claims.sub.parse().unwrap_or_else(|_| {
-        eprintln!("Failed to parse user_id from claims.sub: {}", claims.sub);
-        panic!("Invalid user ID in claims!");
-    });
+// Добавляет игрока в комнату и отправляет ему список ее игроков
+// (с возможностью "maps" — перед ним описание карты комнаты, если она есть,
+// с возможностью "entities" — после него остальные сущности комнаты)
+async fn enter_room(
+    socket: &mut WebSocket,
+    room: &Room,
+    position: PlayerPositionUpdate,
+    combatant: Combatant,
+    maps_enabled: bool,
+    entities_enabled: bool,
+) {
+    Span::current().record("room", room.name.as_str());
+    // Если в комнате уже есть другое соединение игрока, он остается в ней со своим состоянием
+    room.connection_entered(position.user_id);
+    room.combatants.lock().await.entry(position.user_id).or_insert(combatant);
+    if let Some(map) = room.map.as_ref().filter(|_| maps_enabled) {
+        send_message(socket, &GameMessage::MapInfo(map.metadata.clone())).await;
+    }
+    let players: Vec<PlayerPositionUpdate> = {
+        let mut active_players_map = room.active_player_positions.lock().await;
+        active_players_map.entry(position.user_id).or_insert(position);
+        active_players_map.values().cloned().collect()
+    };
+    if send_message(socket, &GameMessage::InitialPlayers(players)).await {
+        debug!("Sent InitialPlayers to client");
+    } else {
+        warn!("Failed to send initial active players to client");
+    }
+    if entities_enabled {
+        let entities = room.entities.lock().await.all();
+        send_message(socket, &GameMessage::InitialEntities(entities)).await;
+    }
+}
 
-    println!("DEBUG: Client {} connected via WebSocket", current_user_id);
+// Текст сообщения чата без пробелов по краям; пустой или длиннее MAX_CHAT_LEN — ошибка
+fn chat_text(text: &str) -> Result<&str, (ErrorCode, String)> {
+    let text = text.trim();
+    if text.is_empty() || text.chars().count() > MAX_CHAT_LEN {
+        return Err((ErrorCode::InvalidChatMessage, format!("chat message must be 1 to {} characters long", MAX_CHAT_LEN)));
+    }
+    Ok(text)
+}
+
+// Новое состояние инвентаря владельцу или ошибка действия
+async fn send_inventory_result(socket: &mut WebSocket, result: Result<Inventory, (ErrorCode, String)>) {
+    match result {
+        Ok(inventory) => send_message(socket, &GameMessage::Inventory(inventory)).await,
+        Err((code, message)) => send_error(socket, code, message).await,
+    };
+}
+
+// Соединение уходит из комнаты. Игрок остается в ней, пока там есть другие его соединения
+// (они же продолжают смотреть его чанки); с последним из них он убирается из комнаты.
+// Возвращает боевое состояние игрока для переноса в другую комнату
+async fn exit_room(app_state: &AppState, room: &Room, user_id: i32, watched: &mut HashSet<ChunkPos>) -> Option<Combatant> {
+    if !room.connection_left(user_id) {
+        watched.clear();
+        return room.combatants.lock().await.get(&user_id).cloned();
+    }
+    scripting::on_leave(app_state, room, user_id).await;
+    release_view(room, user_id, watched).await;
+    remove_from_room(room, user_id).await
+}
+
+// Убирает игрока из комнаты и сообщает об этом остальным (если он там еще был)
+async fn remove_from_room(room: &Room, user_id: i32) -> Option<Combatant> {
+    let mut combatants = room.combatants.lock().await;
+    let mut active_players_map = room.active_player_positions.lock().await;
+    // Новое соединение игрока могло войти в комнату, пока уходило последнее
+    if room.has_connection(user_id) {
+        return combatants.get(&user_id).cloned();
+    }
+    let combatant = combatants.remove(&user_id);
+    drop(combatants);
+    if active_players_map.remove(&user_id).is_none() {
+        return combatant;
+    }
+    drop(active_players_map);
+    if let Err(e) = room.game_state_tx.send(GameMessage::PlayerDisconnected { user_id }) {
+        debug!(error = ?e, "No subscribers for PlayerDisconnected");
+    } else {
+        debug!(subscribers = room.game_state_tx.receiver_count(), "Broadcasted PlayerDisconnected");
+    }
+    combatant
+}
+
+// Выход из комнаты; несохраненные чанки удаленного инстанса записываются здесь
+async fn leave_room(app_state: &AppState, room: &Room) {
+    if app_state.rooms.leave(room) {
+        world::save_chunks(app_state, room).await;
+    }
+}
+
+// Подписывает игрока на чанк и возвращает его тайлы. Незагруженный чанк генерируется, и к нему
+// применяются сохраненные изменения; None — изменения прочитать не удалось
+async fn watch_chunk(app_state: &AppState, room: &Room, pos: ChunkPos, user_id: i32) -> Option<Vec<Tile>> {
+    if let Some(chunk) = room.tiles.lock().await.watch(pos, user_id) {
+        return Some(chunk.tiles().to_vec());
+    }
+    let query_started = Instant::now();
+    let result = app_state.storage.chunks.load_chunk(&room.name, pos).await;
+    telemetry::db_query("load_chunk", query_started.elapsed());
+    let changes = match result {
+        Ok(changes) => changes,
+        Err(e) => {
+            error!(error = %e, cx = pos.cx, cy = pos.cy, "Error loading chunk");
+            return None;
+        },
+    };
+    // Вставка и подписка под одной блокировкой, иначе игровой цикл может выгрузить чанк без зрителей
+    let mut tiles = room.tiles.lock().await;
+    tiles.insert(pos, changes);
+    tiles.watch(pos, user_id).map(|chunk| chunk.tiles().to_vec())
+}
+
+// Приводит набор отправленных клиенту чанков к квадрату game.view_distance вокруг center:
+// лишние выгружаются (ChunkUnload), недостающие отправляются от ближних к дальним (ChunkData).
+// С возможностью "entities" за новыми чанками следует состояние сущностей в них (EntityUpdated):
+// пока чанк не виден, изменения его сущностей клиенту не приходят
+async fn update_view(
+    socket: &mut WebSocket,
+    app_state: &AppState,
+    room: &Room,
+    user_id: i32,
+    center: ChunkPos,
+    watched: &mut HashSet<ChunkPos>,
+    entities_enabled: bool,
+) {
+    // Сразу после входа в комнату сущности уже отправлены в InitialEntities
+    let resend_entities = entities_enabled && !watched.is_empty();
+    let wanted = center.around(app_state.config.game.view_distance);
+    let mut stale: Vec<ChunkPos> = watched.iter().filter(|pos| !wanted.contains(pos)).copied().collect();
+    stale.sort();
+    if !stale.is_empty() {
+        let mut tiles = room.tiles.lock().await;
+        for pos in &stale {
+            tiles.unwatch(*pos, user_id);
+            watched.remove(pos);
+        }
+    }
+    for pos in stale {
+        send_message(socket, &GameMessage::ChunkUnload { cx: pos.cx, cy: pos.cy }).await;
+    }
+    let mut revealed = HashSet::new();
+    for pos in wanted {
+        if watched.contains(&pos) {
+            continue;
+        }
+        if let Some(tiles) = watch_chunk(app_state, room, pos, user_id).await {
+            watched.insert(pos);
+            revealed.insert(pos);
+            send_message(socket, &GameMessage::ChunkData { cx: pos.cx, cy: pos.cy, tiles }).await;
+        }
+    }
+    if resend_entities && !revealed.is_empty() {
+        let entities: Vec<Entity> = room.entities.lock().await
+            .all()
+            .into_iter()
+            .filter(|entity| revealed.contains(&ChunkPos::of_position(entity.x, entity.y)))
+            .collect();
+        for entity in entities {
+            send_message(socket, &GameMessage::EntityUpdated(entity)).await;
+        }
+    }
+}
+
+// Отписывает игрока от всех чанков комнаты (при выходе из нее)
+async fn release_view(room: &Room, user_id: i32, watched: &mut HashSet<ChunkPos>) {
+    let mut tiles = room.tiles.lock().await;
+    for pos in watched.drain() {
+        tiles.unwatch(pos, user_id);
+    }
+}
+
+// Ставит (tile != AIR) или ломает (tile == AIR) тайл от имени игрока и рассылает изменение
+async fn edit_tile(
+    app_state: &AppState,
+    room: &Room,
+    user_id: i32,
+    watched: &HashSet<ChunkPos>,
+    (x, y): (i32, i32),
+    tile: Tile,
+) -> Result<(), (ErrorCode, String)> {
+    if !watched.contains(&ChunkPos::of_tile(x, y)) {
+        return Err((ErrorCode::OutOfReach, format!("tile ({}, {}) is not in a visible chunk", x, y)));
+    }
+    let positions: Vec<(i32, (f64, f64))> = room.active_player_positions.lock().await
+        .values()
+        .map(|p| (p.user_id, (p.x, p.y)))
+        .collect();
+    let (px, py) = positions.iter().find(|(id, _)| *id == user_id).map(|(_, pos)| *pos).unwrap_or_default();
+    // Расстояние до центра тайла
+    let distance = (x as f64 + 0.5 - px).hypot(y as f64 + 0.5 - py);
+    let reach = app_state.config.game.reach_distance;
+    if distance > reach {
+        return Err((ErrorCode::OutOfReach, format!("tile ({}, {}) is {:.1} away, reach is {}", x, y, distance, reach)));
+    }
+    // Препятствие нельзя поставить на игрока
+    if tiles::is_solid(tile) && positions.iter().any(|(_, pos)| collision::circle_hits_tile(*pos, PLAYER_RADIUS, (x, y))) {
+        return Err((ErrorCode::InvalidTileAction, format!("tile ({}, {}) is occupied by a player", x, y)));
+    }
+
+    let mut tiles = room.tiles.lock().await;
+    let current = tiles.tile(x, y)
+        .ok_or_else(|| (ErrorCode::OutOfReach, format!("tile ({}, {}) is not loaded", x, y)))?;
+    // Ломаются только препятствия, ставить можно только на пол
+    if tile == AIR && !tiles::is_solid(current) {
+        return Err((ErrorCode::InvalidTileAction, format!("nothing to break at ({}, {})", x, y)));
+    }
+    if tile != AIR && (tiles::is_solid(current) || current == tile) {
+        return Err((ErrorCode::InvalidTileAction, format!("tile ({}, {}) is occupied", x, y)));
+    }
+    tiles.set_tile(x, y, tile);
+    // Рассылка под блокировкой: порядок TileChanged совпадает с порядком изменений
+    if room.game_state_tx.send(GameMessage::TileChanged { x, y, tile }).is_err() {
+        trace!("No subscribers for TileChanged");
+    }
+    Ok(())
+}
+
+async fn handle_socket(mut socket: WebSocket, app_state: Arc<AppState>, current_user_id: i32, mut kick_rx: broadcast::Receiver<i32>) {
+    let Some(features) = handshake(&mut socket).await else {
+        return;
+    };
+    debug!(?features, "Handshake completed");
 
     // --- Начальная загрузка позиции игрока ---
-    let mut initial_player_pos = sqlx::query_as!(
-        PlayerPositionUpdate,
-        "SELECT user_id, x, y, z FROM players WHERE user_id = $1",
-        current_user_id
-    )
-        .fetch_optional(&app_state.pool)
+    let stored_player = app_state.storage.players.load_player(current_user_id)
         .await
         .unwrap_or_else(|e| {
-            eprintln!("Error fetching player position from DB for user {}: {:?}", current_user_id, e);
+            error!(error = %e, "Error fetching player position from DB");
             None
-        })
-        .unwrap_or_else(|| {
-            PlayerPositionUpdate {
-                user_id: current_user_id,
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            }
         });
+    let stored_room = stored_player.as_ref().map_or(app_state.rooms.default_world(), |p| p.room.as_str()).to_string();
+    // Игрок возвращается в свою комнату; если ее инстанс уже удален — в мир по умолчанию на точку появления
+    let mut room = app_state.rooms.join_existing_or_default(&stored_room);
+    let mut initial_player_pos = match stored_player {
+        Some(player) if player.room == room.name => PlayerPositionUpdate::from(player),
+        _ => room.spawn_position(current_user_id),
+    };
 
-    let mut active_players_map = app_state.active_player_positions.lock().await;
-    active_players_map.insert(current_user_id, initial_player_pos.clone());
-    drop(active_players_map);
+    // Здоровье; игрок, вышедший из игры погибшим, возрождается через game.respawn_delay_secs
+    // после гибели, как если бы не уходил, а если задержка уже прошла — сразу
+    let stored_vitals = app_state.storage.players.load_vitals(current_user_id)
+        .await
+        .unwrap_or_else(|e| {
+            error!(error = %e, "Error fetching player health from DB");
+            None
+        });
+    let mut vitals = stored_vitals.unwrap_or(Vitals::full(app_state.config.game.max_health));
+
+    // Клан игрока; дальше отслеживается по ClanUpdated, ClanLeft и ClanDisbanded
+    let stored_clan = clans::tag_of(&app_state, current_user_id)
+        .await
+        .unwrap_or_else(|e| {
+            error!(error = %e, "Error fetching player clan from DB");
+            None
+        });
+    let mut current_clan: Option<ClanId> = stored_clan.as_ref().map(|(clan, _)| *clan);
+    initial_player_pos.clan_tag = stored_clan.map(|(_, tag)| tag);
+    let mut respawn_at = None;
+    if vitals.is_dead() {
+        let died_at = app_state.storage.players.died_at(current_user_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!(error = %e, "Error fetching player death time from DB");
+                None
+            });
+        let remaining = died_at.and_then(|died_at| {
+            let dead_for = (Utc::now() - died_at).to_std().unwrap_or_default();
+            app_state.config.respawn_delay().checked_sub(dead_for).filter(|remaining| !remaining.is_zero())
+        });
+        respawn_at = remaining.map(|remaining| Instant::now() + remaining);
+    }
+    let revived = vitals.is_dead() && respawn_at.is_none();
+    if revived {
+        vitals = Vitals::full(vitals.max_health);
+        (initial_player_pos.x, initial_player_pos.y) = room.respawn_point((initial_player_pos.x, initial_player_pos.y));
+    }
+
+    // Отмечаем игрока как онлайн (запись создается, если игрок заходит впервые)
+    let player = player_record(&initial_player_pos, &room.name);
+    if room.name != stored_room || revived {
+        if let Err(e) = app_state.storage.players.save_room(&player).await {
+            error!(error = %e, "Error saving player room");
+        }
+    }
+    // У игрока может быть несколько соединений; друзья узнают о входе только по первому
+    let first_connection = app_state.connection_opened(current_user_id);
+    if let Err(e) = app_state.storage.sessions.open_session(&player).await {
+        error!(error = %e, "Error marking user as online");
+    }
+    if revived {
+        if let Err(e) = app_state.storage.players.save_vitals(current_user_id, &vitals).await {
+            error!(error = %e, "Error saving player health");
+        }
+    }
+
+    // Клиенты с дельта-снимками получают состояние мира снимками вместо отдельных PlayerPosition
+    let delta_snapshots = features.iter().any(|feature| feature == snapshot::FEATURE);
+    let mut snapshot_encoder = SnapshotEncoder::default();
+    let mut snapshot_rx = delta_snapshots.then(|| room.snapshot_tx.subscribe());
+    let mut game_state_rx = room.game_state_tx.subscribe();
+    // Канал сообщений игрокам; группа отслеживается по PartyUpdated и PartyLeft
+    let mut players_rx = app_state.players_tx.subscribe();
+    let mut party_positions_rx = app_state.party_positions_tx.subscribe();
+    let mut current_party: Option<PartyId> = None;
+    let mut shutdown_rx = app_state.subscribe_shutdown();
 
-    // Отправляем данные начальных игроков новому подключенному клиенту
-    let active_players_vec: Vec<PlayerPositionUpdate> = app_state.active_player_positions.lock().await.values().cloned().collect();
+    info!(room = %room.name, "Client connected via WebSocket");
+    telemetry::socket_connected();
 
-    if let Ok(serialized_msg) = serde_json::to_string(&GameMessage::InitialPlayers(active_players_vec)) {
-        if socket.send(Message::Text(serialized_msg)).await.is_err() {
-            eprintln!("Failed to send initial active players to client {}.", current_user_id);
-        } else {
-            println!("DEBUG: Sent InitialPlayers to client {}", current_user_id);
+    let welcome = GameMessage::Welcome {
+        protocol_version: PROTOCOL_VERSION,
+        user_id: current_user_id,
+        features: features.clone(),
+        room: room.name.clone(),
+        vitals,
+    };
+    send_message(&mut socket, &welcome).await;
+    let inventory_enabled = features.iter().any(|feature| feature == items::FEATURE);
+    let initial_inventory = inventory::load(&app_state, current_user_id).await;
+    if inventory_enabled {
+        send_message(&mut socket, &GameMessage::Inventory(initial_inventory)).await;
+        trading::register(&app_state, current_user_id).await;
+    }
+    let initial_center = ChunkPos::of_position(initial_player_pos.x, initial_player_pos.y);
+    let maps_enabled = features.iter().any(|feature| feature == map::FEATURE);
+    let entities_enabled = features.iter().any(|feature| feature == entities::FEATURE);
+    let mut combatant = Combatant::new(vitals);
+    combatant.respawn_at = respawn_at;
+    enter_room(&mut socket, &room, initial_player_pos, combatant, maps_enabled, entities_enabled).await;
+
+    // Клиенты с тайловым миром получают чанки вокруг себя по мере движения
+    let tiles_enabled = features.iter().any(|feature| feature == tiles::FEATURE);
+    let mut watched_chunks: HashSet<ChunkPos> = HashSet::new();
+    let mut view_center = tiles_enabled.then_some(initial_center);
+    if let Some(center) = view_center {
+        update_view(&mut socket, &app_state, &room, current_user_id, center, &mut watched_chunks, entities_enabled).await;
+    }
+    if first_connection {
+        party::connect(&app_state, current_user_id).await;
+        friends::connected(&app_state, current_user_id).await;
+    } else {
+        // Остальные участники уже видят игрока онлайн; состав группы нужен только новому соединению
+        let party = app_state.parties.lock().await.party_of(current_user_id).cloned();
+        if let Some(party) = party {
+            current_party = Some(party.id);
+            send_message(&mut socket, &GameMessage::PartyUpdated(party)).await;
         }
     }
+    scripting::on_join(&app_state, &room, current_user_id).await;
 
-    let mut logout_processed = false; // Флаг для отслеживания обработки PlayerLogout
+    // Номер последнего примененного ввода: повторы и опоздавшие вводы отбрасываются
+    let mut last_input_seq: u64 = 0;
+    // После PlayerLogout соединение уже вышло из комнаты
+    let mut logged_out = false;
+    // Счетчик обновлений позиции для выборочного логирования горячего пути
+    let mut position_updates: u64 = 0;
+    let position_sample_rate = app_state.config.log.position_sample_rate;
 
     // Основной цикл для приема и широковещания сообщений
     loop {
@@ -93,95 +820,517 @@ async fn handle_socket(mut socket: WebSocket, app_state: Arc<AppState>, claims:
                 match msg_result {
                     Ok(msg) => {
                         if let Message::Text(text) = msg {
-                            if let Ok(game_msg) = serde_json::from_str::<GameMessage>(&text) {
-                                match game_msg {
-                                    GameMessage::PlayerPosition(mut player_update) => {
-                                        // Проверяем и перезаписываем user_id для безопасности
-                                        if player_update.user_id != current_user_id {
-                                            eprintln!("Client {} tried to send position for user_id {} (mismatch with authenticated user_id {}). Overwriting.",
-                                                current_user_id, player_update.user_id, current_user_id);
-                                            player_update.user_id = current_user_id;
-                                        }
+                            match serde_json::from_str::<GameMessage>(&text) {
+                                Ok(game_msg) => {
+                                    telemetry::message_in(game_msg.kind());
+                                    // Комната назначения и телепорт, через который в нее попадает игрок
+                                    let mut room_change: Option<(String, Option<Teleporter>)> = None;
+                                    match game_msg {
+                                        GameMessage::PlayerPosition(mut player_update) => {
+                                            // Проверяем и перезаписываем user_id для безопасности
+                                            if player_update.user_id != current_user_id {
+                                                warn!(claimed_user_id = player_update.user_id, "Client tried to send position for another user_id. Overwriting.");
+                                                player_update.user_id = current_user_id;
+                                            }
 
-                                        // Сохраняем/обновляем позицию в БД
-                                        if let Err(e) = sqlx::query!(
-                                            "INSERT INTO players (user_id, x, y, z) VALUES ($1, $2, $3, $4)
-                                             ON CONFLICT (user_id) DO UPDATE SET x = $2, y = $3, z = $4",
-                                            current_user_id,
-                                            player_update.x,
-                                            player_update.y,
-                                            player_update.z
-                                        )
-                                        .execute(&app_state.pool)
-                                        .await {
-                                            eprintln!("Error updating player position in DB for user {}: {:?}", current_user_id, e);
-                                        }
+                                            if player_update.seq <= last_input_seq {
+                                                trace!(seq = player_update.seq, last_input_seq, "Discarding duplicate or out-of-order input");
+                                                telemetry::input_discarded();
+                                                continue;
+                                            }
+                                            last_input_seq = player_update.seq;
 
-                                        // Обновляем позицию в in-memory HashMap
-                                        let mut active_players_map = app_state.active_player_positions.lock().await;
-                                        active_players_map.insert(current_user_id, player_update.clone());
-                                        drop(active_players_map);
-
-                                        // Широковещательно отправляем новое состояние
-                                        let broadcast_player_update = PlayerPositionUpdate {
-                                            user_id: current_user_id,
-                                            x: player_update.x,
-                                            y: player_update.y,
-                                            z: player_update.z,
-                                        };
-                                        if let Err(e) = app_state.game_state_tx.send(GameMessage::PlayerPosition(broadcast_player_update)) {
-                                            eprintln!("Error broadcasting PlayerPosition for user {}: {:?}", current_user_id, e);
-                                        } else {
-                                            println!("DEBUG: Broadcasted PlayerPosition for user {}", current_user_id);
-                                        }
-                                    },
-                                    GameMessage::PlayerLogout { user_id } => {
-                                        if user_id == current_user_id {
-                                            println!("DEBUG: Received PlayerLogout for user {}", current_user_id);
-                                            logout_processed = true; // Устанавливаем флаг
-                                            // Отправляем PlayerDisconnected сразу
-                                            let mut active_players_map = app_state.active_player_positions.lock().await;
-                                            active_players_map.remove(&current_user_id);
+                                            // Погибший игрок стоит на месте до возрождения
+                                            if room.combatants.lock().await.get(&current_user_id).is_some_and(Combatant::is_dead) {
+                                                trace!(seq = player_update.seq, "Discarding input of a dead player");
+                                                telemetry::input_discarded();
+                                                continue;
+                                            }
+
+                                            // Сервер не пускает игрока сквозь стены: позиция сдвигается к точке касания
+                                            let previous = room.active_player_positions.lock().await.get(&current_user_id).map(|p| (p.x, p.y));
+                                            if let Some(previous) = previous {
+                                                let requested = (player_update.x, player_update.y);
+                                                let resolved = collision::resolve_move(&*room.tiles.lock().await, previous, requested, PLAYER_RADIUS);
+                                                if resolved != requested {
+                                                    trace!(?requested, ?resolved, "Movement corrected by collision");
+                                                    telemetry::move_corrected();
+                                                    (player_update.x, player_update.y) = resolved;
+                                                }
+                                                // Телепорты карты срабатывают при входе в их область
+                                                let moved_to = (player_update.x, player_update.y);
+                                                if let Some(teleporter) = room.map.as_deref().and_then(|map| map.entered_teleporter(previous, moved_to)) {
+                                                    debug!(teleporter = %teleporter.name, "Player entered teleporter");
+                                                    match &teleporter.room {
+                                                        Some(target) if *target != room.name => room_change = Some((target.clone(), Some(teleporter.clone()))),
+                                                        _ => (player_update.x, player_update.y) = teleporter.destination(room.map.as_deref(), room.spawn),
+                                                    }
+                                                }
+                                            }
+
+                                            // Сохраняем/обновляем позицию в БД
+                                            let query_started = Instant::now();
+                                            let save_result = app_state.storage.players.save_position(&player_record(&player_update, &room.name)).await;
+                                            telemetry::db_query("save_position", query_started.elapsed());
+                                            if let Err(e) = save_result {
+                                                error!(error = %e, "Error updating player position in DB");
+                                            }
+
+                                            position_updates += 1;
+                                            if (position_updates - 1).is_multiple_of(position_sample_rate) {
+                                                debug!(
+                                                    x = player_update.x, y = player_update.y, z = player_update.z,
+                                                    updates = position_updates,
+                                                    "Position update (sampled 1/{})", position_sample_rate
+                                                );
+                                            }
+
+                                            // Обновляем позицию в in-memory HashMap
+                                            let center = ChunkPos::of_position(player_update.x, player_update.y);
+                                            let mut active_players_map = room.active_player_positions.lock().await;
+                                            player_update.clan_tag = active_players_map.get(&current_user_id).and_then(|p| p.clan_tag.clone());
+                                            active_players_map.insert(current_user_id, player_update);
                                             drop(active_players_map);
-                                            println!("DEBUG: Number of subscribers for user {}: {}", current_user_id, app_state.game_state_tx.receiver_count());
-                                            if let Err(e) = app_state.game_state_tx.send(GameMessage::PlayerDisconnected { user_id: current_user_id }) {
-                                                eprintln!("Error broadcasting PlayerDisconnected for user {}: {:?}", current_user_id, e);
+
+                                            // Новое состояние разошлет игровой цикл на ближайшем тике
+                                            room.moved_players.lock().await.insert(current_user_id);
+
+                                            // Игрок перешел в другой чанк: досылаем новые чанки и выгружаем дальние
+                                            if view_center.is_some_and(|old| old != center) {
+                                                view_center = Some(center);
+                                                update_view(&mut socket, &app_state, &room, current_user_id, center, &mut watched_chunks, entities_enabled).await;
+                                            }
+                                            scripting::on_move(&app_state, &room, current_user_id).await;
+                                        },
+                                        GameMessage::SnapshotAck { tick } if delta_snapshots => {
+                                            if !snapshot_encoder.ack(tick) {
+                                                debug!(tick, "Unknown snapshot acknowledged, falling back to a full snapshot");
+                                            }
+                                        },
+                                        GameMessage::PlaceTile { x, y, tile } if tiles_enabled => {
+                                            if tile == AIR {
+                                                send_error(&mut socket, ErrorCode::InvalidTileAction, "use BreakTile to remove tiles".to_string()).await;
+                                            } else if let Err((code, message)) = edit_tile(&app_state, &room, current_user_id, &watched_chunks, (x, y), tile).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::BreakTile { x, y } if tiles_enabled => {
+                                            if let Err((code, message)) = edit_tile(&app_state, &room, current_user_id, &watched_chunks, (x, y), AIR).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::Attack { target } => {
+                                            if let Err((code, message)) = combat::attack(&app_state, &room, current_user_id, target).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::MoveItem { from, to } if inventory_enabled => {
+                                            let result = inventory::move_item(&app_state, current_user_id, from, to).await;
+                                            send_inventory_result(&mut socket, result).await;
+                                        },
+                                        GameMessage::UseItem { slot } if inventory_enabled => {
+                                            let result = inventory::use_item(&app_state, &room, current_user_id, slot).await;
+                                            send_inventory_result(&mut socket, result).await;
+                                        },
+                                        GameMessage::DropItem { slot, count } if inventory_enabled => {
+                                            let result = inventory::drop_item(&app_state, &room, current_user_id, slot, count).await;
+                                            send_inventory_result(&mut socket, result).await;
+                                        },
+                                        GameMessage::PickUpItem { id } if inventory_enabled => {
+                                            let result = inventory::pick_up(&app_state, &room, current_user_id, id).await;
+                                            send_inventory_result(&mut socket, result).await;
+                                        },
+                                        GameMessage::TradeRequest { target } if inventory_enabled => {
+                                            if let Err((code, message)) = trading::request(&app_state, &room, current_user_id, target).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::TradeAccept { from } if inventory_enabled => {
+                                            if let Err((code, message)) = trading::accept(&app_state, &room, current_user_id, from).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::TradeOffer { slot, count } if inventory_enabled => {
+                                            if let Err((code, message)) = trading::offer(&app_state, current_user_id, slot, count).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::TradeLock if inventory_enabled => {
+                                            if let Err((code, message)) = trading::lock(&app_state, current_user_id).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::TradeConfirm if inventory_enabled => {
+                                            if let Err((code, message)) = trading::confirm(&app_state, current_user_id).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::TradeCancel if inventory_enabled => {
+                                            if let Err((code, message)) = trading::cancel(&app_state, current_user_id).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::Chat { text } => {
+                                            let text = match chat_text(&text) {
+                                                Ok(text) => text,
+                                                Err((code, message)) => {
+                                                    send_error(&mut socket, code, message).await;
+                                                    continue;
+                                                },
+                                            };
+                                            match scripting::on_chat(&app_state, &room, current_user_id, text.to_string()).await {
+                                                Some(text) => {
+                                                    let _ = room.game_state_tx.send(GameMessage::ChatMessage { user_id: current_user_id, text });
+                                                },
+                                                None => debug!("Chat message cancelled by a script"),
+                                            }
+                                        },
+                                        GameMessage::PartyInvite { target } => {
+                                            if let Err((code, message)) = party::invite(&app_state, current_user_id, target).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::PartyAccept { from } => {
+                                            if let Err((code, message)) = party::accept(&app_state, current_user_id, from).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::PartyLeave => {
+                                            if let Err((code, message)) = party::leave(&app_state, current_user_id).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::PartyKick { target } => {
+                                            if let Err((code, message)) = party::kick(&app_state, current_user_id, target).await {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::PartyChat { text } => {
+                                            let result = match chat_text(&text) {
+                                                Ok(text) => party::chat(&app_state, current_user_id, text.to_string()).await,
+                                                Err(e) => Err(e),
+                                            };
+                                            if let Err((code, message)) = result {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanCreate { name, tag } => {
+                                            if let Err(e) = clans::create(&app_state, current_user_id, &name, &tag).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanInvite { target } => {
+                                            if let Err(e) = clans::invite(&app_state, current_user_id, target).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanAccept { clan } => {
+                                            if let Err(e) = clans::accept(&app_state, current_user_id, clan).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanLeave => {
+                                            if let Err(e) = clans::leave(&app_state, current_user_id).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanKick { target } => {
+                                            if let Err(e) = clans::kick(&app_state, current_user_id, target).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanSetRank { target, rank } => {
+                                            if let Err(e) = clans::set_rank(&app_state, current_user_id, target, rank).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanDisband => {
+                                            if let Err(e) = clans::disband(&app_state, current_user_id).await {
+                                                let (code, message) = e.into_game_error();
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ClanChat { text } => {
+                                            let result = match chat_text(&text) {
+                                                Ok(text) => clans::chat(&app_state, current_user_id, text.to_string()).await.map_err(clans::ClanError::into_game_error),
+                                                Err(e) => Err(e),
+                                            };
+                                            if let Err((code, message)) = result {
+                                                send_error(&mut socket, code, message).await;
+                                            }
+                                        },
+                                        GameMessage::ChangeRoom { room: target } => {
+                                            if target == room.name {
+                                                send_error(&mut socket, ErrorCode::RoomUnavailable, format!("already in room {}", target)).await;
+                                                continue;
+                                            }
+                                            if room.combatants.lock().await.get(&current_user_id).is_some_and(Combatant::is_dead) {
+                                                send_error(&mut socket, ErrorCode::PlayerDead, "cannot change room before respawning".to_string()).await;
+                                                continue;
+                                            }
+                                            room_change = Some((target, None));
+                                        },
+                                        GameMessage::PlayerLogout { user_id } => {
+                                            if user_id == current_user_id {
+                                                info!("Received PlayerLogout");
+                                                // Отправляем PlayerDisconnected сразу
+                                                exit_room(&app_state, &room, current_user_id, &mut watched_chunks).await;
+                                                logged_out = true;
+                                                // Задержка для гарантии доставки сообщения
+                                                tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+                                                break; // Выходим из цикла после отправки
                                             } else {
-                                                println!("DEBUG: Broadcasted PlayerDisconnected for user {} on PlayerLogout", current_user_id);
+                                                warn!(claimed_user_id = user_id, "Client sent PlayerLogout for another user_id. Ignoring.");
                                             }
-                                            // Задержка для гарантии доставки сообщения
-                                            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-                                            break; // Выходим из цикла после отправки
-                                        } else {
-                                            eprintln!("Client {} sent PlayerLogout for user_id {} (mismatch). Ignoring.", current_user_id, user_id);
+                                        },
+                                        _ => {
+                                            debug!(message_type = game_msg.kind(), "Received unexpected GameMessage type from client");
+                                            let message = format!("{} cannot be sent by a client", game_msg.kind());
+                                            send_error(&mut socket, ErrorCode::UnexpectedMessage, message).await;
                                         }
-                                    },
-                                    _ => {
-                                        eprintln!("Received unexpected GameMessage type from client {}: {:?}", current_user_id, game_msg);
                                     }
-                                }
-                            } else {
-                                eprintln!("Received unparseable text as GameMessage from client {}: {}", current_user_id, text);
+                                    // Переход в другую комнату: по ChangeRoom или через телепорт карты
+                                    if let Some((target, teleporter)) = room_change {
+                                        let new_room = match app_state.rooms.join(&target) {
+                                            Ok(new_room) => new_room,
+                                            Err(e) => {
+                                                send_error(&mut socket, ErrorCode::RoomUnavailable, e.to_string()).await;
+                                                continue;
+                                            },
+                                        };
+                                        trading::leave_room(&app_state, current_user_id).await;
+                                        let clan_tag = room.active_player_positions.lock().await.get(&current_user_id).and_then(|p| p.clan_tag.clone());
+                                        let combatant = exit_room(&app_state, &room, current_user_id, &mut watched_chunks)
+                                            .await
+                                            .unwrap_or_else(|| Combatant::new(Vitals::full(app_state.config.game.max_health)));
+                                        leave_room(&app_state, &room).await;
+                                        info!(from = %room.name, to = %new_room.name, "Player changed room");
+                                        room = new_room;
+                                        game_state_rx = room.game_state_tx.subscribe();
+                                        snapshot_rx = delta_snapshots.then(|| room.snapshot_tx.subscribe());
+                                        snapshot_encoder = SnapshotEncoder::default();
+
+                                        // В новой комнате игрок появляется в точке появления или в точке назначения телепорта
+                                        let mut spawn = PlayerPositionUpdate { seq: last_input_seq, clan_tag, ..room.spawn_position(current_user_id) };
+                                        if let Some(teleporter) = teleporter {
+                                            (spawn.x, spawn.y) = teleporter.destination(room.map.as_deref(), room.spawn);
+                                        }
+                                        if let Err(e) = app_state.storage.players.save_room(&player_record(&spawn, &room.name)).await {
+                                            error!(error = %e, "Error saving player room");
+                                        }
+                                        send_message(&mut socket, &GameMessage::RoomChanged { room: room.name.clone() }).await;
+                                        // Чанки старой комнаты клиент отбрасывает по RoomChanged
+                                        view_center = tiles_enabled.then(|| ChunkPos::of_position(spawn.x, spawn.y));
+                                        enter_room(&mut socket, &room, spawn, combatant, maps_enabled, entities_enabled).await;
+                                        scripting::on_join(&app_state, &room, current_user_id).await;
+                                        if let Some(center) = view_center {
+                                            update_view(&mut socket, &app_state, &room, current_user_id, center, &mut watched_chunks, entities_enabled).await;
+                                        }
+                                    }
+                                },
+                                Err(e) => {
+                                    telemetry::message_in("invalid");
+                                    debug!(len = text.len(), error = %e, "Received unparseable text as GameMessage from client");
+                                    send_error(&mut socket, ErrorCode::InvalidMessage, e.to_string()).await;
+                                },
                             }
                         } else if matches!(msg, Message::Close(_)) {
-                            println!("DEBUG: Client {} sent close message.", current_user_id);
+                            debug!("Client sent close message");
                             break;
                         } else {
-                            println!("DEBUG: Received other message type from client {}: {:?}", current_user_id, msg);
+                            trace!(?msg, "Received other message type from client");
                         }
                     },
                     Err(e) => {
-                        eprintln!("WebSocket receive error for client {}: {:?}", current_user_id, e);
+                        debug!(error = %e, "WebSocket receive error");
                         break;
                     }
                 }
             }
+            // Сервер останавливается: закрываем соединение, сессия закрывается ниже как обычно
+            // (флаг меняется только один раз — с false на true)
+            _ = shutdown_rx.changed() => {
+                info!("Closing connection due to server shutdown");
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+            // Пользователя отключают (запрошено удаление аккаунта): закрываем все его соединения
+            kick_result = kick_rx.recv() => {
+                let kicked = match kick_result {
+                    Ok(user_id) => user_id == current_user_id,
+                    // Пропущенные отключения перепроверяются по хранилищу
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        account::is_deletion_pending(&app_state.storage, current_user_id).await.unwrap_or_else(|e| {
+                            error!(error = %e, "Error checking deletion status");
+                            false
+                        })
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if kicked {
+                    info!("Closing connection: account deletion requested");
+                    let _ = socket.send(Message::Close(Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "account deletion requested".into(),
+                    }))).await;
+                    break;
+                }
+            }
+            // Снимки мира от игрового цикла (только для клиентов с дельта-снимками)
+            snapshot_result = recv_snapshot(&mut snapshot_rx) => {
+                let world_snapshot = match snapshot_result {
+                    Ok(world_snapshot) => world_snapshot,
+                    // Пропущенные снимки не страшны: следующий кодируется относительно подтвержденной базы
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        telemetry::broadcast_lagged(skipped);
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let Some(delta) = snapshot_encoder.encode(&world_snapshot) {
+                    if !send_message(&mut socket, &GameMessage::Snapshot(delta)).await {
+                        debug!("Failed to send snapshot to client");
+                        break;
+                    }
+                }
+            }
+            // Сообщения групп и кланов — только их участникам, уведомления о друзьях — только адресату
+            players_result = players_rx.recv() => {
+                let party_msg = match players_result {
+                    Ok(msg) => msg,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Client lagged behind the players channel");
+                        telemetry::broadcast_lagged(skipped);
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let deliver = match &party_msg {
+                    GameMessage::PartyInvited { to, .. } => *to == current_user_id,
+                    GameMessage::PartyUpdated(party) => {
+                        let member = party.contains(current_user_id);
+                        if member {
+                            current_party = Some(party.id);
+                        }
+                        member
+                    },
+                    GameMessage::PartyLeft { user_id, .. } => {
+                        let left = *user_id == current_user_id;
+                        if left {
+                            current_party = None;
+                        }
+                        left
+                    },
+                    GameMessage::PartyChatMessage { party, .. } => current_party == Some(*party),
+                    // Сообщения кланов: участникам клана (приглашение — только приглашенному)
+                    GameMessage::ClanInvited { to, .. } => *to == current_user_id,
+                    GameMessage::ClanUpdated(clan) => {
+                        let member = clan.member(current_user_id).is_some();
+                        if member {
+                            current_clan = Some(clan.id);
+                        }
+                        member
+                    },
+                    GameMessage::ClanLeft { clan, user_id, .. } => {
+                        if *user_id == current_user_id {
+                            current_clan = None;
+                            true
+                        } else {
+                            current_clan == Some(*clan)
+                        }
+                    },
+                    GameMessage::ClanDisbanded { clan } => {
+                        let member = current_clan == Some(*clan);
+                        if member {
+                            current_clan = None;
+                        }
+                        member
+                    },
+                    GameMessage::ClanChatMessage { clan, .. } => current_clan == Some(*clan),
+                    GameMessage::FriendOnline { to, .. } | GameMessage::FriendOffline { to, .. } => *to == current_user_id,
+                    _ => false,
+                };
+                if deliver && !send_message(&mut socket, &party_msg).await {
+                    debug!("Failed to send player message to client");
+                    break;
+                }
+            }
+            // Позиции остальных участников своей группы
+            position_result = party_positions_rx.recv() => {
+                let position_msg = match position_result {
+                    Ok(msg) => msg,
+                    // Пропущенные позиции устаревают к следующему тику
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        telemetry::broadcast_lagged(skipped);
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let deliver = matches!(&position_msg, GameMessage::PartyMemberPosition { party, user_id, .. }
+                    if current_party == Some(*party) && *user_id != current_user_id);
+                if deliver && !send_message(&mut socket, &position_msg).await {
+                    debug!("Failed to send party position to client");
+                    break;
+                }
+            }
             // Принимаем сообщения из канала широковещания (для других клиентов)
-            Ok(broadcast_msg) = game_state_rx.recv() => {
-                // InitialPlayers предназначено только для нового клиента
-                if matches!(broadcast_msg, GameMessage::InitialPlayers(_)) {
+            broadcast_result = game_state_rx.recv() => {
+                let broadcast_msg = match broadcast_result {
+                    Ok(msg) => msg,
+                    // Клиент не успевал читать канал, и часть сообщений пропала
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Client lagged behind the broadcast channel");
+                        telemetry::broadcast_lagged(skipped);
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                // InitialPlayers и InitialEntities предназначены только для нового клиента
+                if matches!(broadcast_msg, GameMessage::InitialPlayers(_) | GameMessage::InitialEntities(_)) {
                     continue;
                 }
+                // Позиции клиент с дельта-снимками получает в Snapshot
+                if delta_snapshots && matches!(broadcast_msg, GameMessage::PlayerPosition(_)) {
+                    continue;
+                }
+                // Изменения тайлов — только тем, кому отправлен этот чанк
+                if let GameMessage::TileChanged { x, y, .. } = &broadcast_msg {
+                    if !watched_chunks.contains(&ChunkPos::of_tile(*x, *y)) {
+                        continue;
+                    }
+                }
+                // Подбор предметов — только клиентам с инвентарем, сущности — только клиентам с "entities"
+                if !inventory_enabled && matches!(broadcast_msg, GameMessage::ItemPickedUp { .. }) {
+                    continue;
+                }
+                if !entities_enabled && matches!(broadcast_msg, GameMessage::EntitySpawned(_) | GameMessage::EntityUpdated(_) | GameMessage::EntityDespawned { .. }) {
+                    continue;
+                }
+                // Клиент с тайловым миром получает изменения только сущностей в видимых чанках
+                if let GameMessage::EntityUpdated(entity) = &broadcast_msg {
+                    if view_center.is_some() && !watched_chunks.contains(&ChunkPos::of_position(entity.x, entity.y)) {
+                        continue;
+                    }
+                }
+                // Обмен видят только его участники с инвентарем
+                match &broadcast_msg {
+                    GameMessage::TradeRequested { to, .. } if !inventory_enabled || *to != current_user_id => continue,
+                    GameMessage::TradeUpdated(trade) if !inventory_enabled || !trade.parties().contains(&current_user_id) => continue,
+                    GameMessage::TradeClosed { parties, .. } if !inventory_enabled || !parties.contains(&current_user_id) => continue,
+                    _ => {},
+                }
+                // Личные сообщения сервера — только адресату
+                if let GameMessage::SystemMessage { to: Some(recipient), .. } = &broadcast_msg {
+                    if *recipient != current_user_id {
+                        continue;
+                    }
+                }
                 // Сообщение об отключении не отправляем обратно отключившемуся клиенту
                 if let GameMessage::PlayerDisconnected { user_id: disconnected_id } = &broadcast_msg {
                     if current_user_id == *disconnected_id {
@@ -191,10 +1340,17 @@ async fn handle_socket(mut socket: WebSocket, app_state: Arc<AppState>, claims:
 
                 if let Ok(serialized_msg) = serde_json::to_string(&broadcast_msg) {
                     if socket.send(Message::Text(serialized_msg)).await.is_err() {
-                        eprintln!("Failed to send broadcast message to client {}.", current_user_id);
+                        debug!("Failed to send broadcast message to client");
                         break;
                     } else {
-                        println!("DEBUG: Sent broadcast message to client {}: {:?}", current_user_id, broadcast_msg);
+                        telemetry::message_out(broadcast_msg.kind());
+                        trace!(message_type = broadcast_msg.kind(), "Sent broadcast message to client");
+                    }
+                }
+                // После обмена участник получает свой новый инвентарь
+                if let GameMessage::TradeClosed { completed: true, .. } = &broadcast_msg {
+                    if let Some(inventory) = app_state.inventories.lock().await.get(&current_user_id).cloned() {
+                        send_message(&mut socket, &GameMessage::Inventory(inventory)).await;
                     }
                 }
             }
@@ -202,27 +1358,27 @@ async fn handle_socket(mut socket: WebSocket, app_state: Arc<AppState>, claims:
     }
 
     // --- Обработка отключения: отправка сообщения об отключении и удаление из активных ---
-    println!("DEBUG: Client {} disconnected.", current_user_id);
+    info!("Client disconnected");
+    telemetry::socket_disconnected();
 
-    // Удаляем игрока из in-memory HashMap активных игроков, если еще не удален
-    let mut active_players_map = app_state.active_player_positions.lock().await;
-    if active_players_map.contains_key(&current_user_id) {
-        active_players_map.remove(&current_user_id);
-        println!("DEBUG: Removed user {} from active_players_map", current_user_id);
-    } else {
-        println!("DEBUG: User {} was not in active_players_map", current_user_id);
+    let last_connection = app_state.connection_closed(current_user_id);
+
+    // Выходим из комнаты, если еще не вышли по PlayerLogout; игрок остается в ней, пока там
+    // есть другие его соединения
+    if !logged_out {
+        exit_room(&app_state, &room, current_user_id, &mut watched_chunks).await;
     }
-    drop(active_players_map);
+    leave_room(&app_state, &room).await;
 
-    // Отправляем PlayerDisconnected только если не отправляли при PlayerLogout
-    if !logout_processed {
-        println!("DEBUG: Number of subscribers for user {}: {}", current_user_id, app_state.game_state_tx.receiver_count());
-        if let Err(e) = app_state.game_state_tx.send(GameMessage::PlayerDisconnected { user_id: current_user_id }) {
-            eprintln!("Error broadcasting PlayerDisconnected for user {}: {:?}", current_user_id, e);
-        } else {
-            println!("DEBUG: Broadcasted PlayerDisconnected for user {} on disconnect", current_user_id);
+    // Обмены отменяются, участник группы отключается, инвентарь выгружается, сессия закрывается
+    // и друзья узнают о выходе, только когда закрыто последнее соединение игрока
+    if last_connection {
+        trading::unregister(&app_state, current_user_id).await;
+        party::disconnect(&app_state, current_user_id).await;
+        inventory::unload(&app_state, current_user_id).await;
+        if let Err(e) = app_state.storage.sessions.close_session(current_user_id).await {
+            error!(error = %e, "Error marking user as offline");
         }
-    } else {
-        println!("DEBUG: Skipped broadcasting PlayerDisconnected for user {} as it was sent on PlayerLogout", current_user_id);
+        friends::disconnected(&app_state, current_user_id).await;
     }
-}
\ No newline at end of file
+}
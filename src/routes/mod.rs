@@ -1,17 +1,24 @@
 // src/routes/mod.rs
+pub mod account;
 pub mod auth;
+pub mod clans;
+pub mod friends;
 pub mod game;
+pub mod status;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
     middleware,
-    // Extension, // Удален: не используется напрямую в create_router
+    Extension,
 };
 use tokio::sync::broadcast;
+use tower_http::trace::TraceLayer;
 use std::sync::Arc;
 
 use crate::routes::game::GameMessage;
+use crate::state::AppState;
+use crate::telemetry;
 
 pub type SharedGameState = Arc<broadcast::Sender<GameMessage>>;
 
@@ -19,6 +26,44 @@ pub fn create_router() -> Router {
     Router::new()
         .route("/register", post(auth::register))
         .route("/login", post(auth::login))
-        // Применяем auth_middleware только к маршруту /ws
+        .route("/server-info", get(status::server_info))
+        // Маршруты ниже защищены auth_middleware
         .route("/ws", get(game::websocket_handler).layer(middleware::from_fn(auth::auth_middleware)))
-}
\ No newline at end of file
+        // Управление аккаунтом: удаление с периодом отмены и выгрузка данных
+        .route("/account", delete(account::request_deletion).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/account/restore", post(account::cancel_deletion).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/account/export", get(account::export_data).layer(middleware::from_fn(auth::auth_middleware)))
+        // Кланы: /clans — любой клан, /clan — клан текущего игрока
+        .route("/clans", post(clans::create).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clans/:id", get(clans::get).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan", get(clans::my_clan).delete(clans::disband).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan/leave", post(clans::leave).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan/invites", get(clans::invites).post(clans::invite).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan/invites/:clan/accept", post(clans::accept).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan/invites/:clan/decline", post(clans::decline).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan/members/:user_id", delete(clans::kick).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/clan/members/:user_id/rank", put(clans::set_rank).layer(middleware::from_fn(auth::auth_middleware)))
+        // Друзья и запросы дружбы
+        .route("/friends", get(friends::list).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/friends/:user_id", delete(friends::remove).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/friends/requests", post(friends::send_request).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/friends/requests/:user_id/accept", post(friends::accept).layer(middleware::from_fn(auth::auth_middleware)))
+        .route("/friends/requests/:user_id/decline", post(friends::decline).layer(middleware::from_fn(auth::auth_middleware)))
+}
+// Полное приложение: маршруты API под /api и общее состояние.
+// Используется и в main, и тестовым стендом
+pub fn create_app(app_state: Arc<AppState>) -> Router {
+    // Рекордер метрик должен быть установлен до первых измерений
+    telemetry::prometheus_handle();
+
+    Router::new()
+        .nest("/api", create_router())
+        // Метрики для Prometheus (без авторизации; закрывайте на уровне сети)
+        .route("/metrics", get(telemetry::metrics_handler))
+        // Проверки для оркестратора и балансировщика
+        .route("/health", get(status::health))
+        .route("/ready", get(status::ready))
+        // Span на каждый HTTP-запрос (метод, путь, статус, длительность)
+        .layer(TraceLayer::new_for_http())
+        .layer(Extension(app_state))
+}
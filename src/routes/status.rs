@@ -0,0 +1,89 @@
+// src/routes/status.rs
+// Эндпоинты для оркестратора и лаунчера: жив ли процесс, готов ли он принимать игроков,
+// и краткая информация о сервере
+use axum::{
+    extract::Json,
+    response::IntoResponse,
+    http::StatusCode,
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::routes::auth::ErrorResponse;
+use crate::routes::game::PROTOCOL_VERSION;
+use crate::state::AppState;
+
+// Сколько ждать ответа базы, прежде чем считать ее недоступной
+const DB_PING_TIMEOUT: Duration = Duration::from_secs(2);
+// Если игровой цикл не тикал дольше этого, сервер не готов
+const WORLD_STALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub database: bool,
+    pub world: bool,
+    pub shutting_down: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerInfoResponse {
+    pub version: String,
+    pub uptime_secs: u64,
+    pub online_players: usize,
+    pub protocol_version: u32,
+    pub motd: String,
+}
+
+// Процесс жив и обрабатывает запросы
+pub async fn health() -> impl IntoResponse {
+    "OK"
+}
+
+// База доступна, игровой цикл работает и сервер не останавливается; иначе 503
+pub async fn ready(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let database = match tokio::time::timeout(DB_PING_TIMEOUT, app_state.storage.health.ping()).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            warn!(error = %e, "Readiness check: database ping failed");
+            false
+        },
+        Err(_) => {
+            warn!("Readiness check: database ping timed out");
+            false
+        },
+    };
+    let world = app_state.world_running(WORLD_STALL_TIMEOUT);
+    let shutting_down = app_state.is_shutting_down();
+
+    let response = ReadinessResponse {
+        ready: database && world && !shutting_down,
+        database,
+        world,
+        shutting_down,
+    };
+    let status = if response.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(response))
+}
+
+// Онлайн считается по сессиям, как и статус друзей, а не по участникам комнат
+pub async fn server_info(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let online_players = match app_state.storage.sessions.count_online().await {
+        Ok(count) => count,
+        Err(e) => {
+            error!(error = %e, "Error counting online players");
+            let message = "Internal server error".to_string();
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { message })).into_response();
+        },
+    };
+    Json(ServerInfoResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: app_state.uptime().as_secs(),
+        online_players,
+        protocol_version: PROTOCOL_VERSION,
+        motd: app_state.config.server.motd.clone(),
+    }).into_response()
+}
@@ -0,0 +1,646 @@
+// src/scripting.rs
+// Скрипты игровых правил на Rhai. Файлы *.rhai из game.scripts_dir читаются при запуске
+// и перечитываются на лету, когда меняются на диске. Сервер вызывает в скриптах обработчики
+// событий (on_load, on_join, on_leave, on_move, on_chat, on_timer) по порядку имен файлов.
+// Скрипт не меняет состояние сервера напрямую: функции API копят действия (Action),
+// которые сервер применяет после вызова обработчика
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, info, warn};
+
+use crate::combat;
+use crate::config::GameConfig;
+use crate::models::player::Player;
+use crate::rooms::Room;
+use crate::routes::game::GameMessage;
+use crate::state::AppState;
+use crate::telemetry;
+
+pub const SCRIPT_EXTENSION: &str = "rhai";
+// Ограничения для одного вызова скрипта: зациклившийся или слишком жадный скрипт
+// прерывается с ошибкой, а не останавливает игровой цикл
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 4096;
+const MAX_COLLECTION_SIZE: usize = 1024;
+// Длина текста сообщения от скрипта (как у сообщения чата)
+pub const MAX_MESSAGE_LEN: usize = 256;
+// Сколько таймеров может завести один скрипт
+const MAX_TIMERS: usize = 64;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Read(PathBuf, std::io::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Read(path, e) => write!(f, "cannot read script {}: {}", path.display(), e),
+            ScriptError::Invalid(path, reason) => write!(f, "invalid script {}: {}", path.display(), reason),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// Действие, запрошенное скриптом
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    // Сообщение одному игроку
+    Message { user_id: i32, text: String },
+    // Сообщение всем игрокам комнаты
+    Broadcast { room: String, text: String },
+    Teleport { user_id: i32, x: f64, y: f64 },
+    Heal { user_id: i32, amount: i32 },
+}
+
+// Игрок онлайн, как его видят скрипты: #{user_id, room, x, y}
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptPlayer {
+    pub user_id: i32,
+    pub room: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl ScriptPlayer {
+    fn to_map(&self) -> Map {
+        let mut map = Map::new();
+        map.insert("user_id".into(), Dynamic::from_int(self.user_id as i64));
+        map.insert("room".into(), self.room.clone().into());
+        map.insert("x".into(), Dynamic::from_float(self.x));
+        map.insert("y".into(), Dynamic::from_float(self.y));
+        map
+    }
+}
+
+// Состояние текущего вызова, доступное функциям API
+#[derive(Default)]
+struct CallContext {
+    players: Vec<ScriptPlayer>,
+    actions: Vec<Action>,
+    // Заведенные (Some) и отмененные (None) таймеры вызываемого скрипта
+    timers: Vec<(String, Option<Duration>)>,
+}
+
+struct Script {
+    // Имя файла; по нему скрипт узнается при перечитывании
+    name: String,
+    source: String,
+    ast: AST,
+    // this в обработчиках: объектная карта, которая живет до перечитывания скрипта
+    state: Dynamic,
+    timers: HashMap<String, Instant>,
+}
+
+impl Script {
+    fn has_hook(&self, hook: &str) -> bool {
+        self.ast.iter_functions().any(|function| function.name == hook)
+    }
+}
+
+// Версия файла на диске: время изменения и размер
+type FileStamp = (SystemTime, u64);
+
+// Файл скрипта, найденный при просмотре каталога
+struct ScriptFile {
+    path: PathBuf,
+    name: String,
+    stamp: Option<FileStamp>,
+    // Ok(None) — файл не менялся с прошлого чтения и не читался
+    source: std::io::Result<Option<String>>,
+}
+
+// Файлы скриптов каталога по порядку имен; читаются только те, чья версия отличается от known.
+// Блокирующий ввод-вывод, поэтому игровой цикл вызывает его через spawn_blocking
+fn scan(dir: &Path, known: &HashMap<String, FileStamp>) -> Result<Vec<ScriptFile>, ScriptError> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| ScriptError::Read(dir.to_path_buf(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == SCRIPT_EXTENSION))
+        .collect();
+    paths.sort();
+    let files = paths.into_iter().map(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let stamp = std::fs::metadata(&path).and_then(|meta| Ok((meta.modified()?, meta.len()))).ok();
+        let source = match stamp {
+            Some(stamp) if known.get(&name) == Some(&stamp) => Ok(None),
+            _ => std::fs::read_to_string(&path).map(Some),
+        };
+        ScriptFile { path, name, stamp, source }
+    });
+    Ok(files.collect())
+}
+
+pub struct ScriptHost {
+    engine: Engine,
+    dir: Option<PathBuf>,
+    context: Arc<Mutex<CallContext>>,
+    scripts: Mutex<Vec<Script>>,
+    // Версии файлов при последнем чтении, включая файлы с ошибками
+    stamps: Mutex<HashMap<String, FileStamp>>,
+}
+
+impl Default for ScriptHost {
+    fn default() -> Self {
+        ScriptHost::new(None)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Длинные сообщения обрезаются по границе символа
+fn message_text(text: &str) -> String {
+    text.chars().take(MAX_MESSAGE_LEN).collect()
+}
+
+fn engine(context: &Arc<Mutex<CallContext>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        // Скрипты не подключают модули с диска и не выполняют произвольный код из строк
+        .set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new())
+        .disable_symbol("eval");
+    engine.on_print(|text| info!(target: "script", "{}", text));
+    engine.on_debug(|text, source, position| debug!(target: "script", source, %position, "{}", text));
+
+    let ctx = context.clone();
+    engine.register_fn("players", move || -> Array {
+        lock(&ctx).players.iter().map(|player| player.to_map().into()).collect()
+    });
+    let ctx = context.clone();
+    engine.register_fn("send_message", move |user_id: i64, text: &str| {
+        lock(&ctx).actions.push(Action::Message { user_id: user_id as i32, text: message_text(text) });
+    });
+    let ctx = context.clone();
+    engine.register_fn("broadcast", move |room: &str, text: &str| {
+        lock(&ctx).actions.push(Action::Broadcast { room: room.to_string(), text: message_text(text) });
+    });
+    let ctx = context.clone();
+    engine.register_fn("teleport", move |user_id: i64, x: f64, y: f64| {
+        lock(&ctx).actions.push(Action::Teleport { user_id: user_id as i32, x, y });
+    });
+    let ctx = context.clone();
+    engine.register_fn("heal", move |user_id: i64, amount: i64| {
+        lock(&ctx).actions.push(Action::Heal { user_id: user_id as i32, amount: amount.clamp(0, i32::MAX as i64) as i32 });
+    });
+    let ctx = context.clone();
+    engine.register_fn("set_timer", move |name: &str, delay_ms: i64| {
+        lock(&ctx).timers.push((name.to_string(), Some(Duration::from_millis(delay_ms.max(0) as u64))));
+    });
+    let ctx = context.clone();
+    engine.register_fn("clear_timer", move |name: &str| {
+        lock(&ctx).timers.push((name.to_string(), None));
+    });
+    engine
+}
+
+impl ScriptHost {
+    // Без каталога скриптов обработчики ничего не делают
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let context = Arc::new(Mutex::new(CallContext::default()));
+        ScriptHost { engine: engine(&context), dir, context, scripts: Mutex::new(Vec::new()), stamps: Mutex::new(HashMap::new()) }
+    }
+
+    // Читает все скрипты каталога; ошибка в любом из них — ошибка загрузки
+    pub fn load(dir: &Path) -> Result<ScriptHost, ScriptError> {
+        let host = ScriptHost::new(Some(dir.to_path_buf()));
+        host.sync(scan(dir, &HashMap::new())?, &[], true)?;
+        Ok(host)
+    }
+
+    // Имена загруженных скриптов по порядку вызова
+    pub fn names(&self) -> Vec<String> {
+        lock(&self.scripts).iter().map(|script| script.name.clone()).collect()
+    }
+
+    pub fn has_hook(&self, hook: &str) -> bool {
+        lock(&self.scripts).iter().any(|script| script.has_hook(hook))
+    }
+
+    // Задан ли каталог скриптов
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    pub fn has_timers(&self) -> bool {
+        lock(&self.scripts).iter().any(|script| !script.timers.is_empty())
+    }
+
+    // Перечитывает измененные, добавленные и удаленные файлы каталога. Скрипт с ошибкой
+    // не заменяет работающую версию. Возвращает действия из on_load новых скриптов.
+    // Читает файлы в текущем потоке; игровой цикл использует scripting::reload
+    pub fn reload(&self, players: &[ScriptPlayer]) -> Vec<Action> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        let known = lock(&self.stamps).clone();
+        self.update(players, scan(dir, &known))
+    }
+
+    fn update(&self, players: &[ScriptPlayer], files: Result<Vec<ScriptFile>, ScriptError>) -> Vec<Action> {
+        match files.and_then(|files| self.sync(files, players, false)) {
+            Ok(actions) => actions,
+            Err(e) => {
+                error!(error = %e, "Failed to reload scripts");
+                Vec::new()
+            },
+        }
+    }
+
+    fn sync(&self, files: Vec<ScriptFile>, players: &[ScriptPlayer], strict: bool) -> Result<Vec<Action>, ScriptError> {
+        let mut scripts = lock(&self.scripts);
+        let mut previous: HashMap<String, Script> = scripts.drain(..).map(|script| (script.name.clone(), script)).collect();
+        let mut stamps = HashMap::new();
+        let mut actions = Vec::new();
+        for ScriptFile { path, name, stamp, source } in files {
+            let old = previous.remove(&name);
+            if let (Some(stamp), Ok(_)) = (stamp, &source) {
+                stamps.insert(name.clone(), stamp);
+            }
+            let source = match source {
+                Ok(Some(source)) => source,
+                Ok(None) => {
+                    scripts.extend(old);
+                    continue;
+                },
+                Err(e) if strict => return Err(ScriptError::Read(path, e)),
+                Err(e) => {
+                    error!(script = %name, error = %e, "Cannot read script, keeping the loaded version");
+                    scripts.extend(old);
+                    continue;
+                },
+            };
+            let old = match old {
+                Some(old) if old.source == source => {
+                    scripts.push(old);
+                    continue;
+                },
+                old => old,
+            };
+            let ast = match self.engine.compile(&source) {
+                Ok(ast) => ast,
+                Err(e) if strict => return Err(ScriptError::Invalid(path, e.to_string())),
+                Err(e) => {
+                    error!(script = %name, error = %e, "Script has errors, keeping the loaded version");
+                    scripts.extend(old);
+                    continue;
+                },
+            };
+
+            // Новая версия начинает с чистого состояния: код верхнего уровня, затем on_load
+            let mut script = Script { name, source, ast, state: Map::new().into(), timers: HashMap::new() };
+            self.enter(players);
+            let result = self.engine.run_ast_with_scope(&mut Scope::new(), &script.ast).and_then(|()| {
+                if script.has_hook("on_load") {
+                    self.call(&mut script, "on_load", Vec::new()).map(|_| ())
+                } else {
+                    Ok(())
+                }
+            });
+            let (loaded_actions, timers) = self.leave();
+            match result {
+                Ok(()) => {},
+                Err(e) if strict => return Err(ScriptError::Invalid(path, e.to_string())),
+                Err(e) => warn!(script = %script.name, error = %e, "Script failed to load"),
+            }
+            set_timers(&mut script, timers);
+            info!(script = %script.name, reloaded = old.is_some(), "Script loaded");
+            actions.extend(loaded_actions);
+            scripts.push(script);
+        }
+        for name in previous.into_keys() {
+            info!(script = %name, "Script unloaded");
+        }
+        *lock(&self.stamps) = stamps;
+        Ok(actions)
+    }
+
+    // Готовит контекст перед вызовом скрипта
+    fn enter(&self, players: &[ScriptPlayer]) {
+        let mut context = lock(&self.context);
+        context.players = players.to_vec();
+        context.actions.clear();
+        context.timers.clear();
+    }
+
+    fn leave(&self) -> (Vec<Action>, Vec<(String, Option<Duration>)>) {
+        let mut context = lock(&self.context);
+        context.players.clear();
+        (std::mem::take(&mut context.actions), std::mem::take(&mut context.timers))
+    }
+
+    fn call(&self, script: &mut Script, hook: &str, args: Vec<Dynamic>) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut script.state);
+        self.engine.call_fn_with_options(options, &mut Scope::new(), &script.ast, hook, args)
+    }
+
+    // Вызывает hook во всех скриптах, где он есть. fold получает результат каждого вызова
+    // и может поменять аргументы для следующего скрипта; false — остальные скрипты не вызываются
+    fn run_hook(
+        &self,
+        players: &[ScriptPlayer],
+        hook: &str,
+        mut args: Vec<Dynamic>,
+        mut fold: impl FnMut(Dynamic, &mut Vec<Dynamic>) -> bool,
+    ) -> Vec<Action> {
+        let mut scripts = lock(&self.scripts);
+        let mut actions = Vec::new();
+        for script in scripts.iter_mut().filter(|script| script.has_hook(hook)) {
+            self.enter(players);
+            let result = self.call(script, hook, args.clone());
+            let (called_actions, timers) = self.leave();
+            set_timers(script, timers);
+            actions.extend(called_actions);
+            match result {
+                Ok(value) => {
+                    if !fold(value, &mut args) {
+                        break;
+                    }
+                },
+                Err(e) => warn!(script = %script.name, hook, error = %e, "Script hook failed"),
+            }
+        }
+        actions
+    }
+
+    // Вызывает on_timer(name) для таймеров, срок которых истек к now. В скрипте без on_timer
+    // истекшие таймеры просто снимаются
+    pub fn fire_timers(&self, players: &[ScriptPlayer], now: Instant) -> Vec<Action> {
+        let mut scripts = lock(&self.scripts);
+        let mut actions = Vec::new();
+        for script in scripts.iter_mut() {
+            let mut due: Vec<(Instant, String)> = script.timers
+                .iter()
+                .filter(|(_, at)| **at <= now)
+                .map(|(name, at)| (*at, name.clone()))
+                .collect();
+            due.sort();
+            let has_hook = script.has_hook("on_timer");
+            for (_, name) in due {
+                script.timers.remove(&name);
+                if !has_hook {
+                    continue;
+                }
+                self.enter(players);
+                let result = self.call(script, "on_timer", vec![name.clone().into()]);
+                let (called_actions, timers) = self.leave();
+                set_timers(script, timers);
+                actions.extend(called_actions);
+                if let Err(e) = result {
+                    warn!(script = %script.name, timer = %name, error = %e, "Script timer failed");
+                }
+            }
+        }
+        actions
+    }
+
+    pub fn on_join(&self, players: &[ScriptPlayer], player: &ScriptPlayer) -> Vec<Action> {
+        self.run_hook(players, "on_join", vec![player.to_map().into()], |_, _| true)
+    }
+
+    pub fn on_leave(&self, players: &[ScriptPlayer], player: &ScriptPlayer) -> Vec<Action> {
+        self.run_hook(players, "on_leave", vec![player.to_map().into()], |_, _| true)
+    }
+
+    pub fn on_move(&self, players: &[ScriptPlayer], player: &ScriptPlayer) -> Vec<Action> {
+        self.run_hook(players, "on_move", vec![player.to_map().into()], |_, _| true)
+    }
+
+    // Сообщение чата проходит через on_chat всех скриптов: false отменяет его,
+    // строка заменяет текст, остальные значения оставляют как есть
+    pub fn on_chat(&self, players: &[ScriptPlayer], player: &ScriptPlayer, text: &str) -> (Option<String>, Vec<Action>) {
+        let args = vec![player.to_map().into(), text.into()];
+        let mut text = Some(text.to_string());
+        let actions = self.run_hook(players, "on_chat", args, |result, args| {
+            if result.as_bool() == Ok(false) {
+                text = None;
+                return false;
+            }
+            if let Ok(replaced) = result.into_string() {
+                let replaced = message_text(&replaced);
+                args[1] = replaced.clone().into();
+                text = Some(replaced);
+            }
+            true
+        });
+        (text, actions)
+    }
+}
+
+fn set_timers(script: &mut Script, timers: Vec<(String, Option<Duration>)>) {
+    for (name, delay) in timers {
+        match delay {
+            Some(delay) if script.timers.len() < MAX_TIMERS || script.timers.contains_key(&name) => {
+                script.timers.insert(name, Instant::now() + delay);
+            },
+            Some(_) => warn!(script = %script.name, timer = %name, "Too many timers, ignoring"),
+            None => {
+                script.timers.remove(&name);
+            },
+        }
+    }
+}
+
+// Скрипты из game.scripts_dir; без каталога скриптов нет
+pub fn load_scripts(config: &GameConfig) -> Result<ScriptHost, ScriptError> {
+    let Some(dir) = &config.scripts_dir else {
+        return Ok(ScriptHost::default());
+    };
+    let host = ScriptHost::load(dir)?;
+    info!(dir = %dir.display(), scripts = host.names().len(), "Loaded scripts");
+    Ok(host)
+}
+
+// Игроки онлайн во всех комнатах
+pub async fn online_players(app_state: &AppState) -> Vec<ScriptPlayer> {
+    let mut players = Vec::new();
+    for room in app_state.rooms.all() {
+        let positions = room.active_player_positions.lock().await;
+        players.extend(positions.values().map(|p| ScriptPlayer { user_id: p.user_id, room: room.name.clone(), x: p.x, y: p.y }));
+    }
+    players.sort_by_key(|player| player.user_id);
+    players
+}
+
+async fn room_of(app_state: &AppState, user_id: i32) -> Option<Arc<Room>> {
+    for room in app_state.rooms.all() {
+        if room.active_player_positions.lock().await.contains_key(&user_id) {
+            return Some(room);
+        }
+    }
+    None
+}
+
+// Применяет действия скриптов; действия с игроками, которых уже нет онлайн, пропускаются
+pub async fn apply(app_state: &AppState, actions: Vec<Action>) {
+    for action in actions {
+        debug!(?action, "Applying script action");
+        match action {
+            Action::Message { user_id, text } => {
+                if let Some(room) = room_of(app_state, user_id).await {
+                    let _ = room.game_state_tx.send(GameMessage::SystemMessage { to: Some(user_id), text });
+                }
+            },
+            Action::Broadcast { room, text } => {
+                if let Some(room) = app_state.rooms.get(&room) {
+                    let _ = room.game_state_tx.send(GameMessage::SystemMessage { to: None, text });
+                }
+            },
+            Action::Teleport { user_id, x, y } => {
+                if !(x.is_finite() && y.is_finite()) {
+                    warn!(user_id, x, y, "Script tried to teleport a player to an invalid position");
+                    continue;
+                }
+                let Some(room) = room_of(app_state, user_id).await else {
+                    continue;
+                };
+                let position = {
+                    let mut positions = room.active_player_positions.lock().await;
+                    let Some(position) = positions.get_mut(&user_id) else {
+                        continue;
+                    };
+                    (position.x, position.y) = (x, y);
+                    (position.vx, position.vy, position.vz) = (0.0, 0.0, 0.0);
+                    position.clone()
+                };
+                // Новую позицию игрок и остальные получат от игрового цикла
+                room.moved_players.lock().await.insert(user_id);
+                let player = Player { user_id, x, y, z: position.z, room: room.name.clone() };
+                let query_started = Instant::now();
+                let result = app_state.storage.players.save_position(&player).await;
+                telemetry::db_query("save_position", query_started.elapsed());
+                if let Err(e) = result {
+                    error!(error = %e, user_id, "Error saving teleported position");
+                }
+            },
+            Action::Heal { user_id, amount } => {
+                let Some(room) = room_of(app_state, user_id).await else {
+                    continue;
+                };
+                let vitals = {
+                    let mut combatants = room.combatants.lock().await;
+                    let Some(combatant) = combatants.get_mut(&user_id).filter(|combatant| !combatant.is_dead()) else {
+                        continue;
+                    };
+                    let vitals = &mut combatant.vitals;
+                    let amount = amount.min(vitals.max_health - vitals.health);
+                    if amount <= 0 {
+                        continue;
+                    }
+                    vitals.health += amount;
+                    let _ = room.game_state_tx.send(GameMessage::Healed { user_id, amount, health: vitals.health });
+                    *vitals
+                };
+                combat::save_vitals(app_state, user_id, &vitals).await;
+            },
+        }
+    }
+}
+
+// Игрок комнаты room для передачи в обработчик; None — его там уже нет
+async fn player_in(room: &Room, user_id: i32) -> Option<ScriptPlayer> {
+    room.active_player_positions.lock().await
+        .get(&user_id)
+        .map(|p| ScriptPlayer { user_id, room: room.name.clone(), x: p.x, y: p.y })
+}
+
+// Обработчики событий. Список игроков собирается, только если обработчик есть хотя бы в одном скрипте
+
+pub async fn on_join(app_state: &AppState, room: &Room, user_id: i32) {
+    if !app_state.scripts.has_hook("on_join") {
+        return;
+    }
+    let Some(player) = player_in(room, user_id).await else {
+        return;
+    };
+    let players = online_players(app_state).await;
+    let actions = app_state.scripts.on_join(&players, &player);
+    apply(app_state, actions).await;
+}
+
+// Вызывается, пока игрок еще в комнате; повторный вызов после его ухода ничего не делает
+pub async fn on_leave(app_state: &AppState, room: &Room, user_id: i32) {
+    if !app_state.scripts.has_hook("on_leave") {
+        return;
+    }
+    let Some(player) = player_in(room, user_id).await else {
+        return;
+    };
+    let players = online_players(app_state).await;
+    let actions = app_state.scripts.on_leave(&players, &player);
+    apply(app_state, actions).await;
+}
+
+pub async fn on_move(app_state: &AppState, room: &Room, user_id: i32) {
+    if !app_state.scripts.has_hook("on_move") {
+        return;
+    }
+    let Some(player) = player_in(room, user_id).await else {
+        return;
+    };
+    let players = online_players(app_state).await;
+    let actions = app_state.scripts.on_move(&players, &player);
+    apply(app_state, actions).await;
+}
+
+// Текст, который нужно разослать, или None, если скрипт отменил сообщение
+pub async fn on_chat(app_state: &AppState, room: &Room, user_id: i32, text: String) -> Option<String> {
+    if !app_state.scripts.has_hook("on_chat") {
+        return Some(text);
+    }
+    let player = player_in(room, user_id).await?;
+    let players = online_players(app_state).await;
+    let (text, actions) = app_state.scripts.on_chat(&players, &player, &text);
+    apply(app_state, actions).await;
+    text
+}
+
+// Перечитывает измененные файлы каталога скриптов и применяет действия из on_load.
+// Каталог просматривается в потоке для блокирующих операций, а не в игровом цикле
+pub async fn reload(app_state: &AppState) {
+    let Some(dir) = app_state.scripts.dir.clone() else {
+        return;
+    };
+    let known = lock(&app_state.scripts.stamps).clone();
+    let known_count = known.len();
+    let files = match tokio::task::spawn_blocking(move || scan(&dir, &known)).await {
+        Ok(files) => files,
+        Err(e) => {
+            error!(error = %e, "Script directory scan failed");
+            return;
+        },
+    };
+    // Ни один файл не добавлен, не удален и не изменен
+    if files.as_ref().is_ok_and(|files| files.len() == known_count && files.iter().all(|file| matches!(file.source, Ok(None)))) {
+        return;
+    }
+    let players = online_players(app_state).await;
+    let actions = app_state.scripts.update(&players, files);
+    apply(app_state, actions).await;
+}
+
+// Шаг игрового цикла: истекшие таймеры, раз в секунду — проверка файлов скриптов
+pub async fn tick(app_state: &AppState, check_files: bool) {
+    if check_files && app_state.scripts.is_enabled() {
+        reload(app_state).await;
+    }
+    if !app_state.scripts.has_timers() {
+        return;
+    }
+    let players = online_players(app_state).await;
+    let actions = app_state.scripts.fire_timers(&players, Instant::now());
+    apply(app_state, actions).await;
+}
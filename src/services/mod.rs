@@ -1,2 +0,0 @@
-pub mod auth;
-pub mod game;
\ No newline at end of file
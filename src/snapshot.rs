@@ -0,0 +1,240 @@
+// src/snapshot.rs
+// Дельта-сжатие состояния мира. Каждый тик игровой цикл публикует один квантованный
+// снимок на всех, а соединение с возможностью "delta_snapshots" кодирует его относительно
+// последнего снимка, подтвержденного клиентом (SnapshotAck): передаются только изменившиеся
+// поля изменившихся игроков. Без подтвержденной базы отправляется полный снимок
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::f64::consts::TAU;
+use std::sync::Arc;
+
+use crate::routes::game::PlayerPositionUpdate;
+
+// Имя возможности протокола, согласуемой в Hello/Welcome
+pub const FEATURE: &str = "delta_snapshots";
+// Шаг квантования координат и скоростей — 1/100 единицы
+pub const POSITION_SCALE: f64 = 100.0;
+pub const VELOCITY_SCALE: f64 = 100.0;
+// Поворот кодируется в u16: полный оборот делится на 65536 шагов
+const ROTATION_STEPS: f64 = 65536.0;
+// Сколько неподтвержденных снимков помнит соединение
+const HISTORY_LEN: usize = 32;
+// База старше этого числа тиков не используется, клиент получает полный снимок
+pub const MAX_BASELINE_AGE: u64 = 64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuantizedEntity {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub rotation: u16,
+    pub vx: i32,
+    pub vy: i32,
+    pub vz: i32,
+    pub seq: u64,
+}
+
+pub type EntityTable = HashMap<i32, QuantizedEntity>;
+
+// Состояние мира на тике; одно на всех получателей
+#[derive(Debug)]
+pub struct WorldSnapshot {
+    pub tick: u64,
+    pub entities: EntityTable,
+}
+
+// Изменения одного игрока относительно базы. Для игрока, которого нет в базе,
+// заполнены все поля
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntityDelta {
+    pub id: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vx: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vy: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vz: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDelta {
+    pub tick: u64,
+    // Тик базового снимка; None — полный снимок
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<EntityDelta>,
+    // Игроки, которые были в базе, но исчезли из мира
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<i32>,
+}
+
+fn quantize(value: f64, scale: f64) -> i32 {
+    (value * scale).round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+impl QuantizedEntity {
+    pub fn from_update(update: &PlayerPositionUpdate) -> Self {
+        let turns = update.rotation.rem_euclid(TAU) / TAU;
+        QuantizedEntity {
+            x: quantize(update.x, POSITION_SCALE),
+            y: quantize(update.y, POSITION_SCALE),
+            z: quantize(update.z, POSITION_SCALE),
+            rotation: ((turns * ROTATION_STEPS).round() as u32 % ROTATION_STEPS as u32) as u16,
+            vx: quantize(update.vx, VELOCITY_SCALE),
+            vy: quantize(update.vy, VELOCITY_SCALE),
+            vz: quantize(update.vz, VELOCITY_SCALE),
+            seq: update.seq,
+        }
+    }
+
+    pub fn to_update(&self, user_id: i32) -> PlayerPositionUpdate {
+        PlayerPositionUpdate {
+            user_id,
+            x: self.x as f64 / POSITION_SCALE,
+            y: self.y as f64 / POSITION_SCALE,
+            z: self.z as f64 / POSITION_SCALE,
+            seq: self.seq,
+            rotation: self.rotation as f64 / ROTATION_STEPS * TAU,
+            vx: self.vx as f64 / VELOCITY_SCALE,
+            vy: self.vy as f64 / VELOCITY_SCALE,
+            vz: self.vz as f64 / VELOCITY_SCALE,
+            // Теги кланов в снимки не входят: клиент узнает их из InitialPlayers и ClanTagChanged
+            clan_tag: None,
+        }
+    }
+}
+
+impl WorldSnapshot {
+    pub fn new<'a>(tick: u64, players: impl IntoIterator<Item = &'a PlayerPositionUpdate>) -> Self {
+        let entities = players
+            .into_iter()
+            .map(|p| (p.user_id, QuantizedEntity::from_update(p)))
+            .collect();
+        WorldSnapshot { tick, entities }
+    }
+}
+
+fn changed<T: PartialEq + Copy>(old: Option<T>, new: T) -> Option<T> {
+    if old == Some(new) { None } else { Some(new) }
+}
+
+fn entity_delta(id: i32, old: Option<&QuantizedEntity>, new: &QuantizedEntity) -> EntityDelta {
+    EntityDelta {
+        id,
+        x: changed(old.map(|e| e.x), new.x),
+        y: changed(old.map(|e| e.y), new.y),
+        z: changed(old.map(|e| e.z), new.z),
+        rotation: changed(old.map(|e| e.rotation), new.rotation),
+        vx: changed(old.map(|e| e.vx), new.vx),
+        vy: changed(old.map(|e| e.vy), new.vy),
+        vz: changed(old.map(|e| e.vz), new.vz),
+        seq: changed(old.map(|e| e.seq), new.seq),
+    }
+}
+
+// Изменения current относительно baseline: измененные и новые игроки (по возрастанию id)
+// и удаленные игроки
+pub fn diff(baseline: &EntityTable, current: &EntityTable) -> (Vec<EntityDelta>, Vec<i32>) {
+    let mut entities: Vec<EntityDelta> = current
+        .iter()
+        .filter(|(id, entity)| baseline.get(id) != Some(entity))
+        .map(|(id, entity)| entity_delta(*id, baseline.get(id), entity))
+        .collect();
+    entities.sort_by_key(|delta| delta.id);
+    let mut removed: Vec<i32> = baseline.keys().filter(|id| !current.contains_key(id)).copied().collect();
+    removed.sort_unstable();
+    (entities, removed)
+}
+
+// Восстановление снимка на стороне клиента: baseline — снимок с тиком delta.baseline
+// (для полного снимка не используется)
+pub fn apply(baseline: &EntityTable, delta: &SnapshotDelta) -> EntityTable {
+    let mut table = if delta.baseline.is_some() { baseline.clone() } else { EntityTable::new() };
+    for id in &delta.removed {
+        table.remove(id);
+    }
+    for change in &delta.entities {
+        let entity = table.entry(change.id).or_default();
+        if let Some(x) = change.x { entity.x = x; }
+        if let Some(y) = change.y { entity.y = y; }
+        if let Some(z) = change.z { entity.z = z; }
+        if let Some(rotation) = change.rotation { entity.rotation = rotation; }
+        if let Some(vx) = change.vx { entity.vx = vx; }
+        if let Some(vy) = change.vy { entity.vy = vy; }
+        if let Some(vz) = change.vz { entity.vz = vz; }
+        if let Some(seq) = change.seq { entity.seq = seq; }
+    }
+    table
+}
+
+// Состояние кодирования одного соединения
+#[derive(Default)]
+pub struct SnapshotEncoder {
+    // Отправленные, но еще не подтвержденные снимки
+    history: VecDeque<Arc<WorldSnapshot>>,
+    // Последний подтвержденный клиентом снимок
+    baseline: Option<Arc<WorldSnapshot>>,
+    // Последний отправленный снимок: если мир не изменился, отправлять нечего
+    last_sent: Option<Arc<WorldSnapshot>>,
+}
+
+impl SnapshotEncoder {
+    // Подтверждение снимка клиентом. Неизвестный тик (например, 0) сбрасывает базу:
+    // следующим придет полный снимок. false, если база сброшена
+    pub fn ack(&mut self, tick: u64) -> bool {
+        if self.baseline.as_ref().is_some_and(|baseline| baseline.tick >= tick && tick > 0) {
+            // Устаревшее подтверждение: база уже новее
+            return true;
+        }
+        match self.history.iter().position(|snapshot| snapshot.tick == tick) {
+            Some(index) => {
+                // Снимки старше подтвержденного больше не понадобятся
+                self.baseline = self.history.drain(..=index).next_back();
+                true
+            },
+            None => {
+                self.baseline = None;
+                self.last_sent = None;
+                false
+            },
+        }
+    }
+
+    // Кодирует снимок для отправки; None — с прошлой отправки ничего не изменилось
+    pub fn encode(&mut self, snapshot: &Arc<WorldSnapshot>) -> Option<SnapshotDelta> {
+        if self.last_sent.as_ref().is_some_and(|last| last.entities == snapshot.entities) {
+            return None;
+        }
+        if self.baseline.as_ref().is_some_and(|baseline| snapshot.tick.saturating_sub(baseline.tick) > MAX_BASELINE_AGE) {
+            self.baseline = None;
+        }
+
+        let empty = EntityTable::new();
+        let reference = self.baseline.as_ref().map_or(&empty, |baseline| &baseline.entities);
+        let (entities, removed) = diff(reference, &snapshot.entities);
+
+        self.history.push_back(snapshot.clone());
+        if self.history.len() > HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.last_sent = Some(snapshot.clone());
+
+        Some(SnapshotDelta {
+            tick: snapshot.tick,
+            baseline: self.baseline.as_ref().map(|baseline| baseline.tick),
+            entities,
+            removed,
+        })
+    }
+}
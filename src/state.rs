@@ -1,19 +1,167 @@
 // src/state.rs
-use sqlx::PgPool;
-use tokio::sync::broadcast;
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Mutex};
 
-// Импортируем типы из game.rs, так как они используются в AppState
-// Путь зависит от того, где GameMessage и PlayerPositionUpdate определены.
-// Если они в game.rs, то так:
-use crate::routes::game::{GameMessage, PlayerPositionUpdate};
+use crate::config::Config;
+use crate::items::{Inventory, ItemRegistry};
+use crate::map::TiledMap;
+use crate::npc::NpcRegistry;
+use crate::party::PartyRegistry;
+use crate::rooms::RoomRegistry;
+use crate::routes::game::GameMessage;
+use crate::scripting::ScriptHost;
+use crate::storage::Storage;
+use crate::trading::TradeRegistry;
 
 // Структура для общего состояния приложения
 pub struct AppState {
-    pub pool: PgPool,
-    pub game_state_tx: Arc<broadcast::Sender<GameMessage>>,
-    // HashMap для отслеживания текущих позиций ТОЛЬКО активных игроков
-    pub active_player_positions: Arc<Mutex<HashMap<i32, PlayerPositionUpdate>>>,
-}
\ No newline at end of file
+    pub config: Arc<Config>,
+    pub storage: Storage,
+    // Миры и инстансы; у каждой комнаты свои игроки и каналы рассылки
+    pub rooms: RoomRegistry,
+    // Определения предметов из game.items_path
+    pub items: ItemRegistry,
+    // Определения NPC и спаунеры из game.npcs_path
+    pub npcs: NpcRegistry,
+    // Скрипты игровых правил из game.scripts_dir
+    pub scripts: ScriptHost,
+    // Инвентари игроков онлайн; каждое изменение сразу записывается в хранилище
+    pub inventories: Mutex<HashMap<i32, Inventory>>,
+    // Открытые обмены между игроками; блокируется раньше inventories
+    pub trades: Mutex<TradeRegistry>,
+    pub parties: Mutex<PartyRegistry>,
+    // Очередь записи составов групп: занимается под блокировкой parties, а запись идет уже после
+    // ее снятия, поэтому составы попадают в хранилище в порядке изменений
+    pub party_saves: Mutex<()>,
+    // Сообщения игрокам независимо от комнаты (группы, кланы, друзья): адресаты бывают в разных комнатах
+    pub players_tx: broadcast::Sender<GameMessage>,
+    // Позиции участников групп (PartyMemberPosition) идут каждый тик, поэтому отдельно от players_tx:
+    // отставший получатель теряет только позиции, а не сообщения групп, кланов и друзей
+    pub party_positions_tx: broadcast::Sender<GameMessage>,
+    // Счетчик идентификаторов WebSocket-соединений (поле conn_id в логах)
+    pub next_connection_id: AtomicU64,
+    pub started_at: Instant,
+    // Время последнего завершенного тика; по нему /ready понимает, что игровой цикл жив
+    last_tick: std::sync::Mutex<Option<Instant>>,
+    // true после получения сигнала остановки; WebSocket-соединения подписываются на изменения
+    shutdown: watch::Sender<bool>,
+    // Игроки, чьи соединения нужно закрыть (например, после запроса на удаление аккаунта)
+    kicks: broadcast::Sender<i32>,
+    // Число открытых игровых соединений каждого игрока: сессия закрывается с последним из них
+    connections: std::sync::Mutex<HashMap<i32, usize>>,
+}
+
+impl AppState {
+    // Без карт Tiled: game.maps не читается (карты загружает вызывающий, см. with_maps)
+    pub fn new(storage: Storage, config: Config) -> Self {
+        Self::with_maps(storage, config, HashMap::new())
+    }
+
+    // maps — результат map::load_maps для game.maps этой конфигурации
+    pub fn with_maps(storage: Storage, config: Config, maps: HashMap<String, Arc<TiledMap>>) -> Self {
+        AppState {
+            rooms: RoomRegistry::new(&config.game, maps),
+            items: ItemRegistry::default(),
+            npcs: NpcRegistry::default(),
+            scripts: ScriptHost::default(),
+            inventories: Mutex::new(HashMap::new()),
+            trades: Mutex::new(TradeRegistry::default()),
+            parties: Mutex::new(PartyRegistry::default()),
+            party_saves: Mutex::new(()),
+            players_tx: broadcast::channel(config.game.broadcast_capacity).0,
+            party_positions_tx: broadcast::channel(config.game.broadcast_capacity).0,
+            kicks: broadcast::channel(config.game.broadcast_capacity).0,
+            config: Arc::new(config),
+            storage,
+            next_connection_id: AtomicU64::new(1),
+            started_at: Instant::now(),
+            last_tick: std::sync::Mutex::new(None),
+            shutdown: watch::channel(false).0,
+            connections: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    // items — результат items::load_items для game.items_path этой конфигурации
+    pub fn with_items(mut self, items: ItemRegistry) -> Self {
+        self.items = items;
+        self
+    }
+
+    // npcs — результат npc::load_npcs для game.npcs_path этой конфигурации
+    pub fn with_npcs(mut self, npcs: NpcRegistry) -> Self {
+        self.npcs = npcs;
+        self
+    }
+
+    // scripts — результат scripting::load_scripts для game.scripts_dir этой конфигурации
+    pub fn with_scripts(mut self, scripts: ScriptHost) -> Self {
+        self.scripts = scripts;
+        self
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn record_tick(&self) {
+        *self.last_tick.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Instant::now());
+    }
+
+    // Игровой цикл считается работающим, если тик был не дольше max_delay назад
+    pub fn world_running(&self, max_delay: Duration) -> bool {
+        self.last_tick.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .is_some_and(|tick| tick.elapsed() <= max_delay)
+    }
+
+    pub fn begin_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    // Закрывает все игровые соединения пользователя
+    pub fn kick(&self, user_id: i32) {
+        let _ = self.kicks.send(user_id);
+    }
+
+    pub fn subscribe_kicks(&self) -> broadcast::Receiver<i32> {
+        self.kicks.subscribe()
+    }
+
+    // Учитывает новое соединение игрока; true, если других у него нет
+    pub fn connection_opened(&self, user_id: i32) -> bool {
+        let mut connections = self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let count = connections.entry(user_id).or_insert(0);
+        *count += 1;
+        *count == 1
+    }
+
+    // Есть ли у игрока открытые игровые соединения
+    pub fn is_connected(&self, user_id: i32) -> bool {
+        self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).contains_key(&user_id)
+    }
+
+    // Учитывает закрытие соединения игрока; true, если это было последнее
+    pub fn connection_closed(&self, user_id: i32) -> bool {
+        let mut connections = self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(count) = connections.get_mut(&user_id) else {
+            return true;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return false;
+        }
+        connections.remove(&user_id);
+        true
+    }
+}
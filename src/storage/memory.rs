@@ -0,0 +1,533 @@
+// src/storage/memory.rs
+// Хранилище в памяти: повторяет поведение PostgreSQL-реализации,
+// чтобы тесты могли прогонять полный сценарий без базы данных
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::clans::{Clan, ClanId, ClanInvite, ClanMember, ClanRank};
+use crate::friends::{Friend, FriendRequest};
+use crate::items::ItemStack;
+use crate::models::{player::{Player, Vitals}, user::User};
+use crate::storage::{
+    ChunkRepository, ClanRepository, FriendRepository, HealthRepository, InventoryRepository, PartyRepository, PlayerRepository,
+    SessionRepository, StorageError, StorageResult, UserRepository,
+};
+use crate::tiles::{ChunkDiff, ChunkPos};
+
+struct PlayerRow {
+    player: Player,
+    vitals: Option<Vitals>,
+    died_at: Option<DateTime<Utc>>,
+    is_online: bool,
+}
+
+struct ClanRow {
+    name: String,
+    tag: String,
+    created_at: DateTime<Utc>,
+}
+
+struct ClanMemberRow {
+    user_id: i32,
+    clan: ClanId,
+    rank: ClanRank,
+    joined_at: DateTime<Utc>,
+}
+
+struct ClanInviteRow {
+    clan: ClanId,
+    user_id: i32,
+    invited_by: Option<i32>,
+    created_at: DateTime<Utc>,
+}
+
+struct FriendRow {
+    from: i32,
+    to: i32,
+    created_at: DateTime<Utc>,
+    accepted_at: Option<DateTime<Utc>>,
+}
+
+impl FriendRow {
+    fn links(&self, a: i32, b: i32) -> bool {
+        (self.from, self.to) == (a, b) || (self.from, self.to) == (b, a)
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    next_user_id: i32,
+    users: HashMap<i32, User>,
+    players: HashMap<i32, PlayerRow>,
+    chunks: HashMap<(String, ChunkPos), ChunkDiff>,
+    inventories: HashMap<i32, Vec<(u32, ItemStack)>>,
+    parties: HashMap<u64, Vec<i32>>,
+    next_clan_id: ClanId,
+    clans: HashMap<ClanId, ClanRow>,
+    // В порядке вступления
+    clan_members: Vec<ClanMemberRow>,
+    clan_invites: Vec<ClanInviteRow>,
+    friends: Vec<FriendRow>,
+}
+
+impl Tables {
+    fn login(&self, user_id: i32) -> String {
+        self.users.get(&user_id).map(|user| user.login.clone()).unwrap_or_default()
+    }
+
+    // Участники из expected по-прежнему в клане и с теми же рангами
+    fn ranks_unchanged(&self, clan: ClanId, expected: &[(i32, ClanRank)]) -> bool {
+        expected.iter().all(|(user_id, rank)| {
+            self.clan_members.iter().any(|member| (member.clan, member.user_id, member.rank) == (clan, *user_id, *rank))
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    // std::sync::Mutex: блокировка никогда не удерживается через .await
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStorage {
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        // Паника в другом потоке не портит данные настолько, чтобы отказываться от них
+        self.tables.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryStorage {
+    async fn create_user(&self, login: &str, hashed_password: &str) -> StorageResult<i32> {
+        let mut tables = self.tables();
+        if tables.users.values().any(|u| u.login == login) {
+            return Err(StorageError::Conflict(format!("login {} already exists", login)));
+        }
+        tables.next_user_id += 1;
+        let id = tables.next_user_id;
+        tables.users.insert(id, User {
+            id,
+            login: login.to_string(),
+            hashed_password: hashed_password.to_string(),
+            created_at: Utc::now(),
+            deletion_requested_at: None,
+        });
+        Ok(id)
+    }
+
+    async fn find_by_login(&self, login: &str) -> StorageResult<Option<User>> {
+        Ok(self.tables().users.values().find(|u| u.login == login).cloned())
+    }
+
+    async fn find_by_id(&self, user_id: i32) -> StorageResult<Option<User>> {
+        Ok(self.tables().users.get(&user_id).cloned())
+    }
+
+    async fn request_deletion(&self, user_id: i32) -> StorageResult<Option<DateTime<Utc>>> {
+        Ok(self.tables().users.get_mut(&user_id).map(|user| {
+            *user.deletion_requested_at.get_or_insert_with(Utc::now)
+        }))
+    }
+
+    async fn cancel_deletion(&self, user_id: i32, cutoff: DateTime<Utc>) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        let Some(user) = tables.users.get_mut(&user_id) else {
+            return Ok(false);
+        };
+        if user.deletion_requested_at.is_some_and(|t| t <= cutoff) {
+            return Ok(false);
+        }
+        user.deletion_requested_at = None;
+        Ok(true)
+    }
+
+    async fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> StorageResult<u64> {
+        let mut tables = self.tables();
+        let expired: Vec<i32> = tables.users.values()
+            .filter(|u| u.deletion_requested_at.is_some_and(|t| t <= cutoff))
+            .map(|u| u.id)
+            .collect();
+        for user_id in &expired {
+            tables.users.remove(user_id);
+            tables.players.remove(user_id);
+            tables.inventories.remove(user_id);
+            for members in tables.parties.values_mut() {
+                members.retain(|member| member != user_id);
+            }
+        }
+        tables.parties.retain(|_, members| !members.is_empty());
+        tables.friends.retain(|row| !expired.contains(&row.from) && !expired.contains(&row.to));
+        tables.clan_members.retain(|member| !expired.contains(&member.user_id));
+        tables.clan_invites.retain(|invite| !expired.contains(&invite.user_id));
+        for invite in &mut tables.clan_invites {
+            invite.invited_by = invite.invited_by.filter(|user_id| !expired.contains(user_id));
+        }
+        // Опустевшие кланы удаляются, в оставшихся без лидера им становится старший по рангу и стажу
+        let Tables { clans, clan_members, clan_invites, .. } = &mut *tables;
+        clans.retain(|id, _| clan_members.iter().any(|member| member.clan == *id));
+        clan_invites.retain(|invite| clans.contains_key(&invite.clan));
+        for id in clans.keys() {
+            let in_clan = || clan_members.iter().filter(|member| member.clan == *id);
+            if in_clan().any(|member| member.rank == ClanRank::Leader) {
+                continue;
+            }
+            let successor = in_clan().enumerate().min_by_key(|(order, member)| (member.rank, *order)).map(|(_, member)| member.user_id);
+            if let Some(member) = clan_members.iter_mut().find(|member| Some(member.user_id) == successor) {
+                member.rank = ClanRank::Leader;
+            }
+        }
+        Ok(expired.len() as u64)
+    }
+}
+
+#[async_trait]
+impl PlayerRepository for MemoryStorage {
+    async fn load_player(&self, user_id: i32) -> StorageResult<Option<Player>> {
+        Ok(self.tables().players.get(&user_id).map(|row| row.player.clone()))
+    }
+
+    async fn save_position(&self, player: &Player) -> StorageResult<()> {
+        self.tables().players
+            .entry(player.user_id)
+            .and_modify(|row| {
+                row.player.x = player.x;
+                row.player.y = player.y;
+                row.player.z = player.z;
+            })
+            .or_insert_with(|| PlayerRow { player: player.clone(), vitals: None, died_at: None, is_online: false });
+        Ok(())
+    }
+
+    async fn save_room(&self, spawn: &Player) -> StorageResult<()> {
+        self.tables().players
+            .entry(spawn.user_id)
+            .and_modify(|row| row.player = spawn.clone())
+            .or_insert_with(|| PlayerRow { player: spawn.clone(), vitals: None, died_at: None, is_online: false });
+        Ok(())
+    }
+
+    async fn load_vitals(&self, user_id: i32) -> StorageResult<Option<Vitals>> {
+        Ok(self.tables().players.get(&user_id).and_then(|row| row.vitals))
+    }
+
+    async fn save_vitals(&self, user_id: i32, vitals: &Vitals) -> StorageResult<()> {
+        if let Some(row) = self.tables().players.get_mut(&user_id) {
+            row.vitals = Some(*vitals);
+            row.died_at = match vitals.is_dead() {
+                true => row.died_at.or(Some(Utc::now())),
+                false => None,
+            };
+        }
+        Ok(())
+    }
+
+    async fn died_at(&self, user_id: i32) -> StorageResult<Option<DateTime<Utc>>> {
+        Ok(self.tables().players.get(&user_id).and_then(|row| row.died_at))
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MemoryStorage {
+    async fn open_session(&self, spawn: &Player) -> StorageResult<()> {
+        self.tables().players
+            .entry(spawn.user_id)
+            .or_insert_with(|| PlayerRow { player: spawn.clone(), vitals: None, died_at: None, is_online: false })
+            .is_online = true;
+        Ok(())
+    }
+
+    async fn close_session(&self, user_id: i32) -> StorageResult<()> {
+        if let Some(row) = self.tables().players.get_mut(&user_id) {
+            row.is_online = false;
+        }
+        Ok(())
+    }
+
+    async fn reset_sessions(&self) -> StorageResult<()> {
+        for row in self.tables().players.values_mut() {
+            row.is_online = false;
+        }
+        Ok(())
+    }
+
+    async fn count_online(&self) -> StorageResult<usize> {
+        Ok(self.tables().players.values().filter(|row| row.is_online).count())
+    }
+}
+
+#[async_trait]
+impl ChunkRepository for MemoryStorage {
+    async fn load_chunk(&self, room: &str, pos: ChunkPos) -> StorageResult<Option<ChunkDiff>> {
+        Ok(self.tables().chunks.get(&(room.to_string(), pos)).cloned())
+    }
+
+    async fn save_chunk(&self, room: &str, pos: ChunkPos, changes: &ChunkDiff) -> StorageResult<()> {
+        self.tables().chunks.insert((room.to_string(), pos), changes.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InventoryRepository for MemoryStorage {
+    async fn load_inventory(&self, user_id: i32) -> StorageResult<Vec<(u32, ItemStack)>> {
+        Ok(self.tables().inventories.get(&user_id).cloned().unwrap_or_default())
+    }
+
+    async fn save_inventory(&self, user_id: i32, stacks: &[(u32, ItemStack)]) -> StorageResult<()> {
+        let mut tables = self.tables();
+        if tables.users.contains_key(&user_id) {
+            tables.inventories.insert(user_id, stacks.to_vec());
+        }
+        Ok(())
+    }
+
+    async fn save_inventories(&self, inventories: &[(i32, Vec<(u32, ItemStack)>)]) -> StorageResult<()> {
+        // Все инвентари меняются под одной блокировкой
+        let mut tables = self.tables();
+        for (user_id, stacks) in inventories {
+            if tables.users.contains_key(user_id) {
+                tables.inventories.insert(*user_id, stacks.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PartyRepository for MemoryStorage {
+    async fn load_parties(&self) -> StorageResult<Vec<(u64, Vec<i32>)>> {
+        let mut parties: Vec<(u64, Vec<i32>)> = self.tables().parties.iter().map(|(id, members)| (*id, members.clone())).collect();
+        parties.sort_by_key(|(id, _)| *id);
+        Ok(parties)
+    }
+
+    async fn party_of(&self, user_id: i32) -> StorageResult<Option<(u64, Vec<i32>)>> {
+        Ok(self.tables().parties
+            .iter()
+            .find(|(_, members)| members.contains(&user_id))
+            .map(|(id, members)| (*id, members.clone())))
+    }
+
+    async fn save_party(&self, party: u64, members: &[i32]) -> StorageResult<()> {
+        let mut tables = self.tables();
+        let members: Vec<i32> = members.iter().copied().filter(|user_id| tables.users.contains_key(user_id)).collect();
+        // Игрок состоит не больше чем в одной группе
+        for other in tables.parties.values_mut() {
+            other.retain(|user_id| !members.contains(user_id));
+        }
+        if members.is_empty() {
+            tables.parties.remove(&party);
+        } else {
+            tables.parties.insert(party, members);
+        }
+        tables.parties.retain(|_, members| !members.is_empty());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ClanRepository for MemoryStorage {
+    async fn create_clan(&self, name: &str, tag: &str, founder: i32) -> StorageResult<ClanId> {
+        let mut tables = self.tables();
+        if tables.clans.values().any(|clan| clan.name.to_lowercase() == name.to_lowercase() || clan.tag == tag) {
+            return Err(StorageError::Conflict(format!("clan {} [{}] already exists", name, tag)));
+        }
+        if tables.clan_members.iter().any(|member| member.user_id == founder) {
+            return Err(StorageError::Conflict(format!("user {} is already in a clan", founder)));
+        }
+        tables.next_clan_id += 1;
+        let id = tables.next_clan_id;
+        let now = Utc::now();
+        tables.clans.insert(id, ClanRow { name: name.to_string(), tag: tag.to_string(), created_at: now });
+        tables.clan_members.push(ClanMemberRow { user_id: founder, clan: id, rank: ClanRank::Leader, joined_at: now });
+        tables.clan_invites.retain(|invite| invite.user_id != founder);
+        Ok(id)
+    }
+
+    async fn load_clan(&self, clan: ClanId) -> StorageResult<Option<Clan>> {
+        let tables = self.tables();
+        Ok(tables.clans.get(&clan).map(|row| Clan {
+            id: clan,
+            name: row.name.clone(),
+            tag: row.tag.clone(),
+            created_at: row.created_at,
+            members: tables.clan_members.iter()
+                .filter(|member| member.clan == clan)
+                .map(|member| ClanMember {
+                    user_id: member.user_id,
+                    login: tables.users.get(&member.user_id).map(|user| user.login.clone()).unwrap_or_default(),
+                    rank: member.rank,
+                    joined_at: member.joined_at,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn clan_of(&self, user_id: i32) -> StorageResult<Option<ClanId>> {
+        Ok(self.tables().clan_members.iter().find(|member| member.user_id == user_id).map(|member| member.clan))
+    }
+
+    async fn delete_clan(&self, clan: ClanId, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        if !tables.ranks_unchanged(clan, expected) {
+            return Ok(false);
+        }
+        tables.clans.remove(&clan);
+        tables.clan_members.retain(|member| member.clan != clan);
+        tables.clan_invites.retain(|invite| invite.clan != clan);
+        Ok(true)
+    }
+
+    async fn invite(&self, clan: ClanId, user_id: i32, invited_by: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        if !tables.ranks_unchanged(clan, expected) {
+            return Ok(false);
+        }
+        if tables.clan_members.iter().any(|member| member.user_id == user_id) {
+            return Err(StorageError::Conflict(format!("user {} is already in a clan", user_id)));
+        }
+        if !tables.clans.contains_key(&clan) || !tables.users.contains_key(&user_id) {
+            return Ok(true);
+        }
+        tables.clan_invites.retain(|invite| (invite.clan, invite.user_id) != (clan, user_id));
+        tables.clan_invites.push(ClanInviteRow { clan, user_id, invited_by: Some(invited_by), created_at: Utc::now() });
+        Ok(true)
+    }
+
+    async fn invites_for(&self, user_id: i32) -> StorageResult<Vec<ClanInvite>> {
+        let tables = self.tables();
+        Ok(tables.clan_invites.iter()
+            .filter(|invite| invite.user_id == user_id)
+            .filter_map(|invite| tables.clans.get(&invite.clan).map(|clan| ClanInvite {
+                clan: invite.clan,
+                name: clan.name.clone(),
+                tag: clan.tag.clone(),
+                invited_by: invite.invited_by,
+                created_at: invite.created_at,
+            }))
+            .collect())
+    }
+
+    async fn accept_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        if !tables.clan_invites.iter().any(|invite| (invite.clan, invite.user_id) == (clan, user_id)) {
+            return Ok(false);
+        }
+        if tables.clan_members.iter().any(|member| member.user_id == user_id) {
+            return Err(StorageError::Conflict(format!("user {} is already in a clan", user_id)));
+        }
+        tables.clan_invites.retain(|invite| invite.user_id != user_id);
+        tables.clan_members.push(ClanMemberRow { user_id, clan, rank: ClanRank::Recruit, joined_at: Utc::now() });
+        Ok(true)
+    }
+
+    async fn decline_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        let before = tables.clan_invites.len();
+        tables.clan_invites.retain(|invite| (invite.clan, invite.user_id) != (clan, user_id));
+        Ok(tables.clan_invites.len() < before)
+    }
+
+    async fn remove_member(&self, clan: ClanId, user_id: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        if !tables.ranks_unchanged(clan, expected) {
+            return Ok(false);
+        }
+        tables.clan_members.retain(|member| (member.clan, member.user_id) != (clan, user_id));
+        Ok(true)
+    }
+
+    async fn set_ranks(&self, clan: ClanId, ranks: &[(i32, ClanRank)], expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        if !tables.ranks_unchanged(clan, expected) {
+            return Ok(false);
+        }
+        for member in tables.clan_members.iter_mut().filter(|member| member.clan == clan) {
+            if let Some((_, rank)) = ranks.iter().find(|(user_id, _)| *user_id == member.user_id) {
+                member.rank = *rank;
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl FriendRepository for MemoryStorage {
+    async fn create_request(&self, from: i32, to: i32) -> StorageResult<()> {
+        let mut tables = self.tables();
+        if tables.friends.iter().any(|row| row.links(from, to)) {
+            return Err(StorageError::Conflict(format!("users {} and {} are already linked", from, to)));
+        }
+        if tables.users.contains_key(&from) && tables.users.contains_key(&to) {
+            tables.friends.push(FriendRow { from, to, created_at: Utc::now(), accepted_at: None });
+        }
+        Ok(())
+    }
+
+    async fn accept_request(&self, from: i32, to: i32) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        let request = tables.friends.iter_mut().find(|row| (row.from, row.to) == (from, to) && row.accepted_at.is_none());
+        Ok(request.map(|row| row.accepted_at = Some(Utc::now())).is_some())
+    }
+
+    async fn delete_request(&self, from: i32, to: i32) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        let before = tables.friends.len();
+        tables.friends.retain(|row| (row.from, row.to) != (from, to) || row.accepted_at.is_some());
+        Ok(tables.friends.len() < before)
+    }
+
+    async fn remove_friend(&self, user_id: i32, friend_id: i32) -> StorageResult<bool> {
+        let mut tables = self.tables();
+        let before = tables.friends.len();
+        tables.friends.retain(|row| !row.links(user_id, friend_id) || row.accepted_at.is_none());
+        Ok(tables.friends.len() < before)
+    }
+
+    async fn friends(&self, user_id: i32) -> StorageResult<Vec<Friend>> {
+        let tables = self.tables();
+        let mut friends: Vec<Friend> = tables.friends.iter()
+            .filter_map(|row| {
+                let since = row.accepted_at?;
+                let friend = match (row.from, row.to) {
+                    (from, to) if from == user_id => to,
+                    (from, to) if to == user_id => from,
+                    _ => return None,
+                };
+                Some(Friend {
+                    user_id: friend,
+                    login: tables.login(friend),
+                    online: tables.players.get(&friend).is_some_and(|row| row.is_online),
+                    since,
+                })
+            })
+            .collect();
+        friends.sort_by(|a, b| a.login.cmp(&b.login));
+        Ok(friends)
+    }
+
+    async fn incoming_requests(&self, user_id: i32) -> StorageResult<Vec<FriendRequest>> {
+        let tables = self.tables();
+        Ok(tables.friends.iter()
+            .filter(|row| row.to == user_id && row.accepted_at.is_none())
+            .map(|row| FriendRequest { user_id: row.from, login: tables.login(row.from), created_at: row.created_at })
+            .collect())
+    }
+
+    async fn outgoing_requests(&self, user_id: i32) -> StorageResult<Vec<FriendRequest>> {
+        let tables = self.tables();
+        Ok(tables.friends.iter()
+            .filter(|row| row.from == user_id && row.accepted_at.is_none())
+            .map(|row| FriendRequest { user_id: row.to, login: tables.login(row.to), created_at: row.created_at })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl HealthRepository for MemoryStorage {
+    async fn ping(&self) -> StorageResult<()> {
+        Ok(())
+    }
+}
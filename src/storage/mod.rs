@@ -0,0 +1,207 @@
+// src/storage/mod.rs
+// Слой хранилища: обработчики работают только с трейтами ниже и не знают,
+// где лежат данные — в PostgreSQL или в памяти (для тестов)
+pub mod memory;
+pub mod postgres;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::clans::{Clan, ClanId, ClanInvite, ClanRank};
+use crate::friends::{Friend, FriendRequest};
+use crate::items::ItemStack;
+use crate::models::{player::{Player, Vitals}, user::User};
+use crate::tiles::{ChunkDiff, ChunkPos};
+
+#[derive(Debug)]
+pub enum StorageError {
+    // Нарушено ограничение уникальности (например, логин уже занят)
+    Conflict(String),
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Conflict(what) => write!(f, "conflict: {}", what),
+            StorageError::Backend(e) => write!(f, "storage backend error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    // Создает пользователя и возвращает его id; Conflict, если логин занят
+    async fn create_user(&self, login: &str, hashed_password: &str) -> StorageResult<i32>;
+    async fn find_by_login(&self, login: &str) -> StorageResult<Option<User>>;
+    async fn find_by_id(&self, user_id: i32) -> StorageResult<Option<User>>;
+    // Помечает аккаунт на удаление; повторный вызов не сдвигает исходное время.
+    // None, если пользователь не найден
+    async fn request_deletion(&self, user_id: i32) -> StorageResult<Option<DateTime<Utc>>>;
+    // Снимает пометку на удаление, если она поставлена позже cutoff (срок отмены не истек);
+    // false, если пользователь не найден или срок истек
+    async fn cancel_deletion(&self, user_id: i32, cutoff: DateTime<Utc>) -> StorageResult<bool>;
+    // Удаляет аккаунты, запросившие удаление не позже cutoff, вместе со всеми связанными данными
+    async fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> StorageResult<u64>;
+}
+
+#[async_trait]
+pub trait PlayerRepository: Send + Sync {
+    async fn load_player(&self, user_id: i32) -> StorageResult<Option<Player>>;
+    async fn save_position(&self, player: &Player) -> StorageResult<()>;
+    // Переводит игрока в комнату room в позицию spawn
+    async fn save_room(&self, spawn: &Player) -> StorageResult<()>;
+    // None, если записи игрока нет или здоровье еще не сохранялось
+    async fn load_vitals(&self, user_id: i32) -> StorageResult<Option<Vitals>>;
+    // Обновляет здоровье существующей записи игрока; при гибели запоминается ее время,
+    // при возрождении оно сбрасывается
+    async fn save_vitals(&self, user_id: i32, vitals: &Vitals) -> StorageResult<()>;
+    // Когда погиб игрок, который еще не возродился
+    async fn died_at(&self, user_id: i32) -> StorageResult<Option<DateTime<Utc>>>;
+}
+
+// Сессии — факт присутствия игрока в игре (флаг is_online)
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    // Отмечает игрока онлайн; если записи игрока нет, она создается в позиции spawn
+    async fn open_session(&self, spawn: &Player) -> StorageResult<()>;
+    async fn close_session(&self, user_id: i32) -> StorageResult<()>;
+    // Сбрасывает все сессии (после перезапуска сервера никто не может быть онлайн)
+    async fn reset_sessions(&self) -> StorageResult<()>;
+    // Сколько игроков сейчас онлайн
+    async fn count_online(&self) -> StorageResult<usize>;
+}
+
+// Изменения игроков в чанках тайлового мира поверх сгенерированных; ключ — комната и координаты чанка
+#[async_trait]
+pub trait ChunkRepository: Send + Sync {
+    // None, если чанк ни разу не менялся
+    async fn load_chunk(&self, room: &str, pos: ChunkPos) -> StorageResult<Option<ChunkDiff>>;
+    async fn save_chunk(&self, room: &str, pos: ChunkPos, changes: &ChunkDiff) -> StorageResult<()>;
+}
+
+// Инвентари: непустые ячейки игрока с их номерами
+#[async_trait]
+pub trait InventoryRepository: Send + Sync {
+    async fn load_inventory(&self, user_id: i32) -> StorageResult<Vec<(u32, ItemStack)>>;
+    // Заменяет весь инвентарь игрока одной операцией
+    async fn save_inventory(&self, user_id: i32, stacks: &[(u32, ItemStack)]) -> StorageResult<()>;
+    // Заменяет инвентари нескольких игроков в одной транзакции: записываются все или ни один
+    async fn save_inventories(&self, inventories: &[(i32, Vec<(u32, ItemStack)>)]) -> StorageResult<()>;
+}
+
+// Составы групп (при game.persist_parties); участники перечислены лидером вперед
+#[async_trait]
+pub trait PartyRepository: Send + Sync {
+    async fn load_parties(&self) -> StorageResult<Vec<(u64, Vec<i32>)>>;
+    // Сохраненная группа игрока с ее составом
+    async fn party_of(&self, user_id: i32) -> StorageResult<Option<(u64, Vec<i32>)>>;
+    // Заменяет состав группы одной операцией; пустой состав удаляет группу
+    async fn save_party(&self, party: u64, members: &[i32]) -> StorageResult<()>;
+}
+
+// Кланы, их участники и приглашения. Параметр expected — ранги участников, по которым clans.rs
+// проверил права на изменение: запись выполняется в той же транзакции, что и сверка этих рангов,
+// и только если они не изменились (иначе false)
+#[async_trait]
+pub trait ClanRepository: Send + Sync {
+    // Создает клан с лидером founder; Conflict, если имя или тег заняты
+    async fn create_clan(&self, name: &str, tag: &str, founder: i32) -> StorageResult<ClanId>;
+    // Клан с участниками в порядке вступления
+    async fn load_clan(&self, clan: ClanId) -> StorageResult<Option<Clan>>;
+    async fn clan_of(&self, user_id: i32) -> StorageResult<Option<ClanId>>;
+    // Удаляет клан вместе с участниками и приглашениями
+    async fn delete_clan(&self, clan: ClanId, expected: &[(i32, ClanRank)]) -> StorageResult<bool>;
+    // Повторное приглашение обновляет существующее; Conflict, если игрок уже в клане
+    async fn invite(&self, clan: ClanId, user_id: i32, invited_by: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool>;
+    async fn invites_for(&self, user_id: i32) -> StorageResult<Vec<ClanInvite>>;
+    // Принимает приглашение в одной транзакции: игрок вступает рекрутом, его приглашения удаляются.
+    // false, если приглашения нет; Conflict, если игрок уже в клане
+    async fn accept_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool>;
+    // false, если приглашения нет
+    async fn decline_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool>;
+    async fn remove_member(&self, clan: ClanId, user_id: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool>;
+    // Меняет ранги нескольких участников одной операцией
+    async fn set_ranks(&self, clan: ClanId, ranks: &[(i32, ClanRank)], expected: &[(i32, ClanRank)]) -> StorageResult<bool>;
+}
+
+// Друзья: запрос от одного игрока другому, принятый запрос — дружба.
+// Между двумя игроками не больше одного запроса или дружбы
+#[async_trait]
+pub trait FriendRepository: Send + Sync {
+    // Conflict, если игроки уже друзья или между ними есть запрос
+    async fn create_request(&self, from: i32, to: i32) -> StorageResult<()>;
+    // false, если запроса from -> to нет
+    async fn accept_request(&self, from: i32, to: i32) -> StorageResult<bool>;
+    // Удаляет непринятый запрос from -> to; false, если его нет
+    async fn delete_request(&self, from: i32, to: i32) -> StorageResult<bool>;
+    // false, если игроки не друзья
+    async fn remove_friend(&self, user_id: i32, friend_id: i32) -> StorageResult<bool>;
+    // Друзья по логину; online — открыта ли сессия друга
+    async fn friends(&self, user_id: i32) -> StorageResult<Vec<Friend>>;
+    async fn incoming_requests(&self, user_id: i32) -> StorageResult<Vec<FriendRequest>>;
+    async fn outgoing_requests(&self, user_id: i32) -> StorageResult<Vec<FriendRequest>>;
+}
+
+// Проверка доступности хранилища для /ready
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    async fn ping(&self) -> StorageResult<()>;
+}
+
+// Набор репозиториев, с которым работают обработчики
+#[derive(Clone)]
+pub struct Storage {
+    pub users: Arc<dyn UserRepository>,
+    pub players: Arc<dyn PlayerRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub chunks: Arc<dyn ChunkRepository>,
+    pub inventories: Arc<dyn InventoryRepository>,
+    pub parties: Arc<dyn PartyRepository>,
+    pub clans: Arc<dyn ClanRepository>,
+    pub friends: Arc<dyn FriendRepository>,
+    pub health: Arc<dyn HealthRepository>,
+}
+
+impl Storage {
+    pub fn postgres(pool: PgPool) -> Self {
+        Self::from_backend(Arc::new(postgres::PgStorage::new(pool)))
+    }
+
+    pub fn in_memory() -> Self {
+        Self::from_backend(Arc::new(memory::MemoryStorage::default()))
+    }
+
+    fn from_backend<B>(backend: Arc<B>) -> Self
+    where
+        B: UserRepository
+            + PlayerRepository
+            + SessionRepository
+            + ChunkRepository
+            + InventoryRepository
+            + PartyRepository
+            + ClanRepository
+            + FriendRepository
+            + HealthRepository
+            + 'static,
+    {
+        Storage {
+            users: backend.clone(),
+            players: backend.clone(),
+            sessions: backend.clone(),
+            chunks: backend.clone(),
+            inventories: backend.clone(),
+            parties: backend.clone(),
+            clans: backend.clone(),
+            friends: backend.clone(),
+            health: backend,
+        }
+    }
+}
@@ -0,0 +1,644 @@
+// src/storage/postgres.rs
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::clans::{Clan, ClanId, ClanInvite, ClanMember, ClanRank};
+use crate::friends::{Friend, FriendRequest};
+use crate::items::ItemStack;
+use crate::models::{player::{Player, Vitals}, user::User};
+use crate::storage::{
+    ChunkRepository, ClanRepository, FriendRepository, HealthRepository, InventoryRepository, PartyRepository, PlayerRepository,
+    SessionRepository, StorageError, StorageResult, UserRepository,
+};
+use crate::tiles::{ChunkDiff, ChunkPos};
+
+impl From<sqlx::Error> for StorageError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                StorageError::Conflict(db_err.message().to_string())
+            },
+            _ => StorageError::Backend(Box::new(e)),
+        }
+    }
+}
+
+pub struct PgStorage {
+    pool: PgPool,
+}
+
+impl PgStorage {
+    pub fn new(pool: PgPool) -> Self {
+        PgStorage { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgStorage {
+    async fn create_user(&self, login: &str, hashed_password: &str) -> StorageResult<i32> {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO users (login, hashed_password) VALUES ($1, $2) RETURNING id",
+            login,
+            hashed_password
+        )
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    async fn find_by_login(&self, login: &str) -> StorageResult<Option<User>> {
+        let user = sqlx::query_as!(
+            User,
+            "SELECT id, login, hashed_password, created_at, deletion_requested_at FROM users WHERE login = $1",
+            login
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user)
+    }
+
+    async fn find_by_id(&self, user_id: i32) -> StorageResult<Option<User>> {
+        let user = sqlx::query_as!(
+            User,
+            "SELECT id, login, hashed_password, created_at, deletion_requested_at FROM users WHERE id = $1",
+            user_id
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user)
+    }
+
+    async fn request_deletion(&self, user_id: i32) -> StorageResult<Option<DateTime<Utc>>> {
+        // COALESCE: повторный запрос не сдвигает уже назначенную дату удаления
+        let requested_at = sqlx::query_scalar!(
+            "UPDATE users SET deletion_requested_at = COALESCE(deletion_requested_at, NOW())
+             WHERE id = $1 RETURNING deletion_requested_at",
+            user_id
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(requested_at.flatten())
+    }
+
+    async fn cancel_deletion(&self, user_id: i32, cutoff: DateTime<Utc>) -> StorageResult<bool> {
+        let result = sqlx::query!(
+            "UPDATE users SET deletion_requested_at = NULL
+             WHERE id = $1 AND (deletion_requested_at IS NULL OR deletion_requested_at > $2)",
+            user_id,
+            cutoff
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> StorageResult<u64> {
+        let mut tx = self.pool.begin().await?;
+        // Связанные строки удаляются каскадно (ON DELETE CASCADE)
+        let result = sqlx::query!(
+            "DELETE FROM users WHERE deletion_requested_at IS NOT NULL AND deletion_requested_at <= $1",
+            cutoff
+        )
+            .execute(&mut *tx)
+            .await?;
+        // Опустевшие кланы удаляются, в оставшихся без лидера им становится старший по рангу и стажу
+        sqlx::query!("DELETE FROM clans c WHERE NOT EXISTS (SELECT 1 FROM clan_members m WHERE m.clan_id = c.id)")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "UPDATE clan_members SET rank = 'leader' WHERE user_id IN (
+                 SELECT DISTINCT ON (m.clan_id) m.user_id FROM clan_members m
+                 WHERE NOT EXISTS (SELECT 1 FROM clan_members l WHERE l.clan_id = m.clan_id AND l.rank = 'leader')
+                 ORDER BY m.clan_id, CASE m.rank WHEN 'officer' THEN 0 WHEN 'member' THEN 1 ELSE 2 END, m.joined_at, m.user_id
+             )"
+        )
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl PlayerRepository for PgStorage {
+    async fn load_player(&self, user_id: i32) -> StorageResult<Option<Player>> {
+        let player = sqlx::query_as!(
+            Player,
+            "SELECT user_id, x, y, z, room FROM players WHERE user_id = $1",
+            user_id
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(player)
+    }
+
+    async fn save_position(&self, player: &Player) -> StorageResult<()> {
+        sqlx::query!(
+            "INSERT INTO players (user_id, x, y, z, room) VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (user_id) DO UPDATE SET x = $2, y = $3, z = $4",
+            player.user_id,
+            player.x,
+            player.y,
+            player.z,
+            player.room
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn save_room(&self, spawn: &Player) -> StorageResult<()> {
+        sqlx::query!(
+            "INSERT INTO players (user_id, x, y, z, room) VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (user_id) DO UPDATE SET x = $2, y = $3, z = $4, room = $5",
+            spawn.user_id,
+            spawn.x,
+            spawn.y,
+            spawn.z,
+            spawn.room
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn load_vitals(&self, user_id: i32) -> StorageResult<Option<Vitals>> {
+        let row = sqlx::query!("SELECT health, max_health FROM players WHERE user_id = $1", user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.and_then(|row| match (row.health, row.max_health) {
+            (Some(health), Some(max_health)) => Some(Vitals { health, max_health }),
+            _ => None,
+        }))
+    }
+
+    async fn save_vitals(&self, user_id: i32, vitals: &Vitals) -> StorageResult<()> {
+        sqlx::query!(
+            "UPDATE players SET health = $2, max_health = $3,
+                 died_at = CASE WHEN $2 <= 0 THEN COALESCE(died_at, NOW()) END
+             WHERE user_id = $1",
+            user_id,
+            vitals.health,
+            vitals.max_health
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn died_at(&self, user_id: i32) -> StorageResult<Option<DateTime<Utc>>> {
+        let died_at = sqlx::query_scalar!("SELECT died_at FROM players WHERE user_id = $1", user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(died_at.flatten())
+    }
+}
+
+#[async_trait]
+impl SessionRepository for PgStorage {
+    async fn open_session(&self, spawn: &Player) -> StorageResult<()> {
+        sqlx::query!(
+            "INSERT INTO players (user_id, x, y, z, room, is_online) VALUES ($1, $2, $3, $4, $5, TRUE)
+             ON CONFLICT (user_id) DO UPDATE SET is_online = TRUE",
+            spawn.user_id,
+            spawn.x,
+            spawn.y,
+            spawn.z,
+            spawn.room
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn close_session(&self, user_id: i32) -> StorageResult<()> {
+        sqlx::query!("UPDATE players SET is_online = FALSE WHERE user_id = $1", user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset_sessions(&self) -> StorageResult<()> {
+        sqlx::query!("UPDATE players SET is_online = FALSE WHERE is_online")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn count_online(&self) -> StorageResult<usize> {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM players WHERE is_online"#)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+}
+
+#[async_trait]
+impl ChunkRepository for PgStorage {
+    async fn load_chunk(&self, room: &str, pos: ChunkPos) -> StorageResult<Option<ChunkDiff>> {
+        let row = sqlx::query!(
+            "SELECT changes FROM chunks WHERE room = $1 AND cx = $2 AND cy = $3",
+            room,
+            pos.cx,
+            pos.cy
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| {
+            ChunkDiff::from_bytes(&row.changes).ok_or_else(|| {
+                StorageError::Backend(format!("chunk {}:{},{} has corrupted changes", room, pos.cx, pos.cy).into())
+            })
+        })
+            .transpose()
+    }
+
+    async fn save_chunk(&self, room: &str, pos: ChunkPos, changes: &ChunkDiff) -> StorageResult<()> {
+        sqlx::query!(
+            "INSERT INTO chunks (room, cx, cy, changes) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (room, cx, cy) DO UPDATE SET changes = $4, updated_at = NOW()",
+            room,
+            pos.cx,
+            pos.cy,
+            changes.to_bytes()
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InventoryRepository for PgStorage {
+    async fn load_inventory(&self, user_id: i32) -> StorageResult<Vec<(u32, ItemStack)>> {
+        let rows = sqlx::query!(
+            "SELECT slot, item, count FROM inventory_slots WHERE user_id = $1 ORDER BY slot",
+            user_id
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.slot as u32, ItemStack { item: row.item, count: row.count as u32 }))
+            .collect())
+    }
+
+    async fn save_inventory(&self, user_id: i32, stacks: &[(u32, ItemStack)]) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+        replace_inventory(&mut tx, user_id, stacks).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn save_inventories(&self, inventories: &[(i32, Vec<(u32, ItemStack)>)]) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for (user_id, stacks) in inventories {
+            replace_inventory(&mut tx, *user_id, stacks).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// Заменяет ячейки инвентаря в рамках транзакции вызывающего
+async fn replace_inventory(conn: &mut PgConnection, user_id: i32, stacks: &[(u32, ItemStack)]) -> StorageResult<()> {
+    sqlx::query!("DELETE FROM inventory_slots WHERE user_id = $1", user_id)
+        .execute(&mut *conn)
+        .await?;
+    for (slot, stack) in stacks {
+        sqlx::query!(
+            "INSERT INTO inventory_slots (user_id, slot, item, count) VALUES ($1, $2, $3, $4)",
+            user_id,
+            *slot as i32,
+            stack.item,
+            stack.count as i32
+        )
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl PartyRepository for PgStorage {
+    async fn load_parties(&self) -> StorageResult<Vec<(u64, Vec<i32>)>> {
+        let rows = sqlx::query!("SELECT party_id, user_id FROM party_members ORDER BY party_id, position")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut parties: Vec<(u64, Vec<i32>)> = Vec::new();
+        for row in rows {
+            let party = row.party_id as u64;
+            match parties.last_mut() {
+                Some((id, members)) if *id == party => members.push(row.user_id),
+                _ => parties.push((party, vec![row.user_id])),
+            }
+        }
+        Ok(parties)
+    }
+
+    async fn party_of(&self, user_id: i32) -> StorageResult<Option<(u64, Vec<i32>)>> {
+        let rows = sqlx::query!(
+            "SELECT party_id, user_id FROM party_members
+             WHERE party_id = (SELECT party_id FROM party_members WHERE user_id = $1)
+             ORDER BY position",
+            user_id
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        let Some(party) = rows.first().map(|row| row.party_id as u64) else {
+            return Ok(None);
+        };
+        Ok(Some((party, rows.into_iter().map(|row| row.user_id).collect())))
+    }
+
+    async fn save_party(&self, party: u64, members: &[i32]) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM party_members WHERE party_id = $1 OR user_id = ANY($2)", party as i64, members)
+            .execute(&mut *tx)
+            .await?;
+        for (position, user_id) in members.iter().enumerate() {
+            sqlx::query!(
+                "INSERT INTO party_members (user_id, party_id, position) VALUES ($1, $2, $3)",
+                user_id,
+                party as i64,
+                position as i32
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// Блокирует до конца транзакции вызывающего строки участников из expected (в порядке user_id,
+// чтобы параллельные изменения не блокировали друг друга взаимно) и сверяет их ранги
+async fn ranks_unchanged(conn: &mut PgConnection, clan: ClanId, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+    let user_ids: Vec<i32> = expected.iter().map(|(user_id, _)| *user_id).collect();
+    let rows = sqlx::query!(
+        "SELECT user_id, rank FROM clan_members WHERE clan_id = $1 AND user_id = ANY($2) ORDER BY user_id FOR UPDATE",
+        clan,
+        &user_ids
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(expected.iter().all(|(user_id, rank)| rows.iter().any(|row| row.user_id == *user_id && row.rank == rank.as_str())))
+}
+
+fn clan_rank(rank: &str) -> StorageResult<ClanRank> {
+    rank.parse().map_err(|_| StorageError::Backend(format!("unknown clan rank {}", rank).into()))
+}
+
+#[async_trait]
+impl ClanRepository for PgStorage {
+    async fn create_clan(&self, name: &str, tag: &str, founder: i32) -> StorageResult<ClanId> {
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query_scalar!("INSERT INTO clans (name, tag) VALUES ($1, $2) RETURNING id", name, tag)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query!("INSERT INTO clan_members (user_id, clan_id, rank) VALUES ($1, $2, 'leader')", founder, id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM clan_invites WHERE user_id = $1", founder)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn load_clan(&self, clan: ClanId) -> StorageResult<Option<Clan>> {
+        let Some(row) = sqlx::query!("SELECT id, name, tag, created_at FROM clans WHERE id = $1", clan)
+            .fetch_optional(&self.pool)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let members = sqlx::query!(
+            "SELECT m.user_id, u.login, m.rank, m.joined_at FROM clan_members m
+             JOIN users u ON u.id = m.user_id
+             WHERE m.clan_id = $1 ORDER BY m.joined_at, m.user_id",
+            clan
+        )
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|member| Ok(ClanMember {
+                user_id: member.user_id,
+                login: member.login,
+                rank: clan_rank(&member.rank)?,
+                joined_at: member.joined_at,
+            }))
+            .collect::<StorageResult<_>>()?;
+        Ok(Some(Clan { id: row.id, name: row.name, tag: row.tag, created_at: row.created_at, members }))
+    }
+
+    async fn clan_of(&self, user_id: i32) -> StorageResult<Option<ClanId>> {
+        let clan = sqlx::query_scalar!("SELECT clan_id FROM clan_members WHERE user_id = $1", user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(clan)
+    }
+
+    async fn delete_clan(&self, clan: ClanId, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !ranks_unchanged(&mut tx, clan, expected).await? {
+            return Ok(false);
+        }
+        // Участники и приглашения удаляются каскадно
+        sqlx::query!("DELETE FROM clans WHERE id = $1", clan)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn invite(&self, clan: ClanId, user_id: i32, invited_by: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !ranks_unchanged(&mut tx, clan, expected).await? {
+            return Ok(false);
+        }
+        let member = sqlx::query_scalar!("SELECT clan_id FROM clan_members WHERE user_id = $1", user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if member.is_some() {
+            return Err(StorageError::Conflict(format!("user {} is already in a clan", user_id)));
+        }
+        sqlx::query!(
+            "INSERT INTO clan_invites (clan_id, user_id, invited_by) VALUES ($1, $2, $3)
+             ON CONFLICT (clan_id, user_id) DO UPDATE SET invited_by = $3, created_at = NOW()",
+            clan,
+            user_id,
+            invited_by
+        )
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn invites_for(&self, user_id: i32) -> StorageResult<Vec<ClanInvite>> {
+        let invites = sqlx::query_as!(
+            ClanInvite,
+            "SELECT i.clan_id AS clan, c.name, c.tag, i.invited_by, i.created_at FROM clan_invites i
+             JOIN clans c ON c.id = i.clan_id
+             WHERE i.user_id = $1 ORDER BY i.created_at, i.clan_id",
+            user_id
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(invites)
+    }
+
+    async fn accept_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let invited = sqlx::query!("DELETE FROM clan_invites WHERE clan_id = $1 AND user_id = $2", clan, user_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() > 0;
+        if !invited {
+            return Ok(false);
+        }
+        // Уже состоящий в клане игрок нарушает первичный ключ clan_members -> Conflict
+        sqlx::query!("INSERT INTO clan_members (user_id, clan_id, rank) VALUES ($1, $2, 'recruit')", user_id, clan)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM clan_invites WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn decline_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool> {
+        let result = sqlx::query!("DELETE FROM clan_invites WHERE clan_id = $1 AND user_id = $2", clan, user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_member(&self, clan: ClanId, user_id: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !ranks_unchanged(&mut tx, clan, expected).await? {
+            return Ok(false);
+        }
+        sqlx::query!("DELETE FROM clan_members WHERE clan_id = $1 AND user_id = $2", clan, user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn set_ranks(&self, clan: ClanId, ranks: &[(i32, ClanRank)], expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !ranks_unchanged(&mut tx, clan, expected).await? {
+            return Ok(false);
+        }
+        for (user_id, rank) in ranks {
+            sqlx::query!(
+                "UPDATE clan_members SET rank = $3 WHERE clan_id = $1 AND user_id = $2",
+                clan,
+                user_id,
+                rank.as_str()
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl FriendRepository for PgStorage {
+    async fn create_request(&self, from: i32, to: i32) -> StorageResult<()> {
+        // Запрос в обратном направлении нарушает уникальный индекс пары -> Conflict
+        sqlx::query!("INSERT INTO friends (user_id, friend_id) VALUES ($1, $2)", from, to)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn accept_request(&self, from: i32, to: i32) -> StorageResult<bool> {
+        let result = sqlx::query!(
+            "UPDATE friends SET accepted_at = NOW() WHERE user_id = $1 AND friend_id = $2 AND accepted_at IS NULL",
+            from,
+            to
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_request(&self, from: i32, to: i32) -> StorageResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM friends WHERE user_id = $1 AND friend_id = $2 AND accepted_at IS NULL",
+            from,
+            to
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_friend(&self, user_id: i32, friend_id: i32) -> StorageResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM friends
+             WHERE ((user_id = $1 AND friend_id = $2) OR (user_id = $2 AND friend_id = $1)) AND accepted_at IS NOT NULL",
+            user_id,
+            friend_id
+        )
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn friends(&self, user_id: i32) -> StorageResult<Vec<Friend>> {
+        let friends = sqlx::query_as!(
+            Friend,
+            r#"SELECT u.id AS user_id, u.login, COALESCE(p.is_online, FALSE) AS "online!", f.accepted_at AS "since!"
+             FROM friends f
+             JOIN users u ON u.id = CASE WHEN f.user_id = $1 THEN f.friend_id ELSE f.user_id END
+             LEFT JOIN players p ON p.user_id = u.id
+             WHERE (f.user_id = $1 OR f.friend_id = $1) AND f.accepted_at IS NOT NULL
+             ORDER BY u.login"#,
+            user_id
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(friends)
+    }
+
+    async fn incoming_requests(&self, user_id: i32) -> StorageResult<Vec<FriendRequest>> {
+        let requests = sqlx::query_as!(
+            FriendRequest,
+            "SELECT u.id AS user_id, u.login, f.created_at FROM friends f
+             JOIN users u ON u.id = f.user_id
+             WHERE f.friend_id = $1 AND f.accepted_at IS NULL ORDER BY f.created_at, u.id",
+            user_id
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(requests)
+    }
+
+    async fn outgoing_requests(&self, user_id: i32) -> StorageResult<Vec<FriendRequest>> {
+        let requests = sqlx::query_as!(
+            FriendRequest,
+            "SELECT u.id AS user_id, u.login, f.created_at FROM friends f
+             JOIN users u ON u.id = f.friend_id
+             WHERE f.user_id = $1 AND f.accepted_at IS NULL ORDER BY f.created_at, u.id",
+            user_id
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(requests)
+    }
+}
+
+#[async_trait]
+impl HealthRepository for PgStorage {
+    async fn ping(&self) -> StorageResult<()> {
+        self.pool.acquire().await?.ping().await?;
+        Ok(())
+    }
+}
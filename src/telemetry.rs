@@ -0,0 +1,96 @@
+// src/telemetry.rs
+// Метрики Prometheus. Имена метрик собраны здесь, в коде вызываются функции-обертки
+use axum::http::header;
+use axum::response::IntoResponse;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const CONNECTED_SOCKETS: &str = "anarchy_connected_sockets";
+const MESSAGES_IN: &str = "anarchy_messages_in_total";
+const MESSAGES_OUT: &str = "anarchy_messages_out_total";
+const INPUTS_DISCARDED: &str = "anarchy_inputs_discarded_total";
+const MOVES_CORRECTED: &str = "anarchy_moves_corrected_total";
+const BROADCAST_LAG_EVENTS: &str = "anarchy_broadcast_lag_events_total";
+const BROADCAST_LAGGED_MESSAGES: &str = "anarchy_broadcast_lagged_messages_total";
+const DB_QUERY_DURATION: &str = "anarchy_db_query_duration_seconds";
+const LOGINS: &str = "anarchy_logins_total";
+const REGISTRATIONS: &str = "anarchy_registrations_total";
+const TICK_DURATION: &str = "anarchy_tick_duration_seconds";
+
+// Границы корзин гистограмм: от долей миллисекунды до секунды
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+];
+
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+// Глобальный рекордер устанавливается один раз на процесс
+// (несколько тестовых серверов в одном процессе делят его)
+pub fn prometheus_handle() -> &'static PrometheusHandle {
+    PROMETHEUS.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)
+            .expect("Invalid histogram buckets")
+            .install_recorder()
+            .expect("Failed to install Prometheus recorder")
+    })
+}
+
+// GET /metrics в текстовом формате Prometheus
+pub async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        prometheus_handle().render(),
+    )
+}
+
+pub fn socket_connected() {
+    gauge!(CONNECTED_SOCKETS).increment(1.0);
+}
+
+pub fn socket_disconnected() {
+    gauge!(CONNECTED_SOCKETS).decrement(1.0);
+}
+
+// kind — имя варианта GameMessage или "invalid" для неразобранного ввода
+pub fn message_in(kind: &'static str) {
+    counter!(MESSAGES_IN, "type" => kind).increment(1);
+}
+
+pub fn message_out(kind: &'static str) {
+    counter!(MESSAGES_OUT, "type" => kind).increment(1);
+}
+
+// Ввод клиента пришел повторно или не по порядку и был отброшен
+pub fn input_discarded() {
+    counter!(INPUTS_DISCARDED).increment(1);
+}
+
+// Перемещение игрока уперлось в стену или было слишком длинным и было исправлено сервером
+pub fn move_corrected() {
+    counter!(MOVES_CORRECTED).increment(1);
+}
+
+// Получатель не успевал читать канал широковещания и пропустил skipped сообщений
+pub fn broadcast_lagged(skipped: u64) {
+    counter!(BROADCAST_LAG_EVENTS).increment(1);
+    counter!(BROADCAST_LAGGED_MESSAGES).increment(skipped);
+}
+
+pub fn db_query(query: &'static str, elapsed: Duration) {
+    histogram!(DB_QUERY_DURATION, "query" => query).record(elapsed.as_secs_f64());
+}
+
+pub fn login_attempt(success: bool) {
+    counter!(LOGINS, "result" => if success { "success" } else { "failure" }).increment(1);
+}
+
+pub fn registration_attempt(success: bool) {
+    counter!(REGISTRATIONS, "result" => if success { "success" } else { "failure" }).increment(1);
+}
+
+pub fn tick_completed(elapsed: Duration) {
+    histogram!(TICK_DURATION).record(elapsed.as_secs_f64());
+}
@@ -0,0 +1,727 @@
+// src/testing.rs
+// Тестовый стенд: поднимает настоящее приложение (create_app) на случайном порту
+// с хранилищем в памяти и дает клиентов, которые ходят по HTTP и WebSocket так же,
+// как игровой клиент. Доступен при включенной фиче "testing".
+//
+// Пример сценария:
+//     let server = TestServer::start().await;
+//     let alice = server.register_user("alice").await;
+//     let mut ws = server.connect(&alice).await;
+//     let players = ws.expect_initial_players().await;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, protocol::CloseFrame, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::clans::{Clan, ClanId, ClanLeaveReason};
+use crate::config::{Config, GeneratorKind};
+use crate::entities::{Entity, EntityId};
+use crate::items::Inventory;
+use crate::map::MapMetadata;
+use crate::models::player::Vitals;
+use crate::party::{Party, PartyLeaveReason};
+use crate::routes::auth::{LoginResponse, RegisterRequest};
+use crate::routes::create_app;
+use crate::routes::game::{ErrorCode, GameMessage, PlayerPositionUpdate, PROTOCOL_VERSION};
+use crate::snapshot::SnapshotDelta;
+use crate::state::AppState;
+use crate::storage::Storage;
+use crate::tiles::{Chunk, ChunkPos, Tile};
+use crate::trading::Trade;
+use crate::world;
+
+// Сколько ждать очередного сообщения, прежде чем считать тест проваленным
+pub const DEFAULT_RECV_TIMEOUT: Duration = Duration::from_secs(2);
+pub const TEST_PASSWORD: &str = "test-password";
+const TEST_JWT_SECRET: &str = "test-jwt-secret";
+
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<AppState>,
+    http: reqwest::Client,
+    server_task: JoinHandle<()>,
+    world_task: JoinHandle<()>,
+}
+
+// Зарегистрированный и вошедший пользователь
+#[derive(Debug, Clone)]
+pub struct TestUser {
+    pub user_id: i32,
+    pub login: String,
+    pub password: String,
+    pub token: String,
+}
+
+impl TestServer {
+    // Конфигурация по умолчанию для тестов (база данных не используется)
+    pub fn test_config() -> Config {
+        let mut config = Config::default();
+        config.auth.jwt_secret = TEST_JWT_SECRET.to_string();
+        // Пустой мир: тесты не зависят от сгенерированного рельефа
+        config.game.generator = GeneratorKind::Flat;
+        config
+    }
+
+    // Конфигурация для тестов с несколькими постоянными мирами
+    pub fn config_with_worlds(worlds: &[&str]) -> Config {
+        let mut config = Self::test_config();
+        config.game.worlds = worlds.iter().map(|w| w.to_string()).collect();
+        config
+    }
+
+    // Сервер с пустым хранилищем в памяти
+    pub async fn start() -> Self {
+        Self::start_with_config(Self::test_config()).await
+    }
+
+    // Сервер с хранилищем в памяти и заданной конфигурацией
+    pub async fn start_with_config(config: Config) -> Self {
+        Self::start_with_state(Arc::new(AppState::new(Storage::in_memory(), config))).await
+    }
+
+    // Сервер с заранее подготовленным состоянием (например, с другим хранилищем)
+    pub async fn start_with_state(state: Arc<AppState>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind test listener");
+        let addr = listener.local_addr().expect("Failed to read test listener address");
+        let app = create_app(state.clone());
+        let server_task = tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service()).await.expect("Test server failed");
+        });
+        let world_task = world::spawn_world(state.clone());
+
+        TestServer {
+            addr,
+            state,
+            http: reqwest::Client::new(),
+            server_task,
+            world_task,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    // Полный URL для пути API, например url("/login")
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}/api{}", self.addr, path)
+    }
+
+    // Запрос к API от имени пользователя (с его токеном)
+    pub fn authorized(&self, user: &TestUser, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.http.request(method, self.url(path)).bearer_auth(&user.token)
+    }
+
+    // Запрос к API от имени пользователя с JSON-телом, если оно есть
+    pub async fn call(&self, user: &TestUser, method: reqwest::Method, path: &str, body: Option<serde_json::Value>) -> reqwest::Response {
+        let request = self.authorized(user, method, path);
+        let request = match body {
+            Some(body) => request.json(&body),
+            None => request,
+        };
+        request.send().await.expect("API request failed")
+    }
+
+    pub async fn register(&self, login: &str, password: &str) -> reqwest::Response {
+        self.http
+            .post(self.url("/register"))
+            .json(&RegisterRequest { login: login.to_string(), password: password.to_string() })
+            .send()
+            .await
+            .expect("Register request failed")
+    }
+
+    pub async fn login(&self, login: &str, password: &str) -> reqwest::Response {
+        self.http
+            .post(self.url("/login"))
+            .json(&RegisterRequest { login: login.to_string(), password: password.to_string() })
+            .send()
+            .await
+            .expect("Login request failed")
+    }
+
+    // Регистрирует пользователя с паролем TEST_PASSWORD и входит под ним
+    pub async fn register_user(&self, login: &str) -> TestUser {
+        let response = self.register(login, TEST_PASSWORD).await;
+        assert!(response.status().is_success(), "Registration of {} failed: {}", login, response.status());
+
+        let response = self.login(login, TEST_PASSWORD).await;
+        assert!(response.status().is_success(), "Login of {} failed: {}", login, response.status());
+        let token = response.json::<LoginResponse>().await.expect("Malformed login response").token;
+
+        let user = self.state.storage.users.find_by_login(login)
+            .await
+            .expect("Storage error while looking up test user")
+            .expect("Registered test user not found in storage");
+
+        TestUser {
+            user_id: user.id,
+            login: login.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            token,
+        }
+    }
+
+    // Открывает /api/ws от имени пользователя и проходит рукопожатие Hello/Welcome
+    // без необязательных возможностей
+    pub async fn connect(&self, user: &TestUser) -> TestClient {
+        self.connect_with_features(user, &[]).await
+    }
+
+    // Подключение с запросом возможностей протокола; все они должны быть согласованы
+    pub async fn connect_with_features(&self, user: &TestUser, features: &[&str]) -> TestClient {
+        let mut client = self.connect_without_handshake(user).await;
+        client.hello(PROTOCOL_VERSION, features).await;
+        let negotiated = client.expect_welcome().await;
+        assert_eq!(negotiated, features, "Server did not accept all requested features");
+        client
+    }
+
+    // Подключение без Hello: для проверки самого рукопожатия
+    pub async fn connect_without_handshake(&self, user: &TestUser) -> TestClient {
+        self.try_connect(&user.token)
+            .await
+            .unwrap_or_else(|e| panic!("WebSocket connect for {} failed: {}", user.login, e))
+            .with_user_id(user.user_id)
+    }
+
+    // Попытка подключения с произвольным токеном: ошибка HTTP-рукопожатия возвращается как есть
+    pub async fn try_connect(&self, token: &str) -> Result<TestClient, tokio_tungstenite::tungstenite::Error> {
+        let mut request = format!("ws://{}/api/ws", self.addr).into_client_request()?;
+        request.headers_mut().insert(
+            axum::http::header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().expect("Invalid token header"),
+        );
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(TestClient { user_id: 0, last_seq: 0, room: String::new(), vitals: None, stream })
+    }
+
+    // Регистрация, вход и подключение одним вызовом; InitialPlayers уже прочитан
+    pub async fn join(&self, login: &str) -> (TestUser, TestClient) {
+        let user = self.register_user(login).await;
+        let mut client = self.connect(&user).await;
+        client.expect_initial_players().await;
+        (user, client)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_task.abort();
+        self.world_task.abort();
+    }
+}
+
+pub struct TestClient {
+    user_id: i32,
+    // Номер последнего отправленного ввода
+    last_seq: u64,
+    // Комната из Welcome или последнего RoomChanged
+    room: String,
+    // Здоровье из Welcome
+    vitals: Option<Vitals>,
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl TestClient {
+    fn with_user_id(mut self, user_id: i32) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    pub fn user_id(&self) -> i32 {
+        self.user_id
+    }
+
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    pub fn vitals(&self) -> Vitals {
+        self.vitals.expect("Welcome has not been received")
+    }
+
+    pub async fn send(&mut self, msg: &GameMessage) {
+        let text = serde_json::to_string(msg).expect("Failed to serialize GameMessage");
+        self.send_raw(text).await;
+    }
+
+    // Отправка произвольного текста (например, заведомо некорректного JSON)
+    pub async fn send_raw(&mut self, text: String) {
+        self.stream.send(Message::Text(text)).await.expect("Failed to send WebSocket message");
+    }
+
+    pub async fn hello(&mut self, protocol_version: u32, features: &[&str]) {
+        let features = features.iter().map(|f| f.to_string()).collect();
+        self.send(&GameMessage::Hello { protocol_version, features }).await;
+    }
+
+    // Отправляет ввод со следующим номером и возвращает этот номер
+    pub async fn send_position(&mut self, x: f64, y: f64, z: f64) -> u64 {
+        let seq = self.last_seq + 1;
+        self.send_position_with_seq(seq, x, y, z).await;
+        seq
+    }
+
+    // Ввод с произвольным номером (например, повтор или опоздавший)
+    pub async fn send_position_with_seq(&mut self, seq: u64, x: f64, y: f64, z: f64) {
+        self.last_seq = self.last_seq.max(seq);
+        let update = PlayerPositionUpdate { user_id: self.user_id, x, y, z, seq, ..Default::default() };
+        self.send(&GameMessage::PlayerPosition(update)).await;
+    }
+
+    pub async fn change_room(&mut self, room: &str) {
+        self.send(&GameMessage::ChangeRoom { room: room.to_string() }).await;
+    }
+
+    pub async fn place_tile(&mut self, x: i32, y: i32, tile: Tile) {
+        self.send(&GameMessage::PlaceTile { x, y, tile }).await;
+    }
+
+    pub async fn break_tile(&mut self, x: i32, y: i32) {
+        self.send(&GameMessage::BreakTile { x, y }).await;
+    }
+
+    pub async fn attack(&mut self, target: i32) {
+        self.send(&GameMessage::Attack { target }).await;
+    }
+
+    pub async fn move_item(&mut self, from: usize, to: usize) {
+        self.send(&GameMessage::MoveItem { from, to }).await;
+    }
+
+    pub async fn use_item(&mut self, slot: usize) {
+        self.send(&GameMessage::UseItem { slot }).await;
+    }
+
+    pub async fn drop_item(&mut self, slot: usize, count: Option<u32>) {
+        self.send(&GameMessage::DropItem { slot, count }).await;
+    }
+
+    pub async fn pick_up(&mut self, id: EntityId) {
+        self.send(&GameMessage::PickUpItem { id }).await;
+    }
+
+    pub async fn request_trade(&mut self, target: i32) {
+        self.send(&GameMessage::TradeRequest { target }).await;
+    }
+
+    pub async fn accept_trade(&mut self, from: i32) {
+        self.send(&GameMessage::TradeAccept { from }).await;
+    }
+
+    pub async fn offer(&mut self, slot: usize, count: u32) {
+        self.send(&GameMessage::TradeOffer { slot, count }).await;
+    }
+
+    pub async fn invite_to_party(&mut self, target: i32) {
+        self.send(&GameMessage::PartyInvite { target }).await;
+    }
+
+    pub async fn accept_party(&mut self, from: i32) {
+        self.send(&GameMessage::PartyAccept { from }).await;
+    }
+
+    pub async fn chat(&mut self, text: &str) {
+        self.send(&GameMessage::Chat { text: text.to_string() }).await;
+    }
+
+    pub async fn logout(&mut self) {
+        let user_id = self.user_id;
+        self.send(&GameMessage::PlayerLogout { user_id }).await;
+    }
+
+    // Следующее игровое сообщение или None, если за timeout ничего не пришло.
+    // Служебные кадры (ping/pong) пропускаются; закрытие соединения — паника
+    pub async fn try_recv(&mut self, timeout: Duration) -> Option<GameMessage> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let frame = match tokio::time::timeout_at(deadline, self.stream.next()).await {
+                Err(_) => return None,
+                Ok(frame) => frame,
+            };
+            match frame {
+                Some(Ok(Message::Text(text))) => {
+                    return Some(serde_json::from_str(&text).unwrap_or_else(|e| {
+                        panic!("Server sent unparseable GameMessage {:?}: {}", text, e)
+                    }));
+                },
+                Some(Ok(Message::Close(frame))) => panic!("Server closed the connection: {:?}", frame),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => panic!("WebSocket error: {}", e),
+                None => panic!("WebSocket stream ended"),
+            }
+        }
+    }
+
+    pub async fn recv(&mut self) -> GameMessage {
+        self.try_recv(DEFAULT_RECV_TIMEOUT)
+            .await
+            .unwrap_or_else(|| panic!("Client {} received nothing within {:?}", self.user_id, DEFAULT_RECV_TIMEOUT))
+    }
+
+    // Пропускает сообщения, пока не придет подходящее под predicate
+    pub async fn recv_until<F>(&mut self, mut predicate: F) -> GameMessage
+    where
+        F: FnMut(&GameMessage) -> bool,
+    {
+        let deadline = tokio::time::Instant::now() + DEFAULT_RECV_TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.try_recv(remaining).await {
+                Some(msg) if predicate(&msg) => return msg,
+                Some(_) => continue,
+                None => panic!("Client {} did not receive the expected message within {:?}", self.user_id, DEFAULT_RECV_TIMEOUT),
+            }
+        }
+    }
+
+    // Ждет Welcome и возвращает согласованные возможности
+    pub async fn expect_welcome(&mut self) -> Vec<String> {
+        match self.recv().await {
+            GameMessage::Welcome { protocol_version, user_id, features, room, vitals } => {
+                assert_eq!(protocol_version, PROTOCOL_VERSION);
+                assert_eq!(user_id, self.user_id, "Welcome carries a wrong user_id");
+                self.room = room;
+                self.vitals = Some(vitals);
+                features
+            },
+            other => panic!("Expected Welcome, got {:?}", other),
+        }
+    }
+
+    // Ждет подтверждение перехода в комнату и список ее игроков
+    pub async fn expect_room_changed(&mut self, room: &str) -> Vec<PlayerPositionUpdate> {
+        match self.recv_until(|msg| matches!(msg, GameMessage::RoomChanged { .. })).await {
+            GameMessage::RoomChanged { room: actual } => {
+                assert_eq!(actual, room, "Moved to a wrong room");
+                self.room = actual;
+            },
+            _ => unreachable!(),
+        }
+        self.expect_initial_players().await
+    }
+
+    // Описание карты комнаты; с возможностью "maps" приходит перед InitialPlayers
+    pub async fn expect_map_info(&mut self) -> MapMetadata {
+        match self.recv().await {
+            GameMessage::MapInfo(metadata) => metadata,
+            other => panic!("Expected MapInfo, got {:?}", other),
+        }
+    }
+
+    // Ждет Error с указанным кодом и возвращает его текст
+    pub async fn expect_error(&mut self, code: ErrorCode) -> String {
+        match self.recv_until(|msg| matches!(msg, GameMessage::Error { .. })).await {
+            GameMessage::Error { code: actual, message } => {
+                assert_eq!(actual, code, "Unexpected error code, message: {}", message);
+                message
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет следующий снимок мира (возможность "delta_snapshots")
+    pub async fn expect_snapshot(&mut self) -> SnapshotDelta {
+        match self.recv_until(|msg| matches!(msg, GameMessage::Snapshot(_))).await {
+            GameMessage::Snapshot(delta) => delta,
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn ack_snapshot(&mut self, tick: u64) {
+        self.send(&GameMessage::SnapshotAck { tick }).await;
+    }
+
+    // Ждет следующий чанк тайлового мира (возможность "tiles")
+    pub async fn expect_chunk(&mut self) -> (ChunkPos, Chunk) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ChunkData { .. })).await {
+            GameMessage::ChunkData { cx, cy, tiles } => {
+                let chunk = Chunk::from_tiles(tiles).expect("ChunkData has a wrong number of tiles");
+                (ChunkPos { cx, cy }, chunk)
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_chunks(&mut self, count: usize) -> HashMap<ChunkPos, Chunk> {
+        let mut chunks = HashMap::new();
+        while chunks.len() < count {
+            let (pos, chunk) = self.expect_chunk().await;
+            assert!(chunks.insert(pos, chunk).is_none(), "Chunk {:?} was sent twice", pos);
+        }
+        chunks
+    }
+
+    pub async fn expect_chunk_unload(&mut self) -> ChunkPos {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ChunkUnload { .. })).await {
+            GameMessage::ChunkUnload { cx, cy } => ChunkPos { cx, cy },
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет изменение тайла и возвращает (x, y, tile)
+    pub async fn expect_tile_changed(&mut self) -> (i32, i32, Tile) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::TileChanged { .. })).await {
+            GameMessage::TileChanged { x, y, tile } => (x, y, tile),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_initial_players(&mut self) -> Vec<PlayerPositionUpdate> {
+        match self.recv().await {
+            GameMessage::InitialPlayers(players) => players,
+            other => panic!("Expected InitialPlayers, got {:?}", other),
+        }
+    }
+
+    // Ждет позицию указанного игрока, пропуская остальные сообщения
+    pub async fn expect_position_of(&mut self, user_id: i32) -> PlayerPositionUpdate {
+        match self.recv_until(|msg| matches!(msg, GameMessage::PlayerPosition(p) if p.user_id == user_id)).await {
+            GameMessage::PlayerPosition(update) => update,
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет очередное состояние инвентаря
+    pub async fn expect_inventory(&mut self) -> Inventory {
+        match self.recv_until(|msg| matches!(msg, GameMessage::Inventory(_))).await {
+            GameMessage::Inventory(inventory) => inventory,
+            _ => unreachable!(),
+        }
+    }
+
+    // Сущности комнаты; с возможностью "entities" приходят сразу после InitialPlayers
+    pub async fn expect_initial_entities(&mut self) -> Vec<Entity> {
+        match self.recv().await {
+            GameMessage::InitialEntities(entities) => entities,
+            other => panic!("Expected InitialEntities, got {:?}", other),
+        }
+    }
+
+    pub async fn expect_entity_spawned(&mut self) -> Entity {
+        match self.recv_until(|msg| matches!(msg, GameMessage::EntitySpawned(_))).await {
+            GameMessage::EntitySpawned(entity) => entity,
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет очередное состояние указанной сущности, пропуская остальные сообщения
+    pub async fn expect_entity_updated(&mut self, id: EntityId) -> Entity {
+        match self.recv_until(|msg| matches!(msg, GameMessage::EntityUpdated(entity) if entity.id == id)).await {
+            GameMessage::EntityUpdated(entity) => entity,
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_entity_despawned(&mut self, id: EntityId) {
+        self.recv_until(|msg| matches!(msg, GameMessage::EntityDespawned { id: despawned } if *despawned == id)).await;
+    }
+
+    // Ждет подбор предмета и возвращает (id предмета, кто подобрал, сколько)
+    pub async fn expect_item_picked_up(&mut self) -> (EntityId, i32, u32) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ItemPickedUp { .. })).await {
+            GameMessage::ItemPickedUp { id, user_id, count } => (id, user_id, count),
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет урон по указанному игроку и возвращает (атакующий, урон, оставшееся здоровье)
+    pub async fn expect_damaged(&mut self, user_id: i32) -> (i32, i32, i32) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::Damaged { user_id: id, .. } if *id == user_id)).await {
+            GameMessage::Damaged { attacker, amount, health, .. } => (attacker, amount, health),
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет гибель указанного игрока и возвращает id убийцы
+    pub async fn expect_died(&mut self, user_id: i32) -> i32 {
+        match self.recv_until(|msg| matches!(msg, GameMessage::Died { user_id: id, .. } if *id == user_id)).await {
+            GameMessage::Died { killer, .. } => killer,
+            _ => unreachable!(),
+        }
+    }
+
+    // Ждет возрождение указанного игрока и возвращает (x, y, health)
+    pub async fn expect_respawned(&mut self, user_id: i32) -> (f64, f64, i32) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::Respawned { user_id: id, .. } if *id == user_id)).await {
+            GameMessage::Respawned { x, y, health, .. } => (x, y, health),
+            _ => unreachable!(),
+        }
+    }
+
+    // Предложение обмена от игрока
+    pub async fn expect_trade_requested(&mut self) -> i32 {
+        match self.recv_until(|msg| matches!(msg, GameMessage::TradeRequested { .. })).await {
+            GameMessage::TradeRequested { from, .. } => from,
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_trade(&mut self) -> Trade {
+        match self.recv_until(|msg| matches!(msg, GameMessage::TradeUpdated(_))).await {
+            GameMessage::TradeUpdated(trade) => trade,
+            _ => unreachable!(),
+        }
+    }
+
+    // Конец обмена: (завершен ли, причина)
+    pub async fn expect_trade_closed(&mut self) -> (bool, Option<String>) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::TradeClosed { .. })).await {
+            GameMessage::TradeClosed { completed, reason, .. } => (completed, reason),
+            _ => unreachable!(),
+        }
+    }
+
+    // Приглашение в группу от игрока
+    pub async fn expect_party_invited(&mut self) -> i32 {
+        match self.recv_until(|msg| matches!(msg, GameMessage::PartyInvited { .. })).await {
+            GameMessage::PartyInvited { from, .. } => from,
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_party(&mut self) -> Party {
+        match self.recv_until(|msg| matches!(msg, GameMessage::PartyUpdated(_))).await {
+            GameMessage::PartyUpdated(party) => party,
+            _ => unreachable!(),
+        }
+    }
+
+    // Этот игрок выбыл из группы
+    pub async fn expect_party_left(&mut self) -> PartyLeaveReason {
+        match self.recv_until(|msg| matches!(msg, GameMessage::PartyLeft { .. })).await {
+            GameMessage::PartyLeft { reason, .. } => reason,
+            _ => unreachable!(),
+        }
+    }
+
+    // Следующее сообщение чата группы: (user_id, текст)
+    pub async fn expect_party_chat(&mut self) -> (i32, String) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::PartyChatMessage { .. })).await {
+            GameMessage::PartyChatMessage { user_id, text, .. } => (user_id, text),
+            _ => unreachable!(),
+        }
+    }
+
+    // Позиция участника группы: (комната, x, y)
+    pub async fn expect_party_position(&mut self, user_id: i32) -> (String, f64, f64) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::PartyMemberPosition { user_id: id, .. } if *id == user_id)).await {
+            GameMessage::PartyMemberPosition { room, x, y, .. } => (room, x, y),
+            _ => unreachable!(),
+        }
+    }
+
+    // Приглашение в клан: (клан, пригласивший)
+    pub async fn expect_clan_invited(&mut self) -> (ClanId, i32) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ClanInvited { .. })).await {
+            GameMessage::ClanInvited { clan, from, .. } => (clan, from),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_clan(&mut self) -> Clan {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ClanUpdated(_))).await {
+            GameMessage::ClanUpdated(clan) => clan,
+            _ => unreachable!(),
+        }
+    }
+
+    // Игрок user_id выбыл из клана
+    pub async fn expect_clan_left(&mut self, user_id: i32) -> ClanLeaveReason {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ClanLeft { user_id: id, .. } if *id == user_id)).await {
+            GameMessage::ClanLeft { reason, .. } => reason,
+            _ => unreachable!(),
+        }
+    }
+
+    // Следующее сообщение чата клана: (user_id, текст)
+    pub async fn expect_clan_chat(&mut self) -> (i32, String) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ClanChatMessage { .. })).await {
+            GameMessage::ClanChatMessage { user_id, text, .. } => (user_id, text),
+            _ => unreachable!(),
+        }
+    }
+
+    // Новый тег клана игрока user_id
+    pub async fn expect_clan_tag(&mut self, user_id: i32) -> Option<String> {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ClanTagChanged { user_id: id, .. } if *id == user_id)).await {
+            GameMessage::ClanTagChanged { tag, .. } => tag,
+            _ => unreachable!(),
+        }
+    }
+
+    // Друг вошел в игру
+    pub async fn expect_friend_online(&mut self, user_id: i32) {
+        self.recv_until(|msg| matches!(msg, GameMessage::FriendOnline { user_id: id, .. } if *id == user_id)).await;
+    }
+
+    // Друг вышел из игры
+    pub async fn expect_friend_offline(&mut self, user_id: i32) {
+        self.recv_until(|msg| matches!(msg, GameMessage::FriendOffline { user_id: id, .. } if *id == user_id)).await;
+    }
+
+    // Следующее сообщение чата: (user_id, текст)
+    pub async fn expect_chat(&mut self) -> (i32, String) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::ChatMessage { .. })).await {
+            GameMessage::ChatMessage { user_id, text } => (user_id, text),
+            _ => unreachable!(),
+        }
+    }
+
+    // Следующее сообщение сервера: (адресат, текст)
+    pub async fn expect_system_message(&mut self) -> (Option<i32>, String) {
+        match self.recv_until(|msg| matches!(msg, GameMessage::SystemMessage { .. })).await {
+            GameMessage::SystemMessage { to, text } => (to, text),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn expect_disconnected(&mut self, user_id: i32) {
+        self.recv_until(|msg| matches!(msg, GameMessage::PlayerDisconnected { user_id: id } if *id == user_id)).await;
+    }
+
+    // Убеждается, что за duration не пришло ни одного сообщения
+    pub async fn expect_silence(&mut self, duration: Duration) {
+        if let Some(msg) = self.try_recv(duration).await {
+            panic!("Client {} expected no messages, got {:?}", self.user_id, msg);
+        }
+    }
+
+    // Ждет, пока сервер закроет соединение, и возвращает Close-кадр (если он был).
+    // Игровые сообщения до закрытия пропускаются
+    pub async fn expect_closed(&mut self) -> Option<CloseFrame<'static>> {
+        let deadline = tokio::time::Instant::now() + DEFAULT_RECV_TIMEOUT;
+        loop {
+            match tokio::time::timeout_at(deadline, self.stream.next()).await {
+                Err(_) => panic!("Client {} was not disconnected within {:?}", self.user_id, DEFAULT_RECV_TIMEOUT),
+                Ok(Some(Ok(Message::Close(frame)))) => return frame,
+                Ok(None) | Ok(Some(Err(_))) => return None,
+                Ok(Some(Ok(_))) => continue,
+            }
+        }
+    }
+
+    // Корректное закрытие с Close-кадром
+    pub async fn close(mut self) {
+        let _ = self.stream.close(None).await;
+    }
+
+    // Обрыв соединения без Close-кадра (как при падении клиента или потере сети)
+    pub fn drop_connection(self) {
+        drop(self.stream);
+    }
+}
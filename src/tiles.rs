@@ -0,0 +1,255 @@
+// src/tiles.rs
+// Тайловый мир: двумерная сетка тайлов, разбитая на чанки CHUNK_SIZE x CHUNK_SIZE.
+// Чанк загружается в память комнаты, когда рядом с ним появляется игрок, и выгружается
+// (после сохранения в хранилище), когда на него больше никто не смотрит. Хранятся только
+// изменения игроков поверх сгенерированного чанка (см. worldgen)
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::worldgen::WorldGenerator;
+
+// Имя возможности протокола, согласуемой в Hello/Welcome
+pub const FEATURE: &str = "tiles";
+// Сторона чанка в тайлах
+pub const CHUNK_SIZE: i32 = 32;
+pub const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+// Тип тайла. Вид сверху: пол (по нему можно ходить) или препятствие
+pub type Tile = u16;
+// Голая земля; в нее превращается сломанный тайл
+pub const AIR: Tile = 0;
+pub const GRASS: Tile = 1;
+pub const SAND: Tile = 2;
+pub const SNOW: Tile = 3;
+pub const WATER: Tile = 4;
+pub const STONE: Tile = 5;
+pub const TREE: Tile = 6;
+
+// Препятствие: все, кроме пола, включая тайлы игроков с неизвестными серверу id
+pub fn is_solid(tile: Tile) -> bool {
+    !matches!(tile, AIR | GRASS | SAND | SNOW)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChunkPos {
+    pub cx: i32,
+    pub cy: i32,
+}
+
+impl ChunkPos {
+    pub fn of_tile(x: i32, y: i32) -> Self {
+        ChunkPos { cx: x.div_euclid(CHUNK_SIZE), cy: y.div_euclid(CHUNK_SIZE) }
+    }
+
+    // Чанк, в котором находится точка мира (тайл занимает квадрат [x, x + 1) x [y, y + 1))
+    pub fn of_position(x: f64, y: f64) -> Self {
+        Self::of_tile(x.floor() as i32, y.floor() as i32)
+    }
+
+    // Чанки квадрата со стороной 2 * radius + 1 вокруг этого, от ближних к дальним
+    pub fn around(self, radius: i32) -> Vec<ChunkPos> {
+        let mut chunks: Vec<ChunkPos> = (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| ChunkPos { cx: self.cx + dx, cy: self.cy + dy }))
+            .collect();
+        chunks.sort_by_key(|pos| ((pos.cx - self.cx).pow(2) + (pos.cy - self.cy).pow(2), *pos));
+        chunks
+    }
+}
+
+fn tile_index(x: i32, y: i32) -> usize {
+    (y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + x.rem_euclid(CHUNK_SIZE)) as usize
+}
+
+// Тайлы одного чанка построчно: индекс = локальный y * CHUNK_SIZE + локальный x
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    tiles: Vec<Tile>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Chunk { tiles: vec![AIR; CHUNK_AREA] }
+    }
+}
+
+impl Chunk {
+    pub fn from_tiles(tiles: Vec<Tile>) -> Option<Self> {
+        (tiles.len() == CHUNK_AREA).then_some(Chunk { tiles })
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    // Тайл по мировым координатам (координаты берутся по модулю размера чанка)
+    pub fn get(&self, x: i32, y: i32) -> Tile {
+        self.tiles[tile_index(x, y)]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, tile: Tile) {
+        self.tiles[tile_index(x, y)] = tile;
+    }
+
+    // Отличия от baseline (обычно сгенерированного чанка)
+    pub fn diff(&self, baseline: &Chunk) -> ChunkDiff {
+        let changes = self.tiles
+            .iter()
+            .zip(&baseline.tiles)
+            .enumerate()
+            .filter(|(_, (tile, base))| tile != base)
+            .map(|(index, (tile, _))| (index as u16, *tile))
+            .collect();
+        ChunkDiff { changes }
+    }
+}
+
+// Изменения чанка поверх сгенерированного: пары (индекс тайла, тайл) по возрастанию индекса
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkDiff {
+    changes: Vec<(u16, Tile)>,
+}
+
+impl ChunkDiff {
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // Измененный тайл по мировым координатам; None — тайл совпадает со сгенерированным
+    pub fn get(&self, x: i32, y: i32) -> Option<Tile> {
+        let index = tile_index(x, y) as u16;
+        self.changes.binary_search_by_key(&index, |(i, _)| *i).ok().map(|found| self.changes[found].1)
+    }
+
+    pub fn apply(&self, chunk: &mut Chunk) {
+        for (index, tile) in &self.changes {
+            chunk.tiles[*index as usize] = *tile;
+        }
+    }
+
+    // Формат хранения: пары (индекс, тайл), оба u16 little-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.changes
+            .iter()
+            .flat_map(|(index, tile)| index.to_le_bytes().into_iter().chain(tile.to_le_bytes()))
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if !bytes.len().is_multiple_of(4) {
+            return None;
+        }
+        let changes: Vec<(u16, Tile)> = bytes
+            .chunks_exact(4)
+            .map(|c| (u16::from_le_bytes([c[0], c[1]]), Tile::from_le_bytes([c[2], c[3]])))
+            .collect();
+        let valid = changes.iter().all(|(index, _)| (*index as usize) < CHUNK_AREA)
+            && changes.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        valid.then_some(ChunkDiff { changes })
+    }
+}
+
+struct LoadedChunk {
+    chunk: Chunk,
+    // Игроки, которым отправлен этот чанк
+    viewers: HashSet<i32>,
+    // Есть изменения, еще не записанные в хранилище
+    dirty: bool,
+}
+
+// Загруженные чанки одной комнаты
+pub struct TileWorld {
+    generator: WorldGenerator,
+    chunks: HashMap<ChunkPos, LoadedChunk>,
+}
+
+impl TileWorld {
+    pub fn new(generator: WorldGenerator) -> Self {
+        TileWorld { generator, chunks: HashMap::new() }
+    }
+
+    pub fn generator(&self) -> &WorldGenerator {
+        &self.generator
+    }
+
+    pub fn is_loaded(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    pub fn loaded_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Загружает чанк: сгенерированный плюс сохраненные изменения (если его еще не загрузили параллельно)
+    pub fn insert(&mut self, pos: ChunkPos, changes: Option<ChunkDiff>) {
+        if self.chunks.contains_key(&pos) {
+            return;
+        }
+        let mut chunk = self.generator.generate(pos);
+        if let Some(changes) = changes {
+            changes.apply(&mut chunk);
+        }
+        self.chunks.insert(pos, LoadedChunk { chunk, viewers: HashSet::new(), dirty: false });
+    }
+
+    // Подписывает игрока на изменения чанка и возвращает его содержимое; None, если чанк не загружен
+    pub fn watch(&mut self, pos: ChunkPos, user_id: i32) -> Option<&Chunk> {
+        let loaded = self.chunks.get_mut(&pos)?;
+        loaded.viewers.insert(user_id);
+        Some(&loaded.chunk)
+    }
+
+    pub fn unwatch(&mut self, pos: ChunkPos, user_id: i32) {
+        if let Some(loaded) = self.chunks.get_mut(&pos) {
+            loaded.viewers.remove(&user_id);
+        }
+    }
+
+    // None, если чанк с этим тайлом не загружен
+    pub fn tile(&self, x: i32, y: i32) -> Option<Tile> {
+        self.chunks.get(&ChunkPos::of_tile(x, y)).map(|loaded| loaded.chunk.get(x, y))
+    }
+
+    // Для незагруженного чанка — сгенерированный тайл (изменения игроков в нем сейчас не видны)
+    pub fn tile_or_generated(&self, x: i32, y: i32) -> Tile {
+        self.tile(x, y).unwrap_or_else(|| self.generator.tile(x, y))
+    }
+
+    // false, если чанк с этим тайлом не загружен
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile) -> bool {
+        match self.chunks.get_mut(&ChunkPos::of_tile(x, y)) {
+            Some(loaded) => {
+                loaded.chunk.set(x, y, tile);
+                loaded.dirty = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Возвращает изменения измененных чанков для сохранения (флаг изменений сбрасывается)
+    // и выгружает сохраненные чанки без зрителей. Измененный чанк выгружается не раньше
+    // следующего вызова, чтобы его нельзя было перечитать из хранилища до завершения записи
+    pub fn take_dirty(&mut self) -> Vec<(ChunkPos, ChunkDiff)> {
+        self.chunks.retain(|_, loaded| loaded.dirty || !loaded.viewers.is_empty());
+        let generator = &self.generator;
+        self.chunks
+            .iter_mut()
+            .filter(|(_, loaded)| loaded.dirty)
+            .map(|(pos, loaded)| {
+                loaded.dirty = false;
+                (*pos, loaded.chunk.diff(&generator.generate(*pos)))
+            })
+            .collect()
+    }
+
+    // Запись не удалась: чанк сохранится при следующем вызове take_dirty
+    pub fn mark_dirty(&mut self, pos: ChunkPos) {
+        if let Some(loaded) = self.chunks.get_mut(&pos) {
+            loaded.dirty = true;
+        }
+    }
+}
@@ -0,0 +1,345 @@
+// src/trading.rs
+// Обмен предметами между игроками одной комнаты (возможность "inventory"). Игрок предлагает
+// обмен стоящему рядом (TradeRequest), тот соглашается (TradeAccept), стороны выкладывают
+// предметы из своих ячеек (TradeOffer), фиксируют предложение (TradeLock) и подтверждают
+// обмен (TradeConfirm). Предметы до завершения остаются в инвентарях; при завершении оба
+// инвентаря проверяются и записываются в хранилище одной транзакцией, и только после этого
+// меняются в памяти. Обмен отменяется, если одна из сторон уходит из комнаты или игры
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
+
+use crate::items::{Inventory, ItemStack};
+use crate::rooms::Room;
+use crate::routes::game::{ErrorCode, GameMessage};
+use crate::state::AppState;
+use crate::telemetry;
+
+pub type TradeId = u64;
+
+// Сколько действует предложение обмена
+pub const TRADE_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+type TradeResult = Result<(), (ErrorCode, String)>;
+
+fn invalid(message: impl Into<String>) -> (ErrorCode, String) {
+    (ErrorCode::InvalidTradeAction, message.into())
+}
+
+// Предметы, выложенные из ячейки slot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeItem {
+    pub slot: usize,
+    pub item: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeSide {
+    pub user_id: i32,
+    pub items: Vec<TradeItem>,
+    // Предложение зафиксировано; любое изменение предложений снимает фиксацию с обеих сторон
+    pub locked: bool,
+    pub confirmed: bool,
+}
+
+impl TradeSide {
+    fn new(user_id: i32) -> Self {
+        TradeSide { user_id, items: Vec::new(), locked: false, confirmed: false }
+    }
+}
+
+// Состояние обмена, которое получают обе стороны
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trade {
+    pub id: TradeId,
+    pub sides: [TradeSide; 2],
+}
+
+impl Trade {
+    pub fn parties(&self) -> [i32; 2] {
+        [self.sides[0].user_id, self.sides[1].user_id]
+    }
+
+    fn side_mut(&mut self, user_id: i32) -> &mut TradeSide {
+        let index = usize::from(self.sides[1].user_id == user_id);
+        &mut self.sides[index]
+    }
+}
+
+struct OpenTrade {
+    trade: Trade,
+    room: Arc<Room>,
+}
+
+// Открытые обмены и предложения; блокируется раньше AppState::inventories
+#[derive(Default)]
+pub struct TradeRegistry {
+    next_id: TradeId,
+    // Игроки онлайн с возможностью "inventory": обмениваться можно только с ними
+    traders: HashSet<i32>,
+    // Предложения обмена (от кого, кому) и когда они истекают
+    requests: HashMap<(i32, i32), Instant>,
+    trades: HashMap<TradeId, OpenTrade>,
+    by_player: HashMap<i32, TradeId>,
+}
+
+impl TradeRegistry {
+    // Обмен, в котором участвует игрок
+    pub fn trade_of(&self, user_id: i32) -> Option<&Trade> {
+        self.by_player.get(&user_id).and_then(|id| self.trades.get(id)).map(|open| &open.trade)
+    }
+
+    fn open_mut(&mut self, user_id: i32) -> Result<&mut OpenTrade, (ErrorCode, String)> {
+        self.by_player
+            .get(&user_id)
+            .and_then(|id| self.trades.get_mut(id))
+            .ok_or_else(|| invalid("you are not trading"))
+    }
+
+    fn close(&mut self, id: TradeId) -> Option<OpenTrade> {
+        let open = self.trades.remove(&id)?;
+        for user_id in open.trade.parties() {
+            self.by_player.remove(&user_id);
+        }
+        Some(open)
+    }
+}
+
+fn send_update(open: &OpenTrade) {
+    let _ = open.room.game_state_tx.send(GameMessage::TradeUpdated(open.trade.clone()));
+}
+
+fn send_closed(open: &OpenTrade, completed: bool, reason: Option<String>) {
+    let _ = open.room.game_state_tx.send(GameMessage::TradeClosed {
+        id: open.trade.id,
+        parties: open.trade.parties(),
+        completed,
+        reason,
+    });
+}
+
+// Игрок с возможностью "inventory" вошел в игру
+pub async fn register(app_state: &AppState, user_id: i32) {
+    app_state.trades.lock().await.traders.insert(user_id);
+}
+
+// Закрыто последнее соединение игрока: его обмен отменяется, предложения забываются.
+// Если за это время открылось новое соединение, игрок остается участником обменов
+pub async fn unregister(app_state: &AppState, user_id: i32) {
+    let mut trades = app_state.trades.lock().await;
+    if app_state.is_connected(user_id) {
+        return;
+    }
+    trades.traders.remove(&user_id);
+    trades.requests.retain(|(from, to), _| *from != user_id && *to != user_id);
+    if let Some(id) = trades.by_player.get(&user_id).copied() {
+        if let Some(open) = trades.close(id) {
+            debug!(id, user_id, "Trade cancelled: player left the game");
+            send_closed(&open, false, Some("partner left the game".to_string()));
+        }
+    }
+}
+
+// Игрок уходит из комнаты: обмен отменяется
+pub async fn leave_room(app_state: &AppState, user_id: i32) {
+    let mut trades = app_state.trades.lock().await;
+    if let Some(id) = trades.by_player.get(&user_id).copied() {
+        if let Some(open) = trades.close(id) {
+            debug!(id, user_id, "Trade cancelled: player left the room");
+            send_closed(&open, false, Some("partner left the room".to_string()));
+        }
+    }
+}
+
+// Оба игрока в комнате и не дальше game.reach_distance друг от друга
+async fn check_distance(app_state: &AppState, room: &Room, user_id: i32, other: i32) -> TradeResult {
+    let positions = room.active_player_positions.lock().await;
+    let (Some(a), Some(b)) = (positions.get(&user_id), positions.get(&other)) else {
+        return Err(invalid(format!("player {} is not in this room", other)));
+    };
+    let distance = (a.x - b.x).hypot(a.y - b.y);
+    let reach = app_state.config.game.reach_distance;
+    if distance > reach {
+        return Err((ErrorCode::OutOfReach, format!("player {} is {:.1} tiles away, reach is {}", other, distance, reach)));
+    }
+    Ok(())
+}
+
+pub async fn request(app_state: &AppState, room: &Room, user_id: i32, target: i32) -> TradeResult {
+    if target == user_id {
+        return Err(invalid("cannot trade with yourself"));
+    }
+    check_distance(app_state, room, user_id, target).await?;
+    let mut trades = app_state.trades.lock().await;
+    if !trades.traders.contains(&target) {
+        return Err(invalid(format!("player {} cannot trade", target)));
+    }
+    if trades.by_player.contains_key(&user_id) {
+        return Err(invalid("you are already trading"));
+    }
+    if trades.by_player.contains_key(&target) {
+        return Err(invalid(format!("player {} is already trading", target)));
+    }
+    trades.requests.insert((user_id, target), Instant::now() + TRADE_REQUEST_TIMEOUT);
+    let _ = room.game_state_tx.send(GameMessage::TradeRequested { from: user_id, to: target });
+    Ok(())
+}
+
+pub async fn accept(app_state: &AppState, room: &Arc<Room>, user_id: i32, from: i32) -> TradeResult {
+    check_distance(app_state, room, user_id, from).await?;
+    let mut trades = app_state.trades.lock().await;
+    let now = Instant::now();
+    trades.requests.retain(|_, expires_at| *expires_at > now);
+    if trades.requests.remove(&(from, user_id)).is_none() {
+        return Err(invalid(format!("no trade request from player {}", from)));
+    }
+    if trades.by_player.contains_key(&user_id) || trades.by_player.contains_key(&from) {
+        return Err(invalid("one of the players is already trading"));
+    }
+    trades.next_id += 1;
+    let id = trades.next_id;
+    let open = OpenTrade {
+        trade: Trade { id, sides: [TradeSide::new(from), TradeSide::new(user_id)] },
+        room: room.clone(),
+    };
+    debug!(id, from, to = user_id, "Trade opened");
+    send_update(&open);
+    trades.trades.insert(id, open);
+    trades.by_player.insert(from, id);
+    trades.by_player.insert(user_id, id);
+    Ok(())
+}
+
+// Выкладывает count предметов из ячейки slot (0 — убирает ячейку из предложения)
+pub async fn offer(app_state: &AppState, user_id: i32, slot: usize, count: u32) -> TradeResult {
+    let mut trades = app_state.trades.lock().await;
+    let open = trades.open_mut(user_id)?;
+    let side = open.trade.side_mut(user_id);
+    side.items.retain(|item| item.slot != slot);
+    if count > 0 {
+        let inventories = app_state.inventories.lock().await;
+        let stack = inventories
+            .get(&user_id)
+            .and_then(|inventory| inventory.get(slot))
+            .ok_or_else(|| invalid(format!("slot {} is empty", slot)))?;
+        if count > stack.count {
+            return Err(invalid(format!("slot {} has only {} {}", slot, stack.count, stack.item)));
+        }
+        side.items.push(TradeItem { slot, item: stack.item.clone(), count });
+        side.items.sort_by_key(|item| item.slot);
+    }
+    for side in open.trade.sides.iter_mut() {
+        (side.locked, side.confirmed) = (false, false);
+    }
+    send_update(open);
+    Ok(())
+}
+
+pub async fn lock(app_state: &AppState, user_id: i32) -> TradeResult {
+    let mut trades = app_state.trades.lock().await;
+    let open = trades.open_mut(user_id)?;
+    open.trade.side_mut(user_id).locked = true;
+    send_update(open);
+    Ok(())
+}
+
+// Подтверждение; когда подтвердили обе стороны, обмен завершается
+pub async fn confirm(app_state: &AppState, user_id: i32) -> TradeResult {
+    let mut trades = app_state.trades.lock().await;
+    let open = trades.open_mut(user_id)?;
+    if !open.trade.sides.iter().all(|side| side.locked) {
+        return Err(invalid("both offers must be locked first"));
+    }
+    open.trade.side_mut(user_id).confirmed = true;
+    if !open.trade.sides.iter().all(|side| side.confirmed) {
+        send_update(open);
+        return Ok(());
+    }
+
+    // Запись в хранилище не должна держать все обмены сервера: обмен забирается из реестра,
+    // а его стороны остаются занятыми (by_player), пока он не завершится
+    let id = open.trade.id;
+    let open = trades.trades.remove(&id).expect("trade is open");
+    drop(trades);
+    let [a, b] = open.trade.parties();
+    let distance = check_distance(app_state, &open.room, a, b).await;
+    let result = match distance {
+        Ok(()) => settle(app_state, &open.trade).await,
+        Err((_, message)) => Err(message),
+    };
+    let mut trades = app_state.trades.lock().await;
+    for user_id in [a, b] {
+        if trades.by_player.get(&user_id) == Some(&id) {
+            trades.by_player.remove(&user_id);
+        }
+    }
+    match result {
+        Ok(()) => {
+            info!(id, a, b, "Trade completed");
+            send_closed(&open, true, None);
+        },
+        Err(reason) => {
+            debug!(id, %reason, "Trade failed");
+            send_closed(&open, false, Some(reason));
+        },
+    }
+    Ok(())
+}
+
+pub async fn cancel(app_state: &AppState, user_id: i32) -> TradeResult {
+    let mut trades = app_state.trades.lock().await;
+    let id = trades.open_mut(user_id)?.trade.id;
+    let open = trades.close(id).expect("trade is open");
+    debug!(id, user_id, "Trade cancelled");
+    send_closed(&open, false, Some(format!("cancelled by player {}", user_id)));
+    Ok(())
+}
+
+// Забирает выложенные предметы из инвентаря; ошибка — предложение больше не соответствует ячейкам
+fn take_offer(inventory: &mut Inventory, items: &[TradeItem]) -> Result<Vec<ItemStack>, String> {
+    let mut taken = Vec::new();
+    for offered in items {
+        match inventory.get(offered.slot) {
+            Some(stack) if stack.item == offered.item && stack.count >= offered.count => {},
+            _ => return Err(format!("{} {} are no longer in slot {}", offered.count, offered.item, offered.slot)),
+        }
+        taken.extend(inventory.take(offered.slot, offered.count));
+    }
+    Ok(taken)
+}
+
+// Переносит предметы между инвентарями. Блокировка инвентарей удерживается до конца записи:
+// память меняется только после успешной транзакции, поэтому ни сбой базы, ни уход игрока
+// не могут продублировать или потерять предметы
+async fn settle(app_state: &AppState, trade: &Trade) -> Result<(), String> {
+    let [a, b] = trade.parties();
+    let mut inventories = app_state.inventories.lock().await;
+    let (Some(mut inventory_a), Some(mut inventory_b)) = (inventories.get(&a).cloned(), inventories.get(&b).cloned()) else {
+        return Err("inventory is not loaded".to_string());
+    };
+    let from_a = take_offer(&mut inventory_a, &trade.sides[0].items)?;
+    let from_b = take_offer(&mut inventory_b, &trade.sides[1].items)?;
+    for (inventory, stacks, user_id) in [(&mut inventory_b, &from_a, b), (&mut inventory_a, &from_b, a)] {
+        for stack in stacks {
+            if inventory.add(&stack.item, stack.count, app_state.items.max_stack(&stack.item)) > 0 {
+                return Err(format!("player {} has no room for {}", user_id, stack.item));
+            }
+        }
+    }
+
+    let query_started = Instant::now();
+    let result = app_state.storage.inventories
+        .save_inventories(&[(a, inventory_a.stacks()), (b, inventory_b.stacks())])
+        .await;
+    telemetry::db_query("save_inventories", query_started.elapsed());
+    if let Err(e) = result {
+        error!(error = %e, id = trade.id, "Error saving traded inventories");
+        return Err("trade could not be saved".to_string());
+    }
+    inventories.insert(a, inventory_a);
+    inventories.insert(b, inventory_b);
+    Ok(())
+}
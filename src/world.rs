@@ -0,0 +1,115 @@
+// src/world.rs
+// Игровой цикл: работает с частотой game.tick_rate и рассылает накопленные
+// за тик изменения, вместо широковещания на каждое входящее сообщение
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, trace};
+
+use crate::combat;
+use crate::entities;
+use crate::npc;
+use crate::party;
+use crate::rooms::Room;
+use crate::routes::game::GameMessage;
+use crate::scripting;
+use crate::snapshot::WorldSnapshot;
+use crate::state::AppState;
+use crate::telemetry;
+
+pub fn spawn_world(app_state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(run_world(app_state))
+}
+
+pub async fn run_world(app_state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(app_state.config.tick_interval());
+    // Если тик не успел выполниться вовремя, не пытаемся "догнать" пропущенные
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut tick_number: u64 = 0;
+    loop {
+        interval.tick().await;
+        tick_number += 1;
+        let tick_started = Instant::now();
+        tick(&app_state, tick_number).await;
+        app_state.record_tick();
+        telemetry::tick_completed(tick_started.elapsed());
+    }
+}
+
+async fn tick(app_state: &AppState, tick_number: u64) {
+    // Раз в секунду измененные чанки записываются в хранилище, а ненужные выгружаются,
+    // и проверяются файлы скриптов
+    let once_per_second = tick_number.is_multiple_of(app_state.config.game.tick_rate as u64);
+    scripting::tick(app_state, once_per_second).await;
+    if once_per_second {
+        party::expire_offline(app_state, Instant::now()).await;
+    }
+    let dt = app_state.config.tick_interval().as_secs_f64();
+    // Комнаты независимы: каждая рассылает изменения своим игрокам
+    for room in app_state.rooms.all() {
+        // Возрожденные игроки попадают в moved_players и расходятся в этом же тике
+        combat::respawn_due(app_state, &room).await;
+        // NPC выбирают скорость, а entities::tick перемещает их вместе с остальными сущностями
+        npc::tick(app_state, &room, dt).await;
+        entities::tick(&room, dt).await;
+        tick_room(app_state, &room, tick_number).await;
+        if once_per_second {
+            save_chunks(app_state, &room).await;
+        }
+    }
+}
+
+// Записывает измененные чанки комнаты и выгружает те, на которые никто не смотрит
+pub async fn save_chunks(app_state: &AppState, room: &Room) {
+    let dirty = room.tiles.lock().await.take_dirty();
+    for (pos, changes) in dirty {
+        let query_started = Instant::now();
+        let result = app_state.storage.chunks.save_chunk(&room.name, pos, &changes).await;
+        telemetry::db_query("save_chunk", query_started.elapsed());
+        match result {
+            Ok(()) => debug!(room = %room.name, cx = pos.cx, cy = pos.cy, changes = changes.len(), "Chunk saved"),
+            Err(e) => {
+                error!(error = %e, room = %room.name, cx = pos.cx, cy = pos.cy, "Error saving chunk");
+                room.tiles.lock().await.mark_dirty(pos);
+            },
+        }
+    }
+}
+
+// Сохраняет все комнаты (при остановке сервера)
+pub async fn save_all_chunks(app_state: &AppState) {
+    for room in app_state.rooms.all() {
+        save_chunks(app_state, &room).await;
+    }
+}
+
+async fn tick_room(app_state: &AppState, room: &Room, tick_number: u64) {
+    let moved: Vec<i32> = room.moved_players.lock().await.drain().collect();
+    let snapshot_subscribers = room.snapshot_tx.receiver_count() > 0;
+    if moved.is_empty() && !snapshot_subscribers {
+        return;
+    }
+
+    // Блокировка удерживается на время рассылки: так позиция игрока не может
+    // уйти в канал после его PlayerDisconnected (тот отправляется после удаления из карты)
+    let active_players_map = room.active_player_positions.lock().await;
+    let mut moved_positions = Vec::with_capacity(moved.len());
+    if snapshot_subscribers {
+        // Снимок строится раз за тик и кодируется каждым соединением относительно своей базы
+        let snapshot = WorldSnapshot::new(tick_number, active_players_map.values());
+        let _ = room.snapshot_tx.send(Arc::new(snapshot));
+    }
+    for user_id in moved {
+        if let Some(position) = active_players_map.get(&user_id) {
+            // Ошибка означает лишь отсутствие подписчиков
+            if room.game_state_tx.send(GameMessage::PlayerPosition(position.clone())).is_err() {
+                trace!(user_id, room = %room.name, "No subscribers for PlayerPosition");
+            }
+            moved_positions.push(position.clone());
+        }
+    }
+    drop(active_players_map);
+    // Участники групп получают позиции друг друга и из других комнат
+    party::share_positions(app_state, &room.name, &moved_positions).await;
+}
@@ -0,0 +1,182 @@
+// src/worldgen.rs
+// Процедурная генерация тайлового мира (вид сверху). Чанк, который ни разу не менялся,
+// целиком определяется сидом мира, именем комнаты и координатами: высота, влажность
+// и температура берутся из шума, по ним выбирается биом, а по биому — тайл.
+// Если у комнаты есть карта Tiled, в ее пределах тайлы и точка появления берутся с карты
+use std::sync::Arc;
+
+use crate::config::GeneratorKind;
+use crate::map::TiledMap;
+use crate::tiles::{self, Chunk, ChunkPos, Tile, CHUNK_SIZE};
+
+// Масштабы шума в тайлах: рельеф меняется быстрее климата
+const ELEVATION_SCALE: f64 = 64.0;
+const MOISTURE_SCALE: f64 = 128.0;
+const TEMPERATURE_SCALE: f64 = 256.0;
+const OCTAVES: u32 = 4;
+// Границы высот: ниже — вода и пляж, выше — горы
+const SEA_LEVEL: f64 = 0.40;
+const BEACH_LEVEL: f64 = 0.43;
+const MOUNTAIN_LEVEL: f64 = 0.62;
+// Как далеко от начала координат искать точку появления
+const SPAWN_SEARCH_RADIUS: i32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+    Ocean,
+    Beach,
+    Plains,
+    Forest,
+    Desert,
+    Tundra,
+    Mountains,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldGenerator {
+    kind: GeneratorKind,
+    seed: u64,
+    map: Option<Arc<TiledMap>>,
+}
+
+// FNV-1a: стабильный между версиями и платформами хеш имени комнаты
+fn hash_name(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Детерминированное псевдослучайное число для точки решетки (финализатор splitmix64)
+fn hash_point(seed: u64, x: i64, y: i64) -> u64 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9e3779b97f4a7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2ae3d27d4eb4f);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^ (h >> 31)
+}
+
+// Число в [0, 1)
+fn unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Value noise: случайные значения в узлах целочисленной решетки, сглаженно интерполированные
+fn value_noise(seed: u64, x: f64, y: f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let corner = |dx: i64, dy: i64| unit(hash_point(seed, ix + dx, iy + dy));
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    top + (bottom - top) * ty
+}
+
+// Сумма октав шума в [0, 1): крупные формы плюс все более мелкие детали
+fn fractal_noise(seed: u64, x: f64, y: f64, scale: f64) -> f64 {
+    let (mut total, mut amplitude, mut frequency, mut norm) = (0.0, 1.0, 1.0 / scale, 0.0);
+    for octave in 0..OCTAVES {
+        total += amplitude * value_noise(seed.wrapping_add(octave as u64), x * frequency, y * frequency);
+        norm += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    total / norm
+}
+
+impl WorldGenerator {
+    // У каждой комнаты свой рельеф при общем сиде мира
+    pub fn new(kind: GeneratorKind, world_seed: u64, room: &str) -> Self {
+        WorldGenerator { kind, seed: world_seed ^ hash_name(room), map: None }
+    }
+
+    pub fn with_map(self, map: Arc<TiledMap>) -> Self {
+        WorldGenerator { map: Some(map), ..self }
+    }
+
+    pub fn map(&self) -> Option<&Arc<TiledMap>> {
+        self.map.as_ref()
+    }
+
+    pub fn biome(&self, x: i32, y: i32) -> Biome {
+        if self.kind == GeneratorKind::Flat {
+            return Biome::Plains;
+        }
+        let (fx, fy) = (x as f64, y as f64);
+        let elevation = fractal_noise(self.seed, fx, fy, ELEVATION_SCALE);
+        if elevation < SEA_LEVEL {
+            return Biome::Ocean;
+        }
+        if elevation < BEACH_LEVEL {
+            return Biome::Beach;
+        }
+        if elevation > MOUNTAIN_LEVEL {
+            return Biome::Mountains;
+        }
+        let moisture = fractal_noise(self.seed.wrapping_add(0x1000), fx, fy, MOISTURE_SCALE);
+        let temperature = fractal_noise(self.seed.wrapping_add(0x2000), fx, fy, TEMPERATURE_SCALE);
+        if temperature < 0.38 {
+            Biome::Tundra
+        } else if moisture < 0.40 {
+            Biome::Desert
+        } else if moisture > 0.55 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> Tile {
+        if let Some(tile) = self.map.as_ref().and_then(|map| map.tile(x, y)) {
+            return tile;
+        }
+        if self.kind == GeneratorKind::Flat {
+            return tiles::AIR;
+        }
+        // Деревья разбросаны независимо от шума рельефа
+        let scatter = unit(hash_point(self.seed.wrapping_add(0x3000), x as i64, y as i64));
+        match self.biome(x, y) {
+            Biome::Ocean => tiles::WATER,
+            Biome::Beach | Biome::Desert => tiles::SAND,
+            Biome::Mountains => tiles::STONE,
+            Biome::Tundra if scatter < 0.02 => tiles::TREE,
+            Biome::Tundra => tiles::SNOW,
+            Biome::Forest if scatter < 0.15 => tiles::TREE,
+            Biome::Plains if scatter < 0.01 => tiles::TREE,
+            Biome::Forest | Biome::Plains => tiles::GRASS,
+        }
+    }
+
+    pub fn generate(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::default();
+        if self.kind == GeneratorKind::Flat && self.map.is_none() {
+            return chunk;
+        }
+        for y in pos.cy * CHUNK_SIZE..(pos.cy + 1) * CHUNK_SIZE {
+            for x in pos.cx * CHUNK_SIZE..(pos.cx + 1) * CHUNK_SIZE {
+                chunk.set(x, y, self.tile(x, y));
+            }
+        }
+        chunk
+    }
+
+    // Точка появления карты, иначе ближайший к началу координат тайл, вокруг которого (3x3)
+    // можно стоять; возвращается его центр
+    pub fn spawn_point(&self) -> (f64, f64) {
+        if let Some(spawn) = self.map.as_ref().and_then(|map| map.spawn_point(None)) {
+            return spawn;
+        }
+        let walkable = |x: i32, y: i32| {
+            (-1..=1).all(|dy| (-1..=1).all(|dx| !tiles::is_solid(self.tile(x + dx, y + dy))))
+        };
+        for radius in 0..=SPAWN_SEARCH_RADIUS {
+            // Обход квадратного кольца радиуса radius
+            let ring = (-radius..=radius).flat_map(|d| {
+                [(d, -radius), (d, radius), (-radius, d), (radius, d)]
+            });
+            if let Some((x, y)) = ring.filter(|(x, y)| walkable(*x, *y)).min_by_key(|(x, y)| (x * x + y * y, *y, *x)) {
+                return (x as f64 + 0.5, y as f64 + 0.5);
+            }
+        }
+        (0.5, 0.5)
+    }
+}
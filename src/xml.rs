@@ -0,0 +1,108 @@
+// src/xml.rs
+// Разбор XML для карт Tiled (TMX) поверх потокового читателя quick-xml: документ сворачивается
+// в простое дерево элементов с атрибутами и текстом. Объявления, комментарии, DOCTYPE и инструкции
+// обработки пропускаются, пространства имен не учитываются (остается локальное имя)
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+// Глубже карты Tiled не бывают даже с вложенными группами слоев; дерево строится без рекурсии,
+// а ограничение не дает специально собранному файлу раздуть стек элементов
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    // Весь текст элемента (включая CDATA) без вложенных элементов
+    pub text: String,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+// Корневой элемент документа
+pub fn parse(input: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(input);
+    // Незакрытые элементы от корня к текущему
+    let mut open: Vec<Element> = Vec::new();
+    let mut root = None;
+    let error = |position: u64, message: &str| {
+        let line = input.as_bytes()[..(position as usize).min(input.len())].iter().filter(|&&b| b == b'\n').count() + 1;
+        format!("{} (line {})", message, line)
+    };
+
+    loop {
+        let event = reader.read_event().map_err(|e| error(reader.error_position(), &e.to_string()))?;
+        let position = reader.buffer_position();
+        let element = match event {
+            Event::Start(start) | Event::Empty(start) if root.is_some() => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+                return Err(error(position, &format!("unexpected <{}> after the root element", name)));
+            },
+            Event::Start(start) => {
+                if open.len() == MAX_DEPTH {
+                    return Err(error(position, &format!("elements are nested deeper than {} levels", MAX_DEPTH)));
+                }
+                open.push(element(&start).map_err(|e| error(position, &e))?);
+                continue;
+            },
+            Event::Empty(start) => element(&start).map_err(|e| error(position, &e))?,
+            // Имена закрывающих тегов сверяет сам читатель
+            Event::End(_) => open.pop().ok_or_else(|| error(position, "unexpected closing tag"))?,
+            Event::Text(text) => {
+                let text = text.unescape().map_err(|e| error(position, &e.to_string()))?;
+                match open.last_mut() {
+                    Some(parent) => parent.text.push_str(&text),
+                    None if text.trim().is_empty() => {},
+                    None => return Err(error(position, "text outside the root element")),
+                }
+                continue;
+            },
+            Event::CData(data) => {
+                let text = data.decode().map_err(|e| error(position, &e.to_string()))?;
+                match open.last_mut() {
+                    Some(parent) => parent.text.push_str(&text),
+                    None => return Err(error(position, "CDATA outside the root element")),
+                }
+                continue;
+            },
+            Event::Eof => break,
+            Event::Decl(_) | Event::PI(_) | Event::Comment(_) | Event::DocType(_) => continue,
+        };
+        match open.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => root = Some(element),
+        }
+    }
+
+    if let Some(element) = open.last() {
+        return Err(error(input.len() as u64, &format!("element <{}> is not closed", element.name)));
+    }
+    root.ok_or_else(|| error(0, "document has no root element"))
+}
+
+fn element(start: &BytesStart) -> Result<Element, String> {
+    let mut element = Element {
+        name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+        ..Element::default()
+    };
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+        let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+        element.attributes.push((key, value.into_owned()));
+    }
+    Ok(element)
+}
@@ -0,0 +1,46 @@
+// tests/account.rs
+use anarchy_core::testing::TestServer;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+#[tokio::test]
+async fn deletion_can_be_cancelled_only_within_the_grace_period() {
+    let server = TestServer::start().await;
+    let alice = server.register_user("alice").await;
+
+    let response = server.authorized(&alice, Method::DELETE, "/account").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let status: Value = response.json().await.unwrap();
+    assert!(status["deletion_scheduled_for"].is_string());
+    let response = server.authorized(&alice, Method::POST, "/account/restore").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(server.state().storage.users.find_by_id(alice.user_id).await.unwrap().unwrap().deletion_requested_at.is_none());
+
+    // Без периода ожидания отменять уже поздно, хотя очистка еще не прошла
+    let mut config = TestServer::test_config();
+    config.auth.deletion_grace_days = 0;
+    let server = TestServer::start_with_config(config).await;
+    let bob = server.register_user("bob").await;
+    server.authorized(&bob, Method::DELETE, "/account").send().await.unwrap().error_for_status().unwrap();
+    let response = server.authorized(&bob, Method::POST, "/account/restore").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert!(server.state().storage.users.find_by_id(bob.user_id).await.unwrap().unwrap().deletion_requested_at.is_some());
+}
+
+#[tokio::test]
+async fn requesting_deletion_closes_open_game_connections() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let mut second_ws = server.connect(&alice).await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+
+    server.authorized(&alice, Method::DELETE, "/account").send().await.unwrap().error_for_status().unwrap();
+    for ws in [&mut alice_ws, &mut second_ws] {
+        let frame = ws.expect_closed().await.expect("Close frame expected");
+        assert_eq!(u16::from(frame.code), 1008);
+        assert_eq!(frame.reason, "account deletion requested");
+    }
+    bob_ws.expect_disconnected(alice.user_id).await;
+    assert!(server.try_connect(&alice.token).await.is_err());
+    assert!(server.try_connect(&bob.token).await.is_ok());
+}
@@ -0,0 +1,274 @@
+// tests/clans.rs
+use anarchy_core::clans::{Clan, ClanId, ClanInvite, ClanLeaveReason, ClanRank};
+use anarchy_core::config::Config;
+use anarchy_core::routes::game::{ErrorCode, GameMessage};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::{ClanRepository, Storage, StorageResult};
+use anarchy_core::testing::TestServer;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn config() -> Config {
+    TestServer::config_with_worlds(&["lobby", "arena"])
+}
+
+fn ranks(clan: &Clan) -> Vec<(i32, ClanRank)> {
+    clan.members.iter().map(|member| (member.user_id, member.rank)).collect()
+}
+
+#[tokio::test]
+async fn clans_are_managed_over_rest_according_to_ranks() {
+    let server = TestServer::start_with_config(config()).await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let carol = server.register_user("carol").await;
+
+    let response = server.call(&alice, Method::POST, "/clans", Some(json!({ "name": "Night Owls", "tag": "owl" }))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = server.call(&alice, Method::POST, "/clans", Some(json!({ "name": "Night Owls", "tag": "OWL" }))).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let clan: Clan = response.json().await.unwrap();
+    assert_eq!((clan.name.as_str(), clan.tag.as_str()), ("Night Owls", "OWL"));
+    assert_eq!(ranks(&clan), vec![(alice.user_id, ClanRank::Leader)]);
+    assert_eq!(clan.members[0].login, "alice");
+    // Имя занято без учета регистра
+    let response = server.call(&bob, Method::POST, "/clans", Some(json!({ "name": "night owls", "tag": "NO" }))).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Вступление по приглашению; новичок не может приглашать
+    server.call(&alice, Method::POST, "/clan/invites", Some(json!({ "user_id": bob.user_id }))).await.error_for_status().unwrap();
+    let invites: Vec<ClanInvite> = server.call(&bob, Method::GET, "/clan/invites", None).await.json().await.unwrap();
+    assert_eq!((invites.len(), invites[0].clan, invites[0].invited_by), (1, clan.id, Some(alice.user_id)));
+    let path = format!("/clan/invites/{}/accept", clan.id);
+    let joined: Clan = server.call(&bob, Method::POST, &path, None).await.json().await.unwrap();
+    assert_eq!(ranks(&joined), vec![(alice.user_id, ClanRank::Leader), (bob.user_id, ClanRank::Recruit)]);
+    let response = server.call(&bob, Method::POST, "/clan/invites", Some(json!({ "user_id": carol.user_id }))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Офицер приглашает и исключает младших по рангу
+    let path = format!("/clan/members/{}/rank", bob.user_id);
+    server.call(&alice, Method::PUT, &path, Some(json!({ "rank": "officer" }))).await.error_for_status().unwrap();
+    server.call(&bob, Method::POST, "/clan/invites", Some(json!({ "user_id": carol.user_id }))).await.error_for_status().unwrap();
+    let decline = format!("/clan/invites/{}/decline", clan.id);
+    assert_eq!(server.call(&carol, Method::POST, &decline, None).await.status(), StatusCode::NO_CONTENT);
+    let accept = format!("/clan/invites/{}/accept", clan.id);
+    assert_eq!(server.call(&carol, Method::POST, &accept, None).await.status(), StatusCode::NOT_FOUND);
+    server.call(&bob, Method::POST, "/clan/invites", Some(json!({ "user_id": carol.user_id }))).await.error_for_status().unwrap();
+    server.call(&carol, Method::POST, &accept, None).await.error_for_status().unwrap();
+    let kick_alice = format!("/clan/members/{}", alice.user_id);
+    assert_eq!(server.call(&bob, Method::DELETE, &kick_alice, None).await.status(), StatusCode::FORBIDDEN);
+    let kick_carol = format!("/clan/members/{}", carol.user_id);
+    assert_eq!(server.call(&bob, Method::DELETE, &kick_carol, None).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(server.call(&carol, Method::GET, "/clan", None).await.status(), StatusCode::NOT_FOUND);
+
+    // Лидер не уходит, не передав лидерство; назначение лидера делает прежнего офицером
+    assert_eq!(server.call(&alice, Method::POST, "/clan/leave", None).await.status(), StatusCode::FORBIDDEN);
+    server.call(&alice, Method::PUT, &path, Some(json!({ "rank": "leader" }))).await.error_for_status().unwrap();
+    let clan: Clan = server.call(&alice, Method::GET, "/clan", None).await.json().await.unwrap();
+    assert_eq!(ranks(&clan), vec![(alice.user_id, ClanRank::Officer), (bob.user_id, ClanRank::Leader)]);
+    let export: Value = server.call(&alice, Method::GET, "/account/export", None).await.json().await.unwrap();
+    assert_eq!(export["clan"]["tag"], "OWL");
+    assert_eq!(export["clan"]["rank"], "officer");
+
+    assert_eq!(server.call(&alice, Method::DELETE, "/clan", None).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(server.call(&bob, Method::DELETE, "/clan", None).await.status(), StatusCode::NO_CONTENT);
+    let path = format!("/clans/{}", clan.id);
+    assert_eq!(server.call(&carol, Method::GET, &path, None).await.status(), StatusCode::NOT_FOUND);
+    assert!(server.state().storage.clans.clan_of(alice.user_id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn clan_messages_and_tags_reach_online_players() {
+    let server = TestServer::start_with_config(config()).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.send(&GameMessage::ClanCreate { name: "Owls".to_string(), tag: "OWL".to_string() }).await;
+    let clan = alice_ws.expect_clan().await;
+    assert_eq!(bob_ws.expect_clan_tag(alice.user_id).await.as_deref(), Some("OWL"));
+    alice_ws.send(&GameMessage::ClanCreate { name: "Hawks".to_string(), tag: "HWK".to_string() }).await;
+    assert!(alice_ws.expect_error(ErrorCode::InvalidClanAction).await.contains("already in a clan"));
+
+    alice_ws.send(&GameMessage::ClanInvite { target: bob.user_id }).await;
+    assert_eq!(bob_ws.expect_clan_invited().await, (clan.id, alice.user_id));
+    bob_ws.send(&GameMessage::ClanAccept { clan: clan.id }).await;
+    let joined = bob_ws.expect_clan().await;
+    // Состав клана и тег приходят по разным каналам, поэтому в любом порядке
+    let (mut update, mut tag) = (None, None);
+    while update.is_none() || tag.is_none() {
+        match alice_ws.recv_until(|msg| matches!(msg, GameMessage::ClanUpdated(_) | GameMessage::ClanTagChanged { .. })).await {
+            GameMessage::ClanUpdated(clan) => update = Some(clan),
+            GameMessage::ClanTagChanged { user_id, tag: new_tag } if user_id == bob.user_id => tag = new_tag,
+            _ => {},
+        }
+    }
+    assert_eq!(update, Some(joined));
+    assert_eq!(tag.as_deref(), Some("OWL"));
+
+    // Новый игрок видит теги в списке игроков, а позиции от клиента тег не сбрасывают
+    let (_carol, mut carol_ws) = server.join("carol").await;
+    alice_ws.send_position(2.5, 0.5, 0.0).await;
+    assert_eq!(carol_ws.expect_position_of(alice.user_id).await.clan_tag.as_deref(), Some("OWL"));
+
+    // Чат клана доходит до участников в других комнатах и только до них
+    bob_ws.change_room("arena").await;
+    bob_ws.expect_room_changed("arena").await;
+    alice_ws.send(&GameMessage::ClanChat { text: " hoot ".to_string() }).await;
+    assert_eq!(bob_ws.expect_clan_chat().await, (alice.user_id, "hoot".to_string()));
+    assert_eq!(alice_ws.expect_clan_chat().await, (alice.user_id, "hoot".to_string()));
+    carol_ws.send(&GameMessage::ClanChat { text: "hi".to_string() }).await;
+    carol_ws.expect_error(ErrorCode::InvalidClanAction).await;
+    alice_ws.chat("hello").await;
+    let msg = carol_ws.recv_until(|msg| matches!(msg, GameMessage::ChatMessage { .. } | GameMessage::ClanChatMessage { .. })).await;
+    assert!(matches!(msg, GameMessage::ChatMessage { .. }), "unexpected {:?}", msg);
+
+    // Тег сохраняется при смене комнаты и снимается при исключении
+    let (_dave, mut dave_ws) = server.join("dave").await;
+    dave_ws.change_room("arena").await;
+    let players = dave_ws.expect_room_changed("arena").await;
+    let bob_position = players.iter().find(|p| p.user_id == bob.user_id).unwrap();
+    assert_eq!(bob_position.clan_tag.as_deref(), Some("OWL"));
+    bob_ws.send(&GameMessage::ClanKick { target: alice.user_id }).await;
+    bob_ws.expect_error(ErrorCode::InvalidClanAction).await;
+    alice_ws.send(&GameMessage::ClanKick { target: bob.user_id }).await;
+    assert_eq!(bob_ws.expect_clan_left(bob.user_id).await, ClanLeaveReason::Kicked);
+    assert_eq!(alice_ws.expect_clan_left(bob.user_id).await, ClanLeaveReason::Kicked);
+    assert_eq!(dave_ws.expect_clan_tag(bob.user_id).await, None);
+
+    alice_ws.send(&GameMessage::ClanDisband).await;
+    let msg = alice_ws.recv_until(|msg| matches!(msg, GameMessage::ClanDisbanded { .. })).await;
+    assert!(matches!(msg, GameMessage::ClanDisbanded { clan: id } if id == clan.id));
+    assert_eq!(carol_ws.expect_clan_tag(alice.user_id).await, None);
+}
+
+#[tokio::test]
+async fn purged_accounts_leave_their_clans() {
+    let storage = Storage::in_memory();
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage.clone(), config()))).await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let carol = server.register_user("carol").await;
+    let dave = server.register_user("dave").await;
+    let erin = server.register_user("erin").await;
+
+    let owls = storage.clans.create_clan("Owls", "OWL", alice.user_id).await.unwrap();
+    for user in [&bob, &carol] {
+        assert!(storage.clans.invite(owls, user.user_id, alice.user_id, &[]).await.unwrap());
+        assert!(storage.clans.accept_invite(owls, user.user_id).await.unwrap());
+    }
+    assert!(storage.clans.set_ranks(owls, &[(carol.user_id, ClanRank::Officer)], &[]).await.unwrap());
+    let hawks = storage.clans.create_clan("Hawks", "HWK", dave.user_id).await.unwrap();
+    assert!(storage.clans.invite(hawks, erin.user_id, dave.user_id, &[]).await.unwrap());
+
+    for user in [&alice, &dave] {
+        storage.users.request_deletion(user.user_id).await.unwrap();
+    }
+    assert_eq!(storage.users.purge_deleted_before(Utc::now()).await.unwrap(), 2);
+
+    // Лидером становится старший по рангу, опустевший клан удаляется вместе с приглашениями
+    let clan = storage.clans.load_clan(owls).await.unwrap().unwrap();
+    assert_eq!(ranks(&clan), vec![(bob.user_id, ClanRank::Recruit), (carol.user_id, ClanRank::Leader)]);
+    assert!(storage.clans.load_clan(hawks).await.unwrap().is_none());
+    assert!(storage.clans.invites_for(erin.user_id).await.unwrap().is_empty());
+}
+
+// Кланы, состав которых читается с задержкой: параллельные запросы успевают проверить права
+// по одному и тому же составу
+struct SlowClans(Arc<dyn ClanRepository>);
+
+#[async_trait]
+impl ClanRepository for SlowClans {
+    async fn create_clan(&self, name: &str, tag: &str, founder: i32) -> StorageResult<ClanId> {
+        self.0.create_clan(name, tag, founder).await
+    }
+
+    async fn load_clan(&self, clan: ClanId) -> StorageResult<Option<Clan>> {
+        let loaded = self.0.load_clan(clan).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        loaded
+    }
+
+    async fn clan_of(&self, user_id: i32) -> StorageResult<Option<ClanId>> {
+        self.0.clan_of(user_id).await
+    }
+
+    async fn delete_clan(&self, clan: ClanId, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        self.0.delete_clan(clan, expected).await
+    }
+
+    async fn invite(&self, clan: ClanId, user_id: i32, invited_by: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        self.0.invite(clan, user_id, invited_by, expected).await
+    }
+
+    async fn invites_for(&self, user_id: i32) -> StorageResult<Vec<ClanInvite>> {
+        self.0.invites_for(user_id).await
+    }
+
+    async fn accept_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool> {
+        self.0.accept_invite(clan, user_id).await
+    }
+
+    async fn decline_invite(&self, clan: ClanId, user_id: i32) -> StorageResult<bool> {
+        self.0.decline_invite(clan, user_id).await
+    }
+
+    async fn remove_member(&self, clan: ClanId, user_id: i32, expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        self.0.remove_member(clan, user_id, expected).await
+    }
+
+    async fn set_ranks(&self, clan: ClanId, ranks: &[(i32, ClanRank)], expected: &[(i32, ClanRank)]) -> StorageResult<bool> {
+        self.0.set_ranks(clan, ranks, expected).await
+    }
+}
+
+#[tokio::test]
+async fn concurrent_clan_changes_are_checked_against_current_ranks() {
+    let mut storage = Storage::in_memory();
+    let owls = storage.clans.clone();
+    storage.clans = Arc::new(SlowClans(storage.clans.clone()));
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage, config()))).await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let carol = server.register_user("carol").await;
+    let dave = server.register_user("dave").await;
+    let clan = owls.create_clan("Owls", "OWL", alice.user_id).await.unwrap();
+    for user in [&bob, &carol, &dave] {
+        assert!(owls.invite(clan, user.user_id, alice.user_id, &[]).await.unwrap());
+        assert!(owls.accept_invite(clan, user.user_id).await.unwrap());
+    }
+    assert!(owls.set_ranks(clan, &[(carol.user_id, ClanRank::Officer)], &[]).await.unwrap());
+
+    // Лидерство передается дважды одновременно: проходит только одна передача
+    let to_bob = format!("/clan/members/{}/rank", bob.user_id);
+    let to_carol = format!("/clan/members/{}/rank", carol.user_id);
+    let (first, second) = tokio::join!(
+        server.call(&alice, Method::PUT, &to_bob, Some(json!({ "rank": "leader" }))),
+        server.call(&alice, Method::PUT, &to_carol, Some(json!({ "rank": "leader" }))),
+    );
+    let mut statuses = [first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::NO_CONTENT, StatusCode::CONFLICT]);
+    let leaders = owls.load_clan(clan).await.unwrap().unwrap().members.iter().filter(|member| member.rank == ClanRank::Leader).count();
+    assert_eq!(leaders, 1);
+
+    // Исключение, проверенное до повышения dave, не применяется после него
+    let leader = if first.status() == StatusCode::NO_CONTENT { &bob } else { &carol };
+    let officer = if first.status() == StatusCode::NO_CONTENT { &carol } else { &alice };
+    let promote = format!("/clan/members/{}/rank", dave.user_id);
+    let kick = format!("/clan/members/{}", dave.user_id);
+    let (promoted, kicked) = tokio::join!(
+        server.call(leader, Method::PUT, &promote, Some(json!({ "rank": "officer" }))),
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            server.call(officer, Method::DELETE, &kick, None).await
+        },
+    );
+    assert_eq!(promoted.status(), StatusCode::NO_CONTENT);
+    assert_eq!(kicked.status(), StatusCode::CONFLICT);
+    let clan = owls.load_clan(clan).await.unwrap().unwrap();
+    assert_eq!(clan.member(dave.user_id).map(|member| member.rank), Some(ClanRank::Officer));
+}
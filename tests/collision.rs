@@ -0,0 +1,102 @@
+// tests/collision.rs
+use anarchy_core::collision::{resolve_move, CollisionWorld, Polygon, MAX_MOVE_DISTANCE, PLAYER_RADIUS};
+use anarchy_core::routes::game::ErrorCode;
+use anarchy_core::testing::TestServer;
+use anarchy_core::tiles;
+use std::collections::HashSet;
+
+#[derive(Default)]
+struct Grid {
+    solid: HashSet<(i32, i32)>,
+    polygons: Vec<Polygon>,
+}
+
+impl CollisionWorld for Grid {
+    fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.solid.contains(&(x, y))
+    }
+
+    fn polygons(&self) -> &[Polygon] {
+        &self.polygons
+    }
+}
+
+// Вертикальная стена из тайлов x = 5
+fn wall() -> Grid {
+    Grid { solid: (-10..10).map(|y| (5, y)).collect(), ..Grid::default() }
+}
+
+fn assert_near(actual: (f64, f64), expected: (f64, f64)) {
+    assert!(
+        (actual.0 - expected.0).abs() < 0.01 && (actual.1 - expected.1).abs() < 0.01,
+        "expected {:?}, got {:?}", expected, actual
+    );
+}
+
+#[test]
+fn moves_stop_at_walls_and_slide_along_them() {
+    let grid = wall();
+    assert_eq!(resolve_move(&grid, (2.5, 0.5), (4.5, 3.25), PLAYER_RADIUS), (4.5, 3.25));
+    assert_near(resolve_move(&grid, (2.5, 0.5), (8.5, 0.5), PLAYER_RADIUS), (5.0 - PLAYER_RADIUS, 0.5));
+    // Диагональное движение в стену продолжается вдоль нее
+    assert_near(resolve_move(&grid, (2.5, 0.5), (8.5, 3.5), PLAYER_RADIUS), (5.0 - PLAYER_RADIUS, 3.5));
+
+    // Тот же результат дает стена-полигон
+    let polygons = Grid { polygons: vec![Polygon::rectangle(5.0, -10.0, 1.0, 20.0)], ..Grid::default() };
+    assert_near(resolve_move(&polygons, (2.5, 0.5), (8.5, 3.5), PLAYER_RADIUS), (5.0 - PLAYER_RADIUS, 3.5));
+
+    let triangle = Polygon::new(vec![(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)]).unwrap();
+    assert!(triangle.contains((1.0, 1.0)));
+    assert!(!triangle.contains((3.0, 3.0)));
+    assert!(Polygon::new(vec![(0.0, 0.0), (1.0, 1.0)]).is_none());
+}
+
+#[test]
+fn players_inside_geometry_can_walk_out() {
+    let mut grid = wall();
+    grid.solid.insert((0, 0));
+    // Тайл поставили на игрока: выйти из него можно, но стена дальше по-прежнему держит
+    assert_eq!(resolve_move(&grid, (0.5, 0.5), (3.5, 0.5), PLAYER_RADIUS), (3.5, 0.5));
+    assert_near(resolve_move(&grid, (0.5, 0.5), (7.5, 0.5), PLAYER_RADIUS), (5.0 - PLAYER_RADIUS, 0.5));
+}
+
+#[test]
+fn implausible_moves_are_rejected() {
+    let grid = Grid::default();
+    assert_eq!(resolve_move(&grid, (0.5, 0.5), (MAX_MOVE_DISTANCE + 1.0, 0.5), PLAYER_RADIUS), (0.5, 0.5));
+    assert_eq!(resolve_move(&grid, (0.5, 0.5), (f64::NAN, 0.5), PLAYER_RADIUS), (0.5, 0.5));
+    assert_eq!(resolve_move(&grid, (0.5, 0.5), (f64::INFINITY, 0.5), PLAYER_RADIUS), (0.5, 0.5));
+    assert_eq!(resolve_move(&grid, (0.5, 0.5), (100.5, -50.0), PLAYER_RADIUS), (100.5, -50.0));
+}
+
+#[tokio::test]
+async fn server_corrects_moves_into_placed_walls() {
+    let mut config = TestServer::test_config();
+    config.game.view_distance = 1;
+    let server = TestServer::start_with_config(config).await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[tiles::FEATURE]).await;
+    alice_ws.expect_initial_players().await;
+    alice_ws.expect_chunks(9).await;
+    // Bob не запрашивал тайлы, но стены действуют и на него
+    let (bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.place_tile(3, 0, tiles::STONE).await;
+    assert_eq!(alice_ws.expect_tile_changed().await, (3, 0, tiles::STONE));
+    // На игрока препятствие не ставится
+    alice_ws.place_tile(0, 0, tiles::STONE).await;
+    alice_ws.expect_error(ErrorCode::InvalidTileAction).await;
+
+    bob_ws.send_position(6.5, 0.5, 1.0).await;
+    let corrected = alice_ws.expect_position_of(bob.user_id).await;
+    assert!((corrected.x - (3.0 - PLAYER_RADIUS)).abs() < 0.01, "bob went through the wall: {:?}", corrected);
+    assert_eq!((corrected.y, corrected.z), (0.5, 1.0));
+
+    // В обход стены пройти можно
+    bob_ws.send_position(2.5, 1.5, 1.0).await;
+    let moved = alice_ws.expect_position_of(bob.user_id).await;
+    assert_eq!((moved.x, moved.y), (2.5, 1.5));
+    bob_ws.send_position(6.5, 1.5, 1.0).await;
+    let moved = alice_ws.expect_position_of(bob.user_id).await;
+    assert_eq!((moved.x, moved.y), (6.5, 1.5));
+}
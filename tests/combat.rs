@@ -0,0 +1,173 @@
+// tests/combat.rs
+use anarchy_core::config::Config;
+use anarchy_core::map;
+use anarchy_core::models::player::Vitals;
+use anarchy_core::routes::game::ErrorCode;
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::TestServer;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn combat_config(damage: i32, cooldown_ms: u64, respawn_delay_secs: u64) -> Config {
+    let mut config = TestServer::test_config();
+    config.game.max_health = 100;
+    config.game.attack_damage = damage;
+    config.game.attack_cooldown_ms = cooldown_ms;
+    config.game.respawn_delay_secs = respawn_delay_secs;
+    config
+}
+
+#[tokio::test]
+async fn players_fight_die_and_respawn_at_the_nearest_point() {
+    let mut config = combat_config(40, 0, 1);
+    config.game.respawn_points.insert("lobby".to_string(), vec![(-20.5, 0.5), (10.5, 0.5)]);
+    let server = TestServer::start_with_config(config).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    assert_eq!(bob_ws.vitals(), Vitals::full(100));
+
+    bob_ws.send_position(1.5, 0.5, 0.0).await;
+    alice_ws.expect_position_of(bob.user_id).await;
+    // Урон не превышает оставшееся здоровье
+    for (amount, health) in [(40, 60), (40, 20), (20, 0)] {
+        alice_ws.attack(bob.user_id).await;
+        assert_eq!(bob_ws.expect_damaged(bob.user_id).await, (alice.user_id, amount, health));
+    }
+    assert_eq!(alice_ws.expect_died(bob.user_id).await, alice.user_id);
+    assert_eq!(server.state().storage.players.load_vitals(bob.user_id).await.unwrap(), Some(Vitals { health: 0, max_health: 100 }));
+
+    // До возрождения погибший не действует, и добить его нельзя
+    bob_ws.attack(alice.user_id).await;
+    bob_ws.expect_error(ErrorCode::PlayerDead).await;
+    bob_ws.change_room("arena").await;
+    bob_ws.expect_error(ErrorCode::PlayerDead).await;
+    alice_ws.attack(bob.user_id).await;
+    alice_ws.expect_error(ErrorCode::InvalidTarget).await;
+
+    assert_eq!(alice_ws.expect_respawned(bob.user_id).await, (10.5, 0.5, 100));
+    let respawned = alice_ws.expect_position_of(bob.user_id).await;
+    assert_eq!((respawned.x, respawned.y), (10.5, 0.5));
+    assert_eq!(server.state().storage.players.load_vitals(bob.user_id).await.unwrap(), Some(Vitals::full(100)));
+    let player = server.state().storage.players.load_player(bob.user_id).await.unwrap().unwrap();
+    assert_eq!((player.x, player.y), (10.5, 0.5));
+}
+
+#[tokio::test]
+async fn attacks_are_validated_by_the_server() {
+    let mut config = combat_config(10, 60_000, 5);
+    config.game.attack_range = 2.0;
+    let server = TestServer::start_with_config(config).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.attack(alice.user_id).await;
+    alice_ws.expect_error(ErrorCode::InvalidTarget).await;
+    alice_ws.attack(bob.user_id + 100).await;
+    alice_ws.expect_error(ErrorCode::InvalidTarget).await;
+
+    bob_ws.send_position(3.5, 0.5, 0.0).await;
+    alice_ws.expect_position_of(bob.user_id).await;
+    alice_ws.attack(bob.user_id).await;
+    alice_ws.expect_error(ErrorCode::OutOfReach).await;
+
+    bob_ws.send_position(2.0, 0.5, 0.0).await;
+    alice_ws.expect_position_of(bob.user_id).await;
+    alice_ws.attack(bob.user_id).await;
+    assert_eq!(alice_ws.expect_damaged(bob.user_id).await, (alice.user_id, 10, 90));
+    alice_ws.attack(bob.user_id).await;
+    alice_ws.expect_error(ErrorCode::OnCooldown).await;
+}
+
+#[tokio::test]
+async fn health_and_death_survive_reconnects() {
+    let mut config = combat_config(30, 0, 1);
+    config.game.respawn_points.insert("lobby".to_string(), vec![(-5.5, 0.5)]);
+    let server = TestServer::start_with_config(config).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let bob = server.register_user("bob").await;
+    let mut bob_ws = server.connect(&bob).await;
+    bob_ws.expect_initial_players().await;
+
+    alice_ws.attack(bob.user_id).await;
+    alice_ws.expect_damaged(bob.user_id).await;
+    bob_ws.close().await;
+    alice_ws.expect_disconnected(bob.user_id).await;
+    let mut bob_ws = server.connect(&bob).await;
+    assert_eq!(bob_ws.vitals(), Vitals { health: 70, max_health: 100 });
+    bob_ws.expect_initial_players().await;
+
+    for _ in 0..3 {
+        alice_ws.attack(bob.user_id).await;
+        alice_ws.expect_damaged(bob.user_id).await;
+    }
+    alice_ws.expect_died(bob.user_id).await;
+    // Переподключение не сокращает задержку возрождения
+    bob_ws.close().await;
+    alice_ws.expect_disconnected(bob.user_id).await;
+    let mut bob_ws = server.connect(&bob).await;
+    assert_eq!(bob_ws.vitals(), Vitals { health: 0, max_health: 100 });
+    bob_ws.expect_initial_players().await;
+    bob_ws.attack(alice.user_id).await;
+    bob_ws.expect_error(ErrorCode::PlayerDead).await;
+    assert_eq!(bob_ws.expect_respawned(bob.user_id).await, (-5.5, 0.5, 100));
+
+    // Если задержка прошла, пока игрок был вне игры, он сразу появляется в точке возрождения
+    bob_ws.send_position(2.5, 0.5, 0.0).await;
+    alice_ws.expect_position_of(bob.user_id).await;
+    for _ in 0..4 {
+        alice_ws.attack(bob.user_id).await;
+        alice_ws.expect_damaged(bob.user_id).await;
+    }
+    alice_ws.expect_died(bob.user_id).await;
+    bob_ws.close().await;
+    alice_ws.expect_disconnected(bob.user_id).await;
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    let mut bob_ws = server.connect(&bob).await;
+    assert_eq!(bob_ws.vitals(), Vitals::full(100));
+    let players = bob_ws.expect_initial_players().await;
+    let revived = players.iter().find(|p| p.user_id == bob.user_id).unwrap();
+    assert_eq!((revived.x, revived.y), (-5.5, 0.5));
+    assert!(players.iter().any(|p| p.user_id == alice.user_id));
+}
+
+// Карта 8x6 тайлов: безопасная зона в углу и точка возрождения
+fn safe_map() -> String {
+    json!({
+        "width": 8, "height": 6, "tilewidth": 16, "tileheight": 16,
+        "orientation": "orthogonal", "infinite": false,
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 8, "height": 6, "data": vec![0; 48]},
+            {"type": "objectgroup", "name": "objects", "objects": [
+                {"id": 1, "name": "default", "class": "spawn", "x": 24, "y": 24, "point": true},
+                {"id": 2, "name": "safe", "class": "zone", "x": 0, "y": 0, "width": 48, "height": 48,
+                 "properties": [{"name": "pvp", "type": "bool", "value": false}]},
+                {"id": 3, "name": "graveyard", "class": "respawn", "x": 104, "y": 72, "point": true},
+            ]},
+        ],
+    }).to_string()
+}
+
+#[tokio::test]
+async fn safe_zones_block_attacks_and_maps_provide_respawn_points() {
+    let path = std::env::temp_dir().join(format!("anarchy_core_{}_safe.tmj", std::process::id()));
+    std::fs::write(&path, safe_map()).unwrap();
+    let mut config = combat_config(100, 0, 0);
+    config.game.maps.insert("lobby".to_string(), path);
+    let maps = map::load_maps(&config.game).unwrap();
+    let server = TestServer::start_with_state(Arc::new(AppState::with_maps(Storage::in_memory(), config, maps))).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.attack(bob.user_id).await;
+    assert!(alice_ws.expect_error(ErrorCode::InvalidTarget).await.contains("zone"));
+
+    alice_ws.send_position(4.5, 1.5, 0.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    bob_ws.send_position(5.5, 1.5, 0.0).await;
+    alice_ws.expect_position_of(bob.user_id).await;
+    alice_ws.attack(bob.user_id).await;
+    assert_eq!(alice_ws.expect_died(bob.user_id).await, alice.user_id);
+    assert_eq!(bob_ws.expect_respawned(bob.user_id).await, (6.5, 4.5, 100));
+}
@@ -0,0 +1,163 @@
+// tests/config.rs
+use anarchy_core::config::{Config, ConfigError, LogFormat};
+use std::path::PathBuf;
+
+fn write_config(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("anarchy_core_{}_{}.toml", name, std::process::id()));
+    std::fs::write(&path, contents).expect("Failed to write temp config");
+    path
+}
+
+#[test]
+fn example_config_is_valid() {
+    let config = Config::from_file("config.example.toml".as_ref()).expect("config.example.toml must parse");
+    config.validate().expect("config.example.toml must pass validation");
+    assert_eq!(config.bind_address().unwrap().port(), 3000);
+}
+
+#[test]
+fn file_values_override_defaults() {
+    let path = write_config("override", r#"
+        [server]
+        bind_address = "127.0.0.1:4000"
+
+        [database]
+        url = "postgres://localhost/test"
+        max_connections = 20
+
+        [auth]
+        jwt_secret = "secret"
+
+        [game]
+        tick_rate = 30
+    "#);
+    let config = Config::from_file(&path).unwrap();
+    assert_eq!(config.server.bind_address, "127.0.0.1:4000");
+    assert_eq!(config.database.max_connections, 20);
+    assert_eq!(config.game.tick_rate, 30);
+    // Не указанные в файле значения берутся по умолчанию
+    assert_eq!(config.game.broadcast_capacity, 128);
+    assert_eq!(config.auth.token_lifetime_hours, 24);
+    config.validate().unwrap();
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let path = write_config("unknown", "[server]\nbind_adress = \"0.0.0.0:3000\"\n");
+    assert!(matches!(Config::from_file(&path), Err(ConfigError::Parse(..))));
+}
+
+#[test]
+fn validation_reports_missing_and_invalid_settings() {
+    let mut config = Config::default();
+    assert!(matches!(config.validate(), Err(ConfigError::Missing(_))));
+
+    config.database.url = "postgres://localhost/test".to_string();
+    config.auth.jwt_secret = "secret".to_string();
+    config.validate().unwrap();
+
+    config.server.bind_address = "not-an-address".to_string();
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "server.bind_address", .. })));
+
+    config.server.bind_address = "0.0.0.0:3000".to_string();
+    config.server.tls_cert_path = Some("cert.pem".into());
+    assert!(matches!(config.validate(), Err(ConfigError::Missing("server.tls_key_path"))));
+}
+
+#[test]
+fn log_settings_are_parsed_and_validated() {
+    let path = write_config("log", r#"
+        [database]
+        url = "postgres://localhost/test"
+
+        [auth]
+        jwt_secret = "secret"
+
+        [log]
+        level = "anarchy_core=debug,tower_http=info"
+        format = "json"
+    "#);
+    let mut config = Config::from_file(&path).unwrap();
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert_eq!(config.log.position_sample_rate, 100);
+    config.validate().unwrap();
+
+    config.log.position_sample_rate = 0;
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "log.position_sample_rate", .. })));
+
+    config.log.position_sample_rate = 1;
+    config.log.level = "anarchy_core=loud".to_string();
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "log.level", .. })));
+}
+
+#[test]
+fn world_settings_are_validated() {
+    let path = write_config("worlds", r#"
+        [database]
+        url = "postgres://localhost/test"
+
+        [auth]
+        jwt_secret = "secret"
+
+        [game]
+        worlds = ["lobby", "arena"]
+        default_world = "arena"
+    "#);
+    let mut config = Config::from_file(&path).unwrap();
+    assert_eq!(config.game.max_rooms, 1000);
+    config.validate().unwrap();
+
+    config.game.default_world = "dungeon".to_string();
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.default_world", .. })));
+
+    config.game.default_world = "lobby".to_string();
+    config.game.worlds.push("bad name".to_string());
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.worlds", .. })));
+
+    config.game.worlds.pop();
+    config.game.max_rooms = 1;
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.max_rooms", .. })));
+
+    config.game.max_rooms = 1000;
+    config.game.maps.insert("arena".to_string(), path.with_extension("missing.tmx"));
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.maps", .. })));
+    config.game.maps.clear();
+    config.game.maps.insert("bad name".to_string(), path.clone());
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.maps", .. })));
+
+    config.game.maps.clear();
+    config.game.max_health = 0;
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.max_health", .. })));
+    config.game.max_health = 100;
+    config.game.attack_range = f64::NAN;
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.attack_range", .. })));
+    config.game.attack_range = 2.0;
+    config.game.respawn_points.insert("lobby".to_string(), vec![(0.5, f64::INFINITY)]);
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.respawn_points", .. })));
+
+    config.game.respawn_points.clear();
+    config.game.items_path = Some(path.with_extension("missing.toml"));
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.items_path", .. })));
+    config.game.items_path = None;
+    config.game.npcs_path = Some(path.with_extension("missing.toml"));
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.npcs_path", .. })));
+    config.game.npcs_path = None;
+    config.game.scripts_dir = Some(path.with_extension("missing"));
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.scripts_dir", .. })));
+    config.game.scripts_dir = Some(path.clone());
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.scripts_dir", .. })));
+    config.game.scripts_dir = None;
+    config.game.inventory_slots = 0;
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.inventory_slots", .. })));
+    config.game.inventory_slots = 20;
+    config.game.max_party_size = 1;
+    assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "game.max_party_size", .. })));
+    config.game.max_party_size = 5;
+
+    config.auth.deletion_grace_days = 3650;
+    config.validate().unwrap();
+    for days in [-1, 3651, i64::MAX] {
+        config.auth.deletion_grace_days = days;
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid { key: "auth.deletion_grace_days", .. })));
+    }
+}
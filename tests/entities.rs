@@ -0,0 +1,118 @@
+// tests/entities.rs
+use anarchy_core::entities::{self, Components, Entity, EntityKind, EntityStore};
+use anarchy_core::routes::game::GameMessage;
+use anarchy_core::testing::TestServer;
+use anarchy_core::tiles;
+use std::time::{Duration, Instant};
+
+fn projectile(position: (f64, f64), velocity: (f64, f64)) -> Entity {
+    let mut entity = Entity::new(EntityKind::Projectile, position, Components { owner: Some(1), ..Default::default() });
+    (entity.vx, entity.vy) = velocity;
+    entity
+}
+
+#[test]
+fn entity_store_assigns_ids_moves_and_expires_entities() {
+    let mut store = EntityStore::default();
+    let npc = Entity::new(EntityKind::Npc, (1.0, 1.0), Components { name: Some("wolf".to_string()), ..Default::default() });
+    let npc = store.insert(npc).id;
+    let arrow = store.insert(projectile((0.0, 0.0), (10.0, -2.0))).id;
+    assert_eq!((npc, arrow), (1, 2));
+    assert_eq!(store.get(npc).unwrap().components.name.as_deref(), Some("wolf"));
+
+    // Изменяются движущиеся и явно измененные сущности
+    let now = Instant::now();
+    let (changed, expired) = store.step(0.5, now);
+    assert!(expired.is_empty());
+    assert_eq!(changed.iter().map(|entity| (entity.id, entity.x, entity.y)).collect::<Vec<_>>(), vec![(arrow, 5.0, -1.0)]);
+    store.update(npc, |entity| entity.x = 3.0);
+    store.update(arrow, |entity| (entity.vx, entity.vy, entity.expires_at) = (0.0, 0.0, Some(now)));
+    let (changed, expired) = store.step(0.5, now);
+    assert_eq!((changed.len(), changed[0].id, changed[0].x), (1, npc, 3.0));
+    assert_eq!(expired, vec![arrow]);
+    assert!(store.step(0.5, now).0.is_empty());
+
+    // id не переиспользуются
+    assert!(store.remove(npc).is_some());
+    assert!(store.is_empty());
+    assert_eq!(store.insert(projectile((0.0, 0.0), (0.0, 0.0))).id, 3);
+}
+
+#[test]
+fn entities_serialize_only_present_components() {
+    let mut entity = projectile((1.5, 2.5), (3.0, 0.0));
+    entity.id = 7;
+    entity.expires_at = Some(Instant::now());
+    let json = serde_json::to_value(&entity).unwrap();
+    assert_eq!(json, serde_json::json!({
+        "id": 7, "kind": "projectile", "x": 1.5, "y": 2.5, "z": 0.0,
+        "rotation": 0.0, "vx": 3.0, "vy": 0.0, "vz": 0.0, "owner": 1,
+    }));
+}
+
+#[tokio::test]
+async fn entities_are_replicated_to_clients_with_the_feature() {
+    let server = TestServer::start().await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[entities::FEATURE]).await;
+    alice_ws.expect_initial_players().await;
+    assert!(alice_ws.expect_initial_entities().await.is_empty());
+    let (_bob, mut bob_ws) = server.join("bob").await;
+
+    let room = server.state().rooms.get("lobby").unwrap();
+    let mut arrow = projectile((0.5, 0.5), (4.0, 0.0));
+    arrow.expires_at = Some(Instant::now() + Duration::from_millis(300));
+    let arrow = entities::spawn(&room, arrow).await;
+    // Срок жизни клиенту не передается
+    assert_eq!(alice_ws.expect_entity_spawned().await, Entity { expires_at: None, ..arrow.clone() });
+
+    // Снаряд летит сам и исчезает по истечении срока
+    let moved = alice_ws.expect_entity_updated(arrow.id).await;
+    assert!(moved.x > arrow.x && moved.y == arrow.y, "{:?}", moved);
+    alice_ws.expect_entity_despawned(arrow.id).await;
+    assert!(room.entities.lock().await.get(arrow.id).is_none());
+
+    // Опоздавший клиент получает сущности комнаты при входе
+    let wolf = Entity::new(EntityKind::Npc, (2.0, 3.0), Components { name: Some("wolf".to_string()), ..Default::default() });
+    let wolf = entities::spawn(&room, wolf).await;
+    let carol = server.register_user("carol").await;
+    let mut carol_ws = server.connect_with_features(&carol, &[entities::FEATURE]).await;
+    carol_ws.expect_initial_players().await;
+    assert_eq!(carol_ws.expect_initial_entities().await, vec![wolf.clone()]);
+    assert_eq!(entities::despawn(&room, wolf.id).await.map(|entity| entity.id), Some(wolf.id));
+    carol_ws.expect_entity_despawned(wolf.id).await;
+
+    // Клиенты без возможности "entities" сущностей не видят
+    bob_ws.expect_silence(Duration::from_millis(200)).await;
+}
+
+#[tokio::test]
+async fn entity_updates_follow_the_view_of_tile_clients() {
+    let mut config = TestServer::test_config();
+    config.game.view_distance = 1;
+    let server = TestServer::start_with_config(config).await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[tiles::FEATURE, entities::FEATURE]).await;
+    alice_ws.expect_initial_players().await;
+    alice_ws.expect_initial_entities().await;
+    alice_ws.expect_chunks(9).await;
+    let bob = server.register_user("bob").await;
+    let mut bob_ws = server.connect_with_features(&bob, &[entities::FEATURE]).await;
+    bob_ws.expect_initial_players().await;
+    bob_ws.expect_initial_entities().await;
+
+    // Волк в чанке (2, 0), за пределами видимых alice чанков
+    let room = server.state().rooms.get("lobby").unwrap();
+    let wolf = Entity::new(EntityKind::Npc, (80.5, 0.5), Components { name: Some("wolf".to_string()), ..Default::default() });
+    let wolf = entities::spawn(&room, wolf).await;
+    room.entities.lock().await.update(wolf.id, |entity| entity.x = 81.5);
+    assert_eq!(bob_ws.expect_entity_updated(wolf.id).await.x, 81.5);
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+    while let Some(msg) = alice_ws.try_recv(deadline.saturating_duration_since(tokio::time::Instant::now())).await {
+        assert!(!matches!(msg, GameMessage::EntityUpdated(_)), "Unexpected {:?}", msg);
+    }
+
+    // Когда чанк становится видимым, alice получает текущее состояние волка
+    alice_ws.send_position(40.0, 0.5, 0.0).await;
+    assert_eq!(alice_ws.expect_entity_updated(wolf.id).await.x, 81.5);
+}
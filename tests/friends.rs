@@ -0,0 +1,146 @@
+// tests/friends.rs
+use anarchy_core::friends::{FriendList, FriendRequest};
+use anarchy_core::items::{self, ItemStack};
+use anarchy_core::routes::game::GameMessage;
+use anarchy_core::testing::{TestServer, TestUser};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+async fn friend_list(server: &TestServer, user: &TestUser) -> FriendList {
+    server.authorized(user, Method::GET, "/friends").send().await.unwrap().json().await.unwrap()
+}
+
+fn requesters(requests: &[FriendRequest]) -> Vec<(i32, &str)> {
+    requests.iter().map(|request| (request.user_id, request.login.as_str())).collect()
+}
+
+#[tokio::test]
+async fn friend_requests_are_sent_accepted_declined_and_removed() {
+    let server = TestServer::start().await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let carol = server.register_user("carol").await;
+    let request = |user: &TestUser| Some(json!({ "user_id": user.user_id }));
+
+    assert_eq!(server.call(&alice, Method::POST, "/friends/requests", request(&alice)).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.call(&alice, Method::POST, "/friends/requests", Some(json!({ "user_id": 999 }))).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(server.call(&alice, Method::POST, "/friends/requests", request(&bob)).await.status(), StatusCode::CREATED);
+    // Между двумя игроками только один запрос, в каком бы направлении он ни был
+    assert_eq!(server.call(&alice, Method::POST, "/friends/requests", request(&bob)).await.status(), StatusCode::CONFLICT);
+    assert_eq!(server.call(&bob, Method::POST, "/friends/requests", request(&alice)).await.status(), StatusCode::CONFLICT);
+    assert_eq!(requesters(&friend_list(&server, &alice).await.outgoing), vec![(bob.user_id, "bob")]);
+    assert_eq!(requesters(&friend_list(&server, &bob).await.incoming), vec![(alice.user_id, "alice")]);
+
+    let accept = format!("/friends/requests/{}/accept", alice.user_id);
+    assert_eq!(server.call(&carol, Method::POST, &accept, None).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(server.call(&bob, Method::POST, &accept, None).await.status(), StatusCode::NO_CONTENT);
+    let list = friend_list(&server, &alice).await;
+    assert!(list.incoming.is_empty() && list.outgoing.is_empty());
+    assert_eq!(list.friends.len(), 1);
+    assert_eq!((list.friends[0].user_id, list.friends[0].online), (bob.user_id, false));
+
+    // Статус онлайн берется из сессии друга
+    let bob_ws = server.connect(&bob).await;
+    let list = friend_list(&server, &alice).await;
+    assert!(list.friends[0].online);
+    bob_ws.close().await;
+
+    // Отклоненный запрос исчезает у обоих
+    assert_eq!(server.call(&carol, Method::POST, "/friends/requests", request(&alice)).await.status(), StatusCode::CREATED);
+    let decline = format!("/friends/requests/{}/decline", carol.user_id);
+    assert_eq!(server.call(&alice, Method::POST, &decline, None).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(server.call(&alice, Method::POST, &decline, None).await.status(), StatusCode::NOT_FOUND);
+    assert!(friend_list(&server, &carol).await.outgoing.is_empty());
+
+    let export: Value = server.authorized(&bob, Method::GET, "/account/export").send().await.unwrap().json().await.unwrap();
+    assert_eq!(export["friends"][0]["login"], "alice");
+
+    let remove = format!("/friends/{}", alice.user_id);
+    assert_eq!(server.call(&bob, Method::DELETE, &remove, None).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(server.call(&bob, Method::DELETE, &remove, None).await.status(), StatusCode::NOT_FOUND);
+    assert!(friend_list(&server, &alice).await.friends.is_empty());
+}
+
+#[tokio::test]
+async fn friends_are_notified_when_a_player_connects_and_disconnects() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (_carol, mut carol_ws) = server.join("carol").await;
+    let bob = server.register_user("bob").await;
+    assert_eq!(server.call(&bob, Method::POST, "/friends/requests", Some(json!({ "user_id": alice.user_id }))).await.status(), StatusCode::CREATED);
+    let accept = format!("/friends/requests/{}/accept", bob.user_id);
+    assert_eq!(server.call(&alice, Method::POST, &accept, None).await.status(), StatusCode::NO_CONTENT);
+
+    let mut bob_ws = server.connect(&bob).await;
+    bob_ws.expect_initial_players().await;
+    alice_ws.expect_friend_online(bob.user_id).await;
+    bob_ws.close().await;
+    alice_ws.expect_friend_offline(bob.user_id).await;
+
+    // Не друзьям уведомления не приходят
+    alice_ws.chat("bye").await;
+    let msg = carol_ws.recv_until(|msg| {
+        matches!(msg, GameMessage::ChatMessage { .. } | GameMessage::FriendOnline { .. } | GameMessage::FriendOffline { .. })
+    }).await;
+    assert!(matches!(msg, GameMessage::ChatMessage { .. }), "unexpected {:?}", msg);
+}
+
+#[tokio::test]
+async fn players_online_together_are_notified_when_they_become_friends() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    assert_eq!(server.call(&bob, Method::POST, "/friends/requests", Some(json!({ "user_id": alice.user_id }))).await.status(), StatusCode::CREATED);
+    let accept = format!("/friends/requests/{}/accept", bob.user_id);
+    assert_eq!(server.call(&alice, Method::POST, &accept, None).await.status(), StatusCode::NO_CONTENT);
+    alice_ws.expect_friend_online(bob.user_id).await;
+    bob_ws.expect_friend_online(alice.user_id).await;
+}
+
+#[tokio::test]
+async fn friends_go_offline_only_with_the_last_connection() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let bob = server.register_user("bob").await;
+    assert_eq!(server.call(&bob, Method::POST, "/friends/requests", Some(json!({ "user_id": alice.user_id }))).await.status(), StatusCode::CREATED);
+    let accept = format!("/friends/requests/{}/accept", bob.user_id);
+    assert_eq!(server.call(&alice, Method::POST, &accept, None).await.status(), StatusCode::NO_CONTENT);
+
+    let stone = ItemStack { item: "stone".to_string(), count: 10 };
+    server.state().storage.inventories.save_inventory(bob.user_id, &[(0, stone.clone())]).await.unwrap();
+    let mut first_ws = server.connect_with_features(&bob, &[items::FEATURE]).await;
+    first_ws.expect_inventory().await;
+    first_ws.expect_initial_players().await;
+    alice_ws.expect_friend_online(bob.user_id).await;
+    let mut second_ws = server.connect_with_features(&bob, &[items::FEATURE]).await;
+    second_ws.expect_inventory().await;
+    second_ws.expect_initial_players().await;
+
+    // Закрытие одного из двух соединений не выводит игрока из игры: он остается в комнате,
+    // его инвентарь загружен, а друзья не получают FriendOffline
+    first_ws.close().await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    alice_ws.chat("still here?").await;
+    let msg = alice_ws.recv_until(|msg| {
+        matches!(
+            msg,
+            GameMessage::ChatMessage { .. }
+                | GameMessage::FriendOnline { .. }
+                | GameMessage::FriendOffline { .. }
+                | GameMessage::PlayerDisconnected { .. }
+        )
+    }).await;
+    assert!(matches!(msg, GameMessage::ChatMessage { .. }), "unexpected {:?}", msg);
+    assert!(friend_list(&server, &alice).await.friends[0].online);
+    let lobby = server.state().rooms.get("lobby").unwrap();
+    assert!(lobby.active_player_positions.lock().await.contains_key(&bob.user_id));
+    assert!(lobby.combatants.lock().await.contains_key(&bob.user_id));
+    second_ws.move_item(0, 1).await;
+    assert_eq!(second_ws.expect_inventory().await.stacks(), vec![(1, stone)]);
+
+    second_ws.close().await;
+    alice_ws.expect_friend_offline(bob.user_id).await;
+    assert!(!friend_list(&server, &alice).await.friends[0].online);
+    assert!(!lobby.active_player_positions.lock().await.contains_key(&bob.user_id));
+}
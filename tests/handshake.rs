@@ -0,0 +1,62 @@
+// tests/handshake.rs
+use anarchy_core::models::player::Vitals;
+use anarchy_core::routes::game::{ErrorCode, GameMessage, PROTOCOL_VERSION};
+use anarchy_core::testing::TestServer;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+#[tokio::test]
+async fn incompatible_protocol_version_is_rejected() {
+    let server = TestServer::start().await;
+    let alice = server.register_user("alice").await;
+    let mut ws = server.connect_without_handshake(&alice).await;
+
+    ws.hello(PROTOCOL_VERSION + 1, &[]).await;
+    let message = ws.expect_error(ErrorCode::UnsupportedProtocol).await;
+    assert!(message.contains(&PROTOCOL_VERSION.to_string()), "{}", message);
+
+    let frame = ws.expect_closed().await.expect("server must send a close frame");
+    assert_eq!(frame.code, CloseCode::Protocol);
+    assert_eq!(frame.reason, message);
+    // Отклоненный клиент не попадает в игру
+    assert_eq!(server.state().rooms.online_players(), 0);
+    assert_eq!(server.state().storage.sessions.count_online().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn first_message_must_be_hello() {
+    let server = TestServer::start().await;
+    let alice = server.register_user("alice").await;
+    let mut ws = server.connect_without_handshake(&alice).await;
+
+    ws.send_position(1.0, 2.0, 3.0).await;
+    ws.expect_error(ErrorCode::HandshakeRequired).await;
+    let frame = ws.expect_closed().await.expect("server must send a close frame");
+    assert_eq!(frame.code, CloseCode::Protocol);
+}
+
+#[tokio::test]
+async fn unknown_features_are_not_negotiated() {
+    let server = TestServer::start().await;
+    let alice = server.register_user("alice").await;
+    let mut ws = server.connect_without_handshake(&alice).await;
+
+    ws.hello(PROTOCOL_VERSION, &["teleportation"]).await;
+    assert!(ws.expect_welcome().await.is_empty());
+    ws.expect_initial_players().await;
+}
+
+#[tokio::test]
+async fn bad_input_after_handshake_returns_error_and_keeps_connection() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+
+    alice_ws.send_raw("not json".to_string()).await;
+    alice_ws.expect_error(ErrorCode::InvalidMessage).await;
+
+    alice_ws.send(&GameMessage::Welcome { protocol_version: PROTOCOL_VERSION, user_id: alice.user_id, features: vec![], room: "lobby".to_string(), vitals: Vitals::full(100) }).await;
+    alice_ws.expect_error(ErrorCode::UnexpectedMessage).await;
+
+    // Соединение остается рабочим
+    alice_ws.send_position(1.0, 2.0, 3.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+}
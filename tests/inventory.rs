@@ -0,0 +1,211 @@
+// tests/inventory.rs
+use anarchy_core::config::Config;
+use anarchy_core::entities::{self, EntityKind};
+use anarchy_core::items::{self, Inventory, ItemDefinition, ItemRegistry, ItemStack};
+use anarchy_core::map;
+use anarchy_core::routes::game::ErrorCode;
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::{TestClient, TestServer, TestUser};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn definition(id: &str, max_stack: u32, heal: Option<i32>) -> ItemDefinition {
+    ItemDefinition { id: id.to_string(), name: id.to_string(), max_stack, heal }
+}
+
+fn registry() -> ItemRegistry {
+    ItemRegistry::from_definitions(vec![definition("apple", 20, Some(10)), definition("stone", 64, None)]).unwrap()
+}
+
+fn stack(item: &str, count: u32) -> ItemStack {
+    ItemStack { item: item.to_string(), count }
+}
+
+async fn start(config: Config) -> TestServer {
+    let state = AppState::with_maps(Storage::in_memory(), config, HashMap::new()).with_items(registry());
+    TestServer::start_with_state(Arc::new(state)).await
+}
+
+// Подключение с инвентарем; возвращает инвентарь из начальной синхронизации
+async fn connect(server: &TestServer, user: &TestUser) -> (TestClient, Inventory) {
+    let mut ws = server.connect_with_features(user, &[items::FEATURE, entities::FEATURE]).await;
+    let inventory = ws.expect_inventory().await;
+    ws.expect_initial_players().await;
+    ws.expect_initial_entities().await;
+    (ws, inventory)
+}
+
+#[test]
+fn inventories_stack_merge_and_swap_items() {
+    let mut inventory = Inventory::new(3, vec![(1, stack("apple", 15)), (7, stack("stone", 1))]);
+    assert_eq!(inventory.stacks(), vec![(1, stack("apple", 15))]);
+    assert_eq!(inventory.capacity_for("apple", 20), 45);
+    // Сначала дополняется неполная стопка, затем занимаются пустые ячейки
+    assert_eq!(inventory.add("apple", 30, 20), 0);
+    assert_eq!(inventory.stacks(), vec![(0, stack("apple", 20)), (1, stack("apple", 20)), (2, stack("apple", 5))]);
+    assert_eq!(inventory.add("stone", 1, 64), 1);
+
+    // В полную стопку ничего не добавляется
+    inventory.move_stack(2, 1, |_| 20).unwrap();
+    assert_eq!(inventory.get(2), Some(&stack("apple", 5)));
+    assert_eq!(inventory.take(0, 15), Some(stack("apple", 15)));
+    inventory.move_stack(2, 0, |_| 20).unwrap();
+    assert_eq!(inventory.stacks(), vec![(0, stack("apple", 10)), (1, stack("apple", 20))]);
+    assert!(inventory.move_stack(2, 1, |_| 20).is_err());
+    assert!(inventory.move_stack(1, 3, |_| 20).is_err());
+
+    // Разные предметы меняются местами
+    assert_eq!(inventory.add("stone", 1, 64), 0);
+    inventory.move_stack(2, 0, |_| 20).unwrap();
+    assert_eq!(inventory.stacks(), vec![(0, stack("stone", 1)), (1, stack("apple", 20)), (2, stack("apple", 10))]);
+    assert_eq!(inventory.take(1, 100), Some(stack("apple", 20)));
+    assert_eq!(inventory.get(1), None);
+}
+
+#[test]
+fn item_definitions_are_validated() {
+    let shipped = ItemRegistry::load(std::path::Path::new("data/items.toml")).unwrap();
+    assert_eq!(shipped.get("apple").map(|apple| apple.heal), Some(Some(10)));
+    assert_eq!(shipped.max_stack("sword"), 1);
+    assert_eq!(shipped.max_stack("unknown"), 1);
+
+    assert!(ItemRegistry::from_definitions(vec![definition("apple", 1, None), definition("apple", 2, None)]).is_err());
+    assert!(ItemRegistry::from_definitions(vec![definition("", 1, None)]).is_err());
+    assert!(ItemRegistry::from_definitions(vec![definition("rock", 0, None)]).is_err());
+    assert!(ItemRegistry::from_definitions(vec![definition("poison", 1, Some(-5))]).is_err());
+}
+
+#[tokio::test]
+async fn inventory_is_synced_persisted_and_used() {
+    let server = start(TestServer::test_config()).await;
+    let (_alice, mut alice_ws) = server.join("alice").await;
+    let bob = server.register_user("bob").await;
+    let storage = &server.state().storage;
+    storage.inventories.save_inventory(bob.user_id, &[(0, stack("apple", 5)), (3, stack("stone", 10))]).await.unwrap();
+
+    let (mut bob_ws, inventory) = connect(&server, &bob).await;
+    assert_eq!(inventory.stacks(), vec![(0, stack("apple", 5)), (3, stack("stone", 10))]);
+
+    bob_ws.move_item(3, 1).await;
+    assert_eq!(bob_ws.expect_inventory().await.stacks(), vec![(0, stack("apple", 5)), (1, stack("stone", 10))]);
+    bob_ws.move_item(7, 0).await;
+    bob_ws.expect_error(ErrorCode::InvalidItemAction).await;
+    bob_ws.use_item(1).await;
+    bob_ws.expect_error(ErrorCode::InvalidItemAction).await;
+    bob_ws.use_item(0).await;
+    assert!(bob_ws.expect_error(ErrorCode::InvalidItemAction).await.contains("full health"));
+
+    alice_ws.attack(bob.user_id).await;
+    alice_ws.expect_damaged(bob.user_id).await;
+    bob_ws.use_item(0).await;
+    assert_eq!(bob_ws.expect_inventory().await.get(0), Some(&stack("apple", 4)));
+    assert_eq!(storage.players.load_vitals(bob.user_id).await.unwrap().map(|v| v.health), Some(100));
+
+    // Инвентарь переживает переподключение
+    bob_ws.close().await;
+    alice_ws.expect_disconnected(bob.user_id).await;
+    let (_bob_ws, inventory) = connect(&server, &bob).await;
+    assert_eq!(inventory.stacks(), vec![(0, stack("apple", 4)), (1, stack("stone", 10))]);
+    assert_eq!(storage.inventories.load_inventory(bob.user_id).await.unwrap(), inventory.stacks());
+
+    // Без возможности "inventory" действия с предметами недоступны
+    alice_ws.move_item(0, 1).await;
+    alice_ws.expect_error(ErrorCode::UnexpectedMessage).await;
+}
+
+#[tokio::test]
+async fn all_connections_of_a_player_share_one_inventory() {
+    let server = start(TestServer::test_config()).await;
+    let bob = server.register_user("bob").await;
+    server.state().storage.inventories.save_inventory(bob.user_id, &[(0, stack("stone", 10))]).await.unwrap();
+    let (mut first_ws, _) = connect(&server, &bob).await;
+    first_ws.move_item(0, 1).await;
+    first_ws.expect_inventory().await;
+
+    // Второе соединение получает уже загруженный инвентарь, а закрытие первого его не выгружает
+    let (mut second_ws, inventory) = connect(&server, &bob).await;
+    assert_eq!(inventory.stacks(), vec![(1, stack("stone", 10))]);
+    first_ws.close().await;
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    second_ws.move_item(1, 2).await;
+    assert_eq!(second_ws.expect_inventory().await.stacks(), vec![(2, stack("stone", 10))]);
+
+    second_ws.close().await;
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!server.state().inventories.lock().await.contains_key(&bob.user_id));
+}
+
+#[tokio::test]
+async fn dropped_items_can_be_picked_up_by_other_players() {
+    let mut config = TestServer::test_config();
+    config.game.inventory_slots = 2;
+    config.game.reach_distance = 3.0;
+    let server = start(config).await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let storage = &server.state().storage;
+    storage.inventories.save_inventory(alice.user_id, &[(0, stack("apple", 10))]).await.unwrap();
+    storage.inventories.save_inventory(bob.user_id, &[(0, stack("stone", 64)), (1, stack("apple", 19))]).await.unwrap();
+    let (mut alice_ws, _) = connect(&server, &alice).await;
+    let (mut bob_ws, _) = connect(&server, &bob).await;
+
+    alice_ws.drop_item(0, Some(0)).await;
+    alice_ws.expect_error(ErrorCode::InvalidItemAction).await;
+    alice_ws.drop_item(0, Some(3)).await;
+    assert_eq!(alice_ws.expect_inventory().await.get(0), Some(&stack("apple", 7)));
+    let dropped = bob_ws.expect_entity_spawned().await;
+    assert_eq!((dropped.kind, dropped.components.item, dropped.x, dropped.y), (EntityKind::Item, Some(stack("apple", 3)), 0.5, 0.5));
+
+    bob_ws.send_position(5.5, 0.5, 0.0).await;
+    bob_ws.pick_up(dropped.id).await;
+    bob_ws.expect_error(ErrorCode::OutOfReach).await;
+    bob_ws.send_position(2.5, 0.5, 0.0).await;
+    bob_ws.pick_up(dropped.id + 100).await;
+    bob_ws.expect_error(ErrorCode::InvalidItemAction).await;
+
+    // В инвентаре Bob место только для одного яблока: остальные остаются лежать
+    bob_ws.pick_up(dropped.id).await;
+    assert_eq!(bob_ws.expect_inventory().await.get(1), Some(&stack("apple", 20)));
+    assert_eq!(alice_ws.expect_item_picked_up().await, (dropped.id, bob.user_id, 1));
+    assert_eq!(alice_ws.expect_entity_updated(dropped.id).await.components.item, Some(stack("apple", 2)));
+    bob_ws.pick_up(dropped.id).await;
+    assert!(bob_ws.expect_error(ErrorCode::InvalidItemAction).await.contains("full"));
+
+    // Вошедший позже видит оставшиеся предметы
+    let carol = server.register_user("carol").await;
+    let mut carol_ws = server.connect_with_features(&carol, &[entities::FEATURE]).await;
+    carol_ws.expect_initial_players().await;
+    let lying: Vec<_> = carol_ws.expect_initial_entities().await.into_iter().map(|entity| entity.components.item).collect();
+    assert_eq!(lying, vec![Some(stack("apple", 2))]);
+    alice_ws.pick_up(dropped.id).await;
+    assert_eq!(alice_ws.expect_inventory().await.get(0), Some(&stack("apple", 9)));
+    assert_eq!(alice_ws.expect_item_picked_up().await, (dropped.id, alice.user_id, 2));
+    carol_ws.expect_entity_despawned(dropped.id).await;
+    assert!(server.state().rooms.get("lobby").unwrap().entities.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn map_item_objects_lie_in_the_room() {
+    let path = std::env::temp_dir().join(format!("anarchy_core_{}_items.tmj", std::process::id()));
+    let map = json!({
+        "width": 4, "height": 4, "tilewidth": 16, "tileheight": 16,
+        "layers": [{"type": "objectgroup", "name": "loot", "objects": [
+            {"id": 1, "class": "item", "x": 40, "y": 8, "point": true,
+             "properties": [{"name": "item", "type": "string", "value": "stone"}, {"name": "count", "type": "int", "value": 12}]},
+        ]}],
+    });
+    std::fs::write(&path, map.to_string()).unwrap();
+    let mut config = TestServer::test_config();
+    config.game.maps.insert("lobby".to_string(), path);
+    let maps = map::load_maps(&config.game).unwrap();
+    let server = TestServer::start_with_state(Arc::new(AppState::with_maps(Storage::in_memory(), config, maps).with_items(registry()))).await;
+
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[entities::FEATURE]).await;
+    alice_ws.expect_initial_players().await;
+    let items = alice_ws.expect_initial_entities().await;
+    assert_eq!(items.len(), 1);
+    assert_eq!((items[0].components.item.clone(), items[0].x, items[0].y), (Some(stack("stone", 12)), 2.5, 0.5));
+}
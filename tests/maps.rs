@@ -0,0 +1,269 @@
+// tests/maps.rs
+use anarchy_core::collision::PLAYER_RADIUS;
+use anarchy_core::config::Config;
+use anarchy_core::map::{self, MapError, TiledMap};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::TestServer;
+use anarchy_core::tiles::{self, ChunkPos, AIR};
+use anarchy_core::xml;
+use base64::Engine;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Карта 10x8 тайлов по 16 пикселей: трава, каменная стена в столбце x = 9
+const WIDTH: usize = 10;
+const HEIGHT: usize = 8;
+const GRASS_GID: u32 = 2;
+const STONE_GID: u32 = 6;
+// Флаг отражения по горизонтали
+const FLIPPED: u32 = 0x8000_0000;
+
+fn write_map(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("anarchy_core_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn walls() -> Vec<u32> {
+    (0..WIDTH * HEIGHT)
+        .map(|i| match (i % WIDTH, i / WIDTH) {
+            (9, 0) => STONE_GID | FLIPPED,
+            (9, _) => STONE_GID,
+            _ => 0,
+        })
+        .collect()
+}
+
+fn csv(gids: &[u32]) -> String {
+    gids.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+}
+
+fn arena_tmx() -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="10" height="8" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="pvp" type="bool" value="true"/>
+  <property name="title" value="Arena &amp; Pit"/>
+ </properties>
+ <tileset firstgid="1" source="terrain.tsx"/>
+ <layer id="1" name="ground" width="10" height="8">
+  <data encoding="csv">
+{ground}
+</data>
+ </layer>
+ <layer id="2" name="walls" width="10" height="8">
+  <data encoding="csv">{walls}</data>
+ </layer>
+ <objectgroup id="3" name="objects">
+  <object id="1" name="default" type="spawn" x="32" y="32"><point/></object>
+  <object id="2" name="arrival" class="spawn" x="112" y="32"><point/></object>
+  <object id="3" name="safe" class="zone" x="0" y="0" width="48" height="48">
+   <properties><property name="pvp" type="bool" value="false"/></properties>
+  </object>
+  <object id="4" name="portal" class="teleport" x="64" y="96" width="16" height="16">
+   <properties><property name="spawn" value="arrival"/></properties>
+  </object>
+  <object id="5" name="exit" class="teleport" x="0" y="96" width="16" height="16">
+   <properties><property name="room" value="lobby"/></properties>
+  </object>
+  <object id="6" class="collider" x="80" y="0" width="16" height="48"/>
+  <object id="7" name="loot" class="chest" x="16" y="112"><point/></object>
+ </objectgroup>
+ <group id="4" name="physics">
+  <properties><property name="collision" type="bool" value="true"/></properties>
+  <objectgroup id="5" name="rocks">
+   <object id="8" x="128" y="96"><polygon points="0,0 16,0 16,16"/></object>
+  </objectgroup>
+ </group>
+</map>
+"#, ground = csv(&[GRASS_GID; WIDTH * HEIGHT]), walls = csv(&walls()))
+}
+
+fn arena_json() -> String {
+    let walls: Vec<u8> = walls().iter().flat_map(|gid| gid.to_le_bytes()).collect();
+    json!({
+        "width": 10, "height": 8, "tilewidth": 16, "tileheight": 16,
+        "orientation": "orthogonal", "infinite": false,
+        "properties": [
+            {"name": "pvp", "type": "bool", "value": true},
+            {"name": "title", "type": "string", "value": "Arena & Pit"},
+        ],
+        "tilesets": [{"firstgid": 1, "source": "terrain.tsx"}],
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 10, "height": 8, "data": vec![GRASS_GID; WIDTH * HEIGHT]},
+            {"type": "tilelayer", "name": "walls", "width": 10, "height": 8, "encoding": "base64",
+             "data": base64::engine::general_purpose::STANDARD.encode(walls)},
+            {"type": "objectgroup", "name": "objects", "objects": [
+                {"id": 1, "name": "default", "type": "spawn", "x": 32, "y": 32, "point": true},
+                {"id": 2, "name": "arrival", "class": "spawn", "x": 112, "y": 32, "point": true},
+                {"id": 3, "name": "safe", "class": "zone", "x": 0, "y": 0, "width": 48, "height": 48,
+                 "properties": [{"name": "pvp", "type": "bool", "value": false}]},
+                {"id": 4, "name": "portal", "class": "teleport", "x": 64, "y": 96, "width": 16, "height": 16,
+                 "properties": [{"name": "spawn", "type": "string", "value": "arrival"}]},
+                {"id": 5, "name": "exit", "class": "teleport", "x": 0, "y": 96, "width": 16, "height": 16,
+                 "properties": [{"name": "room", "type": "string", "value": "lobby"}]},
+                {"id": 6, "class": "collider", "x": 80, "y": 0, "width": 16, "height": 48},
+                {"id": 7, "name": "loot", "class": "chest", "x": 16, "y": 112, "point": true},
+            ]},
+            {"type": "group", "name": "physics",
+             "properties": [{"name": "collision", "type": "bool", "value": true}],
+             "layers": [{"type": "objectgroup", "name": "rocks", "objects": [
+                 {"id": 8, "x": 128, "y": 96, "polygon": [{"x": 0, "y": 0}, {"x": 16, "y": 0}, {"x": 16, "y": 16}]},
+             ]}]},
+        ],
+    }).to_string()
+}
+
+#[test]
+fn tmx_and_json_maps_describe_the_same_world() {
+    let tmx = TiledMap::load(&write_map("arena.tmx", &arena_tmx())).unwrap();
+    let tmj = TiledMap::load(&write_map("arena.tmj", &arena_json())).unwrap();
+    assert_eq!(tmx.metadata, tmj.metadata);
+    assert_eq!(tmx.layers, tmj.layers);
+    assert_eq!(tmx.objects, tmj.objects);
+
+    let map = tmx;
+    assert_eq!(map.metadata.layers, ["ground", "walls"]);
+    assert_eq!(map.metadata.properties["pvp"], json!(true));
+    assert_eq!(map.metadata.properties["title"], json!("Arena & Pit"));
+    // Номер тайла в наборе, без флагов отражения; верхний слой перекрывает нижний
+    assert_eq!(map.tile(0, 0), Some(tiles::GRASS));
+    assert_eq!(map.tile(9, 0), Some(tiles::STONE));
+    assert_eq!(map.tile(9, 7), Some(tiles::STONE));
+    assert_eq!(map.tile(10, 0), None);
+    assert_eq!(map.tile(0, -1), None);
+
+    assert_eq!(map.spawn_point(None), Some((2.0, 2.0)));
+    assert_eq!(map.spawn_point(Some("arrival")), Some((7.0, 2.0)));
+    let zones: Vec<&str> = map.zones_at((1.0, 1.0)).map(|zone| zone.name.as_str()).collect();
+    assert_eq!(zones, ["safe"]);
+    assert_eq!(map.zones_at((5.0, 5.0)).count(), 0);
+    assert_eq!(map.metadata.zones[0].properties["pvp"], json!(false));
+
+    let portal = map.entered_teleporter((4.5, 5.5), (4.5, 6.5)).expect("portal was not entered");
+    assert_eq!(portal.destination(Some(&map), (0.0, 0.0)), (7.0, 2.0));
+    assert!(map.entered_teleporter((4.5, 6.2), (4.5, 6.5)).is_none(), "moving inside a teleporter must not trigger it");
+    assert_eq!(map.metadata.teleporters[1].room.as_deref(), Some("lobby"));
+
+    // Коллайдер-прямоугольник и многоугольник из слоя с collision = true
+    assert_eq!(map.metadata.colliders.len(), 2);
+    assert_eq!(map.metadata.colliders[1].points(), [(8.0, 6.0), (9.0, 6.0), (9.0, 7.0)]);
+    let chest = map.objects.iter().find(|object| object.class == "chest").unwrap();
+    assert_eq!((chest.name.as_str(), chest.layer.as_str(), chest.shape.center()), ("loot", "objects", (1.0, 7.0)));
+}
+
+#[test]
+fn unsupported_and_inconsistent_maps_are_rejected() {
+    let map = |layers: serde_json::Value| json!({
+        "width": 2, "height": 1, "tilewidth": 16, "tileheight": 16, "tilesets": [{"firstgid": 1}], "layers": layers,
+    }).to_string();
+
+    let compressed = map(json!([{"type": "tilelayer", "name": "ground", "encoding": "base64", "compression": "zlib", "data": "eJxjYGBgAAAABAAB"}]));
+    let error = TiledMap::load(&write_map("compressed.tmj", &compressed)).unwrap_err();
+    assert!(matches!(error, MapError::Unsupported(..)), "{}", error);
+
+    let short = map(json!([{"type": "tilelayer", "name": "ground", "data": [1]}]));
+    assert!(matches!(TiledMap::load(&write_map("short.tmj", &short)), Err(MapError::Invalid(..))));
+
+    let lost = map(json!([{"type": "objectgroup", "name": "objects", "objects": [
+        {"id": 1, "class": "teleport", "x": 0, "y": 0, "width": 16, "height": 16,
+         "properties": [{"name": "spawn", "type": "string", "value": "nowhere"}]},
+    ]}]));
+    let error = TiledMap::load(&write_map("lost.tmj", &lost)).unwrap_err();
+    assert!(error.to_string().contains("nowhere"), "{}", error);
+
+    let broken = "<map width=\"2\" height=\"1\" tilewidth=\"16\" tileheight=\"16\"><layer name=\"ground\"></map>";
+    assert!(matches!(TiledMap::load(&write_map("broken.tmx", broken)), Err(MapError::Invalid(..))));
+    assert!(matches!(TiledMap::load(&write_map("arena.txt", &arena_json())), Err(MapError::Unsupported(..))));
+
+    // Телепорт в другую комнату ведет в точку появления ее карты
+    let mut config = TestServer::test_config();
+    config.game.maps.insert("arena".to_string(), write_map("arena_targets.tmx", &arena_tmx().replace(
+        r#"<property name="room" value="lobby"/>"#,
+        r#"<property name="room" value="lobby"/><property name="spawn" value="arrival"/>"#,
+    )));
+    let error = map::load_maps(&config.game).unwrap_err();
+    assert!(error.to_string().contains("lobby"), "{}", error);
+}
+
+#[test]
+fn malformed_xml_is_rejected_without_panicking() {
+    // Сущности, ссылки на символы, CDATA и комментарии внутри текста
+    let root = xml::parse("<?xml version=\"1.0\"?><!-- map --><data a=\"&lt;&#x41;&#66;&quot;\">1,<![CDATA[2&amp;]]><!-- x -->&amp;3</data>").unwrap();
+    assert_eq!(root.attr("a"), Some("<AB\""));
+    assert_eq!(root.text, "1,2&amp;&3");
+
+    let malformed = [
+        "",
+        "<map>",
+        "<map></layer>",
+        "<map a=\"1\" a=\"2\"/>",
+        "<map a=1/>",
+        "<map>&unknown;</map>",
+        "<map>&#xFFFFFFFF;</map>",
+        "<map><![CDATA[open</map>",
+        "<map/><map/>",
+        "<map><!-- open </map>",
+        "<\u{e9}\u{0301}",
+    ];
+    for input in malformed {
+        assert!(xml::parse(input).is_err(), "{:?} must be rejected", input);
+    }
+
+    // Слишком глубокая вложенность отклоняется, а не переполняет стек
+    let nested = |depth: usize| format!("{}{}", "<g>".repeat(depth), "</g>".repeat(depth));
+    assert!(xml::parse(&nested(xml::MAX_DEPTH)).is_ok());
+    let error = xml::parse(&nested(xml::MAX_DEPTH + 1)).unwrap_err();
+    assert!(error.contains("nested"), "{}", error);
+    assert!(xml::parse(&nested(100_000)).is_err());
+    let broken = format!("<map width=\"2\" height=\"1\" tilewidth=\"16\" tileheight=\"16\">{}</map>", nested(xml::MAX_DEPTH));
+    assert!(matches!(TiledMap::load(&write_map("deep.tmx", &broken)), Err(MapError::Invalid(..))));
+}
+
+fn arena_config() -> Config {
+    let mut config = TestServer::test_config();
+    config.game.worlds = vec!["arena".to_string(), "lobby".to_string()];
+    config.game.default_world = "arena".to_string();
+    config.game.view_distance = 0;
+    config.game.maps.insert("arena".to_string(), write_map("arena_server.tmx", &arena_tmx()));
+    config
+}
+
+#[tokio::test]
+async fn map_rooms_use_map_terrain_colliders_and_teleporters() {
+    let config = arena_config();
+    let maps = map::load_maps(&config.game).unwrap();
+    let server = TestServer::start_with_state(Arc::new(AppState::with_maps(Storage::in_memory(), config, maps))).await;
+
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[map::FEATURE, tiles::FEATURE]).await;
+    let metadata = alice_ws.expect_map_info().await;
+    assert_eq!((metadata.width, metadata.height, metadata.spawns.len()), (10, 8, 2));
+    let players = alice_ws.expect_initial_players().await;
+    assert_eq!((players[0].x, players[0].y), (2.0, 2.0));
+    let chunk = alice_ws.expect_chunk().await;
+    assert_eq!((chunk.0, chunk.1.get(0, 0), chunk.1.get(9, 3), chunk.1.get(12, 0)), (ChunkPos { cx: 0, cy: 0 }, tiles::GRASS, tiles::STONE, AIR));
+
+    // Без возможности "maps" описание карты не приходит
+    let (_bob, _bob_ws) = server.join("bob").await;
+
+    // Коллайдер карты останавливает игрока
+    alice_ws.send_position(7.0, 2.0, 0.0).await;
+    let blocked = alice_ws.expect_position_of(alice.user_id).await;
+    assert!((blocked.x - (5.0 - PLAYER_RADIUS)).abs() < 0.01, "alice went through the collider: {:?}", blocked);
+
+    // Телепорт внутри карты переносит в точку появления "arrival"
+    alice_ws.send_position(4.5, 6.5, 0.0).await;
+    let teleported = alice_ws.expect_position_of(alice.user_id).await;
+    assert_eq!((teleported.x, teleported.y), (7.0, 2.0));
+
+    // Телепорт "exit" ведет в другую комнату
+    alice_ws.send_position(7.0, 5.0, 0.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    alice_ws.send_position(0.5, 6.5, 0.0).await;
+    let players = alice_ws.expect_room_changed("lobby").await;
+    let lobby = server.state().rooms.get("lobby").unwrap();
+    assert_eq!(players.iter().find(|p| p.user_id == alice.user_id).map(|p| (p.x, p.y)), Some(lobby.spawn));
+}
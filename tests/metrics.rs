@@ -0,0 +1,37 @@
+// tests/metrics.rs
+use anarchy_core::testing::{TestServer, TEST_PASSWORD};
+
+#[tokio::test]
+async fn metrics_endpoint_exposes_gameplay_counters() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    alice_ws.send_position(1.0, 2.0, 3.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    alice_ws.send_raw("not json".to_string()).await;
+    assert_eq!(server.login("alice", "wrong").await.status(), 401);
+    assert!(server.login("alice", TEST_PASSWORD).await.status().is_success());
+
+    let body = server.http()
+        .get(format!("http://{}/metrics", server.addr()))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    for expected in [
+        "anarchy_connected_sockets",
+        "anarchy_messages_in_total{type=\"PlayerPosition\"}",
+        "anarchy_messages_in_total{type=\"invalid\"}",
+        "anarchy_messages_out_total{type=\"InitialPlayers\"}",
+        "anarchy_messages_out_total{type=\"PlayerPosition\"}",
+        "anarchy_db_query_duration_seconds_bucket{query=\"save_position\"",
+        "anarchy_logins_total{result=\"success\"}",
+        "anarchy_logins_total{result=\"failure\"}",
+        "anarchy_registrations_total{result=\"success\"}",
+        "anarchy_tick_duration_seconds_bucket",
+    ] {
+        assert!(body.contains(expected), "metric {} is missing from:\n{}", expected, body);
+    }
+}
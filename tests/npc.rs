@@ -0,0 +1,150 @@
+// tests/npc.rs
+use anarchy_core::collision::CollisionWorld;
+use anarchy_core::entities::{self, Entity, EntityKind};
+use anarchy_core::npc::{Behavior, NpcDefinition, NpcRegistry, SpawnerDefinition};
+use anarchy_core::pathfinding;
+use anarchy_core::routes::game::GameMessage;
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::{TestClient, TestServer};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Сетка с препятствиями в заданных тайлах
+struct Walls(HashSet<(i32, i32)>);
+
+impl CollisionWorld for Walls {
+    fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.0.contains(&(x, y))
+    }
+}
+
+fn definition(id: &str, aggro_range: f64, wander_radius: f64) -> NpcDefinition {
+    NpcDefinition {
+        id: id.to_string(),
+        name: id.to_string(),
+        max_health: 20,
+        speed: 4.0,
+        aggro_range,
+        leash_range: 8.0,
+        wander_radius,
+    }
+}
+
+fn spawner(npc: &str, (x, y): (f64, f64), count: usize) -> SpawnerDefinition {
+    SpawnerDefinition { room: "lobby".to_string(), npc: npc.to_string(), x, y, count }
+}
+
+async fn start(npcs: NpcRegistry) -> TestServer {
+    let state = AppState::with_maps(Storage::in_memory(), TestServer::test_config(), HashMap::new()).with_npcs(npcs);
+    let server = TestServer::start_with_state(Arc::new(state)).await;
+    // Спаунеры срабатывают на первом тике игрового цикла
+    let room = server.state().rooms.get("lobby").unwrap();
+    for _ in 0..50 {
+        if !room.npcs.lock().await.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    server
+}
+
+// Ждет состояние NPC, удовлетворяющее predicate
+async fn wait_for_npc(ws: &mut TestClient, predicate: impl Fn(&Entity) -> bool) -> Entity {
+    match ws.recv_until(|msg| matches!(msg, GameMessage::EntityUpdated(entity) if predicate(entity))).await {
+        GameMessage::EntityUpdated(entity) => entity,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn paths_go_around_walls_without_cutting_corners() {
+    // Стена x = 2 от y = -3 до y = 3
+    let walls = Walls((-3..=3).map(|y| (2, y)).collect());
+    let path = pathfinding::find_path(&walls, (0, 0), (4, 0), 0.3, 1000).unwrap();
+    assert_eq!(path.last(), Some(&(4, 0)));
+    assert!(path.iter().all(|tile| !walls.0.contains(tile)));
+    // Соседние тайлы пути касаются сторонами или углом, но не срезают угол стены
+    let mut previous = (0, 0);
+    for &tile in &path {
+        let (dx, dy) = (tile.0 - previous.0, tile.1 - previous.1);
+        assert!(dx.abs() <= 1 && dy.abs() <= 1);
+        if dx != 0 && dy != 0 {
+            assert!(!walls.0.contains(&(previous.0 + dx, previous.1)) && !walls.0.contains(&(previous.0, previous.1 + dy)));
+        }
+        previous = tile;
+    }
+    assert!(path.contains(&(2, 4)) || path.contains(&(2, -4)));
+
+    assert_eq!(pathfinding::find_path(&walls, (0, 0), (0, 0), 0.3, 1000), Some(Vec::new()));
+    assert_eq!(pathfinding::find_path(&walls, (0, 0), (2, 0), 0.3, 1000), None);
+    // Замкнутая область: путь ограничен числом просмотренных тайлов
+    let boxed = Walls((-2..=2).flat_map(|d| [(d, -2), (d, 2), (-2, d), (2, d)]).collect());
+    assert_eq!(pathfinding::find_path(&boxed, (0, 0), (10, 0), 0.3, 1000), None);
+    assert_eq!(pathfinding::find_path(&Walls(HashSet::new()), (0, 0), (100, 0), 0.3, 50), None);
+}
+
+#[test]
+fn npc_definitions_are_validated() {
+    let shipped = NpcRegistry::load(std::path::Path::new("data/npcs.toml")).unwrap();
+    assert_eq!(shipped.get("wolf").map(|wolf| wolf.max_health), Some(30));
+    assert!(shipped.spawners("lobby").count() > 0);
+    assert_eq!(shipped.spawners("arena").count(), 0);
+
+    let wolf = || definition("wolf", 5.0, 0.0);
+    assert!(NpcRegistry::from_definitions(vec![wolf(), wolf()], vec![]).is_err());
+    assert!(NpcRegistry::from_definitions(vec![NpcDefinition { speed: 0.0, ..wolf() }], vec![]).is_err());
+    assert!(NpcRegistry::from_definitions(vec![NpcDefinition { aggro_range: f64::NAN, ..wolf() }], vec![]).is_err());
+    assert!(NpcRegistry::from_definitions(vec![wolf()], vec![spawner("bear", (0.0, 0.0), 1)]).is_err());
+    assert!(NpcRegistry::from_definitions(vec![wolf()], vec![spawner("wolf", (0.0, 0.0), 0)]).is_err());
+    assert!(NpcRegistry::from_definitions(vec![wolf()], vec![SpawnerDefinition { room: "bad room".to_string(), ..spawner("wolf", (0.0, 0.0), 1) }]).is_err());
+    assert!(NpcRegistry::from_definitions(vec![wolf()], vec![spawner("wolf", (0.5, 0.5), 2)]).is_ok());
+}
+
+#[tokio::test]
+async fn spawners_keep_their_npcs_alive() {
+    let npcs = NpcRegistry::from_definitions(vec![definition("deer", 0.0, 0.0)], vec![spawner("deer", (20.5, 0.5), 2)]).unwrap();
+    let server = start(npcs).await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[entities::FEATURE]).await;
+    alice_ws.expect_initial_players().await;
+    let deer = alice_ws.expect_initial_entities().await;
+    assert_eq!(deer.len(), 2);
+    for npc in &deer {
+        assert_eq!((npc.kind, npc.x, npc.y), (EntityKind::Npc, 20.5, 0.5));
+        assert_eq!((npc.components.name.as_deref(), npc.components.vitals.map(|v| v.health)), (Some("deer"), Some(20)));
+        assert_eq!(npc.components.behavior, Some(Behavior::Idle));
+    }
+
+    // Пропавшего NPC спаунер восполняет
+    let room = server.state().rooms.get("lobby").unwrap();
+    entities::despawn(&room, deer[0].id).await;
+    alice_ws.expect_entity_despawned(deer[0].id).await;
+    let replacement = alice_ws.expect_entity_spawned().await;
+    assert_eq!((replacement.kind, replacement.x, replacement.y), (EntityKind::Npc, 20.5, 0.5));
+    assert!(replacement.id > deer[1].id);
+    assert_eq!(room.npcs.lock().await.len(), 2);
+}
+
+#[tokio::test]
+async fn npcs_chase_the_nearest_player_and_return_home() {
+    let npcs = NpcRegistry::from_definitions(vec![definition("wolf", 5.0, 0.0)], vec![spawner("wolf", (4.5, 0.5), 1)]).unwrap();
+    let server = start(npcs).await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[entities::FEATURE]).await;
+    alice_ws.expect_initial_players().await;
+    let wolf = alice_ws.expect_initial_entities().await.remove(0);
+
+    // Игрок в точке появления (0.5, 0.5) ближе радиуса агрессии: волк подходит к нему вплотную
+    let chasing = wait_for_npc(&mut alice_ws, |entity| entity.id == wolf.id && entity.components.behavior == Some(Behavior::Chase)).await;
+    assert!(chasing.vx < 0.0, "{:?}", chasing);
+    let caught = wait_for_npc(&mut alice_ws, |entity| entity.id == wolf.id && entity.vx == 0.0 && entity.vy == 0.0).await;
+    assert!((caught.x - 0.5).hypot(caught.y - 0.5) <= 1.5, "{:?}", caught);
+
+    // Игрок ушел дальше привязи: волк возвращается домой и останавливается там
+    alice_ws.send_position(-20.5, 0.5, 0.0).await;
+    wait_for_npc(&mut alice_ws, |entity| entity.id == wolf.id && entity.components.behavior == Some(Behavior::ReturnHome)).await;
+    let home = wait_for_npc(&mut alice_ws, |entity| entity.id == wolf.id && entity.components.behavior == Some(Behavior::Idle)).await;
+    assert!((home.x - 4.5).hypot(home.y - 0.5) <= 0.2, "{:?}", home);
+}
@@ -0,0 +1,257 @@
+// tests/party.rs
+use anarchy_core::config::Config;
+use anarchy_core::party::{self, Party, PartyLeaveReason, PartyMember};
+use anarchy_core::routes::game::{ErrorCode, GameMessage};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::{PartyRepository, Storage, StorageResult};
+use anarchy_core::testing::{TestClient, TestServer, TestUser};
+use async_trait::async_trait;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn config() -> Config {
+    let mut config = TestServer::config_with_worlds(&["lobby", "arena"]);
+    config.game.max_party_size = 3;
+    config
+}
+
+fn members(party: &Party) -> Vec<(i32, bool)> {
+    party.members.iter().map(|member| (member.user_id, member.online)).collect()
+}
+
+// leader приглашает игрока, тот соглашается; возвращает состав, который получил новый участник
+async fn invite(leader: (&TestUser, &mut TestClient), user: (&TestUser, &mut TestClient)) -> Party {
+    leader.1.invite_to_party(user.0.user_id).await;
+    assert_eq!(user.1.expect_party_invited().await, leader.0.user_id);
+    user.1.accept_party(leader.0.user_id).await;
+    let party = user.1.expect_party().await;
+    assert_eq!(leader.1.expect_party().await, party);
+    party
+}
+
+#[tokio::test]
+async fn parties_are_formed_and_managed_by_the_leader() {
+    let server = TestServer::start_with_config(config()).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let (carol, mut carol_ws) = server.join("carol").await;
+    let (dave, mut dave_ws) = server.join("dave").await;
+
+    alice_ws.invite_to_party(alice.user_id).await;
+    alice_ws.expect_error(ErrorCode::InvalidPartyAction).await;
+    bob_ws.accept_party(alice.user_id).await;
+    assert!(bob_ws.expect_error(ErrorCode::InvalidPartyAction).await.contains("no party invite"));
+
+    let party = invite((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    assert_eq!(party.leader, alice.user_id);
+    assert_eq!(members(&party), vec![(alice.user_id, true), (bob.user_id, true)]);
+    bob_ws.invite_to_party(carol.user_id).await;
+    assert!(bob_ws.expect_error(ErrorCode::InvalidPartyAction).await.contains("leader"));
+
+    let party = invite((&alice, &mut alice_ws), (&carol, &mut carol_ws)).await;
+    assert_eq!(bob_ws.expect_party().await, party);
+    assert_eq!(party.members.len(), 3);
+    carol_ws.invite_to_party(dave.user_id).await;
+    carol_ws.expect_error(ErrorCode::InvalidPartyAction).await;
+    alice_ws.invite_to_party(dave.user_id).await;
+    assert!(alice_ws.expect_error(ErrorCode::InvalidPartyAction).await.contains("full"));
+    dave_ws.invite_to_party(bob.user_id).await;
+    assert!(dave_ws.expect_error(ErrorCode::InvalidPartyAction).await.contains("already in a party"));
+
+    // Исключать может только лидер
+    bob_ws.send(&GameMessage::PartyKick { target: carol.user_id }).await;
+    bob_ws.expect_error(ErrorCode::InvalidPartyAction).await;
+    alice_ws.send(&GameMessage::PartyKick { target: dave.user_id }).await;
+    alice_ws.expect_error(ErrorCode::InvalidPartyAction).await;
+
+    // Ушедшего лидера сменяет следующий участник
+    alice_ws.send(&GameMessage::PartyLeave).await;
+    assert_eq!(alice_ws.expect_party_left().await, PartyLeaveReason::Left);
+    let party = bob_ws.expect_party().await;
+    assert_eq!((party.leader, party.members.len()), (bob.user_id, 2));
+    assert_eq!(carol_ws.expect_party().await, party);
+
+    // Группа из одного игрока распускается
+    bob_ws.send(&GameMessage::PartyKick { target: carol.user_id }).await;
+    assert_eq!(carol_ws.expect_party_left().await, PartyLeaveReason::Kicked);
+    assert_eq!(bob_ws.expect_party_left().await, PartyLeaveReason::Disbanded);
+    assert!(server.state().parties.lock().await.party_of(bob.user_id).is_none());
+    bob_ws.send(&GameMessage::PartyLeave).await;
+    bob_ws.expect_error(ErrorCode::InvalidPartyAction).await;
+}
+
+#[tokio::test]
+async fn party_chat_and_positions_reach_members_in_other_rooms() {
+    let server = TestServer::start_with_config(config()).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let (_carol, mut carol_ws) = server.join("carol").await;
+    invite((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+
+    bob_ws.change_room("arena").await;
+    bob_ws.expect_room_changed("arena").await;
+    alice_ws.send_position(3.5, 0.5, 0.0).await;
+    assert_eq!(bob_ws.expect_party_position(alice.user_id).await, ("lobby".to_string(), 3.5, 0.5));
+    bob_ws.send_position(-2.5, 0.5, 0.0).await;
+    assert_eq!(alice_ws.expect_party_position(bob.user_id).await, ("arena".to_string(), -2.5, 0.5));
+
+    alice_ws.send(&GameMessage::PartyChat { text: "  regroup  ".to_string() }).await;
+    assert_eq!(bob_ws.expect_party_chat().await, (alice.user_id, "regroup".to_string()));
+    assert_eq!(alice_ws.expect_party_chat().await, (alice.user_id, "regroup".to_string()));
+    alice_ws.send(&GameMessage::PartyChat { text: " ".to_string() }).await;
+    alice_ws.expect_error(ErrorCode::InvalidChatMessage).await;
+    carol_ws.send(&GameMessage::PartyChat { text: "hi".to_string() }).await;
+    carol_ws.expect_error(ErrorCode::InvalidPartyAction).await;
+
+    // Посторонний игрок не получает ни чат, ни позиции группы
+    alice_ws.chat("hello").await;
+    let msg = carol_ws.recv_until(|msg| matches!(msg, GameMessage::ChatMessage { .. } | GameMessage::PartyChatMessage { .. } | GameMessage::PartyMemberPosition { .. })).await;
+    assert!(matches!(msg, GameMessage::ChatMessage { .. }), "unexpected {:?}", msg);
+}
+
+#[tokio::test]
+async fn parties_survive_brief_reconnects() {
+    let server = TestServer::start_with_config(config()).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let (carol, mut carol_ws) = server.join("carol").await;
+    invite((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    invite((&alice, &mut alice_ws), (&carol, &mut carol_ws)).await;
+
+    bob_ws.close().await;
+    let party = alice_ws.expect_party().await;
+    assert_eq!(members(&party), vec![(alice.user_id, true), (bob.user_id, false), (carol.user_id, true)]);
+    let mut bob_ws = server.connect(&bob).await;
+    let party = bob_ws.expect_party().await;
+    assert_eq!(members(&party), vec![(alice.user_id, true), (bob.user_id, true), (carol.user_id, true)]);
+    assert_eq!(alice_ws.expect_party().await, party);
+
+    // Не вернувшийся вовремя лидер исключается, лидером становится следующий участник
+    alice_ws.close().await;
+    bob_ws.expect_party().await;
+    let grace = server.state().config.party_reconnect_grace();
+    party::expire_offline(server.state(), Instant::now() + grace / 2).await;
+    assert!(server.state().parties.lock().await.party_of(alice.user_id).is_some());
+    party::expire_offline(server.state(), Instant::now() + grace + Duration::from_secs(1)).await;
+    let party = bob_ws.expect_party().await;
+    assert_eq!(party.leader, bob.user_id);
+    assert_eq!(members(&party), vec![(bob.user_id, true), (carol.user_id, true)]);
+    let update = carol_ws.recv_until(|msg| matches!(msg, GameMessage::PartyUpdated(party) if party.leader == bob.user_id)).await;
+    assert!(matches!(update, GameMessage::PartyUpdated(update) if update == party));
+    assert!(server.state().parties.lock().await.party_of(alice.user_id).is_none());
+}
+
+#[tokio::test]
+async fn members_stay_online_while_any_of_their_connections_is_open() {
+    let server = TestServer::start_with_config(config()).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let party = invite((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+
+    // Второе соединение получает состав группы и может писать в ее чат
+    let mut extra_ws = server.connect(&bob).await;
+    assert_eq!(extra_ws.expect_party().await, party);
+    extra_ws.send(&GameMessage::PartyChat { text: "from my phone".to_string() }).await;
+    assert_eq!(alice_ws.expect_party_chat().await, (bob.user_id, "from my phone".to_string()));
+    extra_ws.close().await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let grace = server.state().config.party_reconnect_grace();
+    party::expire_offline(server.state(), Instant::now() + grace + Duration::from_secs(1)).await;
+    let parties = server.state().parties.lock().await;
+    let party = parties.party_of(bob.user_id).expect("bob is still in the party");
+    assert_eq!(members(party), vec![(alice.user_id, true), (bob.user_id, true)]);
+}
+
+#[tokio::test]
+async fn persisted_parties_are_restored_after_restart() {
+    let mut config = config();
+    config.game.persist_parties = true;
+    let storage = Storage::in_memory();
+    let state = AppState::new(storage.clone(), config.clone());
+    let server = TestServer::start_with_state(Arc::new(state)).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let (carol, mut carol_ws) = server.join("carol").await;
+    let party = invite((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    invite((&alice, &mut alice_ws), (&carol, &mut carol_ws)).await;
+    bob_ws.send(&GameMessage::PartyLeave).await;
+    bob_ws.expect_party_left().await;
+    alice_ws.expect_party().await;
+    assert_eq!(storage.parties.load_parties().await.unwrap(), vec![(party.id, vec![alice.user_id, carol.user_id])]);
+    // Сохраненная группа попадает в выгрузку персональных данных
+    let export: Value = server.authorized(&carol, Method::GET, "/account/export").send().await.unwrap().json().await.unwrap();
+    assert_eq!(export["party"], json!({ "id": party.id, "leader": alice.user_id, "members": [alice.user_id, carol.user_id] }));
+    let export: Value = server.authorized(&bob, Method::GET, "/account/export").send().await.unwrap().json().await.unwrap();
+    assert!(export["party"].is_null());
+
+    // После перезапуска группа ждет своих участников
+    let restarted = AppState::with_maps(storage.clone(), config, HashMap::new());
+    assert_eq!(party::restore(&restarted).await.unwrap(), 1);
+    let parties = restarted.parties.lock().await;
+    let restored = parties.party_of(carol.user_id).unwrap();
+    assert_eq!(restored.leader, alice.user_id);
+    assert_eq!(restored.members, vec![
+        PartyMember { user_id: alice.user_id, online: false },
+        PartyMember { user_id: carol.user_id, online: false },
+    ]);
+    drop(parties);
+
+    carol_ws.send(&GameMessage::PartyLeave).await;
+    carol_ws.expect_party_left().await;
+    assert!(storage.parties.load_parties().await.unwrap().is_empty());
+}
+
+// Составы групп, которые записываются с задержкой
+struct SlowParties(Arc<dyn PartyRepository>);
+
+#[async_trait]
+impl PartyRepository for SlowParties {
+    async fn load_parties(&self) -> StorageResult<Vec<(u64, Vec<i32>)>> {
+        self.0.load_parties().await
+    }
+
+    async fn party_of(&self, user_id: i32) -> StorageResult<Option<(u64, Vec<i32>)>> {
+        self.0.party_of(user_id).await
+    }
+
+    async fn save_party(&self, id: u64, members: &[i32]) -> StorageResult<()> {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        self.0.save_party(id, members).await
+    }
+}
+
+#[tokio::test]
+async fn slow_party_saves_do_not_hold_up_parties() {
+    let mut config = config();
+    config.game.persist_parties = true;
+    let mut storage = Storage::in_memory();
+    let stored = storage.parties.clone();
+    storage.parties = Arc::new(SlowParties(stored.clone()));
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage, config))).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let (carol, mut carol_ws) = server.join("carol").await;
+    let party = invite((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+
+    // Пока записывается состав с carol, остальные участники пользуются группой без ожидания
+    alice_ws.invite_to_party(carol.user_id).await;
+    assert_eq!(carol_ws.expect_party_invited().await, alice.user_id);
+    let started = Instant::now();
+    carol_ws.accept_party(alice.user_id).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    bob_ws.send(&GameMessage::PartyChat { text: "hi".to_string() }).await;
+    assert_eq!(alice_ws.expect_party_chat().await, (bob.user_id, "hi".to_string()));
+    assert!(started.elapsed() < Duration::from_millis(400), "party chat waited {:?}", started.elapsed());
+    assert_eq!(stored.load_parties().await.unwrap(), vec![(party.id, vec![alice.user_id, bob.user_id])]);
+
+    // Составы записываются в порядке изменений
+    bob_ws.send(&GameMessage::PartyLeave).await;
+    bob_ws.expect_party_left().await;
+    carol_ws.expect_party().await;
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    assert_eq!(stored.load_parties().await.unwrap(), vec![(party.id, vec![alice.user_id, carol.user_id])]);
+}
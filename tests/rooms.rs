@@ -0,0 +1,127 @@
+// tests/rooms.rs
+use anarchy_core::routes::game::ErrorCode;
+use anarchy_core::testing::TestServer;
+use std::time::Duration;
+
+// Ждет, пока сервер удалит опустевший инстанс
+async fn wait_room_removed(server: &TestServer, room: &str) {
+    for _ in 0..50 {
+        if server.state().rooms.get(room).is_none() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Room {} was not removed", room);
+}
+
+#[tokio::test]
+async fn players_in_different_rooms_do_not_see_each_other() {
+    let server = TestServer::start_with_config(TestServer::config_with_worlds(&["lobby", "arena"])).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    assert_eq!(bob_ws.room(), "lobby");
+
+    bob_ws.change_room("arena").await;
+    let players = bob_ws.expect_room_changed("arena").await;
+    assert_eq!(players.iter().map(|p| p.user_id).collect::<Vec<_>>(), vec![bob.user_id]);
+    alice_ws.expect_disconnected(bob.user_id).await;
+
+    // Движения в лобби не попадают в арену, и наоборот
+    alice_ws.send_position(1.0, 0.0, 0.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    bob_ws.send_position(2.0, 0.0, 0.0).await;
+    bob_ws.expect_position_of(bob.user_id).await;
+    alice_ws.expect_silence(Duration::from_millis(300)).await;
+    bob_ws.expect_silence(Duration::from_millis(300)).await;
+    assert_eq!(server.state().rooms.online_players(), 2);
+}
+
+#[tokio::test]
+async fn instance_is_created_on_demand_and_removed_when_empty() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = server.join("bob").await;
+    assert!(server.state().rooms.get("dungeon-1").is_none());
+
+    alice_ws.change_room("dungeon-1").await;
+    alice_ws.expect_room_changed("dungeon-1").await;
+    let instance = server.state().rooms.get("dungeon-1").expect("instance was not created");
+    assert!(!instance.persistent);
+
+    bob_ws.change_room("dungeon-1").await;
+    let players = bob_ws.expect_room_changed("dungeon-1").await;
+    assert!(players.iter().any(|p| p.user_id == alice.user_id));
+
+    alice_ws.change_room("lobby").await;
+    alice_ws.expect_room_changed("lobby").await;
+    assert!(server.state().rooms.get("dungeon-1").is_some());
+    bob_ws.expect_disconnected(alice.user_id).await;
+
+    bob_ws.drop_connection();
+    wait_room_removed(&server, "dungeon-1").await;
+    // Постоянный мир не удаляется, даже когда пуст
+    alice_ws.close().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(server.state().rooms.get("lobby").is_some());
+}
+
+#[tokio::test]
+async fn room_is_restored_on_reconnect() {
+    let server = TestServer::start_with_config(TestServer::config_with_worlds(&["lobby", "arena"])).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+
+    alice_ws.change_room("arena").await;
+    alice_ws.expect_room_changed("arena").await;
+    alice_ws.send_position(3.0, 4.0, 0.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    alice_ws.close().await;
+
+    let mut alice_ws = server.connect(&alice).await;
+    assert_eq!(alice_ws.room(), "arena");
+    let players = alice_ws.expect_initial_players().await;
+    let me = players.iter().find(|p| p.user_id == alice.user_id).expect("reconnected player is missing");
+    assert_eq!((me.x, me.y), (3.0, 4.0));
+}
+
+#[tokio::test]
+async fn removed_instance_falls_back_to_default_world() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+
+    alice_ws.change_room("cave").await;
+    alice_ws.expect_room_changed("cave").await;
+    alice_ws.send_position(7.0, 0.0, 0.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    alice_ws.close().await;
+    wait_room_removed(&server, "cave").await;
+
+    let mut alice_ws = server.connect(&alice).await;
+    assert_eq!(alice_ws.room(), "lobby");
+    let players = alice_ws.expect_initial_players().await;
+    let me = players.iter().find(|p| p.user_id == alice.user_id).expect("reconnected player is missing");
+    assert_eq!((me.x, me.y), server.state().rooms.get("lobby").unwrap().spawn);
+}
+
+#[tokio::test]
+async fn unavailable_rooms_are_rejected() {
+    let mut config = TestServer::test_config();
+    config.game.max_rooms = 2;
+    let server = TestServer::start_with_config(config).await;
+    let (_alice, mut alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.change_room("no spaces!").await;
+    alice_ws.expect_error(ErrorCode::RoomUnavailable).await;
+    alice_ws.change_room("lobby").await;
+    alice_ws.expect_error(ErrorCode::RoomUnavailable).await;
+
+    alice_ws.change_room("first").await;
+    alice_ws.expect_room_changed("first").await;
+    // Лимит комнат исчерпан: лобби и один инстанс
+    bob_ws.change_room("second").await;
+    bob_ws.expect_error(ErrorCode::RoomUnavailable).await;
+    assert_eq!(bob_ws.room(), "lobby");
+    // В существующий инстанс войти можно
+    bob_ws.change_room("first").await;
+    bob_ws.expect_room_changed("first").await;
+}
@@ -0,0 +1,200 @@
+// tests/scripting.rs
+use anarchy_core::routes::game::{ErrorCode, GameMessage};
+use anarchy_core::scripting::{self, Action, ScriptError, ScriptHost, ScriptPlayer};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::TestServer;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+// Пустой каталог скриптов для теста
+fn scripts_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("anarchy_core_{}_scripts_{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(dir: &Path, name: &str, source: &str) {
+    std::fs::write(dir.join(name), source).unwrap();
+}
+
+fn player(user_id: i32) -> ScriptPlayer {
+    ScriptPlayer { user_id, room: "lobby".to_string(), x: 0.5, y: 0.5 }
+}
+
+async fn start(dir: &Path) -> TestServer {
+    let scripts = ScriptHost::load(dir).unwrap();
+    let config = TestServer::config_with_worlds(&["lobby", "arena"]);
+    let state = AppState::with_maps(Storage::in_memory(), config, HashMap::new()).with_scripts(scripts);
+    TestServer::start_with_state(Arc::new(state)).await
+}
+
+#[test]
+fn scripts_are_loaded_in_order_and_keep_their_state() {
+    let shipped = ScriptHost::load(Path::new("scripts")).unwrap();
+    assert!(shipped.has_hook("on_join"));
+
+    let dir = scripts_dir("order");
+    write(&dir, "b.rhai", r#"fn on_join(player) { send_message(player.user_id, "b"); }"#);
+    write(&dir, "a.rhai", r#"
+        fn on_load() { this.joins = 0; }
+        fn on_join(player) {
+            this.joins += 1;
+            send_message(player.user_id, `a${this.joins} of ${players().len()}`);
+        }
+    "#);
+    write(&dir, "notes.txt", "not a script");
+    let host = ScriptHost::load(&dir).unwrap();
+    assert_eq!(host.names(), vec!["a.rhai", "b.rhai"]);
+    assert!(host.has_hook("on_join") && !host.has_hook("on_chat"));
+
+    let players = [player(1), player(2)];
+    assert_eq!(host.on_join(&players, &players[0]), vec![
+        Action::Message { user_id: 1, text: "a1 of 2".to_string() },
+        Action::Message { user_id: 1, text: "b".to_string() },
+    ]);
+    assert_eq!(host.on_join(&players, &players[1])[0], Action::Message { user_id: 2, text: "a2 of 2".to_string() });
+
+    // Ошибка в скрипте при запуске — ошибка загрузки
+    write(&dir, "c.rhai", "fn on_join(player) {");
+    assert!(matches!(ScriptHost::load(&dir), Err(ScriptError::Invalid(..))));
+}
+
+#[test]
+fn scripts_are_reloaded_without_losing_working_versions() {
+    let dir = scripts_dir("reload");
+    write(&dir, "rules.rhai", r#"fn on_chat(player, text) { "v1: " + text }"#);
+    write(&dir, "extra.rhai", r#"fn on_chat(player, text) { text + "!" }"#);
+    let host = ScriptHost::load(&dir).unwrap();
+    let alice = player(1);
+    assert_eq!(host.on_chat(&[], &alice, "hi").0.as_deref(), Some("v1: hi!"));
+
+    // Версия с ошибкой не заменяет работающую
+    write(&dir, "rules.rhai", r#"fn on_chat(player, text) { "v2: " + "#);
+    assert!(host.reload(&[]).is_empty());
+    assert_eq!(host.on_chat(&[], &alice, "hi").0.as_deref(), Some("v1: hi!"));
+
+    write(&dir, "rules.rhai", r#"
+        fn on_load() { broadcast("lobby", "rules updated"); }
+        fn on_chat(player, text) { if text == "/quiet" { return false; } "v2: " + text }
+    "#);
+    std::fs::remove_file(dir.join("extra.rhai")).unwrap();
+    assert_eq!(host.reload(&[]), vec![Action::Broadcast { room: "lobby".to_string(), text: "rules updated".to_string() }]);
+    assert_eq!(host.names(), vec!["rules.rhai"]);
+    assert_eq!(host.on_chat(&[], &alice, "hi").0.as_deref(), Some("v2: hi"));
+    assert_eq!(host.on_chat(&[], &alice, "/quiet").0, None);
+    // Неизмененный файл не перезагружается
+    assert!(host.reload(&[]).is_empty());
+
+    // Файлы сверяются по времени изменения и размеру, без чтения содержимого
+    let path = dir.join("rules.rhai");
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    let source = std::fs::read_to_string(&path).unwrap();
+    write(&dir, "rules.rhai", &source.replace("v2", "v3"));
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+    host.reload(&[]);
+    assert_eq!(host.on_chat(&[], &alice, "hi").0.as_deref(), Some("v2: hi"));
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(SystemTime::now()).unwrap();
+    host.reload(&[]);
+    assert_eq!(host.on_chat(&[], &alice, "hi").0.as_deref(), Some("v3: hi"));
+}
+
+#[test]
+fn runaway_scripts_are_stopped_and_timers_fire() {
+    let dir = scripts_dir("limits");
+    write(&dir, "loop.rhai", r#"
+        fn on_move(player) { send_message(player.user_id, "before"); loop { } }
+        fn on_join(player) { set_timer("greet", 0); set_timer("later", 60000); }
+        fn on_timer(name) { broadcast("lobby", name); }
+    "#);
+    // Таймеры скрипта без on_timer снимаются без вызова
+    write(&dir, "silent.rhai", r#"fn on_join(player) { set_timer("silent", 0); }"#);
+    let host = ScriptHost::load(&dir).unwrap();
+    // Зациклившийся обработчик прерывается, накопленные до этого действия сохраняются
+    assert_eq!(host.on_move(&[], &player(1)), vec![Action::Message { user_id: 1, text: "before".to_string() }]);
+
+    assert!(!host.has_timers());
+    assert!(host.on_join(&[], &player(1)).is_empty());
+    assert!(host.has_timers());
+    assert_eq!(host.fire_timers(&[], Instant::now()), vec![Action::Broadcast { room: "lobby".to_string(), text: "greet".to_string() }]);
+    // Таймер срабатывает один раз
+    assert!(host.fire_timers(&[], Instant::now()).is_empty());
+    assert!(host.has_timers());
+}
+
+#[tokio::test]
+async fn chat_and_player_events_go_through_scripts() {
+    let dir = scripts_dir("events");
+    write(&dir, "rules.rhai", r#"
+        fn on_join(player) { send_message(player.user_id, `Welcome, ${player.user_id}`); }
+        fn on_leave(player) { broadcast(player.room, `${player.user_id} left`); }
+        fn on_move(player) {
+            // За x = 10 проход закрыт
+            if player.x > 10.0 { teleport(player.user_id, 0.5, 0.5); }
+        }
+        fn on_chat(player, text) {
+            if text == "/secret" { return false; }
+            text.replace("darn", "d**n");
+            text
+        }
+    "#);
+    let server = start(&dir).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    assert_eq!(alice_ws.expect_system_message().await, (Some(alice.user_id), format!("Welcome, {}", alice.user_id)));
+    let (bob, mut bob_ws) = server.join("bob").await;
+    assert_eq!(bob_ws.expect_system_message().await, (Some(bob.user_id), format!("Welcome, {}", bob.user_id)));
+
+    alice_ws.chat("  hello  ").await;
+    assert_eq!(bob_ws.expect_chat().await, (alice.user_id, "hello".to_string()));
+    assert_eq!(alice_ws.expect_chat().await, (alice.user_id, "hello".to_string()));
+    alice_ws.chat("/secret").await;
+    alice_ws.chat("darn it").await;
+    assert_eq!(bob_ws.expect_chat().await, (alice.user_id, "d**n it".to_string()));
+    alice_ws.chat("   ").await;
+    alice_ws.expect_error(ErrorCode::InvalidChatMessage).await;
+    alice_ws.chat(&"a".repeat(257)).await;
+    alice_ws.expect_error(ErrorCode::InvalidChatMessage).await;
+
+    // Скрипт возвращает игрока, зашедшего за x = 10
+    alice_ws.send_position(12.5, 0.5, 0.0).await;
+    let returned = bob_ws.recv_until(|msg| matches!(msg, GameMessage::PlayerPosition(p) if p.user_id == alice.user_id && p.x < 1.0)).await;
+    assert!(matches!(returned, GameMessage::PlayerPosition(p) if (p.x, p.y) == (0.5, 0.5)));
+    let room = server.state().rooms.get("lobby").unwrap();
+    assert_eq!(room.active_player_positions.lock().await[&alice.user_id].x, 0.5);
+
+    // Смена комнаты — выход из старой и вход в новую
+    alice_ws.change_room("arena").await;
+    assert_eq!(bob_ws.expect_system_message().await, (None, format!("{} left", alice.user_id)));
+    alice_ws.expect_room_changed("arena").await;
+    assert_eq!(alice_ws.expect_system_message().await, (Some(alice.user_id), format!("Welcome, {}", alice.user_id)));
+    alice_ws.change_room("lobby").await;
+    alice_ws.expect_room_changed("lobby").await;
+    assert_eq!(alice_ws.expect_system_message().await, (Some(alice.user_id), format!("Welcome, {}", alice.user_id)));
+
+    alice_ws.logout().await;
+    assert_eq!(bob_ws.expect_system_message().await, (None, format!("{} left", alice.user_id)));
+}
+
+#[tokio::test]
+async fn scripts_are_hot_reloaded_while_players_are_online() {
+    let dir = scripts_dir("hot");
+    write(&dir, "rules.rhai", r#"fn on_chat(player, text) { "v1: " + text }"#);
+    let server = start(&dir).await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    alice_ws.chat("hi").await;
+    assert_eq!(alice_ws.expect_chat().await, (alice.user_id, "v1: hi".to_string()));
+
+    write(&dir, "rules.rhai", r#"
+        fn on_load() { set_timer("announce", 0); }
+        fn on_timer(name) { broadcast("lobby", `${players().len()} online`); }
+        fn on_chat(player, text) { "v2: " + text }
+    "#);
+    scripting::reload(server.state()).await;
+    // Таймер нового скрипта срабатывает на ближайшем тике игрового цикла
+    assert_eq!(alice_ws.expect_system_message().await, (None, "1 online".to_string()));
+    alice_ws.chat("hi").await;
+    assert_eq!(alice_ws.expect_chat().await, (alice.user_id, "v2: hi".to_string()));
+}
@@ -0,0 +1,119 @@
+// tests/snapshots.rs
+use anarchy_core::routes::game::{GameMessage, PlayerPositionUpdate};
+use anarchy_core::snapshot::{self, EntityDelta, EntityTable, MAX_BASELINE_AGE};
+use anarchy_core::testing::{TestClient, TestServer, TestUser};
+use std::time::Duration;
+
+// Время, за которое сервер гарантированно обработает подтверждение
+const ACK_SETTLE: Duration = Duration::from_millis(100);
+
+async fn join_with_snapshots(server: &TestServer, login: &str) -> (TestUser, TestClient) {
+    let user = server.register_user(login).await;
+    let mut client = server.connect_with_features(&user, &[snapshot::FEATURE]).await;
+    client.expect_initial_players().await;
+    (user, client)
+}
+
+#[tokio::test]
+async fn delta_contains_only_changed_fields_against_acknowledged_baseline() {
+    let server = TestServer::start().await;
+    let (_alice, _alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+    let (_carol, _carol_ws) = server.join("carol").await;
+    let (_dave, mut dave_ws) = join_with_snapshots(&server, "dave").await;
+
+    let full = dave_ws.expect_snapshot().await;
+    assert_eq!(full.baseline, None);
+    assert_eq!(full.entities.len(), 4);
+    assert!(full.entities.iter().all(|e| e.x.is_some() && e.y.is_some() && e.seq.is_some()));
+
+    dave_ws.ack_snapshot(full.tick).await;
+    tokio::time::sleep(ACK_SETTLE).await;
+    // Игрок появляется в центре тайла (0.5, 0.5) и сдвигается только по x
+    bob_ws.send_position(1.0, 0.5, 0.0).await;
+
+    // Клиент с дельта-снимками не получает отдельных PlayerPosition
+    let delta = match dave_ws.recv().await {
+        GameMessage::Snapshot(delta) => delta,
+        other => panic!("Expected Snapshot, got {:?}", other),
+    };
+    assert_eq!(delta.baseline, Some(full.tick));
+    assert!(delta.removed.is_empty());
+    assert_eq!(delta.entities, vec![EntityDelta { id: bob.user_id, x: Some(100), seq: Some(1), ..Default::default() }]);
+
+    // Дельта меньше полного сообщения о позиции даже для одного игрока
+    let full_update = GameMessage::PlayerPosition(PlayerPositionUpdate { user_id: bob.user_id, x: 1.0, y: 0.5, seq: 1, ..Default::default() });
+    let delta_size = serde_json::to_string(&GameMessage::Snapshot(delta)).unwrap().len();
+    let full_size = serde_json::to_string(&full_update).unwrap().len();
+    assert!(delta_size < full_size, "delta {} bytes vs full {} bytes", delta_size, full_size);
+}
+
+#[tokio::test]
+async fn applying_deltas_reconstructs_world_state() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, bob_ws) = server.join("bob").await;
+    let (_carol, mut carol_ws) = join_with_snapshots(&server, "carol").await;
+
+    let full = carol_ws.expect_snapshot().await;
+    let baseline = snapshot::apply(&EntityTable::new(), &full);
+    carol_ws.ack_snapshot(full.tick).await;
+    tokio::time::sleep(ACK_SETTLE).await;
+
+    let update = PlayerPositionUpdate { user_id: alice.user_id, x: 3.257, y: -2.5, z: 1.0, seq: 1, rotation: 1.0, vx: 0.5, vy: -0.25, vz: 0.0, clan_tag: None };
+    alice_ws.send(&GameMessage::PlayerPosition(update.clone())).await;
+    bob_ws.drop_connection();
+
+    let mut state = baseline.clone();
+    let mut tick = full.tick;
+    while state.contains_key(&bob.user_id) || state.get(&alice.user_id).is_none_or(|e| e.seq != 1) {
+        let delta = carol_ws.expect_snapshot().await;
+        assert_eq!(delta.baseline, Some(full.tick));
+        // Без новых подтверждений каждая дельта считается от одной и той же базы
+        state = snapshot::apply(&baseline, &delta);
+        tick = delta.tick;
+    }
+    assert!(tick > full.tick);
+
+    let restored = state[&alice.user_id].to_update(alice.user_id);
+    assert!((restored.x - update.x).abs() <= 0.5 / snapshot::POSITION_SCALE);
+    assert!((restored.y - update.y).abs() <= 0.5 / snapshot::POSITION_SCALE);
+    assert!((restored.rotation - update.rotation).abs() < 0.001);
+    assert!((restored.vx - update.vx).abs() <= 0.5 / snapshot::VELOCITY_SCALE);
+    assert!((restored.vy - update.vy).abs() <= 0.5 / snapshot::VELOCITY_SCALE);
+}
+
+#[tokio::test]
+async fn unknown_acknowledgement_falls_back_to_full_snapshot() {
+    let server = TestServer::start().await;
+    let (_alice, _alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = join_with_snapshots(&server, "bob").await;
+
+    let first = bob_ws.expect_snapshot().await;
+    bob_ws.ack_snapshot(first.tick).await;
+    // Клиент потерял свое состояние и просит полный снимок
+    bob_ws.ack_snapshot(0).await;
+
+    let full = bob_ws.expect_snapshot().await;
+    assert_eq!(full.baseline, None);
+    assert_eq!(full.entities.len(), 2);
+}
+
+#[tokio::test]
+async fn stale_baseline_falls_back_to_full_snapshot() {
+    let mut config = TestServer::test_config();
+    config.game.tick_rate = 200;
+    let server = TestServer::start_with_config(config).await;
+    let (_alice, mut alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = join_with_snapshots(&server, "bob").await;
+
+    let first = bob_ws.expect_snapshot().await;
+    bob_ws.ack_snapshot(first.tick).await;
+    // Ждем, пока база устареет
+    tokio::time::sleep(Duration::from_millis(5 * (MAX_BASELINE_AGE + 20))).await;
+    alice_ws.send_position(1.0, 1.0, 0.0).await;
+
+    let next = bob_ws.expect_snapshot().await;
+    assert_eq!(next.baseline, None);
+    assert_eq!(next.entities.len(), 2);
+}
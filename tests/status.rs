@@ -0,0 +1,75 @@
+// tests/status.rs
+use anarchy_core::routes::game::PROTOCOL_VERSION;
+use anarchy_core::routes::status::{ReadinessResponse, ServerInfoResponse};
+use anarchy_core::testing::TestServer;
+use std::time::Duration;
+
+async fn get_ready(server: &TestServer) -> (u16, ReadinessResponse) {
+    let response = server.http()
+        .get(format!("http://{}/ready", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.expect("Malformed readiness response"))
+}
+
+#[tokio::test]
+async fn health_and_readiness_report_status() {
+    let server = TestServer::start().await;
+
+    let health = server.http().get(format!("http://{}/health", server.addr())).send().await.unwrap();
+    assert_eq!(health.status(), 200);
+
+    // Первый тик игрового цикла может еще не пройти сразу после запуска
+    let mut ready = get_ready(&server).await;
+    for _ in 0..20 {
+        if ready.0 == 200 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        ready = get_ready(&server).await;
+    }
+    let (status, body) = ready;
+    assert_eq!(status, 200);
+    assert!(body.ready && body.database && body.world && !body.shutting_down);
+
+    // После сигнала остановки сервер перестает быть готовым и не принимает новых игроков
+    server.state().begin_shutdown();
+    let (status, body) = get_ready(&server).await;
+    assert_eq!(status, 503);
+    assert!(!body.ready && body.shutting_down);
+
+    let user = server.register_user("late").await;
+    assert!(server.try_connect(&user.token).await.is_err());
+}
+
+#[tokio::test]
+async fn shutdown_closes_open_connections() {
+    let server = TestServer::start().await;
+    let (_alice, mut alice_ws) = server.join("alice").await;
+
+    server.state().begin_shutdown();
+    alice_ws.expect_closed().await;
+}
+
+#[tokio::test]
+async fn server_info_reports_online_players() {
+    let server = TestServer::start().await;
+    let (_alice, _alice_ws) = server.join("alice").await;
+    let (_bob, _bob_ws) = server.join("bob").await;
+
+    let info: ServerInfoResponse = server.http()
+        .get(server.url("/server-info"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .expect("Malformed server info response");
+
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.online_players, 2);
+    assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+    assert_eq!(info.motd, TestServer::test_config().server.motd);
+}
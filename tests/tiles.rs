@@ -0,0 +1,152 @@
+// tests/tiles.rs
+use anarchy_core::config::Config;
+use anarchy_core::routes::game::{ErrorCode, GameMessage};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::{TestClient, TestServer, TestUser};
+use anarchy_core::tiles::{self, Chunk, ChunkPos, AIR};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Квадрат 3x3 чанка вокруг игрока
+fn tiles_config() -> Config {
+    let mut config = TestServer::test_config();
+    config.game.view_distance = 1;
+    config
+}
+
+async fn join_with_tiles(server: &TestServer, login: &str) -> (TestUser, TestClient, HashMap<ChunkPos, Chunk>) {
+    let user = server.register_user(login).await;
+    let mut client = server.connect_with_features(&user, &[tiles::FEATURE]).await;
+    client.expect_initial_players().await;
+    let chunks = client.expect_chunks(9).await;
+    (user, client, chunks)
+}
+
+// Ждет, пока condition не станет истинным (сохранение и выгрузка чанков идут раз в секунду)
+async fn eventually<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..50 {
+        if condition().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Timed out waiting for {}", what);
+}
+
+async fn assert_no_tile_changes(client: &mut TestClient, duration: Duration) {
+    let deadline = tokio::time::Instant::now() + duration;
+    while let Some(msg) = client.try_recv(deadline.saturating_duration_since(tokio::time::Instant::now())).await {
+        assert!(!matches!(msg, GameMessage::TileChanged { .. }), "Unexpected {:?}", msg);
+    }
+}
+
+#[tokio::test]
+async fn chunks_around_player_are_streamed_as_they_move() {
+    let server = TestServer::start_with_config(tiles_config()).await;
+    let (_alice, mut alice_ws, chunks) = join_with_tiles(&server, "alice").await;
+
+    let expected: HashSet<ChunkPos> = ChunkPos { cx: 0, cy: 0 }.around(1).into_iter().collect();
+    assert_eq!(chunks.keys().copied().collect::<HashSet<_>>(), expected);
+    assert!(chunks.values().all(|chunk| chunk.tiles().iter().all(|tile| *tile == AIR)));
+
+    // Переход в чанк (1, 0): левый столбец выгружается, справа приходит новый
+    alice_ws.send_position(40.0, 0.5, 0.0).await;
+    let mut unloaded = Vec::new();
+    for _ in 0..3 {
+        unloaded.push(alice_ws.expect_chunk_unload().await);
+    }
+    assert!(unloaded.iter().all(|pos| pos.cx == -1), "{:?}", unloaded);
+    let loaded = alice_ws.expect_chunks(3).await;
+    assert!(loaded.keys().all(|pos| pos.cx == 2), "{:?}", loaded.keys());
+}
+
+#[tokio::test]
+async fn tile_edits_are_validated_and_sent_to_viewers() {
+    let server = TestServer::start_with_config(tiles_config()).await;
+    let (_alice, mut alice_ws, _) = join_with_tiles(&server, "alice").await;
+    let (_bob, mut bob_ws, _) = join_with_tiles(&server, "bob").await;
+    let (_carol, mut carol_ws, _) = join_with_tiles(&server, "carol").await;
+    // Carol уходит далеко, и чанки у начала координат ей больше не видны
+    carol_ws.send_position(200.0, 0.5, 0.0).await;
+    carol_ws.expect_chunks(9).await;
+
+    alice_ws.place_tile(2, 3, 7).await;
+    assert_eq!(alice_ws.expect_tile_changed().await, (2, 3, 7));
+    assert_eq!(bob_ws.expect_tile_changed().await, (2, 3, 7));
+
+    alice_ws.place_tile(2, 3, 8).await;
+    alice_ws.expect_error(ErrorCode::InvalidTileAction).await;
+    alice_ws.place_tile(1, 1, AIR).await;
+    alice_ws.expect_error(ErrorCode::InvalidTileAction).await;
+    alice_ws.place_tile(20, 20, 1).await;
+    alice_ws.expect_error(ErrorCode::OutOfReach).await;
+    alice_ws.place_tile(-200, 0, 1).await;
+    alice_ws.expect_error(ErrorCode::OutOfReach).await;
+
+    bob_ws.break_tile(2, 3).await;
+    assert_eq!(bob_ws.expect_tile_changed().await, (2, 3, AIR));
+    assert_eq!(alice_ws.expect_tile_changed().await, (2, 3, AIR));
+    bob_ws.break_tile(2, 3).await;
+    bob_ws.expect_error(ErrorCode::InvalidTileAction).await;
+
+    assert_no_tile_changes(&mut carol_ws, Duration::from_millis(300)).await;
+
+    // Без возможности "tiles" менять мир нельзя
+    let (_dave, mut dave_ws) = server.join("dave").await;
+    dave_ws.place_tile(0, 0, 1).await;
+    dave_ws.expect_error(ErrorCode::UnexpectedMessage).await;
+}
+
+#[tokio::test]
+async fn tiles_survive_chunk_unload_and_restart() {
+    let storage = Storage::in_memory();
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage.clone(), tiles_config()))).await;
+    let (_alice, mut alice_ws, _) = join_with_tiles(&server, "alice").await;
+
+    alice_ws.place_tile(1, 1, 5).await;
+    alice_ws.expect_tile_changed().await;
+    // Отрицательные координаты лежат в чанке (-1, -1)
+    alice_ws.place_tile(-1, -2, 6).await;
+    alice_ws.expect_tile_changed().await;
+    alice_ws.close().await;
+
+    let lobby = server.state().rooms.get("lobby").unwrap();
+    eventually("chunks to be unloaded", || async { lobby.tiles.lock().await.loaded_count() == 0 }).await;
+    let saved = storage.chunks.load_chunk("lobby", ChunkPos { cx: 0, cy: 0 }).await.unwrap().expect("chunk was not saved");
+    assert_eq!(saved.get(1, 1), Some(5));
+    drop(server);
+
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage, tiles_config()))).await;
+    let (_bob, _bob_ws, chunks) = join_with_tiles(&server, "bob").await;
+    assert_eq!(chunks[&ChunkPos { cx: 0, cy: 0 }].get(1, 1), 5);
+    assert_eq!(chunks[&ChunkPos { cx: -1, cy: -1 }].get(-1, -2), 6);
+}
+
+#[tokio::test]
+async fn instance_chunks_are_saved_when_instance_is_removed() {
+    let server = TestServer::start_with_config(tiles_config()).await;
+    let (_alice, mut alice_ws, _) = join_with_tiles(&server, "alice").await;
+
+    alice_ws.change_room("mine").await;
+    alice_ws.expect_room_changed("mine").await;
+    alice_ws.expect_chunks(9).await;
+    alice_ws.place_tile(0, 2, 3).await;
+    alice_ws.expect_tile_changed().await;
+    alice_ws.drop_connection();
+
+    let storage = server.state().storage.clone();
+    eventually("instance chunk to be saved", || {
+        let storage = storage.clone();
+        async move {
+            let chunk = storage.chunks.load_chunk("mine", ChunkPos { cx: 0, cy: 0 }).await.unwrap();
+            chunk.is_some_and(|changes| changes.get(0, 2) == Some(3))
+        }
+    }).await;
+    assert!(server.state().rooms.get("mine").is_none());
+}
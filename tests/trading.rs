@@ -0,0 +1,292 @@
+// tests/trading.rs
+use anarchy_core::items::{self, Inventory, ItemDefinition, ItemRegistry, ItemStack};
+use anarchy_core::routes::game::{ErrorCode, GameMessage};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::{InventoryRepository, Storage, StorageResult};
+use anarchy_core::testing::{TestClient, TestServer, TestUser};
+use anarchy_core::trading::TradeItem;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn stack(item: &str, count: u32) -> ItemStack {
+    ItemStack { item: item.to_string(), count }
+}
+
+async fn start() -> TestServer {
+    start_with_storage(Storage::in_memory()).await
+}
+
+async fn start_with_storage(storage: Storage) -> TestServer {
+    let items = ItemRegistry::from_definitions(vec![
+        ItemDefinition { id: "apple".to_string(), name: "Apple".to_string(), max_stack: 20, heal: None },
+        ItemDefinition { id: "stone".to_string(), name: "Stone".to_string(), max_stack: 64, heal: None },
+    ]).unwrap();
+    let mut config = TestServer::test_config();
+    config.game.inventory_slots = 2;
+    let state = AppState::with_maps(storage, config, HashMap::new()).with_items(items);
+    TestServer::start_with_state(Arc::new(state)).await
+}
+
+async fn connect(server: &TestServer, user: &TestUser, stacks: &[(u32, ItemStack)]) -> (TestClient, Inventory) {
+    server.state().storage.inventories.save_inventory(user.user_id, stacks).await.unwrap();
+    let mut ws = server.connect_with_features(user, &[items::FEATURE]).await;
+    let inventory = ws.expect_inventory().await;
+    ws.expect_initial_players().await;
+    (ws, inventory)
+}
+
+// Открывает обмен alice -> bob
+async fn open_trade(alice: (&TestUser, &mut TestClient), bob: (&TestUser, &mut TestClient)) {
+    alice.1.request_trade(bob.0.user_id).await;
+    assert_eq!(bob.1.expect_trade_requested().await, alice.0.user_id);
+    bob.1.accept_trade(alice.0.user_id).await;
+    let trade = alice.1.expect_trade().await;
+    assert_eq!(trade.parties(), [alice.0.user_id, bob.0.user_id]);
+    assert_eq!(bob.1.expect_trade().await, trade);
+}
+
+// Обе стороны фиксируют предложения, alice подтверждает обмен
+async fn lock_and_confirm_first(alice_ws: &mut TestClient, bob_ws: &mut TestClient) {
+    alice_ws.send_raw(r#"{"type": "TradeLock"}"#.to_string()).await;
+    for msg in [GameMessage::TradeLock, GameMessage::TradeConfirm] {
+        alice_ws.expect_trade().await;
+        bob_ws.expect_trade().await;
+        let sender = if matches!(msg, GameMessage::TradeLock) { &mut *bob_ws } else { &mut *alice_ws };
+        sender.send(&msg).await;
+    }
+    alice_ws.expect_trade().await;
+    bob_ws.expect_trade().await;
+}
+
+// Обе стороны фиксируют предложения и подтверждают обмен
+async fn lock_and_confirm(alice_ws: &mut TestClient, bob_ws: &mut TestClient) {
+    lock_and_confirm_first(alice_ws, bob_ws).await;
+    bob_ws.send(&GameMessage::TradeConfirm).await;
+}
+
+// Инвентари, которые записываются с задержкой
+struct SlowInventories(Arc<dyn InventoryRepository>);
+
+#[async_trait]
+impl InventoryRepository for SlowInventories {
+    async fn load_inventory(&self, user_id: i32) -> StorageResult<Vec<(u32, ItemStack)>> {
+        self.0.load_inventory(user_id).await
+    }
+
+    async fn save_inventory(&self, user_id: i32, stacks: &[(u32, ItemStack)]) -> StorageResult<()> {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        self.0.save_inventory(user_id, stacks).await
+    }
+
+    async fn save_inventories(&self, inventories: &[(i32, Vec<(u32, ItemStack)>)]) -> StorageResult<()> {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        self.0.save_inventories(inventories).await
+    }
+}
+
+#[tokio::test]
+async fn trades_exchange_items_in_one_settlement() {
+    let server = start().await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let (mut alice_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10))]).await;
+    let (mut bob_ws, _) = connect(&server, &bob, &[(0, stack("stone", 64)), (1, stack("stone", 5))]).await;
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+
+    alice_ws.offer(1, 1).await;
+    assert!(alice_ws.expect_error(ErrorCode::InvalidTradeAction).await.contains("empty"));
+    alice_ws.offer(0, 11).await;
+    alice_ws.expect_error(ErrorCode::InvalidTradeAction).await;
+    alice_ws.offer(0, 4).await;
+    alice_ws.expect_trade().await;
+    let trade = bob_ws.expect_trade().await;
+    assert_eq!(trade.sides[0].items, vec![TradeItem { slot: 0, item: "apple".to_string(), count: 4 }]);
+    alice_ws.send(&GameMessage::TradeConfirm).await;
+    assert!(alice_ws.expect_error(ErrorCode::InvalidTradeAction).await.contains("locked"));
+
+    // Изменение предложения снимает фиксацию с обеих сторон
+    alice_ws.send(&GameMessage::TradeLock).await;
+    assert!(alice_ws.expect_trade().await.sides[0].locked);
+    assert!(bob_ws.expect_trade().await.sides[0].locked);
+    bob_ws.offer(1, 5).await;
+    bob_ws.expect_trade().await;
+    let trade = alice_ws.expect_trade().await;
+    assert!(!trade.sides[0].locked && trade.sides[1].items.len() == 1);
+
+    lock_and_confirm(&mut alice_ws, &mut bob_ws).await;
+    assert_eq!(alice_ws.expect_trade_closed().await, (true, None));
+    assert_eq!(bob_ws.expect_trade_closed().await, (true, None));
+    let alice_inventory = alice_ws.expect_inventory().await;
+    let bob_inventory = bob_ws.expect_inventory().await;
+    assert_eq!(alice_inventory.stacks(), vec![(0, stack("apple", 6)), (1, stack("stone", 5))]);
+    assert_eq!(bob_inventory.stacks(), vec![(0, stack("stone", 64)), (1, stack("apple", 4))]);
+    let storage = &server.state().storage;
+    assert_eq!(storage.inventories.load_inventory(alice.user_id).await.unwrap(), alice_inventory.stacks());
+    assert_eq!(storage.inventories.load_inventory(bob.user_id).await.unwrap(), bob_inventory.stacks());
+    assert!(server.state().trades.lock().await.trade_of(alice.user_id).is_none());
+}
+
+#[tokio::test]
+async fn trades_require_nearby_free_partners() {
+    let server = start().await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let (mut alice_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10))]).await;
+    let (mut bob_ws, _) = connect(&server, &bob, &[]).await;
+    let (carol, mut carol_ws) = server.join("carol").await;
+
+    alice_ws.request_trade(alice.user_id).await;
+    alice_ws.expect_error(ErrorCode::InvalidTradeAction).await;
+    // Без возможности "inventory" обмениваться нельзя
+    alice_ws.request_trade(carol.user_id).await;
+    alice_ws.expect_error(ErrorCode::InvalidTradeAction).await;
+    carol_ws.send(&GameMessage::TradeAccept { from: alice.user_id }).await;
+    carol_ws.expect_error(ErrorCode::UnexpectedMessage).await;
+    bob_ws.accept_trade(alice.user_id).await;
+    assert!(bob_ws.expect_error(ErrorCode::InvalidTradeAction).await.contains("no trade request"));
+
+    bob_ws.send_position(20.5, 0.5, 0.0).await;
+    alice_ws.recv_until(|msg| matches!(msg, GameMessage::PlayerPosition(p) if p.user_id == bob.user_id)).await;
+    alice_ws.request_trade(bob.user_id).await;
+    alice_ws.expect_error(ErrorCode::OutOfReach).await;
+    bob_ws.send_position(1.5, 0.5, 0.0).await;
+    alice_ws.recv_until(|msg| matches!(msg, GameMessage::PlayerPosition(p) if p.user_id == bob.user_id)).await;
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+
+    // Игроку, который уже обменивается, обмен не предложить
+    let dave = server.register_user("dave").await;
+    let (mut dave_ws, _) = connect(&server, &dave, &[]).await;
+    dave_ws.request_trade(bob.user_id).await;
+    assert!(dave_ws.expect_error(ErrorCode::InvalidTradeAction).await.contains("already trading"));
+
+    bob_ws.send(&GameMessage::TradeCancel).await;
+    assert_eq!(alice_ws.expect_trade_closed().await, (false, Some(format!("cancelled by player {}", bob.user_id))));
+    bob_ws.send(&GameMessage::TradeCancel).await;
+    bob_ws.expect_error(ErrorCode::InvalidTradeAction).await;
+}
+
+#[tokio::test]
+async fn failed_or_abandoned_trades_leave_inventories_untouched() {
+    let server = start().await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let (mut alice_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10)), (1, stack("stone", 1))]).await;
+    let (mut bob_ws, _) = connect(&server, &bob, &[(0, stack("stone", 64)), (1, stack("stone", 64))]).await;
+    let storage = &server.state().storage;
+
+    // Предметов некуда положить
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    alice_ws.offer(0, 10).await;
+    alice_ws.expect_trade().await;
+    bob_ws.expect_trade().await;
+    lock_and_confirm(&mut alice_ws, &mut bob_ws).await;
+    let (completed, reason) = bob_ws.expect_trade_closed().await;
+    assert!(!completed && reason.unwrap().contains("no room"));
+    alice_ws.expect_trade_closed().await;
+
+    // Выложенные предметы исчезли из ячейки до подтверждения
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    alice_ws.offer(1, 1).await;
+    alice_ws.expect_trade().await;
+    bob_ws.expect_trade().await;
+    alice_ws.drop_item(1, None).await;
+    alice_ws.expect_inventory().await;
+    lock_and_confirm(&mut alice_ws, &mut bob_ws).await;
+    let (completed, reason) = alice_ws.expect_trade_closed().await;
+    assert!(!completed && reason.unwrap().contains("no longer"));
+    bob_ws.expect_trade_closed().await;
+    assert_eq!(storage.inventories.load_inventory(bob.user_id).await.unwrap(), vec![(0, stack("stone", 64)), (1, stack("stone", 64))]);
+
+    // Уход партнера отменяет обмен
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    alice_ws.offer(0, 5).await;
+    bob_ws.expect_trade().await;
+    alice_ws.close().await;
+    assert_eq!(bob_ws.expect_trade_closed().await, (false, Some("partner left the game".to_string())));
+    assert_eq!(storage.inventories.load_inventory(alice.user_id).await.unwrap(), vec![(0, stack("apple", 10))]);
+    assert!(server.state().trades.lock().await.trade_of(bob.user_id).is_none());
+}
+
+#[tokio::test]
+async fn item_actions_during_a_settlement_do_not_overwrite_it() {
+    let mut storage = Storage::in_memory();
+    storage.inventories = Arc::new(SlowInventories(storage.inventories.clone()));
+    let server = start_with_storage(storage).await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let (mut alice_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10)), (1, stack("stone", 5))]).await;
+    let (mut bob_ws, _) = connect(&server, &bob, &[]).await;
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    alice_ws.offer(0, 4).await;
+    alice_ws.expect_trade().await;
+    bob_ws.expect_trade().await;
+    lock_and_confirm_first(&mut alice_ws, &mut bob_ws).await;
+
+    // Обмен завершается, пока записывается выброшенный камень
+    alice_ws.drop_item(1, None).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    bob_ws.send(&GameMessage::TradeConfirm).await;
+    assert_eq!(bob_ws.expect_trade_closed().await, (true, None));
+    let bob_inventory = bob_ws.expect_inventory().await;
+    assert_eq!(bob_inventory.stacks(), vec![(0, stack("apple", 4))]);
+    alice_ws.expect_trade_closed().await;
+    let alice_inventory = alice_ws.expect_inventory().await;
+    assert_eq!(alice_inventory.stacks(), vec![(0, stack("apple", 6))]);
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let storage = &server.state().storage;
+    assert_eq!(storage.inventories.load_inventory(alice.user_id).await.unwrap(), alice_inventory.stacks());
+    assert_eq!(storage.inventories.load_inventory(bob.user_id).await.unwrap(), bob_inventory.stacks());
+}
+
+#[tokio::test]
+async fn a_settlement_does_not_hold_up_other_trades() {
+    let mut storage = Storage::in_memory();
+    storage.inventories = Arc::new(SlowInventories(storage.inventories.clone()));
+    let server = start_with_storage(storage).await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let carol = server.register_user("carol").await;
+    let dave = server.register_user("dave").await;
+    let (mut alice_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10))]).await;
+    let (mut bob_ws, _) = connect(&server, &bob, &[]).await;
+    let (mut carol_ws, _) = connect(&server, &carol, &[]).await;
+    let (mut dave_ws, _) = connect(&server, &dave, &[]).await;
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    alice_ws.offer(0, 10).await;
+    alice_ws.expect_trade().await;
+    bob_ws.expect_trade().await;
+    lock_and_confirm(&mut alice_ws, &mut bob_ws).await;
+
+    // Пока обмен alice и bob записывается, другие игроки договариваются об обмене без ожидания
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let started = std::time::Instant::now();
+    carol_ws.request_trade(dave.user_id).await;
+    assert_eq!(dave_ws.expect_trade_requested().await, carol.user_id);
+    assert!(started.elapsed() < Duration::from_millis(250), "trade request waited {:?}", started.elapsed());
+    // Стороны записываемого обмена заняты, пока он не завершится
+    carol_ws.request_trade(alice.user_id).await;
+    assert!(carol_ws.expect_error(ErrorCode::InvalidTradeAction).await.contains("already trading"));
+
+    assert_eq!(bob_ws.expect_trade_closed().await, (true, None));
+    alice_ws.expect_trade_closed().await;
+    assert!(alice_ws.expect_inventory().await.stacks().is_empty());
+}
+
+#[tokio::test]
+async fn closing_one_of_several_connections_keeps_the_trade() {
+    let server = start().await;
+    let alice = server.register_user("alice").await;
+    let bob = server.register_user("bob").await;
+    let (mut alice_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10))]).await;
+    let (mut bob_ws, _) = connect(&server, &bob, &[]).await;
+    open_trade((&alice, &mut alice_ws), (&bob, &mut bob_ws)).await;
+    let (extra_ws, _) = connect(&server, &alice, &[(0, stack("apple", 10))]).await;
+    extra_ws.close().await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    alice_ws.offer(0, 4).await;
+    assert_eq!(bob_ws.expect_trade().await.sides[0].items, vec![TradeItem { slot: 0, item: "apple".to_string(), count: 4 }]);
+}
@@ -0,0 +1,128 @@
+// tests/websocket.rs
+use anarchy_core::routes::game::GameMessage;
+use anarchy_core::testing::TestServer;
+use std::time::Duration;
+
+#[tokio::test]
+async fn initial_players_contains_connected_players() {
+    let server = TestServer::start().await;
+    let (alice, _alice_ws) = server.join("alice").await;
+
+    let bob = server.register_user("bob").await;
+    let mut bob_ws = server.connect(&bob).await;
+    let players = bob_ws.expect_initial_players().await;
+
+    let mut ids: Vec<i32> = players.iter().map(|p| p.user_id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![alice.user_id, bob.user_id]);
+}
+
+#[tokio::test]
+async fn position_is_broadcast_to_all_clients() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.send_position(1.5, -2.0, 0.5).await;
+
+    let seen_by_bob = bob_ws.expect_position_of(alice.user_id).await;
+    assert_eq!((seen_by_bob.x, seen_by_bob.y, seen_by_bob.z), (1.5, -2.0, 0.5));
+    let echoed = alice_ws.expect_position_of(alice.user_id).await;
+    assert_eq!(echoed.x, 1.5);
+}
+
+#[tokio::test]
+async fn spoofed_user_id_is_overwritten() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (bob, mut bob_ws) = server.join("bob").await;
+
+    let forged = anarchy_core::routes::game::PlayerPositionUpdate { user_id: bob.user_id, x: 9.0, y: 9.0, z: 9.0, seq: 1, ..Default::default() };
+    alice_ws.send(&GameMessage::PlayerPosition(forged)).await;
+
+    let update = bob_ws.expect_position_of(alice.user_id).await;
+    assert_eq!(update.x, 9.0);
+}
+
+#[tokio::test]
+async fn logout_broadcasts_disconnect() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.logout().await;
+    bob_ws.expect_disconnected(alice.user_id).await;
+    // Повторного PlayerDisconnected после закрытия сокета быть не должно
+    bob_ws.expect_silence(Duration::from_millis(400)).await;
+}
+
+#[tokio::test]
+async fn abrupt_close_broadcasts_disconnect() {
+    let server = TestServer::start().await;
+    let (alice, alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.drop_connection();
+    bob_ws.expect_disconnected(alice.user_id).await;
+    let lobby = server.state().rooms.get("lobby").unwrap();
+    assert!(!lobby.active_player_positions.lock().await.contains_key(&alice.user_id));
+}
+
+#[tokio::test]
+async fn position_survives_reconnect() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    alice_ws.send_position(4.0, 5.0, 6.0).await;
+    alice_ws.expect_position_of(alice.user_id).await;
+    alice_ws.close().await;
+
+    let mut alice_ws = server.connect(&alice).await;
+    let players = alice_ws.expect_initial_players().await;
+    let me = players.iter().find(|p| p.user_id == alice.user_id).expect("reconnected player is missing");
+    assert_eq!((me.x, me.y, me.z), (4.0, 5.0, 6.0));
+}
+
+#[tokio::test]
+async fn websocket_requires_valid_token() {
+    let server = TestServer::start().await;
+    assert!(server.try_connect("not-a-jwt").await.is_err());
+}
+
+#[tokio::test]
+async fn position_updates_acknowledge_last_applied_input() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+
+    alice_ws.send_position(1.0, 0.0, 0.0).await;
+    alice_ws.send_position(2.0, 0.0, 0.0).await;
+    let last = alice_ws.send_position(3.0, 0.0, 0.0).await;
+
+    let ack = loop {
+        let update = alice_ws.expect_position_of(alice.user_id).await;
+        if update.seq == last {
+            break update;
+        }
+    };
+    assert_eq!(ack.x, 3.0);
+}
+
+#[tokio::test]
+async fn duplicate_and_out_of_order_inputs_are_discarded() {
+    let server = TestServer::start().await;
+    let (alice, mut alice_ws) = server.join("alice").await;
+    let (_bob, mut bob_ws) = server.join("bob").await;
+
+    alice_ws.send_position_with_seq(5, 5.0, 0.0, 0.0).await;
+    alice_ws.send_position_with_seq(3, 3.0, 0.0, 0.0).await;
+    alice_ws.send_position_with_seq(5, 99.0, 0.0, 0.0).await;
+    alice_ws.send_position_with_seq(6, 6.0, 0.0, 0.0).await;
+
+    loop {
+        let update = bob_ws.expect_position_of(alice.user_id).await;
+        assert!(update.seq == 5 || update.seq == 6, "unexpected ack {}", update.seq);
+        assert_eq!(update.x, update.seq as f64, "discarded input was applied");
+        if update.seq == 6 {
+            break;
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// tests/worldgen.rs
+use anarchy_core::config::{Config, GeneratorKind};
+use anarchy_core::state::AppState;
+use anarchy_core::storage::Storage;
+use anarchy_core::testing::TestServer;
+use anarchy_core::tiles::{self, ChunkPos, Tile};
+use anarchy_core::worldgen::WorldGenerator;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SEED: u64 = 42;
+
+fn noise_config() -> Config {
+    let mut config = TestServer::test_config();
+    config.game.generator = GeneratorKind::Noise;
+    config.game.world_seed = SEED;
+    config.game.view_distance = 1;
+    config
+}
+
+#[test]
+fn generation_is_deterministic_per_seed_and_room() {
+    let generator = WorldGenerator::new(GeneratorKind::Noise, SEED, "lobby");
+    let pos = ChunkPos { cx: 3, cy: -2 };
+    assert_eq!(generator.generate(pos), WorldGenerator::new(GeneratorKind::Noise, SEED, "lobby").generate(pos));
+    assert_ne!(generator.generate(pos), WorldGenerator::new(GeneratorKind::Noise, SEED + 1, "lobby").generate(pos));
+    assert_ne!(generator.generate(pos), WorldGenerator::new(GeneratorKind::Noise, SEED, "arena").generate(pos));
+
+    // На достаточно большой площади встречаются и вода, и суша разных биомов
+    let kinds: HashSet<Tile> = (-8..8)
+        .flat_map(|cy| (-8..8).map(move |cx| ChunkPos { cx, cy }))
+        .flat_map(|pos| generator.generate(pos).tiles().to_vec())
+        .collect();
+    for tile in [tiles::WATER, tiles::SAND, tiles::GRASS, tiles::TREE] {
+        assert!(kinds.contains(&tile), "tile {} was never generated, got {:?}", tile, kinds);
+    }
+
+    let (x, y) = generator.spawn_point();
+    let (tx, ty) = (x.floor() as i32, y.floor() as i32);
+    for (dx, dy) in [(-1, -1), (0, 0), (1, 1), (-1, 1), (1, -1)] {
+        assert!(!tiles::is_solid(generator.tile(tx + dx, ty + dy)), "spawn ({}, {}) is blocked", x, y);
+    }
+}
+
+#[tokio::test]
+async fn new_players_spawn_on_generated_walkable_ground() {
+    let server = TestServer::start_with_config(noise_config()).await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[tiles::FEATURE]).await;
+    let players = alice_ws.expect_initial_players().await;
+
+    let generator = WorldGenerator::new(GeneratorKind::Noise, SEED, "lobby");
+    assert_eq!((players[0].x, players[0].y), generator.spawn_point());
+    // Клиент получает ровно сгенерированные чанки
+    for (pos, chunk) in alice_ws.expect_chunks(9).await {
+        assert_eq!(chunk, generator.generate(pos), "chunk {:?} differs from the generated one", pos);
+    }
+}
+
+#[tokio::test]
+async fn only_player_changes_are_persisted() {
+    let storage = Storage::in_memory();
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage.clone(), noise_config()))).await;
+    let alice = server.register_user("alice").await;
+    let mut alice_ws = server.connect_with_features(&alice, &[tiles::FEATURE]).await;
+    let me = alice_ws.expect_initial_players().await.remove(0);
+    let chunks = alice_ws.expect_chunks(9).await;
+
+    // Точка появления окружена проходимыми тайлами: ставим стену рядом
+    let (x, y) = (me.x.floor() as i32 + 1, me.y.floor() as i32);
+    let pos = ChunkPos::of_tile(x, y);
+    assert!(!tiles::is_solid(chunks[&pos].get(x, y)));
+    alice_ws.place_tile(x, y, tiles::STONE).await;
+    assert_eq!(alice_ws.expect_tile_changed().await, (x, y, tiles::STONE));
+    alice_ws.close().await;
+
+    let lobby = server.state().rooms.get("lobby").unwrap();
+    for _ in 0..50 {
+        if lobby.tiles.lock().await.loaded_count() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(lobby.tiles.lock().await.loaded_count(), 0, "chunks were not unloaded");
+
+    // Сохранен только измененный чанк, и в нем только один тайл
+    for other in chunks.keys().filter(|other| **other != pos) {
+        assert!(storage.chunks.load_chunk("lobby", *other).await.unwrap().is_none(), "untouched chunk {:?} was saved", other);
+    }
+    let changes = storage.chunks.load_chunk("lobby", pos).await.unwrap().expect("changed chunk was not saved");
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes.get(x, y), Some(tiles::STONE));
+    drop(server);
+
+    let server = TestServer::start_with_state(Arc::new(AppState::new(storage, noise_config()))).await;
+    let bob = server.register_user("bob").await;
+    let mut bob_ws = server.connect_with_features(&bob, &[tiles::FEATURE]).await;
+    bob_ws.expect_initial_players().await;
+    let chunks = bob_ws.expect_chunks(9).await;
+    let mut expected = WorldGenerator::new(GeneratorKind::Noise, SEED, "lobby").generate(pos);
+    expected.set(x, y, tiles::STONE);
+    assert_eq!(chunks[&pos], expected);
+}